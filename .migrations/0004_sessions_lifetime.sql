@@ -0,0 +1,8 @@
+ALTER TABLE sessions ADD COLUMN IF NOT EXISTS absolute_expires_at TIMESTAMPTZ;
+ALTER TABLE sessions ADD COLUMN IF NOT EXISTS remember_me BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- Existing sessions were created with the long lived profile
+UPDATE sessions SET absolute_expires_at = created_at + INTERVAL '90 days', remember_me = TRUE
+WHERE absolute_expires_at IS NULL;
+
+ALTER TABLE sessions ALTER COLUMN absolute_expires_at SET NOT NULL;
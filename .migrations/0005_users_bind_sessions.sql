@@ -0,0 +1,2 @@
+ALTER TABLE users ADD COLUMN IF NOT EXISTS bind_sessions BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE deleted_users ADD COLUMN IF NOT EXISTS bind_sessions BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,16 @@
+ALTER TABLE sessions ADD COLUMN IF NOT EXISTS impersonated_by UUID REFERENCES users(id) ON DELETE CASCADE;
+
+CREATE TABLE IF NOT EXISTS impersonations (
+    unsigned_ssid  UUID PRIMARY KEY,
+
+    admin_id       UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    target_id      UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    reason         TEXT,
+
+    started_at     TIMESTAMPTZ NOT NULL,
+    expires_at     TIMESTAMPTZ NOT NULL,
+    ended_at       TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS idx_impersonations_admin_id ON impersonations(admin_id);
+CREATE INDEX IF NOT EXISTS idx_impersonations_target_id ON impersonations(target_id);
@@ -0,0 +1,29 @@
+CREATE TABLE IF NOT EXISTS roles (
+    name           VARCHAR(64) PRIMARY KEY,
+    description    TEXT NOT NULL DEFAULT ''
+);
+
+CREATE TABLE IF NOT EXISTS role_permissions (
+    role           VARCHAR(64) NOT NULL REFERENCES roles(name) ON DELETE CASCADE ON UPDATE CASCADE,
+    permission     VARCHAR(64) NOT NULL,
+    PRIMARY KEY (role, permission)
+);
+
+CREATE TABLE IF NOT EXISTS user_roles (
+    user_id        UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    role           VARCHAR(64) NOT NULL REFERENCES roles(name) ON DELETE CASCADE ON UPDATE CASCADE,
+    granted_by     UUID REFERENCES users(id) ON DELETE SET NULL,
+    granted_at     TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (user_id, role)
+);
+
+CREATE INDEX IF NOT EXISTS idx_user_roles_role ON user_roles(role);
+
+INSERT INTO roles (name, description) VALUES ('admin', 'Full administrative access')
+ON CONFLICT DO NOTHING;
+
+INSERT INTO role_permissions (role, permission) VALUES
+    ('admin', 'health:read'),
+    ('admin', 'users:impersonate'),
+    ('admin', 'roles:manage')
+ON CONFLICT DO NOTHING;
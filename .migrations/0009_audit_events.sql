@@ -0,0 +1,20 @@
+CREATE TABLE IF NOT EXISTS audit_events (
+    id             BIGSERIAL PRIMARY KEY,
+    occurred_at    TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    kind           VARCHAR(64) NOT NULL,
+
+    -- not foreign keys, the history outlives deleted and purged users
+    user_id        UUID,
+    actor_id       UUID,
+
+    ip_address     INET,
+    user_agent     TEXT,
+    details        TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_audit_events_user_id ON audit_events(user_id, id);
+CREATE INDEX IF NOT EXISTS idx_audit_events_actor_id ON audit_events(actor_id);
+CREATE INDEX IF NOT EXISTS idx_audit_events_occurred_at ON audit_events(occurred_at);
+
+INSERT INTO role_permissions (role, permission) VALUES ('admin', 'audit:read')
+ON CONFLICT DO NOTHING;
@@ -0,0 +1,15 @@
+-- password hashes of imported users in a foreign format, replaced on their next login
+CREATE TABLE IF NOT EXISTS imported_passwords (
+    user_id        UUID PRIMARY KEY NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    hash           TEXT NOT NULL
+);
+
+-- accounts of users at external identity providers (like the ids of an older system)
+CREATE TABLE IF NOT EXISTS user_identities (
+    provider       VARCHAR(64) NOT NULL,
+    subject        VARCHAR(255) NOT NULL,
+    user_id        UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    PRIMARY KEY (provider, subject)
+);
+
+CREATE INDEX IF NOT EXISTS idx_user_identities_user_id ON user_identities(user_id);
@@ -0,0 +1,36 @@
+-- customers served by this deployment, empty values fall back to the environment
+-- (`SERVICE_NAME`, `SERVICE_DOMAIN`, `GOOGLE_CLIENT_ID`, `GOOGLE_CLIENT_SECRET`)
+CREATE TABLE IF NOT EXISTS tenants (
+    id                   VARCHAR(64) PRIMARY KEY NOT NULL,
+    service_name         VARCHAR(255) NOT NULL DEFAULT '',
+    service_domain       VARCHAR(255) NOT NULL DEFAULT '',
+    host                 VARCHAR(255) UNIQUE,
+    google_client_id     TEXT,
+    google_client_secret TEXT,
+    -- json object of email kind => { "subject", "body" }
+    email_templates      TEXT NOT NULL DEFAULT '{}',
+    created              TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- existing users belong to the tenant that uses the environment
+INSERT INTO tenants (id) VALUES ('default') ON CONFLICT DO NOTHING;
+
+ALTER TABLE users ADD COLUMN IF NOT EXISTS tenant_id VARCHAR(64) NOT NULL DEFAULT 'default' REFERENCES tenants(id);
+ALTER TABLE deleted_users ADD COLUMN IF NOT EXISTS tenant_id VARCHAR(64) NOT NULL DEFAULT 'default';
+
+-- emails and usernames are unique per tenant
+ALTER TABLE users DROP CONSTRAINT IF EXISTS users_email_key;
+ALTER TABLE users DROP CONSTRAINT IF EXISTS users_username_key;
+ALTER TABLE users DROP CONSTRAINT IF EXISTS users_tenant_email_key;
+ALTER TABLE users DROP CONSTRAINT IF EXISTS users_tenant_username_key;
+ALTER TABLE users ADD CONSTRAINT users_tenant_email_key UNIQUE (tenant_id, email);
+ALTER TABLE users ADD CONSTRAINT users_tenant_username_key UNIQUE (tenant_id, username);
+CREATE INDEX IF NOT EXISTS idx_users_tenant_id ON users(tenant_id);
+CREATE INDEX IF NOT EXISTS idx_deleted_users_tenant_id ON deleted_users(tenant_id);
+
+ALTER TABLE user_identities ADD COLUMN IF NOT EXISTS tenant_id VARCHAR(64) NOT NULL DEFAULT 'default';
+ALTER TABLE user_identities DROP CONSTRAINT IF EXISTS user_identities_pkey;
+ALTER TABLE user_identities ADD PRIMARY KEY (tenant_id, provider, subject);
+
+INSERT INTO role_permissions (role, permission) VALUES ('admin', 'tenants:manage')
+ON CONFLICT DO NOTHING;
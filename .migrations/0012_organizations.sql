@@ -0,0 +1,34 @@
+CREATE TABLE IF NOT EXISTS organizations (
+    id             UUID PRIMARY KEY NOT NULL,
+    tenant_id      VARCHAR(64) NOT NULL REFERENCES tenants(id),
+    name           VARCHAR(64) NOT NULL,
+    created_by     UUID REFERENCES users(id) ON DELETE SET NULL,
+    created        TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_organizations_tenant_id ON organizations(tenant_id);
+
+CREATE TABLE IF NOT EXISTS memberships (
+    org_id         UUID NOT NULL REFERENCES organizations(id) ON DELETE CASCADE,
+    user_id        UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    role           VARCHAR(16) NOT NULL CHECK (role IN ('owner', 'admin', 'member')),
+    joined_at      TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (org_id, user_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_memberships_user_id ON memberships(user_id);
+
+-- pending invitations, removed once accepted or declined
+CREATE TABLE IF NOT EXISTS organization_invitations (
+    id             UUID PRIMARY KEY NOT NULL,
+    org_id         UUID NOT NULL REFERENCES organizations(id) ON DELETE CASCADE,
+    email          VARCHAR(320) NOT NULL,
+    role           VARCHAR(16) NOT NULL CHECK (role IN ('owner', 'admin', 'member')),
+    invited_by     UUID REFERENCES users(id) ON DELETE SET NULL,
+    created        TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    expires_at     TIMESTAMPTZ NOT NULL,
+    UNIQUE (org_id, email)
+);
+
+CREATE INDEX IF NOT EXISTS idx_organization_invitations_email ON organization_invitations(email);
+CREATE INDEX IF NOT EXISTS idx_organization_invitations_expires_at ON organization_invitations(expires_at);
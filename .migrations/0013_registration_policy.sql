@@ -0,0 +1,19 @@
+-- 'open' or 'invite_only', the domain lists apply to both modes (an empty allowlist allows every domain)
+ALTER TABLE tenants ADD COLUMN IF NOT EXISTS registration_mode VARCHAR(16) NOT NULL DEFAULT 'open';
+ALTER TABLE tenants ADD COLUMN IF NOT EXISTS allowed_email_domains TEXT[] NOT NULL DEFAULT '{}';
+ALTER TABLE tenants ADD COLUMN IF NOT EXISTS denied_email_domains TEXT[] NOT NULL DEFAULT '{}';
+
+CREATE TABLE IF NOT EXISTS registration_invites (
+    code           VARCHAR(64) PRIMARY KEY NOT NULL,
+    tenant_id      VARCHAR(64) NOT NULL REFERENCES tenants(id) ON DELETE CASCADE,
+    max_uses       INTEGER NOT NULL CHECK (max_uses > 0),
+    uses           INTEGER NOT NULL DEFAULT 0,
+    expires_at     TIMESTAMPTZ,
+    created_by     UUID REFERENCES users(id) ON DELETE SET NULL,
+    created        TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_registration_invites_tenant_id ON registration_invites(tenant_id);
+
+INSERT INTO role_permissions (role, permission) VALUES ('admin', 'registration:manage')
+ON CONFLICT DO NOTHING;
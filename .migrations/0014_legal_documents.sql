@@ -0,0 +1,25 @@
+-- versions of the terms of service and privacy policy, the latest published one of each kind is
+-- the one users have to accept
+CREATE TABLE IF NOT EXISTS legal_documents (
+    tenant_id      VARCHAR(64) NOT NULL REFERENCES tenants(id) ON DELETE CASCADE,
+    kind           VARCHAR(32) NOT NULL,
+    version        VARCHAR(32) NOT NULL,
+    url            VARCHAR(511) NOT NULL,
+    published_at   TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (tenant_id, kind, version)
+);
+
+CREATE TABLE IF NOT EXISTS legal_acceptances (
+    user_id        UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    tenant_id      VARCHAR(64) NOT NULL,
+    kind           VARCHAR(32) NOT NULL,
+    version        VARCHAR(32) NOT NULL,
+    accepted_at    TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    ip_address     INET,
+    user_agent     TEXT,
+    PRIMARY KEY (user_id, kind, version),
+    FOREIGN KEY (tenant_id, kind, version) REFERENCES legal_documents(tenant_id, kind, version)
+);
+
+INSERT INTO role_permissions (role, permission) VALUES ('admin', 'legal:manage')
+ON CONFLICT DO NOTHING;
@@ -0,0 +1,8 @@
+-- accounts of users under the age threshold of their country are restricted, birth date changes
+-- are counted to keep users from lifting the restriction themselves
+ALTER TABLE users ADD COLUMN IF NOT EXISTS restricted BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE users ADD COLUMN IF NOT EXISTS birth_date_changes INT NOT NULL DEFAULT 0;
+ALTER TABLE deleted_users ADD COLUMN IF NOT EXISTS restricted BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE deleted_users ADD COLUMN IF NOT EXISTS birth_date_changes INT NOT NULL DEFAULT 0;
+
+CREATE INDEX IF NOT EXISTS idx_users_restricted ON users(restricted) WHERE restricted;
@@ -0,0 +1,154 @@
+use clap::{Parser, Subcommand};
+use database::{Db, bucket::BlackBlazeB2};
+use std::sync::Arc;
+use util::AppError;
+
+mod ops;
+mod users;
+
+/// operational tasks for the basic-auth server (reads the same `.env` as the server)
+#[derive(Parser)]
+#[command(version)]
+struct Cli {
+    /// print the result as json instead of text
+    #[arg(long, global = true)]
+    json: bool,
+    /// tenant of the users the command works on
+    #[arg(long, global = true, default_value = database::tenants::DEFAULT_TENANT)]
+    tenant: String,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// apply pending migrations and list them all
+    Migrate,
+    /// create a user with the `admin` role
+    CreateAdmin {
+        #[arg(long)]
+        username: String,
+        #[arg(long)]
+        email: String,
+        #[arg(long, default_value = "Admin")]
+        display_name: String,
+        /// generated and printed when not given
+        #[arg(long)]
+        password: Option<String>,
+    },
+    /// give a role to a user
+    GrantRole { username: String, role: String },
+    /// take a role away from a user
+    RevokeRole { username: String, role: String },
+    /// set a new password for a user and log them out everywhere
+    ResetPassword {
+        username: String,
+        /// generated and printed when not given
+        #[arg(long)]
+        password: Option<String>,
+    },
+    /// log a user out everywhere
+    RevokeSessions { username: String },
+    /// permanently remove the users deleted more than `older_than_days` days ago
+    PurgeDeleted {
+        #[arg(long)]
+        older_than_days: u32,
+    },
+    /// generate a new `SECRET_KEY` and print the env to roll it out with, nothing is changed
+    GenerateKey,
+    /// export everything stored about a user
+    ExportUser { username: String },
+    /// import users from a csv or json lines file (see the README for the columns)
+    ImportUsers {
+        file: std::path::PathBuf,
+        /// `csv` or `jsonl`, guessed from the file extension when not given
+        #[arg(long)]
+        format: Option<String>,
+    },
+}
+
+/// result of a command, printed as json with `--json` or as text otherwise
+pub struct Output {
+    json: serde_json::Value,
+    text: String,
+}
+
+impl Output {
+    pub fn new(json: serde_json::Value, text: impl Into<String>) -> Self {
+        Self { json, text: text.into() }
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    dotenv::dotenv().ok();
+    let cli = Cli::parse();
+
+    let tenant = cli.tenant.as_str();
+    let result = match cli.command {
+        // a new key can be generated without any configuration
+        Command::GenerateKey => Ok(ops::generate_key()),
+        command => run(command, &connect().await, tenant).await,
+    };
+
+    match result {
+        Ok(output) if cli.json => println!("{}", output.json),
+        Ok(output) => println!("{}", output.text),
+        Err(e) => {
+            if cli.json {
+                println!("{}", serde_json::json!({ "error": format!("{e:?}") }));
+            } else {
+                eprintln!("error: {e:?}");
+            }
+            std::process::exit(1);
+        }
+    }
+}
+
+/// loads the configuration and connects to its database, exits on failure
+async fn connect() -> Arc<Db> {
+    let config = util::config::Config::load().unwrap_or_else(|errors| {
+        eprintln!("error: invalid configuration:\n  {}", errors.join("\n  "));
+        std::process::exit(1);
+    });
+    let bucket = Arc::new(BlackBlazeB2::from(&config.bucket));
+    Db::connect(&config, bucket).await.unwrap_or_else(|e| {
+        eprintln!("error: {e}");
+        std::process::exit(1);
+    })
+}
+
+async fn run(command: Command, db: &Arc<Db>, tenant: &str) -> Result<Output, AppError> {
+    match command {
+        Command::Migrate => ops::migrate(db).await,
+        Command::CreateAdmin { username, email, display_name, password } => {
+            users::create_admin(db, tenant, username, email, display_name, password).await
+        }
+        Command::GrantRole { username, role } => {
+            users::grant_role(db, tenant, &username, &role).await
+        }
+        Command::RevokeRole { username, role } => {
+            users::revoke_role(db, tenant, &username, &role).await
+        }
+        Command::ResetPassword { username, password } => {
+            users::reset_password(db, tenant, &username, password).await
+        }
+        Command::RevokeSessions { username } => users::revoke_sessions(db, tenant, &username).await,
+        Command::PurgeDeleted { older_than_days } => ops::purge_deleted(db, older_than_days).await,
+        Command::GenerateKey => Ok(ops::generate_key()),
+        Command::ExportUser { username } => users::export_user(db, tenant, &username).await,
+        Command::ImportUsers { file, format } => {
+            users::import_users(db, tenant, &file, format).await
+        }
+    }
+}
+
+/// returns a random password that passes `is_password_strong`
+pub fn generate_password() -> String {
+    loop {
+        let password = util::generate::random_string(24);
+        if util::validation::is_password_strong(&password).is_ok() {
+            return password;
+        }
+    }
+}
@@ -0,0 +1,63 @@
+use crate::Output;
+use database::Db;
+use std::sync::Arc;
+use util::AppError;
+
+pub async fn migrate(db: &Arc<Db>) -> Result<Output, AppError> {
+    // `Db::connect` applied the pending migrations
+    let migrations = db.migration_status().await?;
+
+    let json = migrations
+        .iter()
+        .map(|m| {
+            serde_json::json!({
+                "version": m.version,
+                "description": m.description,
+                "applied": m.applied,
+            })
+        })
+        .collect::<Vec<_>>();
+    let text = migrations
+        .iter()
+        .map(|m| {
+            let state = if m.applied { "applied" } else { "pending" };
+            format!("{:>4} {:<8} {}", m.version, state, m.description)
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    Ok(Output::new(serde_json::json!({ "migrations": json }), text))
+}
+
+pub async fn purge_deleted(db: &Arc<Db>, older_than_days: u32) -> Result<Output, AppError> {
+    let deleted_before =
+        time::OffsetDateTime::now_utc() - time::Duration::days(older_than_days.into());
+    let purged = db.purge_deleted_users(deleted_before).await?;
+    Ok(Output::new(
+        serde_json::json!({ "purged": purged }),
+        format!("Purged {purged} users deleted more than {older_than_days} days ago"),
+    ))
+}
+
+/// generates a new secret key, keeping the current one for verification only
+///
+/// nothing is stored, the printed env has to be deployed to every server
+pub fn generate_key() -> Output {
+    let new_key = util::generate::random_string(64);
+    // the configuration can be incomplete when generating the first key
+    let previous = util::config::Config::load()
+        .map(|config| {
+            std::iter::once(config.secret_key)
+                .chain(config.previous_secret_keys)
+                .collect::<Vec<_>>()
+                .join(",")
+        })
+        .unwrap_or_default();
+
+    Output::new(
+        serde_json::json!({ "SECRET_KEY": new_key, "PREVIOUS_SECRET_KEYS": previous }),
+        format!(
+            "SECRET_KEY={new_key}\nPREVIOUS_SECRET_KEYS={previous}\n\n\
+            Sessions signed with the previous keys keep working until they're removed from PREVIOUS_SECRET_KEYS."
+        ),
+    )
+}
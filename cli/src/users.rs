@@ -0,0 +1,298 @@
+use crate::{Output, generate_password};
+use database::{
+    Db,
+    audit::{AuditEvent, AuditKind, AuditQuery},
+    users::{
+        User,
+        import::{ImportedRow, PortableFormat},
+    },
+};
+use std::sync::Arc;
+use util::{AppError, oauth::OAuthProvider};
+
+pub async fn create_admin(
+    db: &Arc<Db>,
+    tenant: &str,
+    username: String,
+    email: String,
+    display_name: String,
+    password: Option<String>,
+) -> Result<Output, AppError> {
+    util::validation::is_username_valid(&username)?;
+    util::validation::is_email_valid(&email)?;
+    util::validation::is_display_name_valid(&display_name)?;
+    let generated = password.is_none();
+    let password = password.unwrap_or_else(generate_password);
+    util::validation::is_password_strong(&password)?;
+
+    db.get_tenant(tenant).await?.ok_or(AppError::BadReq("Unknown tenant"))?;
+    let user = User {
+        id: uuid::Uuid::new_v4(),
+        display_name,
+        email,
+        birth_date: None,
+        password: Some(password.clone()),
+        username,
+        banner: None,
+        icon: None,
+        bio: None,
+        legal_name: None,
+        gender: None,
+        phone: None,
+        country: None,
+        oauth_provider: OAuthProvider::None,
+        created: time::OffsetDateTime::now_utc(),
+        bind_sessions: false,
+        status: database::users::AccountStatus::Active,
+        tenant_id: tenant.to_string(),
+        restricted: false,
+        birth_date_changes: 0,
+    };
+    db.create_user(&user).await?;
+    db.assign_role(user.id, "admin", None).await?;
+    db.record_audit(cli_action(user.id, "create_admin")).await;
+
+    let mut text = format!("Created admin @{} ({})", user.username, user.email);
+    if generated {
+        text.push_str(&format!("\nPassword: {password}"));
+    }
+    Ok(Output::new(
+        serde_json::json!({
+            "id": user.id.to_string(),
+            "username": user.username,
+            "email": user.email,
+            "password": generated.then_some(password),
+        }),
+        text,
+    ))
+}
+
+pub async fn grant_role(
+    db: &Arc<Db>,
+    tenant: &str,
+    username: &str,
+    role: &str,
+) -> Result<Output, AppError> {
+    let user = db.get_user_by_username(tenant, username).await?;
+    db.assign_role(user.id, role, None).await?;
+    db.notify_user_changed(user.id).await?;
+    db.record_audit(cli_action(user.id, "grant_role").details(serde_json::json!({
+        "action": "grant_role",
+        "role": role,
+        "via": "cli",
+    })))
+    .await;
+    roles_output(db, &user, format!("Granted `{role}` to @{username}")).await
+}
+
+pub async fn revoke_role(
+    db: &Arc<Db>,
+    tenant: &str,
+    username: &str,
+    role: &str,
+) -> Result<Output, AppError> {
+    let user = db.get_user_by_username(tenant, username).await?;
+    db.revoke_role(user.id, role).await?;
+    db.notify_user_changed(user.id).await?;
+    db.record_audit(cli_action(user.id, "revoke_role").details(serde_json::json!({
+        "action": "revoke_role",
+        "role": role,
+        "via": "cli",
+    })))
+    .await;
+    roles_output(db, &user, format!("Revoked `{role}` from @{username}")).await
+}
+
+/// audit event for a change made from the cli (no actor or client)
+fn cli_action(user_id: uuid::Uuid, action: &str) -> AuditEvent {
+    AuditEvent::new(AuditKind::AdminAction, Some(user_id))
+        .details(serde_json::json!({ "action": action, "via": "cli" }))
+}
+
+async fn roles_output(db: &Arc<Db>, user: &User, message: String) -> Result<Output, AppError> {
+    let roles = db.get_user_roles(user.id).await?;
+    Ok(Output::new(
+        serde_json::json!({ "username": user.username, "roles": roles }),
+        format!("{message}\nRoles: {}", roles.join(", ")),
+    ))
+}
+
+pub async fn reset_password(
+    db: &Arc<Db>,
+    tenant: &str,
+    username: &str,
+    password: Option<String>,
+) -> Result<Output, AppError> {
+    let generated = password.is_none();
+    let password = password.unwrap_or_else(generate_password);
+    util::validation::is_password_strong(&password)?;
+
+    let user = db.get_user_by_username(tenant, username).await?;
+    db.update_password(tenant, &user.email, &password).await?;
+    let revoked = db.revoke_user_sessions(user.id).await?;
+    db.notify_sessions_revoked(user.id).await?;
+    db.record_audit(cli_action(user.id, "reset_password")).await;
+
+    let mut text = format!("Reset the password of @{username} and revoked {revoked} sessions");
+    if generated {
+        text.push_str(&format!("\nPassword: {password}"));
+    }
+    Ok(Output::new(
+        serde_json::json!({
+            "username": username,
+            "revoked_sessions": revoked,
+            "password": generated.then_some(password),
+        }),
+        text,
+    ))
+}
+
+pub async fn revoke_sessions(
+    db: &Arc<Db>,
+    tenant: &str,
+    username: &str,
+) -> Result<Output, AppError> {
+    let user = db.get_user_by_username(tenant, username).await?;
+    let revoked = db.revoke_user_sessions(user.id).await?;
+    db.notify_sessions_revoked(user.id).await?;
+    db.record_audit(cli_action(user.id, "revoke_sessions")).await;
+    Ok(Output::new(
+        serde_json::json!({ "username": username, "revoked_sessions": revoked }),
+        format!("Revoked {revoked} sessions of @{username}"),
+    ))
+}
+
+/// exports the profile, roles, sessions and audit events of a user (never the password)
+pub async fn export_user(db: &Arc<Db>, tenant: &str, username: &str) -> Result<Output, AppError> {
+    let user = db.get_user_by_username(tenant, username).await?;
+    let roles = db.get_user_roles(user.id).await?;
+    let sessions = db.get_user_sessions(user.id, time::OffsetDateTime::now_utc()).await?;
+
+    let mut events = vec![];
+    let mut query = AuditQuery { user_id: Some(user.id), limit: 500, ..Default::default() };
+    loop {
+        let page = db.list_audit_events(&query).await?;
+        let Some(last) = page.last() else { break };
+        query.before_id = Some(last.id);
+        events.extend(page);
+    }
+
+    let profile = serde_json::json!({
+        "id": user.id.to_string(),
+        "username": user.username,
+        "email": user.email,
+        "display_name": user.display_name,
+        "birth_date": user.birth_date.map(|v| v.to_string()),
+        "bio": user.bio,
+        "legal_name": user.legal_name,
+        "gender": user.gender,
+        "phone": user.phone,
+        "country": user.country,
+        "icon": user.icon,
+        "banner": user.banner,
+        "oauth_provider": user.oauth_provider.get_str(),
+        "status": user.status.get_str(),
+        "restricted": user.restricted,
+        "bind_sessions": user.bind_sessions,
+        "created": user.created.to_string(),
+    });
+    let session_list = sessions
+        .iter()
+        .map(|s| {
+            serde_json::json!({
+                "unsigned_ssid": s.unsigned_ssid.to_string(),
+                "user_agent": s.user_agent,
+                "ip_address": s.ip_address.to_string(),
+                "created_at": s.created_at.to_string(),
+                "last_used": s.last_used.to_string(),
+                "expires_at": s.expires_at.to_string(),
+            })
+        })
+        .collect::<Vec<_>>();
+    let event_list = events
+        .iter()
+        .map(|e| {
+            serde_json::json!({
+                "occurred_at": e.occurred_at.to_string(),
+                "kind": e.kind,
+                "actor_id": e.actor_id.map(|v| v.to_string()),
+                "ip_address": e.ip_address.map(|v| v.to_string()),
+                "user_agent": e.user_agent,
+                "details": e.details,
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let mut text = String::new();
+    if let Some(fields) = profile.as_object() {
+        for (key, value) in fields {
+            text.push_str(&format!("{key}: {value}\n"));
+        }
+    }
+    text.push_str(&format!("roles: {}\n\nsessions ({}):\n", roles.join(", "), sessions.len()));
+    for s in sessions.iter() {
+        text.push_str(&format!(
+            "  {} last used {} from {} ({})\n",
+            s.unsigned_ssid,
+            s.last_used,
+            s.ip_address,
+            s.user_agent.as_deref().unwrap_or("unknown client")
+        ));
+    }
+    text.push_str(&format!("\naudit events ({}):\n", events.len()));
+    for e in events.iter() {
+        text.push_str(&format!("  {} {}\n", e.occurred_at, e.kind));
+    }
+
+    Ok(Output::new(
+        serde_json::json!({
+            "profile": profile,
+            "roles": roles,
+            "sessions": session_list,
+            "audit_events": event_list,
+        }),
+        text.trim_end(),
+    ))
+}
+
+/// imports users from `file`, a failing row doesn't stop the others
+pub async fn import_users(
+    db: &Arc<Db>,
+    tenant: &str,
+    file: &std::path::Path,
+    format: Option<String>,
+) -> Result<Output, AppError> {
+    let format = match format.as_deref() {
+        Some(format) => PortableFormat::try_from(format)?,
+        None => match file.extension().and_then(|v| v.to_str()) {
+            Some("csv") => PortableFormat::Csv,
+            _ => PortableFormat::JsonLines,
+        },
+    };
+    let data = std::fs::read(file).map_err(|e| AppError::InvalidDataFmt(e.to_string()))?;
+
+    db.get_tenant(tenant).await?.ok_or(AppError::BadReq("Unknown tenant"))?;
+    let rows = db.import_users(tenant, format, &data).await;
+    let imported = rows.iter().filter(|v| v.result.is_ok()).count();
+    let failed = rows.len() - imported;
+    let event = AuditEvent::new(AuditKind::AdminAction, None).details(serde_json::json!({
+        "action": "import_users",
+        "tenant": tenant,
+        "imported": imported,
+        "failed": failed,
+        "via": "cli",
+    }));
+    db.record_audit(event).await;
+
+    let mut text = format!("Imported {imported} users, {failed} failed");
+    for row in rows.iter() {
+        if let Err(e) = &row.result {
+            text.push_str(&format!("\n  row {}: {} ({})", row.row, e.message(), e.get_str()));
+        }
+    }
+    let row_list = rows.iter().map(ImportedRow::to_json).collect::<Vec<_>>();
+    Ok(Output::new(
+        serde_json::json!({ "imported": imported, "failed": failed, "rows": row_list }),
+        text,
+    ))
+}
@@ -0,0 +1,143 @@
+//! runs the cli binary against the database of the configuration (`CONFIG_FILE`, `config.toml`
+//! or the environment), like the server's integration tests
+
+use serde_json::Value;
+use std::process::Command;
+
+const PASSWORD: &str = "Passw0rd!x";
+
+/// runs the cli with `--json` and returns its exit status and output
+fn cli(args: &[&str]) -> (bool, Value) {
+    let output = Command::new(env!("CARGO_BIN_EXE_cli")).arg("--json").args(args).output().unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let json = serde_json::from_str(&stdout)
+        .unwrap_or_else(|_| panic!("{stdout}{}", String::from_utf8_lossy(&output.stderr)));
+    (output.status.success(), json)
+}
+
+fn ok(args: &[&str]) -> Value {
+    let (success, json) = cli(args);
+    assert!(success, "{json}");
+    json
+}
+
+/// creates an admin with a random username and returns it
+fn create_admin() -> String {
+    let username = format!("u{}", &uuid::Uuid::new_v4().simple().to_string()[..12]);
+    let email = format!("{username}@example.com");
+    let created =
+        ok(&["create-admin", "--username", &username, "--email", &email, "--password", PASSWORD]);
+    assert_eq!(created["username"], username.as_str());
+    assert_eq!(created["password"], Value::Null);
+    username
+}
+
+#[test]
+fn generates_a_key() {
+    let first = ok(&["generate-key"]);
+    let second = ok(&["generate-key"]);
+    let key = first["SECRET_KEY"].as_str().unwrap();
+    assert_eq!(key.len(), 64);
+    assert_ne!(first["SECRET_KEY"], second["SECRET_KEY"]);
+    // the current key is kept for the sessions it signed
+    assert!(!first["PREVIOUS_SECRET_KEYS"].as_str().unwrap().is_empty());
+    assert!(!first["PREVIOUS_SECRET_KEYS"].as_str().unwrap().contains(key));
+}
+
+#[test]
+fn applies_every_migration() {
+    let migrations = ok(&["migrate"]);
+    let migrations = migrations["migrations"].as_array().unwrap();
+    assert!(!migrations.is_empty());
+    assert!(migrations.iter().all(|m| m["applied"] == true), "{migrations:?}");
+}
+
+#[test]
+fn grants_and_revokes_roles() {
+    let username = create_admin();
+
+    let revoked = ok(&["revoke-role", &username, "admin"]);
+    assert_eq!(revoked["roles"], serde_json::json!([]));
+    let granted = ok(&["grant-role", &username, "admin"]);
+    assert_eq!(granted["roles"], serde_json::json!(["admin"]));
+}
+
+#[test]
+fn resets_a_password() {
+    let username = create_admin();
+
+    let reset = ok(&["reset-password", &username]);
+    let password = reset["password"].as_str().unwrap();
+    assert!(util::validation::is_password_strong(password).is_ok());
+    assert_eq!(reset["revoked_sessions"], 0);
+
+    let reset = ok(&["reset-password", &username, "--password", PASSWORD]);
+    assert_eq!(reset["password"], Value::Null);
+    let (success, _) = cli(&["reset-password", &username, "--password", "weak"]);
+    assert!(!success);
+}
+
+#[test]
+fn revokes_sessions() {
+    let username = create_admin();
+    let revoked = ok(&["revoke-sessions", &username]);
+    assert_eq!(revoked["revoked_sessions"], 0);
+}
+
+#[test]
+fn exports_a_user() {
+    let username = create_admin();
+    ok(&["revoke-sessions", &username]);
+
+    let export = ok(&["export-user", &username]);
+    assert_eq!(export["profile"]["username"], username.as_str());
+    assert_eq!(export["roles"], serde_json::json!(["admin"]));
+    assert!(export["profile"].get("password").is_none());
+    let actions = export["audit_events"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|e| e["details"]["action"].as_str().unwrap_or_default().to_owned())
+        .collect::<Vec<_>>();
+    assert!(actions.contains(&"create_admin".to_owned()), "{actions:?}");
+    assert!(actions.contains(&"revoke_sessions".to_owned()), "{actions:?}");
+}
+
+#[test]
+fn imports_users() {
+    let username = format!("u{}", &uuid::Uuid::new_v4().simple().to_string()[..12]);
+    let rows = [
+        serde_json::json!({
+            "email": format!("{username}@example.com"),
+            "username": username,
+            "display_name": "Imported",
+        }),
+        serde_json::json!({ "email": "not an email", "username": "x", "display_name": "X" }),
+    ];
+    let file = std::env::temp_dir().join(format!("{username}.jsonl"));
+    let lines = rows.iter().map(Value::to_string).collect::<Vec<_>>().join("\n");
+    std::fs::write(&file, lines).unwrap();
+
+    let imported = ok(&["import-users", file.to_str().unwrap()]);
+    std::fs::remove_file(&file).unwrap();
+    assert_eq!(imported["imported"], 1);
+    assert_eq!(imported["failed"], 1);
+    assert_eq!(imported["rows"][1]["error"], "invalid");
+    assert_eq!(ok(&["export-user", &username])["profile"]["display_name"], "Imported");
+}
+
+#[test]
+fn purges_deleted_users() {
+    let purged = ok(&["purge-deleted", "--older-than-days", "36500"]);
+    assert!(purged["purged"].is_u64());
+}
+
+#[test]
+fn reports_errors() {
+    let (success, json) = cli(&["grant-role", "nobody-with-this-name", "admin"]);
+    assert!(!success);
+    assert!(json["error"].is_string());
+
+    let (success, json) = cli(&["--tenant", "no-such-tenant", "revoke-sessions", "x"]);
+    assert!(!success, "{json}");
+}
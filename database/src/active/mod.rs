@@ -19,7 +19,8 @@ impl crate::Db {
         self: &Arc<Self>,
         parsed_session: &ParsedSession,
     ) -> Option<(UserData, bool)> {
-        let arc_wrapped = self.active.get(&parsed_session.user_id)?;
+        let arc_wrapped =
+            util::metrics::cache_lookup("active", self.active.get(&parsed_session.user_id))?;
         let mut flag = false;
         let guard = arc_wrapped.lock().unwrap();
         for i in guard.1.iter() {
@@ -31,6 +32,23 @@ impl crate::Db {
         Some((arc_wrapped, flag))
     }
 
+    /// removes the sessions that matches `unsigned_ssids` from the cached User with `user_id`
+    pub fn remove_active_sessions(
+        self: &Arc<Self>,
+        user_id: sqlx::types::Uuid,
+        unsigned_ssids: &[sqlx::types::Uuid],
+    ) {
+        let Some(arc_wrapped) = self.active.get(&user_id) else {
+            return;
+        };
+        let mut guard = arc_wrapped.lock().unwrap();
+        guard.1.retain(|v| !unsigned_ssids.contains(&v.unsigned_ssid));
+        if guard.1.is_empty() {
+            drop(guard);
+            self.active.remove(&user_id);
+        }
+    }
+
     pub fn remove_active_user(self: &Arc<Self>, parsed_session: &ParsedSession) -> Option<UserData> {
         let arc_wrapped = self.active.get(&parsed_session.user_id)?;
         let mut guard = arc_wrapped.lock().unwrap();
@@ -1,5 +1,7 @@
+use crate::{legal::AcceptedDocuments, users::User};
 use moka::sync::Cache;
 use std::{net::SocketAddr, time::Duration};
+use util::AppError;
 
 mod post_oidc;
 mod pre_oidc;
@@ -9,9 +11,9 @@ mod updating;
 
 pub struct Applications {
     socket_index: Cache<SocketAddr, DropType>,
-    registrants: Cache<String, RegistrantEntry>, // Email [post_oidc, registration, updating]
-    oidconnect: Cache<String, OidcInfo>,         // CSRF State [pre_oidc]
-    passwd_reset: Cache<String, String>,         // Code/Email [recovering]
+    registrants: Cache<String, RegistrantEntry>, // Tenant+Email [post_oidc, registration, updating]
+    oidconnect: Cache<String, OidcInfo>,         // Tenant+CSRF State [pre_oidc]
+    passwd_reset: Cache<String, String>,         // Code/Tenant+Email [recovering]
 }
 
 #[derive(Clone)]
@@ -22,12 +24,15 @@ pub enum DropType {
 #[derive(Clone)]
 pub struct RegistrantEntry {
     pub socket_addr: std::net::SocketAddr,
+    pub created_at: std::time::Instant,
     pub display_name: Option<String>,
     pub password: Option<String>,
     pub icon: Option<String>,
     pub phone: Option<String>,
     pub oauth_provider: util::oauth::OAuthProvider,
     pub status: RegistrantStatus,
+    /// used up when the user is created
+    pub invite_code: Option<String>,
 }
 
 #[derive(PartialEq, Debug, Clone)]
@@ -46,6 +51,16 @@ pub struct OidcInfo {
     pub code_verifier: String,
     pub nonce: String,
     pub provider: util::oauth::OAuthProvider,
+    pub invite_code: Option<String>,
+}
+
+// emails (and the other keys) are only unique within a tenant
+fn scoped_key(tenant_id: &str, email: &str) -> String {
+    format!("{tenant_id}\n{email}")
+}
+
+fn split_key(key: &str) -> (&str, &str) {
+    key.split_once('\n').unwrap_or((crate::tenants::DEFAULT_TENANT, key))
 }
 
 impl Applications {
@@ -71,32 +86,111 @@ impl Applications {
         }
     }
 
-    fn insert_registrant(&self, email: String, metadata: RegistrantEntry) {
-        self.socket_index.insert(metadata.socket_addr, DropType::Registrant(email.clone()));
-        self.registrants.insert(email, metadata);
+    fn insert_registrant(&self, tenant_id: &str, email: &str, metadata: RegistrantEntry) {
+        let key = scoped_key(tenant_id, email);
+        self.socket_index.insert(metadata.socket_addr, DropType::Registrant(key.clone()));
+        self.registrants.insert(key, metadata);
+    }
+
+    fn get_registrant(&self, tenant_id: &str, email: &str) -> Option<RegistrantEntry> {
+        let key = scoped_key(tenant_id, email);
+        util::metrics::cache_lookup("registrants", self.registrants.get(&key))
     }
 
-    fn remove_registrant(&self, email: &str) -> Option<RegistrantEntry> {
-        let drop_type = self.registrants.remove(email);
+    fn remove_registrant(&self, tenant_id: &str, email: &str) -> Option<RegistrantEntry> {
+        self.remove_registrant_key(&scoped_key(tenant_id, email))
+    }
+
+    fn remove_registrant_key(&self, key: &str) -> Option<RegistrantEntry> {
+        let drop_type = self.registrants.remove(key);
         if let Some(entry) = drop_type.as_ref() {
             self.socket_index.remove(&entry.socket_addr);
         }
         drop_type
     }
 
-    pub fn is_email_present(&self, email: &str) -> bool {
-        self.registrants.contains_key(email)
+    /// returns the approximate number of entries of each cache
+    pub(crate) fn entry_counts(&self) -> [(&'static str, u64); 4] {
+        [
+            ("socket_index", self.socket_index.entry_count()),
+            ("registrants", self.registrants.entry_count()),
+            ("oidconnect", self.oidconnect.entry_count()),
+            ("passwd_reset", self.passwd_reset.entry_count()),
+        ]
+    }
+
+    pub fn is_email_present(&self, tenant_id: &str, email: &str) -> bool {
+        self.registrants.contains_key(&scoped_key(tenant_id, email))
+    }
+
+    /// removes registrants that haven't verified their email within `max_age`
+    fn prune_unverified(&self, max_age: Duration) -> u64 {
+        let stale = self
+            .registrants
+            .iter()
+            .filter(|(_, entry)| {
+                matches!(entry.status, RegistrantStatus::Created(_))
+                    && entry.created_at.elapsed() > max_age
+            })
+            .map(|(key, _)| key)
+            .collect::<Vec<_>>();
+        for key in stale.iter() {
+            self.remove_registrant_key(key);
+        }
+        stale.len() as u64
     }
 }
 
 impl crate::Db {
+    /// removes unverified registrants older than `max_age` (returns the count removed)
+    pub fn prune_unverified_registrants(self: &std::sync::Arc<Self>, max_age: Duration) -> u64 {
+        self.applications.prune_unverified(max_age)
+    }
+
+    /// returns the approximate number of registrations in progress
+    pub fn pending_registrants_count(self: &std::sync::Arc<Self>) -> u64 {
+        self.applications.registrants.entry_count()
+    }
+
+    /// returns the approximate number of unused password reset codes
+    pub fn pending_password_resets_count(self: &std::sync::Arc<Self>) -> u64 {
+        // each reset is stored both by code and by email
+        self.applications.passwd_reset.entry_count() / 2
+    }
+
+    /// adds the user of a finished application, records the legal documents it accepted and uses
+    /// up its invite code in one transaction
+    ///
+    /// the invite is only redeemed once the user has been inserted, so a taken username or email
+    /// leaves the code untouched
+    async fn create_applicant(
+        self: &std::sync::Arc<Self>,
+        user: &User,
+        invite_code: Option<&str>,
+        accepted: &AcceptedDocuments<'_>,
+    ) -> Result<(), AppError> {
+        let mut tx = self.pool.begin().await.map_err(|e| {
+            tracing::error!("{:?}", e);
+            AppError::ServerError
+        })?;
+        crate::users::insert_user(&mut *tx, user).await?;
+        crate::legal::insert_legal_acceptances(&mut tx, user.id, accepted).await?;
+        if let Some(code) = invite_code {
+            crate::tenants::invites::redeem_invite(&mut tx, &user.tenant_id, code).await?;
+        }
+        tx.commit().await.map_err(|e| {
+            tracing::error!("{:?}", e);
+            AppError::ServerError
+        })
+    }
+
     /// This method is implemented like this to be extensible on new feature additions
     #[inline]
     pub fn drop_application(self: &std::sync::Arc<Self>, socket_addr: &SocketAddr) {
         if let Some(entry) = self.applications.socket_index.remove(socket_addr) {
             match entry {
-                DropType::Registrant(email) => {
-                    self.applications.registrants.remove(&email);
+                DropType::Registrant(key) => {
+                    self.applications.registrants.remove(&key);
                 }
             }
         }
@@ -1,43 +1,57 @@
 use super::{RegistrantEntry, RegistrantStatus};
-use crate::users::User;
+use crate::{legal::AcceptedDocuments, users::User};
 use sqlx::types::time::OffsetDateTime;
 use std::{net::SocketAddr, sync::Arc};
 use util::AppError;
 
 // sub steps for registering an user
 impl crate::Db {
+    #[allow(clippy::too_many_arguments)]
     pub async fn create_registrant_oidc(
         self: &Arc<Self>,
+        tenant_id: &str,
         socket_addr: SocketAddr,
         name: String,
         email: String,
         icon: String,
         oauth_provider: util::oauth::OAuthProvider,
+        invite_code: Option<String>,
     ) -> Result<(), AppError> {
-        self.is_email_available(&email).await?;
+        self.is_email_available(tenant_id, &email).await?;
+        self.check_registration_policy(tenant_id, &email, invite_code.as_deref()).await?;
         self.applications.insert_registrant(
-            email,
+            tenant_id,
+            &email,
             RegistrantEntry {
                 socket_addr,
+                created_at: std::time::Instant::now(),
                 display_name: Some(name),
                 password: None,
                 icon: Some(icon),
                 phone: None,
                 oauth_provider,
                 status: RegistrantStatus::OpenIDConnected,
+                invite_code,
             },
         );
         Ok(())
     }
 
+    /// `restricted` tells if the user is under the age threshold of their country
+    #[allow(clippy::too_many_arguments)]
     pub async fn finish_oidc_application(
         self: &Arc<Self>,
+        tenant_id: &str,
         email: String,
         username: String,
+        birth_date: Option<OffsetDateTime>,
+        country: Option<String>,
+        restricted: bool,
+        accepted: &AcceptedDocuments<'_>,
     ) -> Result<User, AppError> {
-        self.is_username_available(&username).await?;
+        self.is_username_available(tenant_id, &username).await?;
         let mut registrant =
-            self.applications.registrants.get(&email).ok_or(AppError::UserNotFound)?;
+            self.applications.get_registrant(tenant_id, &email).ok_or(AppError::UserNotFound)?;
 
         let id = sqlx::types::Uuid::new_v4();
         // creating a new object in the bucket from the cdn url
@@ -72,7 +86,7 @@ impl crate::Db {
             id,
             display_name: registrant.display_name.unwrap(),
             email,
-            birth_date: None,
+            birth_date,
             password: None,
             username,
             banner: None,
@@ -81,12 +95,17 @@ impl crate::Db {
             legal_name: None,
             gender: None,
             phone: None,
-            country: None,
+            country,
             oauth_provider: registrant.oauth_provider,
             created: OffsetDateTime::now_utc(),
+            bind_sessions: false,
+            status: crate::users::AccountStatus::Active,
+            tenant_id: tenant_id.to_string(),
+            restricted,
+            birth_date_changes: 0,
         };
-        self.create_user_forced(&user).await;
-        self.applications.remove_registrant(&user.email);
+        self.create_applicant(&user, registrant.invite_code.as_deref(), accepted).await?;
+        self.applications.remove_registrant(tenant_id, &user.email);
         Ok(user)
     }
 }
@@ -5,25 +5,35 @@ use util::oauth::OAuthProvider;
 // implementation block for those users who are authenticating using open_id_connect
 impl crate::Db {
     #[inline]
+    #[allow(clippy::too_many_arguments)]
     pub fn add_oidc_info(
         self: &Arc<Self>,
+        tenant_id: &str,
         socket_addr: SocketAddr,
         csrf_state: String,
         code_verifier: String,
         nonce: String,
         provider: OAuthProvider,
+        invite_code: Option<String>,
     ) {
-        let oauth_info = OidcInfo { socket_addr, code_verifier, nonce, provider };
-        self.applications.oidconnect.insert(csrf_state, oauth_info);
+        let oauth_info = OidcInfo { socket_addr, code_verifier, nonce, provider, invite_code };
+        // the callback has to reach the tenant that started the login
+        let key = super::scoped_key(tenant_id, &csrf_state);
+        self.applications.oidconnect.insert(key, oauth_info);
     }
 
     #[inline]
-    pub fn get_oidc_info(self: &Arc<Self>, csrf_state: &str) -> Option<OidcInfo> {
-        self.applications.oidconnect.get(csrf_state)
+    pub fn get_oidc_info(self: &Arc<Self>, tenant_id: &str, csrf_state: &str) -> Option<OidcInfo> {
+        let key = super::scoped_key(tenant_id, csrf_state);
+        util::metrics::cache_lookup("oidconnect", self.applications.oidconnect.get(&key))
     }
 
     #[inline]
-    pub fn remove_oidc_info(self: &Arc<Self>, csrf_state: &str) -> Option<OidcInfo> {
-        self.applications.oidconnect.remove(csrf_state)
+    pub fn remove_oidc_info(
+        self: &Arc<Self>,
+        tenant_id: &str,
+        csrf_state: &str,
+    ) -> Option<OidcInfo> {
+        self.applications.oidconnect.remove(&super::scoped_key(tenant_id, csrf_state))
     }
 }
@@ -5,41 +5,47 @@ use util::AppError;
 impl crate::Db {
     pub fn request_password_reset(
         self: &Arc<Self>,
+        tenant_id: &str,
         socket_addr: SocketAddr,
         email: String,
         code: String,
     ) {
-        self.applications.passwd_reset.get(&email).inspect(|code| {
+        let key = super::scoped_key(tenant_id, &email);
+        self.applications.passwd_reset.get(&key).inspect(|code| {
             self.applications.passwd_reset.invalidate(code);
         });
         tracing::info!(
-            "[Password Reset Request] Email: {email}, Code: {code}, Socket: {}",
+            "[Password Reset Request] Tenant: {tenant_id}, Email: {email}, Socket: {}",
             socket_addr.to_string()
         );
-        self.applications.passwd_reset.insert(code.clone(), email.clone());
-        self.applications.passwd_reset.insert(email, code);
+        self.applications.passwd_reset.insert(code.clone(), key.clone());
+        self.applications.passwd_reset.insert(key, code);
     }
 
     // updates password of the given user (returns email)
     pub async fn reset_password(
         self: &Arc<Self>,
+        tenant_id: &str,
         socket_addr: SocketAddr,
         code: &str,
         password: &str,
     ) -> Result<String, AppError> {
-        match self.applications.passwd_reset.get(code) {
-            Some(email) => {
-                self.update_password(&email, password).await?;
-                self.applications.passwd_reset.invalidate(&email);
+        let key =
+            util::metrics::cache_lookup("passwd_reset", self.applications.passwd_reset.get(code))
+                .unwrap_or_default();
+        match super::split_key(&key) {
+            // codes sent by another tenant are unknown here
+            (key_tenant, email) if key_tenant == tenant_id && !email.is_empty() => {
+                self.update_password(tenant_id, email, password).await?;
+                self.applications.passwd_reset.invalidate(&key);
                 self.applications.passwd_reset.invalidate(code);
                 tracing::info!(
-                    "[Password Reset] Email: {}, Password: {password}, Socket: {}",
-                    &email,
+                    "[Password Reset] Tenant: {tenant_id}, Email: {email}, Socket: {}",
                     socket_addr.to_string()
                 );
-                Ok(email)
+                Ok(email.to_string())
             }
-            None => Err(AppError::BadReq("Password Reset code not found")),
+            _ => Err(AppError::BadReq("Password Reset code not found")),
         }
     }
 }
@@ -1,5 +1,5 @@
 use super::{RegistrantEntry, RegistrantStatus};
-use crate::users::User;
+use crate::{legal::AcceptedDocuments, users::User};
 use sqlx::types::time::OffsetDateTime;
 use std::{net::SocketAddr, sync::Arc};
 use util::AppError;
@@ -8,22 +8,28 @@ use util::AppError;
 impl crate::Db {
     pub async fn create_registrant(
         self: &Arc<Self>,
+        tenant_id: &str,
         socket: SocketAddr,
         name: String,
         email: String,
         otp: String,
+        invite_code: Option<String>,
     ) -> Result<(), AppError> {
-        self.is_email_available(&email).await?;
+        self.is_email_available(tenant_id, &email).await?;
+        self.check_registration_policy(tenant_id, &email, invite_code.as_deref()).await?;
         self.applications.insert_registrant(
-            email,
+            tenant_id,
+            &email,
             RegistrantEntry {
                 socket_addr: socket,
+                created_at: std::time::Instant::now(),
                 display_name: Some(name),
                 password: None,
                 icon: None,
                 phone: None,
                 oauth_provider: util::oauth::OAuthProvider::None,
                 status: RegistrantStatus::Created(otp),
+                invite_code,
             },
         );
         Ok(())
@@ -31,12 +37,13 @@ impl crate::Db {
 
     pub async fn update_registrant_otp(
         self: &Arc<Self>,
+        tenant_id: &str,
         email: &str,
         otp: String,
     ) -> Result<(), AppError> {
-        if let Some(mut entry) = self.applications.registrants.get(email) {
+        if let Some(mut entry) = self.applications.get_registrant(tenant_id, email) {
             entry.status = RegistrantStatus::Created(otp);
-            self.applications.insert_registrant(email.to_string(), entry);
+            self.applications.insert_registrant(tenant_id, email, entry);
             Ok(())
         } else {
             Err(AppError::UserNotFound)
@@ -45,13 +52,15 @@ impl crate::Db {
 
     pub async fn verify_registrant_email(
         self: &Arc<Self>,
+        tenant_id: &str,
         email: &str,
         otp: &str,
     ) -> Result<(), AppError> {
-        let entry = self.applications.registrants.get(email).ok_or(AppError::UserNotFound)?;
+        let entry =
+            self.applications.get_registrant(tenant_id, email).ok_or(AppError::UserNotFound)?;
         match &entry.status {
             RegistrantStatus::Created(db_otp) if db_otp == otp => {
-                self.applications.insert_registrant(email.to_string(), entry);
+                self.applications.insert_registrant(tenant_id, email, entry);
                 Ok(())
             }
             RegistrantStatus::Created(_) => Err(AppError::InvalidOTP),
@@ -61,31 +70,39 @@ impl crate::Db {
 
     pub async fn set_registrant_password(
         self: &Arc<Self>,
+        tenant_id: &str,
         email: &str,
         password: String,
     ) -> Result<(), AppError> {
-        if let Some(mut entry) = self.applications.registrants.get(email) {
+        if let Some(mut entry) = self.applications.get_registrant(tenant_id, email) {
             entry.password = Some(password);
-            self.applications.insert_registrant(email.to_string(), entry);
+            self.applications.insert_registrant(tenant_id, email, entry);
             Ok(())
         } else {
             Err(AppError::UserNotFound)
         }
     }
 
+    /// `restricted` tells if the user is under the age threshold of their country
+    #[allow(clippy::too_many_arguments)]
     pub async fn set_registrant_username(
         self: &Arc<Self>,
+        tenant_id: &str,
         email: String,
         username: String,
+        birth_date: Option<OffsetDateTime>,
+        country: Option<String>,
+        restricted: bool,
+        accepted: &AcceptedDocuments<'_>,
     ) -> Result<User, AppError> {
-        self.is_username_available(&username).await?;
-        let registrant = self.applications.registrants.get(&email).ok_or(AppError::UserNotFound)?;
-
+        self.is_username_available(tenant_id, &username).await?;
+        let registrant =
+            self.applications.get_registrant(tenant_id, &email).ok_or(AppError::UserNotFound)?;
         let user = User {
             id: sqlx::types::Uuid::new_v4(),
             display_name: registrant.display_name.unwrap(),
             email,
-            birth_date: None,
+            birth_date,
             password: registrant.password,
             username,
             banner: None,
@@ -94,12 +111,17 @@ impl crate::Db {
             legal_name: None,
             gender: None,
             phone: None,
-            country: None,
+            country,
             oauth_provider: util::oauth::OAuthProvider::None,
             created: OffsetDateTime::now_utc(),
+            bind_sessions: false,
+            status: crate::users::AccountStatus::Active,
+            tenant_id: tenant_id.to_string(),
+            restricted,
+            birth_date_changes: 0,
         };
-        self.create_user_forced(&user).await;
-        self.applications.remove_registrant(&user.email);
+        self.create_applicant(&user, registrant.invite_code.as_deref(), accepted).await?;
+        self.applications.remove_registrant(tenant_id, &user.email);
         Ok(user)
     }
 }
@@ -6,22 +6,26 @@ use util::AppError;
 impl crate::Db {
     pub async fn request_email_update(
         self: &Arc<Self>,
+        tenant_id: &str,
         socket_addr: SocketAddr,
         old_email: String,
         new_email: String,
         otp: String,
     ) -> Result<(), AppError> {
-        self.is_email_available(&new_email).await?;
+        self.is_email_available(tenant_id, &new_email).await?;
         self.applications.insert_registrant(
-            new_email,
+            tenant_id,
+            &new_email,
             RegistrantEntry {
                 socket_addr,
+                created_at: std::time::Instant::now(),
                 display_name: None,
                 password: None,
                 icon: None,
                 phone: None,
                 oauth_provider: util::oauth::OAuthProvider::None,
                 status: RegistrantStatus::UpdatingEmail { old_email, otp },
+                invite_code: None,
             },
         );
         Ok(())
@@ -30,23 +34,30 @@ impl crate::Db {
     // checks and updates email of the given user
     pub async fn update_email(
         self: &Arc<Self>,
+        tenant_id: &str,
         old_email: &str,
         new_email: String,
         otp: &str,
     ) -> Result<(), AppError> {
-        let entry = self.applications.registrants.get(&new_email).ok_or(AppError::UserNotFound)?;
+        let entry =
+            self.applications.get_registrant(tenant_id, &new_email).ok_or(AppError::UserNotFound)?;
         match &entry.status {
             RegistrantStatus::UpdatingEmail { old_email: mem_old_email, otp: mem_otp }
                 if otp == mem_otp && old_email == mem_old_email =>
             {
-                sqlx::query!("UPDATE users SET email = $1 WHERE email = $2", new_email, old_email)
-                    .execute(&self.pool)
-                    .await
-                    .map_err(|e| {
-                        tracing::error!("{:?}", e);
-                        AppError::ServerError
-                    })?;
-                self.applications.remove_registrant(&new_email);
+                sqlx::query!(
+                    "UPDATE users SET email = $1 WHERE tenant_id = $2 AND email = $3",
+                    new_email,
+                    tenant_id,
+                    old_email
+                )
+                .execute(&self.pool)
+                .await
+                .map_err(|e| {
+                    tracing::error!("{:?}", e);
+                    AppError::ServerError
+                })?;
+                self.applications.remove_registrant(tenant_id, &new_email);
 
                 tracing::info!("[Email Updated] Old: {old_email}, New: {new_email}");
                 Ok(())
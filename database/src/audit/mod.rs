@@ -0,0 +1,215 @@
+use sqlx::types::{Uuid, ipnetwork::IpNetwork, time::OffsetDateTime};
+use std::{net::IpAddr, sync::Arc};
+use util::AppError;
+
+/// security relevant events that are recorded in `audit_events`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AuditKind {
+    LoginSucceeded,
+    LoginFailed,
+    Logout,
+    SessionsRevoked,
+    SessionRefreshed,
+    PasswordChanged,
+    PasswordReset,
+    EmailChanged,
+    UsernameChanged,
+    OidcLinked,
+    SessionBindingChanged,
+    AccountDeleted,
+    AdminAction,
+}
+
+impl AuditKind {
+    pub const ALL: [AuditKind; 13] = [
+        AuditKind::LoginSucceeded,
+        AuditKind::LoginFailed,
+        AuditKind::Logout,
+        AuditKind::SessionsRevoked,
+        AuditKind::SessionRefreshed,
+        AuditKind::PasswordChanged,
+        AuditKind::PasswordReset,
+        AuditKind::EmailChanged,
+        AuditKind::UsernameChanged,
+        AuditKind::OidcLinked,
+        AuditKind::SessionBindingChanged,
+        AuditKind::AccountDeleted,
+        AuditKind::AdminAction,
+    ];
+
+    pub fn get_str(&self) -> &'static str {
+        match self {
+            AuditKind::LoginSucceeded => "login_succeeded",
+            AuditKind::LoginFailed => "login_failed",
+            AuditKind::Logout => "logout",
+            AuditKind::SessionsRevoked => "sessions_revoked",
+            AuditKind::SessionRefreshed => "session_refreshed",
+            AuditKind::PasswordChanged => "password_changed",
+            AuditKind::PasswordReset => "password_reset",
+            AuditKind::EmailChanged => "email_changed",
+            AuditKind::UsernameChanged => "username_changed",
+            AuditKind::OidcLinked => "oidc_linked",
+            AuditKind::SessionBindingChanged => "session_binding_changed",
+            AuditKind::AccountDeleted => "account_deleted",
+            AuditKind::AdminAction => "admin_action",
+        }
+    }
+
+    pub fn from_name(kind: &str) -> Option<Self> {
+        AuditKind::ALL.into_iter().find(|k| k.get_str() == kind)
+    }
+}
+
+/// an event about to be recorded
+///
+/// `user_id` is the user the event is about, `actor_id` the one who caused it
+/// (only set when it's someone else, like an admin)
+#[derive(Clone, Debug)]
+pub struct AuditEvent {
+    pub kind: AuditKind,
+    pub user_id: Option<Uuid>,
+    pub actor_id: Option<Uuid>,
+    pub ip_address: Option<IpAddr>,
+    pub user_agent: Option<String>,
+    pub details: Option<serde_json::Value>,
+}
+
+impl AuditEvent {
+    pub fn new(kind: AuditKind, user_id: Option<Uuid>) -> Self {
+        Self { kind, user_id, actor_id: None, ip_address: None, user_agent: None, details: None }
+    }
+
+    /// sets the client the event came from
+    pub fn client(mut self, ip_address: IpAddr, headers: &axum::http::HeaderMap) -> Self {
+        self.ip_address = Some(ip_address);
+        self.user_agent = headers
+            .get(axum::http::header::USER_AGENT)
+            .map(|v| v.to_str().unwrap_or_default().to_owned());
+        self
+    }
+
+    pub fn actor(mut self, actor_id: Uuid) -> Self {
+        self.actor_id = Some(actor_id);
+        self
+    }
+
+    pub fn details(mut self, details: serde_json::Value) -> Self {
+        self.details = Some(details);
+        self
+    }
+}
+
+/// a recorded event
+#[derive(Clone, Debug)]
+pub struct AuditRecord {
+    pub id: i64,
+    pub occurred_at: OffsetDateTime,
+    pub kind: String,
+    pub user_id: Option<Uuid>,
+    pub actor_id: Option<Uuid>,
+    pub ip_address: Option<IpAddr>,
+    pub user_agent: Option<String>,
+    pub details: Option<serde_json::Value>,
+}
+
+/// filters and page of an audit log query, newest events first
+#[derive(Default)]
+pub struct AuditQuery {
+    pub user_id: Option<Uuid>,
+    pub actor_id: Option<Uuid>,
+    /// only events about users (or by actors) of this tenant
+    pub tenant_id: Option<String>,
+    pub kind: Option<AuditKind>,
+    /// only events older than the event with this id (for paging)
+    pub before_id: Option<i64>,
+    pub limit: i64,
+}
+
+impl crate::Db {
+    /// writes `event` to the audit log
+    ///
+    /// failures are logged but never fail the request that caused the event
+    pub async fn record_audit(self: &Arc<Self>, event: AuditEvent) {
+        let result = sqlx::query!(
+            r#"INSERT INTO audit_events (kind, user_id, actor_id, ip_address, user_agent, details)
+            VALUES ($1, $2, $3, $4, $5, $6)"#,
+            event.kind.get_str(),
+            event.user_id,
+            event.actor_id,
+            event.ip_address.map(IpNetwork::from),
+            event.user_agent,
+            event.details.map(|v| v.to_string()),
+        )
+        .execute(&self.pool)
+        .await;
+
+        if let Err(e) = result {
+            tracing::error!("[Audit Event Lost] kind: {}, {:?}", event.kind.get_str(), e);
+        }
+    }
+
+    /// returns the events matching `query`, newest first
+    pub async fn list_audit_events(
+        self: &Arc<Self>,
+        query: &AuditQuery,
+    ) -> Result<Vec<AuditRecord>, AppError> {
+        let rows = sqlx::query!(
+            r#"SELECT * FROM audit_events
+            WHERE ($1::UUID IS NULL OR user_id = $1)
+                AND ($2::UUID IS NULL OR actor_id = $2)
+                AND ($3::VARCHAR IS NULL OR kind = $3)
+                AND ($4::BIGINT IS NULL OR id < $4)
+                AND ($6::VARCHAR IS NULL OR COALESCE(user_id, actor_id) IN (
+                    SELECT id FROM users WHERE tenant_id = $6
+                    UNION ALL SELECT id FROM deleted_users WHERE tenant_id = $6
+                ))
+            ORDER BY id DESC
+            LIMIT $5"#,
+            query.user_id,
+            query.actor_id,
+            query.kind.map(|k| k.get_str()),
+            query.before_id,
+            query.limit,
+            query.tenant_id,
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("{:?}", e);
+            AppError::ServerError
+        })?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| AuditRecord {
+                id: row.id,
+                occurred_at: row.occurred_at,
+                kind: row.kind,
+                user_id: row.user_id,
+                actor_id: row.actor_id,
+                ip_address: row.ip_address.map(|v| v.ip()),
+                user_agent: row.user_agent,
+                details: row.details.and_then(|v| serde_json::from_str(&v).ok()),
+            })
+            .collect())
+    }
+
+    /// removes the events that occurred before `occurred_before`
+    ///
+    /// returns the number of events removed
+    pub async fn prune_audit_events(
+        self: &Arc<Self>,
+        occurred_before: OffsetDateTime,
+    ) -> Result<u64, AppError> {
+        let result =
+            sqlx::query!("DELETE FROM audit_events WHERE occurred_at < $1", occurred_before)
+                .execute(&self.pool)
+                .await
+                .map_err(|e| {
+                    tracing::error!("{:?}", e);
+                    AppError::ServerError
+                })?;
+
+        Ok(result.rows_affected())
+    }
+}
@@ -37,18 +37,18 @@ struct GetUploadUrlResponse {
     authorization_token: String,
 }
 
-impl Default for BlackBlazeB2 {
-    fn default() -> Self {
+impl From<&util::config::BucketConfig> for BlackBlazeB2 {
+    fn from(config: &util::config::BucketConfig) -> Self {
         Self {
             client: reqwest::Client::new(),
-            account_id: std::env::var("BUCKET_ACCESS_KEY").unwrap(),
-            application_key: std::env::var("BUCKET_SECRET_KEY").unwrap(),
-            endpoint: std::env::var("BUCKET_ENDPOINT").unwrap(),
+            account_id: config.access_key.clone(),
+            application_key: config.secret_key.clone(),
+            endpoint: config.endpoint.clone(),
             auth_token: RwLock::new(None),
-            api_url: RwLock::new(std::env::var("BUCKET_ENDPOINT").unwrap()),
-            name: std::env::var("BUCKET_NAME").unwrap(),
-            bucket_id: std::env::var("BUCKET_ID").unwrap(),
-            public_url: std::env::var("BUCKET_PUBLIC_URL").unwrap(),
+            api_url: RwLock::new(config.endpoint.clone()),
+            name: config.name.clone(),
+            bucket_id: config.id.clone(),
+            public_url: config.public_url.clone(),
         }
     }
 }
@@ -62,7 +62,23 @@ impl BlackBlazeB2 {
                 return Ok(t.clone());
             }
         }
+        self.authorize_account().await
+    }
+
+    /// checks that the bucket is reachable by asking for an upload url with the cached
+    /// authorization, which is renewed once when it has expired
+    pub async fn ping(&self) -> Result<(), AppError> {
+        let had_token = self.auth_token.read().await.is_some();
+        match self.get_upload_url().await {
+            // the expired token was dropped by `get_upload_url`
+            Err(_) if had_token && self.auth_token.read().await.is_none() => {
+                self.get_upload_url().await.map(|_| ())
+            }
+            result => result.map(|_| ()),
+        }
+    }
 
+    async fn authorize_account(&self) -> Result<String, AppError> {
         // Authorize account using the base API URL
         let auth_string = format!("{}:{}", self.account_id, self.application_key);
         let auth_header = format!(
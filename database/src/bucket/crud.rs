@@ -11,7 +11,10 @@ impl crate::Db {
         // checking if the user sent icon is valid or not
         let content_type = util::validation::is_icon_valid(&mut filename, &data)?;
         filename = format!("icon/{_id}-{filename}");
-        self.bucket.upload_image(data, &filename, &content_type).await
+        self.bucket
+            .upload_image(data, &filename, &content_type)
+            .await
+            .inspect_err(|_| util::metrics::BUCKET_UPLOAD_FAILURES.inc())
     }
 
     pub async fn upload_banner(
@@ -23,7 +26,10 @@ impl crate::Db {
         // checking if the user sent banner is valid or not
         let content_type = util::validation::is_banner_valid(&mut filename, &data)?;
         filename = format!("banner/{_id}-{filename}");
-        self.bucket.upload_image(data, &filename, &content_type).await
+        self.bucket
+            .upload_image(data, &filename, &content_type)
+            .await
+            .inspect_err(|_| util::metrics::BUCKET_UPLOAD_FAILURES.inc())
     }
 }
 
@@ -15,16 +15,16 @@ pub struct CloudflareR2 {
     pub public_url: String,
 }
 
-impl Default for CloudflareR2 {
-    fn default() -> Self {
+impl From<&util::config::BucketConfig> for CloudflareR2 {
+    fn from(config: &util::config::BucketConfig) -> Self {
         Self {
             client: reqwest::Client::new(),
-            access_key: std::env::var("BUCKET_ACCESS_KEY").unwrap(),
-            secret_key: std::env::var("BUCKET_SECRET_KEY").unwrap(),
-            endpoint: std::env::var("BUCKET_ENDPOINT").unwrap(),
-            region: std::env::var("BUCKET_REGION").unwrap(),
-            name: std::env::var("BUCKET_NAME").unwrap(),
-            public_url: std::env::var("BUCKET_PUBLIC_URL").unwrap(),
+            access_key: config.access_key.clone(),
+            secret_key: config.secret_key.clone(),
+            endpoint: config.endpoint.clone(),
+            region: config.region.clone(),
+            name: config.name.clone(),
+            public_url: config.public_url.clone(),
         }
     }
 }
@@ -202,6 +202,35 @@ impl CloudflareR2 {
         Ok(format!("{}/{}", self.public_url, filename))
     }
 
+    /// Checks that the bucket is reachable with a HEAD request
+    pub async fn ping(&self) -> Result<(), AppError> {
+        let path = format!("/{}", self.name);
+        let headers = self.get_signed_headers(
+            "HEAD",
+            &path,
+            "",
+            None,
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855", // Empty string SHA256
+        );
+
+        let mut request = self.client.head(format!("{}/{}", self.endpoint, self.name));
+        for (key, value) in headers {
+            request = request.header(key, value);
+        }
+
+        let response = request.send().await.map_err(|e| {
+            tracing::error!("HEAD bucket failed: {:#?}", e);
+            AppError::ServerError
+        })?;
+
+        if !response.status().is_success() {
+            tracing::error!("HEAD bucket error ({})", response.status());
+            return Err(AppError::ServerError);
+        }
+
+        Ok(())
+    }
+
     /// Deletes a file from Cloudflare R2
     pub async fn delete_file(&self, filename: &str) -> Result<(), AppError> {
         let path = format!("/{}/{}", self.name, filename);
@@ -0,0 +1,55 @@
+use sqlx::types::Uuid;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+use tokio::sync::broadcast;
+
+/// number of events a slow subscriber can fall behind before it starts missing them
+const CHANNEL_CAPACITY: usize = 16;
+
+/// changes of a user's account that their connected clients are notified about
+#[derive(Clone, Debug)]
+pub enum SessionEvent {
+    SessionsRevoked { sessions: Vec<Uuid> },
+    AllSessionsRevoked { except: Uuid },
+    ProfileChanged,
+    EmailChanged { email: String },
+    UsernameChanged { username: String },
+    AccountDeleted,
+}
+
+/// one broadcast channel per user with at least one connected client
+#[derive(Default)]
+pub struct EventHub {
+    channels: Mutex<HashMap<Uuid, broadcast::Sender<SessionEvent>>>,
+}
+
+impl crate::Db {
+    /// subscribes to the events of User with `user_id`
+    pub fn subscribe_events(self: &Arc<Self>, user_id: Uuid) -> broadcast::Receiver<SessionEvent> {
+        let mut channels = self.events.channels.lock().unwrap();
+        channels.entry(user_id).or_insert_with(|| broadcast::channel(CHANNEL_CAPACITY).0).subscribe()
+    }
+
+    /// sends `event` to every connected client of User with `user_id`
+    pub fn publish_event(self: &Arc<Self>, user_id: Uuid, event: SessionEvent) {
+        let mut channels = self.events.channels.lock().unwrap();
+        if let Some(sender) = channels.get(&user_id)
+            && sender.send(event).is_err()
+        {
+            // every client has disconnected
+            channels.remove(&user_id);
+        }
+    }
+
+    /// removes the channels that have no connected client left
+    ///
+    /// returns the number of channels removed
+    pub fn prune_event_channels(self: &Arc<Self>) -> u64 {
+        let mut channels = self.events.channels.lock().unwrap();
+        let before = channels.len();
+        channels.retain(|_, sender| sender.receiver_count() > 0);
+        (before - channels.len()) as u64
+    }
+}
@@ -0,0 +1,298 @@
+use sqlx::{
+    PgConnection,
+    types::{Uuid, ipnetwork::IpNetwork, time::OffsetDateTime},
+};
+use std::{collections::HashMap, net::IpAddr, sync::Arc};
+use util::{AppError, PendingDocument};
+
+/// documents users have to accept to use the service
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LegalKind {
+    TermsOfService,
+    PrivacyPolicy,
+}
+
+impl LegalKind {
+    pub const ALL: [LegalKind; 2] = [LegalKind::TermsOfService, LegalKind::PrivacyPolicy];
+
+    pub fn get_str(&self) -> &'static str {
+        match self {
+            LegalKind::TermsOfService => "terms_of_service",
+            LegalKind::PrivacyPolicy => "privacy_policy",
+        }
+    }
+}
+
+impl TryFrom<&str> for LegalKind {
+    type Error = AppError;
+
+    fn try_from(kind: &str) -> Result<Self, Self::Error> {
+        LegalKind::ALL
+            .into_iter()
+            .find(|k| k.get_str() == kind)
+            .ok_or_else(|| AppError::InvalidDataFmt(format!("Unknown legal document: {kind}")))
+    }
+}
+
+/// a published version of a legal document
+#[derive(Clone, Debug, sqlx::FromRow)]
+pub struct LegalDocument {
+    pub tenant_id: String,
+    pub kind: String,
+    pub version: String,
+    pub url: String,
+    pub published_at: OffsetDateTime,
+}
+
+impl From<&LegalDocument> for PendingDocument {
+    fn from(document: &LegalDocument) -> Self {
+        Self {
+            kind: document.kind.clone(),
+            version: document.version.clone(),
+            url: document.url.clone(),
+        }
+    }
+}
+
+/// the documents a user accepts and the client they accepted them from
+pub struct AcceptedDocuments<'a> {
+    pub documents: &'a [LegalDocument],
+    pub ip_address: IpAddr,
+    pub user_agent: Option<&'a str>,
+}
+
+/// a version of a legal document accepted by a user
+#[derive(Clone, Debug)]
+pub struct LegalAcceptance {
+    pub kind: String,
+    pub version: String,
+    pub accepted_at: OffsetDateTime,
+}
+
+// implementation block for the versioned legal documents and their acceptance
+// the current documents of a tenant and the documents a user still has to accept are cached
+// (`tenant:{id}` and `user:{tenant}:{id}`), publishing a version invalidates every entry
+impl crate::Db {
+    /// returns the latest version of each legal document of the tenant
+    pub async fn current_legal_documents(
+        self: &Arc<Self>,
+        tenant_id: &str,
+    ) -> Result<Arc<Vec<LegalDocument>>, AppError> {
+        let key = format!("tenant:{tenant_id}");
+        if let Some(documents) = util::metrics::cache_lookup("legal", self.legal.get(&key)) {
+            return Ok(documents);
+        }
+        let documents = sqlx::query_as!(
+            LegalDocument,
+            r#"SELECT DISTINCT ON (kind) * FROM legal_documents WHERE tenant_id = $1
+            ORDER BY kind, published_at DESC"#,
+            tenant_id
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("{:?}", e);
+            AppError::ServerError
+        })?;
+        let documents = Arc::new(documents);
+        self.legal.insert(key, documents.clone());
+        Ok(documents)
+    }
+
+    /// returns the current documents that User with `user_id` hasn't accepted yet
+    pub async fn pending_legal_documents(
+        self: &Arc<Self>,
+        tenant_id: &str,
+        user_id: Uuid,
+    ) -> Result<Arc<Vec<LegalDocument>>, AppError> {
+        let key = format!("user:{tenant_id}:{user_id}");
+        if let Some(documents) = util::metrics::cache_lookup("legal", self.legal.get(&key)) {
+            return Ok(documents);
+        }
+        let current = self.current_legal_documents(tenant_id).await?;
+        let pending = if current.is_empty() {
+            Arc::new(vec![])
+        } else {
+            let accepted = sqlx::query!(
+                "SELECT kind, version FROM legal_acceptances WHERE user_id = $1",
+                user_id
+            )
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| {
+                tracing::error!("{:?}", e);
+                AppError::ServerError
+            })?;
+            Arc::new(
+                current
+                    .iter()
+                    .filter(|d| !accepted.iter().any(|a| a.kind == d.kind && a.version == d.version))
+                    .cloned()
+                    .collect(),
+            )
+        };
+        self.legal.insert(key, pending.clone());
+        Ok(pending)
+    }
+
+    /// checks that `accepted` (kind => version) covers every current document of the tenant
+    ///
+    /// returns the documents to record once the user exists
+    pub async fn check_legal_acceptance(
+        self: &Arc<Self>,
+        tenant_id: &str,
+        accepted: &HashMap<String, String>,
+    ) -> Result<Arc<Vec<LegalDocument>>, AppError> {
+        let current = self.current_legal_documents(tenant_id).await?;
+        let missing = current
+            .iter()
+            .filter(|d| accepted.get(&d.kind) != Some(&d.version))
+            .map(PendingDocument::from)
+            .collect::<Vec<_>>();
+        if !missing.is_empty() {
+            return Err(AppError::LegalAcceptanceRequired(missing));
+        }
+        Ok(current)
+    }
+
+    /// records that User with `user_id` of the tenant accepted `accepted.documents`
+    pub async fn record_legal_acceptances(
+        self: &Arc<Self>,
+        tenant_id: &str,
+        user_id: Uuid,
+        accepted: &AcceptedDocuments<'_>,
+    ) -> Result<(), AppError> {
+        let mut conn = self.pool.acquire().await.map_err(|e| {
+            tracing::error!("{:?}", e);
+            AppError::ServerError
+        })?;
+        insert_legal_acceptances(&mut conn, user_id, accepted).await?;
+        self.legal.invalidate(&format!("user:{tenant_id}:{user_id}"));
+        Ok(())
+    }
+
+    /// returns the versions accepted by User with `user_id`, newest first
+    pub async fn list_legal_acceptances(
+        self: &Arc<Self>,
+        user_id: Uuid,
+    ) -> Result<Vec<LegalAcceptance>, AppError> {
+        sqlx::query_as!(
+            LegalAcceptance,
+            r#"SELECT kind, version, accepted_at FROM legal_acceptances
+            WHERE user_id = $1 ORDER BY accepted_at DESC"#,
+            user_id
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("{:?}", e);
+            AppError::ServerError
+        })
+    }
+
+    /// returns every version of the legal documents of the tenant with the number of users who
+    /// accepted it, newest first
+    pub async fn list_legal_documents(
+        self: &Arc<Self>,
+        tenant_id: &str,
+    ) -> Result<Vec<(LegalDocument, i64)>, AppError> {
+        let rows = sqlx::query!(
+            r#"SELECT d.tenant_id, d.kind, d.version, d.url, d.published_at,
+                COUNT(a.user_id) as "acceptances!"
+            FROM legal_documents d
+            LEFT JOIN legal_acceptances a
+                ON a.tenant_id = d.tenant_id AND a.kind = d.kind AND a.version = d.version
+            WHERE d.tenant_id = $1
+            GROUP BY d.tenant_id, d.kind, d.version
+            ORDER BY d.published_at DESC"#,
+            tenant_id
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("{:?}", e);
+            AppError::ServerError
+        })?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| {
+                let document = LegalDocument {
+                    tenant_id: row.tenant_id,
+                    kind: row.kind,
+                    version: row.version,
+                    url: row.url,
+                    published_at: row.published_at,
+                };
+                (document, row.acceptances)
+            })
+            .collect())
+    }
+
+    /// publishes a new version of a legal document, every user of the tenant has to accept it
+    /// before using the api again
+    pub async fn publish_legal_document(
+        self: &Arc<Self>,
+        tenant_id: &str,
+        kind: LegalKind,
+        version: &str,
+        url: &str,
+    ) -> Result<(), AppError> {
+        sqlx::query!(
+            "INSERT INTO legal_documents (tenant_id, kind, version, url) VALUES ($1, $2, $3, $4)",
+            tenant_id,
+            kind.get_str(),
+            version,
+            url
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| {
+            if let Some(db_err) = e.as_database_error()
+                && db_err.code() == Some(std::borrow::Cow::Borrowed("23505"))
+            {
+                return AppError::BadReq("This version has already been published");
+            }
+            tracing::error!("{:?}", e);
+            AppError::ServerError
+        })?;
+
+        self.legal.invalidate_all();
+        tracing::info!("[Legal Published] tenant: {tenant_id}, {}: {version}", kind.get_str());
+        Ok(())
+    }
+}
+
+/// inserts the acceptances with `conn`, which lets the registrations record them inside their
+/// transaction
+pub(crate) async fn insert_legal_acceptances(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    accepted: &AcceptedDocuments<'_>,
+) -> Result<(), AppError> {
+    for document in accepted.documents {
+        sqlx::query!(
+            r#"INSERT INTO legal_acceptances
+                (user_id, tenant_id, kind, version, ip_address, user_agent)
+            VALUES ($1, $2, $3, $4, $5, $6) ON CONFLICT DO NOTHING"#,
+            user_id,
+            document.tenant_id,
+            document.kind,
+            document.version,
+            IpNetwork::from(accepted.ip_address),
+            accepted.user_agent
+        )
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| {
+            tracing::error!("{:?}", e);
+            AppError::ServerError
+        })?;
+        tracing::info!(
+            "[Legal Accepted] user_id: {user_id}, {}: {}",
+            document.kind,
+            document.version
+        );
+    }
+    Ok(())
+}
@@ -1,51 +1,113 @@
 use moka::sync::Cache;
-use std::{sync::Arc, time::Duration};
-use tokio::sync::OnceCell;
-use util::session::Session;
+use std::sync::Arc;
+use util::{
+    config::Config,
+    session::{CookiePolicy, Session},
+};
 
 mod active;
 pub mod applications;
+pub mod audit;
 pub mod bucket;
+pub mod events;
+pub mod legal;
+mod notify;
+pub mod organizations;
+pub mod readiness;
+pub mod roles;
 pub mod sessions;
+pub mod tenants;
 pub mod users;
 
 pub type UserData = Arc<std::sync::Mutex<(users::User, Vec<Session>)>>;
 
 pub struct Db {
     pool: sqlx::Pool<sqlx::Postgres>,
-    bucket: bucket::BlackBlazeB2,
+    bucket: Arc<bucket::BlackBlazeB2>,
+    cookies: CookiePolicy,
     // in memory stores
     active: Cache<sqlx::types::Uuid, UserData>,
+    permissions:
+        Cache<sqlx::types::Uuid, Arc<std::collections::HashSet<util::permission::Permission>>>,
     applications: applications::Applications,
+    events: events::EventHub,
+    /// tenants by `id:{id}` and `host:{host}`
+    tenants: Cache<String, Arc<tenants::Tenant>>,
+    /// the `id:{id}` and `host:{host}` keys without a tenant
+    unknown_tenants: Cache<String, ()>,
+    /// current legal documents by `tenant:{id}` and the ones a user has to accept by
+    /// `user:{tenant}:{id}`
+    legal: Cache<String, Arc<Vec<legal::LegalDocument>>>,
 }
 
-static DB: OnceCell<Arc<Db>> = OnceCell::const_new();
+static MIGRATOR: sqlx::migrate::Migrator = sqlx::migrate!("../.migrations");
 
 impl Db {
-    pub async fn new() -> Arc<Self> {
-        DB.get_or_init(|| async {
-            // establishing connection with postgresql database
-            let pool = sqlx::postgres::PgPool::connect(&std::env::var("DATABASE_URL").unwrap())
-                .await
-                .unwrap();
-
-            sqlx::migrate!("../.migrations").run(&pool).await.unwrap();
-
-            Arc::new(Db {
-                pool,
-                bucket: bucket::BlackBlazeB2::default(),
-                active: Cache::builder()
-                    .max_capacity(32728)
-                    .time_to_live(Duration::from_secs(Session::MEM_CACHE_DURATION))
-                    .build(),
-                applications: applications::Applications::new(),
-            })
-        })
-        .await
-        .clone()
+    /// connects to the database of `config` and applies the pending migrations
+    ///
+    /// every call opens its own pool and in-memory stores, files are stored in `bucket`
+    pub async fn connect(
+        config: &Config,
+        bucket: Arc<bucket::BlackBlazeB2>,
+    ) -> Result<Arc<Self>, String> {
+        // establishing connection with postgresql database
+        let pool = sqlx::postgres::PgPool::connect(&config.database_url)
+            .await
+            .map_err(|e| format!("can't connect to the database: {e}"))?;
+
+        MIGRATOR.run(&pool).await.map_err(|e| format!("can't apply the migrations: {e}"))?;
+
+        let mem_cache_duration = config.cookies.mem_cache_duration;
+        Ok(Arc::new(Db {
+            pool,
+            bucket,
+            cookies: config.cookies.clone(),
+            active: Cache::builder().max_capacity(32728).time_to_live(mem_cache_duration).build(),
+            permissions: Cache::builder()
+                .max_capacity(4096)
+                .time_to_live(mem_cache_duration)
+                .build(),
+            applications: applications::Applications::new(),
+            events: events::EventHub::default(),
+            tenants: Cache::builder()
+                .max_capacity(1024)
+                .time_to_live(std::time::Duration::from_secs(60))
+                .build(),
+            unknown_tenants: Cache::builder()
+                .max_capacity(4096)
+                .time_to_live(std::time::Duration::from_secs(60))
+                .build(),
+            legal: Cache::builder().max_capacity(32728).time_to_live(mem_cache_duration).build(),
+        }))
     }
 
     pub fn logged_users_count(self: &Arc<Self>) -> u64 {
         self.active.entry_count()
     }
+
+    /// returns the number of sessions of the cached users
+    pub fn active_sessions_count(self: &Arc<Self>) -> u64 {
+        self.active.iter().map(|(_, user)| user.lock().unwrap().1.len() as u64).sum()
+    }
+
+    /// updates the cache and connection pool gauges of `util::metrics`
+    pub fn observe_metrics(self: &Arc<Self>) {
+        use util::metrics::{CACHE_ENTRIES, DB_POOL_CONNECTIONS, DB_POOL_MAX_CONNECTIONS};
+
+        CACHE_ENTRIES.with_label_values(&["active"]).set(self.active.entry_count() as i64);
+        CACHE_ENTRIES.with_label_values(&["permissions"]).set(self.permissions.entry_count() as i64);
+        CACHE_ENTRIES.with_label_values(&["tenants"]).set(self.tenants.entry_count() as i64);
+        CACHE_ENTRIES
+            .with_label_values(&["unknown_tenants"])
+            .set(self.unknown_tenants.entry_count() as i64);
+        CACHE_ENTRIES.with_label_values(&["legal"]).set(self.legal.entry_count() as i64);
+        for (cache, count) in self.applications.entry_counts() {
+            CACHE_ENTRIES.with_label_values(&[cache]).set(count as i64);
+        }
+
+        let idle = self.pool.num_idle() as i64;
+        DB_POOL_CONNECTIONS.with_label_values(&["idle"]).set(idle);
+        DB_POOL_CONNECTIONS.with_label_values(&["in_use"]).set(self.pool.size() as i64 - idle);
+        DB_POOL_MAX_CONNECTIONS.set(self.pool.options().get_max_connections() as i64);
+    }
 }
@@ -0,0 +1,75 @@
+use crate::events::SessionEvent;
+use sqlx::{postgres::PgListener, types::Uuid};
+use std::{sync::Arc, time::Duration};
+use util::AppError;
+
+/// postgres channel on which the ids of users changed outside the server are sent
+const USER_CHANGES_CHANNEL: &str = "user_changes";
+
+/// suffix of the notifications sent when every session of the user has been revoked
+const SESSIONS_REVOKED: &str = "sessions_revoked";
+
+// implementation block for keeping the in-memory caches of every process consistent
+impl crate::Db {
+    /// tells every listening server to drop its cached data of User with `user_id`
+    ///
+    /// used by processes that change users without going through the server (like the cli), and
+    /// by the server for the changes the other servers can't miss until their cache expires
+    pub async fn notify_user_changed(self: &Arc<Self>, user_id: Uuid) -> Result<(), AppError> {
+        self.notify(user_id.to_string()).await
+    }
+
+    /// like `notify_user_changed`, the connected clients of the user are also told that all
+    /// their sessions have been revoked
+    pub async fn notify_sessions_revoked(self: &Arc<Self>, user_id: Uuid) -> Result<(), AppError> {
+        self.notify(format!("{user_id} {SESSIONS_REVOKED}")).await
+    }
+
+    async fn notify(self: &Arc<Self>, payload: String) -> Result<(), AppError> {
+        sqlx::query!("SELECT pg_notify($1, $2)", USER_CHANGES_CHANNEL, payload)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| {
+                tracing::error!("{:?}", e);
+                AppError::ServerError
+            })?;
+        Ok(())
+    }
+
+    /// spawns the task that drops the cached data of users changed by other processes and tells
+    /// their connected clients
+    pub fn spawn_change_listener(self: &Arc<Self>) {
+        let db = self.clone();
+        tokio::spawn(async move {
+            loop {
+                if let Err(e) = db.listen_user_changes().await {
+                    tracing::error!("[User Changes Listener] {e:?}");
+                }
+                tokio::time::sleep(Duration::from_secs(5)).await;
+            }
+        });
+    }
+
+    async fn listen_user_changes(self: &Arc<Self>) -> Result<(), sqlx::Error> {
+        let mut listener = PgListener::connect_with(&self.pool).await?;
+        listener.listen(USER_CHANGES_CHANNEL).await?;
+        loop {
+            let notification = listener.recv().await?;
+            let (user_id, change) = match notification.payload().split_once(' ') {
+                Some((user_id, change)) => (user_id, Some(change)),
+                None => (notification.payload(), None),
+            };
+            let Ok(user_id) = Uuid::try_parse(user_id) else {
+                continue;
+            };
+            self.active.invalidate(&user_id);
+            self.permissions.invalidate(&user_id);
+            let event = match change {
+                Some(SESSIONS_REVOKED) => SessionEvent::AllSessionsRevoked { except: Uuid::nil() },
+                _ => SessionEvent::ProfileChanged,
+            };
+            self.publish_event(user_id, event);
+            tracing::info!("[User Changed Externally] user_id: {user_id}");
+        }
+    }
+}
@@ -0,0 +1,619 @@
+use sqlx::{
+    Postgres, Transaction,
+    types::{Uuid, time::OffsetDateTime},
+};
+use std::sync::Arc;
+use util::AppError;
+
+/// role of a user in an organization, ordered by the rights it gives
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum OrgRole {
+    Member,
+    Admin,
+    Owner,
+}
+
+impl OrgRole {
+    pub fn get_str(&self) -> &'static str {
+        match self {
+            OrgRole::Member => "member",
+            OrgRole::Admin => "admin",
+            OrgRole::Owner => "owner",
+        }
+    }
+
+    /// owners and admins invite, promote and remove the members
+    pub fn can_manage(&self) -> bool {
+        *self >= OrgRole::Admin
+    }
+}
+
+impl TryFrom<&str> for OrgRole {
+    type Error = AppError;
+
+    fn try_from(role: &str) -> Result<Self, Self::Error> {
+        match role {
+            "member" => Ok(OrgRole::Member),
+            "admin" => Ok(OrgRole::Admin),
+            "owner" => Ok(OrgRole::Owner),
+            _ => Err(AppError::BadReq("Organization roles are owner, admin or member")),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Organization {
+    pub id: Uuid,
+    pub name: String,
+    pub created: OffsetDateTime,
+}
+
+/// an organization as seen by one of its members
+#[derive(Clone, Debug)]
+pub struct Membership {
+    pub org_id: Uuid,
+    pub name: String,
+    pub role: OrgRole,
+    pub joined_at: OffsetDateTime,
+}
+
+#[derive(Clone, Debug)]
+pub struct Member {
+    pub user_id: Uuid,
+    pub username: String,
+    pub display_name: String,
+    pub icon: Option<String>,
+    pub role: OrgRole,
+    pub joined_at: OffsetDateTime,
+}
+
+#[derive(Clone, Debug)]
+pub struct Invitation {
+    pub id: Uuid,
+    pub org_id: Uuid,
+    pub org_name: String,
+    pub email: String,
+    pub role: OrgRole,
+    /// username of the member who sent the invitation
+    pub invited_by: Option<String>,
+    pub created: OffsetDateTime,
+    pub expires_at: OffsetDateTime,
+}
+
+// implementation block for organizations and their memberships
+// every change to the members locks the organization row, so that an organization always
+// keeps at least one owner
+impl crate::Db {
+    /// creates an organization owned by User with `owner_id`
+    pub async fn create_organization(
+        self: &Arc<Self>,
+        tenant_id: &str,
+        owner_id: Uuid,
+        name: &str,
+    ) -> Result<Organization, AppError> {
+        let mut tx = self.pool.begin().await.map_err(db_error)?;
+        let org = sqlx::query_as!(
+            Organization,
+            r#"INSERT INTO organizations (id, tenant_id, name, created_by) VALUES ($1, $2, $3, $4)
+            RETURNING id, name, created"#,
+            Uuid::new_v4(),
+            tenant_id,
+            name,
+            owner_id
+        )
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(db_error)?;
+        sqlx::query!(
+            "INSERT INTO memberships (org_id, user_id, role) VALUES ($1, $2, $3)",
+            org.id,
+            owner_id,
+            OrgRole::Owner.get_str()
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(db_error)?;
+        tx.commit().await.map_err(db_error)?;
+
+        tracing::info!("[Organization Created] id: {}, owner: {owner_id}", org.id);
+        Ok(org)
+    }
+
+    /// returns the organizations User with `user_id` is a member of
+    pub async fn list_user_organizations(
+        self: &Arc<Self>,
+        user_id: Uuid,
+    ) -> Result<Vec<Membership>, AppError> {
+        let rows = sqlx::query!(
+            r#"SELECT o.id, o.name, m.role, m.joined_at FROM memberships m
+            INNER JOIN organizations o ON o.id = m.org_id
+            WHERE m.user_id = $1 ORDER BY m.joined_at, o.name"#,
+            user_id
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| Membership {
+                org_id: row.id,
+                name: row.name,
+                role: OrgRole::try_from(row.role.as_str()).unwrap_or(OrgRole::Member),
+                joined_at: row.joined_at,
+            })
+            .collect())
+    }
+
+    /// returns the role of User with `user_id` in the organization, `NotFound` if they aren't a
+    /// member (organizations are hidden from the other users)
+    pub async fn get_membership(
+        self: &Arc<Self>,
+        org_id: Uuid,
+        user_id: Uuid,
+    ) -> Result<(Organization, OrgRole), AppError> {
+        let row = sqlx::query!(
+            r#"SELECT o.id, o.name, o.created, m.role FROM memberships m
+            INNER JOIN organizations o ON o.id = m.org_id
+            WHERE m.org_id = $1 AND m.user_id = $2"#,
+            org_id,
+            user_id
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(db_error)?
+        .ok_or(AppError::NotFound)?;
+
+        let org = Organization { id: row.id, name: row.name, created: row.created };
+        Ok((org, OrgRole::try_from(row.role.as_str())?))
+    }
+
+    /// returns the members of the organization, owners first
+    pub async fn list_members(self: &Arc<Self>, org_id: Uuid) -> Result<Vec<Member>, AppError> {
+        let rows = sqlx::query!(
+            r#"SELECT u.id, u.username, u.display_name, u.icon, m.role, m.joined_at
+            FROM memberships m INNER JOIN users u ON u.id = m.user_id
+            WHERE m.org_id = $1
+            ORDER BY CASE m.role WHEN 'owner' THEN 0 WHEN 'admin' THEN 1 ELSE 2 END, m.joined_at"#,
+            org_id
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| Member {
+                user_id: row.id,
+                username: row.username,
+                display_name: row.display_name,
+                icon: row.icon,
+                role: OrgRole::try_from(row.role.as_str()).unwrap_or(OrgRole::Member),
+                joined_at: row.joined_at,
+            })
+            .collect())
+    }
+
+    /// returns the pending invitations of the organization
+    pub async fn list_org_invitations(
+        self: &Arc<Self>,
+        org_id: Uuid,
+    ) -> Result<Vec<Invitation>, AppError> {
+        let rows = sqlx::query!(
+            r#"SELECT i.id, i.org_id, o.name, i.email, i.role, u.username as "invited_by?",
+                i.created, i.expires_at
+            FROM organization_invitations i
+            INNER JOIN organizations o ON o.id = i.org_id
+            LEFT JOIN users u ON u.id = i.invited_by
+            WHERE i.org_id = $1 AND i.expires_at > NOW() ORDER BY i.created"#,
+            org_id
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| Invitation {
+                id: row.id,
+                org_id: row.org_id,
+                org_name: row.name,
+                email: row.email,
+                role: OrgRole::try_from(row.role.as_str()).unwrap_or(OrgRole::Member),
+                invited_by: row.invited_by,
+                created: row.created,
+                expires_at: row.expires_at,
+            })
+            .collect())
+    }
+
+    /// returns the pending invitations sent to `email` by the organizations of the tenant
+    pub async fn list_user_invitations(
+        self: &Arc<Self>,
+        tenant_id: &str,
+        email: &str,
+    ) -> Result<Vec<Invitation>, AppError> {
+        let rows = sqlx::query!(
+            r#"SELECT i.id, i.org_id, o.name, i.email, i.role, u.username as "invited_by?",
+                i.created, i.expires_at
+            FROM organization_invitations i
+            INNER JOIN organizations o ON o.id = i.org_id
+            LEFT JOIN users u ON u.id = i.invited_by
+            WHERE o.tenant_id = $1 AND i.email = $2 AND i.expires_at > NOW()
+            ORDER BY i.created"#,
+            tenant_id,
+            email
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| Invitation {
+                id: row.id,
+                org_id: row.org_id,
+                org_name: row.name,
+                email: row.email,
+                role: OrgRole::try_from(row.role.as_str()).unwrap_or(OrgRole::Member),
+                invited_by: row.invited_by,
+                created: row.created,
+                expires_at: row.expires_at,
+            })
+            .collect())
+    }
+
+    /// invites `email` to join the organization as `role`, replacing a previous invitation
+    ///
+    /// returns the id of the invitation and the name of the organization
+    pub async fn invite_member(
+        self: &Arc<Self>,
+        org_id: Uuid,
+        actor_id: Uuid,
+        email: &str,
+        role: OrgRole,
+        expires_at: OffsetDateTime,
+    ) -> Result<(Uuid, String), AppError> {
+        let mut tx = self.pool.begin().await.map_err(db_error)?;
+        let (name, actor_role) = lock_organization(&mut tx, org_id, actor_id).await?;
+        if !actor_role.can_manage() || role > actor_role {
+            return Err(AppError::Forbidden("You can't invite members with this role"));
+        }
+
+        let is_member = sqlx::query_scalar!(
+            r#"SELECT EXISTS(
+                SELECT 1 FROM memberships m
+                INNER JOIN users u ON u.id = m.user_id
+                INNER JOIN organizations o ON o.id = m.org_id
+                WHERE m.org_id = $1 AND u.email = $2 AND u.tenant_id = o.tenant_id
+            ) as "exists!""#,
+            org_id,
+            email
+        )
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(db_error)?;
+        if is_member {
+            return Err(AppError::BadReq("This user is already a member of the organization"));
+        }
+
+        let id = sqlx::query_scalar!(
+            r#"INSERT INTO organization_invitations (id, org_id, email, role, invited_by, expires_at)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            ON CONFLICT (org_id, email) DO UPDATE SET
+                role = EXCLUDED.role,
+                invited_by = EXCLUDED.invited_by,
+                created = NOW(),
+                expires_at = EXCLUDED.expires_at
+            RETURNING id"#,
+            Uuid::new_v4(),
+            org_id,
+            email,
+            role.get_str(),
+            actor_id,
+            expires_at
+        )
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(db_error)?;
+        tx.commit().await.map_err(db_error)?;
+
+        tracing::info!("[Member Invited] org: {org_id}, email: {email}, role: {}", role.get_str());
+        Ok((id, name))
+    }
+
+    /// makes User with `user_id` a member of the organization that invited them
+    ///
+    /// returns the id of the organization
+    pub async fn accept_invitation(
+        self: &Arc<Self>,
+        invitation_id: Uuid,
+        tenant_id: &str,
+        user_id: Uuid,
+        email: &str,
+    ) -> Result<Uuid, AppError> {
+        let mut tx = self.pool.begin().await.map_err(db_error)?;
+        let invitation = sqlx::query!(
+            r#"DELETE FROM organization_invitations i USING organizations o
+            WHERE i.id = $1 AND i.email = $2 AND o.id = i.org_id AND o.tenant_id = $3
+                AND i.expires_at > NOW()
+            RETURNING i.org_id, i.role"#,
+            invitation_id,
+            email,
+            tenant_id
+        )
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(db_error)?
+        .ok_or(AppError::NotFound)?;
+
+        // an existing membership keeps its role
+        sqlx::query!(
+            r#"INSERT INTO memberships (org_id, user_id, role) VALUES ($1, $2, $3)
+            ON CONFLICT DO NOTHING"#,
+            invitation.org_id,
+            user_id,
+            invitation.role
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(db_error)?;
+        tx.commit().await.map_err(db_error)?;
+
+        tracing::info!("[Invitation Accepted] org: {}, user_id: {user_id}", invitation.org_id);
+        Ok(invitation.org_id)
+    }
+
+    /// removes the invitation sent to `email`
+    pub async fn decline_invitation(
+        self: &Arc<Self>,
+        invitation_id: Uuid,
+        email: &str,
+    ) -> Result<(), AppError> {
+        let result = sqlx::query!(
+            "DELETE FROM organization_invitations WHERE id = $1 AND email = $2",
+            invitation_id,
+            email
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(db_error)?;
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound);
+        }
+        Ok(())
+    }
+
+    /// changes the role of the member `username`
+    ///
+    /// members can only be given roles up to the one of the actor, and the last owner can't
+    /// be demoted
+    pub async fn update_member_role(
+        self: &Arc<Self>,
+        org_id: Uuid,
+        actor_id: Uuid,
+        username: &str,
+        role: OrgRole,
+    ) -> Result<(), AppError> {
+        let mut tx = self.pool.begin().await.map_err(db_error)?;
+        let (_, actor_role) = lock_organization(&mut tx, org_id, actor_id).await?;
+        let (target_id, target_role) = get_member(&mut tx, org_id, username).await?;
+        if !actor_role.can_manage() || target_role > actor_role || role > actor_role {
+            return Err(AppError::Forbidden("You can't change the role of this member"));
+        }
+        if target_role == OrgRole::Owner && role != OrgRole::Owner {
+            ensure_other_owner(&mut tx, org_id).await?;
+        }
+
+        sqlx::query!(
+            "UPDATE memberships SET role = $3 WHERE org_id = $1 AND user_id = $2",
+            org_id,
+            target_id,
+            role.get_str()
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(db_error)?;
+        tx.commit().await.map_err(db_error)?;
+
+        tracing::info!(
+            "[Member Role] org: {org_id}, user_id: {target_id}, role: {}",
+            role.get_str()
+        );
+        Ok(())
+    }
+
+    /// removes the member `username` from the organization
+    pub async fn remove_member(
+        self: &Arc<Self>,
+        org_id: Uuid,
+        actor_id: Uuid,
+        username: &str,
+    ) -> Result<(), AppError> {
+        let mut tx = self.pool.begin().await.map_err(db_error)?;
+        let (_, actor_role) = lock_organization(&mut tx, org_id, actor_id).await?;
+        let (target_id, target_role) = get_member(&mut tx, org_id, username).await?;
+        if target_id == actor_id {
+            return Err(AppError::BadReq("Leave the organization instead"));
+        }
+        if !actor_role.can_manage() || target_role > actor_role {
+            return Err(AppError::Forbidden("You can't remove this member"));
+        }
+
+        sqlx::query!(
+            "DELETE FROM memberships WHERE org_id = $1 AND user_id = $2",
+            org_id,
+            target_id
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(db_error)?;
+        tx.commit().await.map_err(db_error)?;
+
+        tracing::info!("[Member Removed] org: {org_id}, user_id: {target_id}, by: {actor_id}");
+        Ok(())
+    }
+
+    /// removes User with `user_id` from the organization, the organization is deleted when its
+    /// last member leaves
+    ///
+    /// returns true if the organization has been deleted
+    pub async fn leave_organization(
+        self: &Arc<Self>,
+        org_id: Uuid,
+        user_id: Uuid,
+    ) -> Result<bool, AppError> {
+        let mut tx = self.pool.begin().await.map_err(db_error)?;
+        let (_, role) = lock_organization(&mut tx, org_id, user_id).await?;
+        let members = sqlx::query_scalar!(
+            r#"SELECT COUNT(*) as "count!" FROM memberships WHERE org_id = $1"#,
+            org_id
+        )
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(db_error)?;
+
+        let deleted = if members == 1 {
+            sqlx::query!("DELETE FROM organizations WHERE id = $1", org_id)
+                .execute(&mut *tx)
+                .await
+                .map_err(db_error)?;
+            true
+        } else {
+            if role == OrgRole::Owner {
+                ensure_other_owner(&mut tx, org_id).await?;
+            }
+            sqlx::query!(
+                "DELETE FROM memberships WHERE org_id = $1 AND user_id = $2",
+                org_id,
+                user_id
+            )
+            .execute(&mut *tx)
+            .await
+            .map_err(db_error)?;
+            false
+        };
+        tx.commit().await.map_err(db_error)?;
+
+        tracing::info!("[Member Left] org: {org_id}, user_id: {user_id}, deleted: {deleted}");
+        Ok(deleted)
+    }
+
+    /// returns true if User with `user_id` is the only owner of an organization with other members
+    pub async fn is_sole_owner(self: &Arc<Self>, user_id: Uuid) -> Result<bool, AppError> {
+        sqlx::query_scalar!(
+            r#"SELECT EXISTS(
+                SELECT 1 FROM memberships m WHERE m.user_id = $1 AND m.role = 'owner'
+                AND NOT EXISTS(SELECT 1 FROM memberships o
+                    WHERE o.org_id = m.org_id AND o.user_id <> $1 AND o.role = 'owner')
+                AND EXISTS(SELECT 1 FROM memberships o
+                    WHERE o.org_id = m.org_id AND o.user_id <> $1)
+            ) as "exists!""#,
+            user_id
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(db_error)
+    }
+
+    /// removes the invitations that expired
+    ///
+    /// returns the number of invitations removed
+    pub async fn prune_expired_invitations(self: &Arc<Self>) -> Result<u64, AppError> {
+        let result = sqlx::query!("DELETE FROM organization_invitations WHERE expires_at <= NOW()")
+            .execute(&self.pool)
+            .await
+            .map_err(db_error)?;
+        Ok(result.rows_affected())
+    }
+}
+
+/// locks the organization until the end of `tx` and returns its name and the role of User with
+/// `user_id`
+async fn lock_organization(
+    tx: &mut Transaction<'static, Postgres>,
+    org_id: Uuid,
+    user_id: Uuid,
+) -> Result<(String, OrgRole), AppError> {
+    let name =
+        sqlx::query_scalar!("SELECT name FROM organizations WHERE id = $1 FOR UPDATE", org_id)
+            .fetch_optional(&mut **tx)
+            .await
+            .map_err(db_error)?
+            .ok_or(AppError::NotFound)?;
+    let role = sqlx::query_scalar!(
+        "SELECT role FROM memberships WHERE org_id = $1 AND user_id = $2",
+        org_id,
+        user_id
+    )
+    .fetch_optional(&mut **tx)
+    .await
+    .map_err(db_error)?
+    .ok_or(AppError::NotFound)?;
+    Ok((name, OrgRole::try_from(role.as_str())?))
+}
+
+/// returns the id and role of the member `username`
+async fn get_member(
+    tx: &mut Transaction<'static, Postgres>,
+    org_id: Uuid,
+    username: &str,
+) -> Result<(Uuid, OrgRole), AppError> {
+    let row = sqlx::query!(
+        r#"SELECT m.user_id, m.role FROM memberships m
+        INNER JOIN users u ON u.id = m.user_id
+        INNER JOIN organizations o ON o.id = m.org_id
+        WHERE m.org_id = $1 AND u.username = $2 AND u.tenant_id = o.tenant_id"#,
+        org_id,
+        username
+    )
+    .fetch_optional(&mut **tx)
+    .await
+    .map_err(db_error)?
+    .ok_or(AppError::UserNotFound)?;
+    Ok((row.user_id, OrgRole::try_from(row.role.as_str())?))
+}
+
+async fn ensure_other_owner(
+    tx: &mut Transaction<'static, Postgres>,
+    org_id: Uuid,
+) -> Result<(), AppError> {
+    let owners = sqlx::query_scalar!(
+        r#"SELECT COUNT(*) as "count!" FROM memberships WHERE org_id = $1 AND role = 'owner'"#,
+        org_id
+    )
+    .fetch_one(&mut **tx)
+    .await
+    .map_err(db_error)?;
+    if owners < 2 {
+        return Err(AppError::BadReq("The organization needs another owner first"));
+    }
+    Ok(())
+}
+
+fn db_error(e: sqlx::Error) -> AppError {
+    tracing::error!("{:?}", e);
+    AppError::ServerError
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roles_are_ordered_by_rights() {
+        assert!(OrgRole::Member < OrgRole::Admin);
+        assert!(OrgRole::Admin < OrgRole::Owner);
+        assert!(!OrgRole::Member.can_manage());
+        assert!(OrgRole::Admin.can_manage());
+        assert!(OrgRole::Owner.can_manage());
+    }
+
+    #[test]
+    fn roles_round_trip() {
+        for role in [OrgRole::Member, OrgRole::Admin, OrgRole::Owner] {
+            assert_eq!(OrgRole::try_from(role.get_str()).unwrap(), role);
+        }
+        assert!(OrgRole::try_from("Owner").is_err());
+    }
+}
@@ -0,0 +1,52 @@
+use std::{collections::HashSet, sync::Arc};
+use util::AppError;
+
+/// a migration found in `.migrations` and whether it has been applied
+pub struct MigrationStatus {
+    pub version: i64,
+    pub description: String,
+    pub applied: bool,
+}
+
+// implementation block for checking the dependencies of the database
+// the errors are meant for readiness probes, so they're short descriptions
+impl crate::Db {
+    /// returns every migration known to this build, oldest first
+    pub async fn migration_status(self: &Arc<Self>) -> Result<Vec<MigrationStatus>, AppError> {
+        let applied = sqlx::query_scalar!("SELECT version FROM _sqlx_migrations WHERE success")
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| {
+                tracing::error!("{:?}", e);
+                AppError::ServerError
+            })?
+            .into_iter()
+            .collect::<HashSet<_>>();
+
+        Ok(crate::MIGRATOR
+            .iter()
+            .filter(|m| !m.migration_type.is_down_migration())
+            .map(|m| MigrationStatus {
+                version: m.version,
+                description: m.description.to_string(),
+                applied: applied.contains(&m.version),
+            })
+            .collect())
+    }
+
+    /// checks that postgres accepts queries and every migration has been applied
+    pub async fn check_database(self: &Arc<Self>) -> Result<(), String> {
+        let migrations =
+            self.migration_status().await.map_err(|_| "database query failed".to_string())?;
+        let pending = migrations.iter().filter(|m| !m.applied).count();
+        if pending > 0 {
+            return Err(format!("{pending} pending migrations"));
+        }
+        Ok(())
+    }
+
+    /// checks that the bucket accepts the credentials
+    pub async fn check_bucket(self: &Arc<Self>) -> Result<(), String> {
+        self.bucket.ping().await.map_err(|_| "bucket unreachable".to_string())
+    }
+}
@@ -0,0 +1,224 @@
+use sqlx::types::Uuid;
+use std::{collections::HashSet, sync::Arc};
+use util::{AppError, permission::Permission};
+
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct Role {
+    pub name: String,
+    pub description: String,
+    pub permissions: Vec<String>,
+}
+
+// implementation block for role based access control
+// the permissions of a user are cached in memory until their roles change
+impl crate::Db {
+    /// returns true if User with `user_id` has `permission` through any of their roles
+    pub async fn has_permission(
+        self: &Arc<Self>,
+        user_id: Uuid,
+        permission: Permission,
+    ) -> Result<bool, AppError> {
+        Ok(self.get_user_permissions(user_id).await?.contains(&permission))
+    }
+
+    /// returns the permissions User with `user_id` has through all of their roles
+    pub async fn get_user_permissions(
+        self: &Arc<Self>,
+        user_id: Uuid,
+    ) -> Result<Arc<HashSet<Permission>>, AppError> {
+        if let Some(permissions) =
+            util::metrics::cache_lookup("permissions", self.permissions.get(&user_id))
+        {
+            return Ok(permissions);
+        }
+
+        let rows = sqlx::query_scalar!(
+            r#"SELECT DISTINCT rp.permission FROM user_roles ur
+            INNER JOIN role_permissions rp ON rp.role = ur.role
+            WHERE ur.user_id = $1"#,
+            user_id
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("{:?}", e);
+            AppError::ServerError
+        })?;
+
+        // permissions that are no longer known to the server are ignored
+        let permissions = Arc::new(
+            rows.iter()
+                .filter_map(|v| Permission::try_from(v.as_str()).ok())
+                .collect::<HashSet<_>>(),
+        );
+        self.permissions.insert(user_id, permissions.clone());
+        Ok(permissions)
+    }
+
+    /// returns every role along with its permissions
+    pub async fn list_roles(self: &Arc<Self>) -> Result<Vec<Role>, AppError> {
+        let rows = sqlx::query!(
+            r#"SELECT r.name, r.description,
+                COALESCE(ARRAY_AGG(rp.permission ORDER BY rp.permission)
+                    FILTER (WHERE rp.permission IS NOT NULL), '{}') as "permissions!"
+            FROM roles r
+            LEFT JOIN role_permissions rp ON rp.role = r.name
+            GROUP BY r.name ORDER BY r.name"#
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("{:?}", e);
+            AppError::ServerError
+        })?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| Role {
+                name: row.name,
+                description: row.description,
+                permissions: row.permissions,
+            })
+            .collect())
+    }
+
+    /// creates the role `name` or replaces its description and permissions
+    pub async fn upsert_role(
+        self: &Arc<Self>,
+        name: &str,
+        description: &str,
+        permissions: &[Permission],
+    ) -> Result<(), AppError> {
+        let permissions = permissions.iter().map(|p| p.get_str().to_string()).collect::<Vec<_>>();
+        let mut tx = self.pool.begin().await.map_err(|e| {
+            tracing::error!("{:?}", e);
+            AppError::ServerError
+        })?;
+
+        sqlx::query!(
+            r#"INSERT INTO roles (name, description) VALUES ($1, $2)
+            ON CONFLICT (name) DO UPDATE SET description = EXCLUDED.description"#,
+            name,
+            description
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| {
+            tracing::error!("{:?}", e);
+            AppError::ServerError
+        })?;
+
+        sqlx::query!(r#"DELETE FROM role_permissions WHERE role = $1"#, name)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| {
+                tracing::error!("{:?}", e);
+                AppError::ServerError
+            })?;
+
+        sqlx::query!(
+            r#"INSERT INTO role_permissions (role, permission) SELECT $1, UNNEST($2::VARCHAR[])"#,
+            name,
+            &permissions
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| {
+            tracing::error!("{:?}", e);
+            AppError::ServerError
+        })?;
+
+        tx.commit().await.map_err(|e| {
+            tracing::error!("{:?}", e);
+            AppError::ServerError
+        })?;
+
+        self.permissions.invalidate_all();
+        tracing::info!("[Role Updated] role: {name}, permissions: {permissions:?}");
+        Ok(())
+    }
+
+    /// deletes the role `name` and removes it from every user
+    pub async fn delete_role(self: &Arc<Self>, name: &str) -> Result<(), AppError> {
+        let result = sqlx::query!(r#"DELETE FROM roles WHERE name = $1"#, name)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| {
+                tracing::error!("{:?}", e);
+                AppError::ServerError
+            })?;
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound);
+        }
+
+        self.permissions.invalidate_all();
+        tracing::info!("[Role Deleted] role: {name}");
+        Ok(())
+    }
+
+    /// returns the names of the roles assigned to User with `user_id`
+    pub async fn get_user_roles(self: &Arc<Self>, user_id: Uuid) -> Result<Vec<String>, AppError> {
+        sqlx::query_scalar!(
+            r#"SELECT role FROM user_roles WHERE user_id = $1 ORDER BY role"#,
+            user_id
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("{:?}", e);
+            AppError::ServerError
+        })
+    }
+
+    /// assigns the role `role` to User with `user_id`
+    pub async fn assign_role(
+        self: &Arc<Self>,
+        user_id: Uuid,
+        role: &str,
+        granted_by: Option<Uuid>,
+    ) -> Result<(), AppError> {
+        let exists = sqlx::query_scalar!("SELECT EXISTS(SELECT 1 FROM roles WHERE name = $1)", role)
+            .fetch_one(&self.pool)
+            .await
+            .map_err(|e| {
+                tracing::error!("{:?}", e);
+                AppError::ServerError
+            })?;
+        if !exists.unwrap_or(false) {
+            return Err(AppError::BadReq("Role not found"));
+        }
+
+        sqlx::query!(
+            r#"INSERT INTO user_roles (user_id, role, granted_by) VALUES ($1, $2, $3)
+            ON CONFLICT DO NOTHING"#,
+            user_id,
+            role,
+            granted_by
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("{:?}", e);
+            AppError::ServerError
+        })?;
+
+        self.permissions.invalidate(&user_id);
+        tracing::info!("[Role Assigned] user_id: {user_id}, role: {role}, by: {granted_by:?}");
+        Ok(())
+    }
+
+    /// removes the role `role` from User with `user_id`
+    pub async fn revoke_role(self: &Arc<Self>, user_id: Uuid, role: &str) -> Result<(), AppError> {
+        sqlx::query!(r#"DELETE FROM user_roles WHERE user_id = $1 AND role = $2"#, user_id, role)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| {
+                tracing::error!("{:?}", e);
+                AppError::ServerError
+            })?;
+
+        self.permissions.invalidate(&user_id);
+        tracing::info!("[Role Revoked] user_id: {user_id}, role: {role}");
+        Ok(())
+    }
+}
@@ -0,0 +1,77 @@
+use sqlx::types::Uuid;
+use std::sync::Arc;
+use util::{AppError, session::Session};
+
+impl crate::Db {
+    /// records the start of an impersonation by `admin_id` and adds its `session`
+    pub async fn start_impersonation(
+        self: &Arc<Self>,
+        admin_id: Uuid,
+        target_id: Uuid,
+        session: Session,
+        reason: Option<&str>,
+    ) -> Result<(), AppError> {
+        sqlx::query!(
+            r#"INSERT INTO impersonations (
+                unsigned_ssid, admin_id, target_id, reason, started_at, expires_at
+            ) VALUES ($1, $2, $3, $4, $5, $6)"#,
+            session.unsigned_ssid,
+            admin_id,
+            target_id,
+            reason,
+            session.created_at,
+            session.expires_at,
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("{:?}", e);
+            AppError::ServerError
+        })?;
+
+        tracing::info!(
+            "[Impersonation Started] admin_id: {admin_id}, target_id: {target_id}, session_id: {}",
+            session.unsigned_ssid
+        );
+        self.add_session(target_id, session).await
+    }
+
+    /// records the end of the impersonation using the session `unsigned_ssid`
+    pub async fn end_impersonation(self: &Arc<Self>, unsigned_ssid: Uuid) -> Result<(), AppError> {
+        sqlx::query!(
+            r#"UPDATE impersonations SET ended_at = NOW()
+            WHERE unsigned_ssid = $1 AND ended_at IS NULL"#,
+            unsigned_ssid
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("{:?}", e);
+            AppError::ServerError
+        })?;
+
+        tracing::info!("[Impersonation Ended] session_id: {unsigned_ssid}");
+        Ok(())
+    }
+
+    /// records the end of every impersonation whose session has expired or was removed
+    ///
+    /// returns the number of impersonations closed
+    pub async fn close_finished_impersonations(self: &Arc<Self>) -> Result<u64, AppError> {
+        let result = sqlx::query!(
+            r#"UPDATE impersonations i SET ended_at = LEAST(NOW(), i.expires_at)
+            WHERE i.ended_at IS NULL AND NOT EXISTS (
+                SELECT 1 FROM sessions s
+                WHERE s.unsigned_ssid = i.unsigned_ssid AND s.expires_at > NOW()
+            )"#
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("{:?}", e);
+            AppError::ServerError
+        })?;
+
+        Ok(result.rows_affected())
+    }
+}
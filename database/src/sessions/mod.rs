@@ -1,11 +1,13 @@
 use crate::users::User;
-use sqlx::types::{Uuid, ipnetwork::IpNetwork};
+use sqlx::types::{Uuid, ipnetwork::IpNetwork, time::OffsetDateTime};
 use std::sync::Arc;
 use util::{
     AppError,
     session::{ParsedSession, Session},
 };
 
+mod impersonation;
+
 impl crate::Db {
     /// returns the session that matches `parsed_session.unsigned_ssid`
     pub async fn get_session(
@@ -31,9 +33,50 @@ impl crate::Db {
             created_at: row.created_at,
             last_used: row.last_used,
             expires_at: row.expires_at,
+            absolute_expires_at: row.absolute_expires_at,
+            remember_me: row.remember_me,
+            impersonated_by: row.impersonated_by,
         })
     }
 
+    /// returns the sessions of User with `user_id` that are still valid or refreshable on `now`,
+    /// least recently used first
+    pub async fn get_user_sessions(
+        self: &Arc<Self>,
+        user_id: Uuid,
+        now: OffsetDateTime,
+    ) -> Result<Vec<Session>, AppError> {
+        let rows = sqlx::query!(
+            r#"SELECT * FROM sessions
+            WHERE user_id = $1 AND expires_at > $2 AND absolute_expires_at > $3
+            ORDER BY last_used ASC"#,
+            user_id,
+            now - self.cookies.max_refresh_duration,
+            now
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("{:?}", e);
+            AppError::ServerError
+        })?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| Session {
+                unsigned_ssid: row.unsigned_ssid,
+                user_agent: row.user_agent,
+                ip_address: row.ip_address.ip(),
+                created_at: row.created_at,
+                last_used: row.last_used,
+                expires_at: row.expires_at,
+                absolute_expires_at: row.absolute_expires_at,
+                remember_me: row.remember_me,
+                impersonated_by: row.impersonated_by,
+            })
+            .collect())
+    }
+
     /// returns the `User` and `Session` that matches the `parsed_session.unsigned_ssid`
     pub async fn get_all_by_parsed_session(
         self: &Arc<Self>,
@@ -43,8 +86,10 @@ impl crate::Db {
             r#"SELECT 
                 u.id as user_id, u.display_name, u.email, u.birth_date, u.password, 
                 u.username, u.banner, u.icon, u.bio, u.legal_name, u.gender, 
-                u.phone, u.country, u.oauth_provider, u.created, s.unsigned_ssid,
-                s.user_agent, s.ip_address, s.created_at, s.last_used, s.expires_at
+                u.phone, u.country, u.oauth_provider, u.created, u.bind_sessions, u.status,
+                u.tenant_id, u.restricted, u.birth_date_changes, s.unsigned_ssid,
+                s.user_agent, s.ip_address, s.created_at, s.last_used, s.expires_at,
+                s.absolute_expires_at, s.remember_me, s.impersonated_by
             FROM users u
             INNER JOIN sessions s ON s.user_id = u.id
             WHERE s.unsigned_ssid = $1 AND s.expires_at > NOW()"#,
@@ -74,6 +119,11 @@ impl crate::Db {
             country: row.country,
             oauth_provider: util::oauth::OAuthProvider::from(row.oauth_provider.as_str()),
             created: row.created,
+            bind_sessions: row.bind_sessions,
+            status: crate::users::AccountStatus::from(row.status),
+            tenant_id: row.tenant_id,
+            restricted: row.restricted,
+            birth_date_changes: row.birth_date_changes,
         };
 
         let session = Session {
@@ -83,6 +133,9 @@ impl crate::Db {
             created_at: row.created_at,
             last_used: row.last_used,
             expires_at: row.expires_at,
+            absolute_expires_at: row.absolute_expires_at,
+            remember_me: row.remember_me,
+            impersonated_by: row.impersonated_by,
         };
 
         Ok((user, session))
@@ -96,8 +149,9 @@ impl crate::Db {
     ) -> Result<(), AppError> {
         sqlx::query!(
             r#"INSERT INTO sessions (
-                unsigned_ssid, user_id, user_agent, ip_address, created_at, last_used, expires_at
-            ) VALUES ($1, $2, $3, $4, $5, $6, $7)"#,
+                unsigned_ssid, user_id, user_agent, ip_address, created_at, last_used, expires_at,
+                absolute_expires_at, remember_me, impersonated_by
+            ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)"#,
             session.unsigned_ssid,
             user_id,
             session.user_agent,
@@ -105,6 +159,9 @@ impl crate::Db {
             session.created_at,
             session.last_used,
             session.expires_at,
+            session.absolute_expires_at,
+            session.remember_me,
+            session.impersonated_by,
         )
         .execute(&self.pool)
         .await
@@ -117,6 +174,61 @@ impl crate::Db {
         Ok(())
     }
 
+    /// updates the last time the session that matches `unsigned_ssid` was used
+    pub async fn touch_session(
+        self: &Arc<Self>,
+        unsigned_ssid: Uuid,
+        last_used: OffsetDateTime,
+    ) -> Result<(), AppError> {
+        sqlx::query!(
+            r#"UPDATE sessions SET last_used = $1 WHERE unsigned_ssid = $2"#,
+            last_used,
+            unsigned_ssid
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("{:?}", e);
+            AppError::ServerError
+        })?;
+
+        Ok(())
+    }
+
+    /// binds the session that matches `unsigned_ssid` to a new client
+    /// (in both primary and in-memory database)
+    pub async fn rebind_session(
+        self: &Arc<Self>,
+        user_id: Uuid,
+        unsigned_ssid: Uuid,
+        ip_address: std::net::IpAddr,
+        user_agent: Option<String>,
+    ) -> Result<(), AppError> {
+        sqlx::query!(
+            r#"UPDATE sessions SET ip_address = $1, user_agent = $2 WHERE unsigned_ssid = $3"#,
+            IpNetwork::from(ip_address),
+            user_agent.as_deref(),
+            unsigned_ssid
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("{:?}", e);
+            AppError::ServerError
+        })?;
+
+        if let Some(arc_wrapped) = self.active.get(&user_id) {
+            let mut guard = arc_wrapped.lock().unwrap();
+            if let Some(session) = guard.1.iter_mut().find(|s| s.unsigned_ssid == unsigned_ssid) {
+                session.ip_address = ip_address;
+                session.user_agent = user_agent;
+            }
+        }
+
+        tracing::info!("[Session Rebound] session_id: {unsigned_ssid}, ip: {ip_address}");
+        Ok(())
+    }
+
     /// removes the session that matches `unsigned_ssid`
     pub async fn remove_session(
         self: &Arc<Self>,
@@ -187,9 +299,45 @@ impl crate::Db {
         Ok(())
     }
 
+    /// removes the sessions of every user that can no longer be refreshed, because they expired
+    /// or were left idle for longer than the idle timeout of their profile
+    ///
+    /// returns the number of sessions removed
+    pub async fn clear_all_expired_sessions(
+        self: &Arc<Self>,
+        now: OffsetDateTime,
+    ) -> Result<u64, AppError> {
+        // `last_used` is only written every `LAST_USED_RESOLUTION` seconds
+        let idle_since =
+            |timeout| now - timeout - time::Duration::seconds(Session::LAST_USED_RESOLUTION);
+        let result = sqlx::query!(
+            r#"DELETE FROM sessions
+            WHERE expires_at <= $1
+                OR absolute_expires_at <= $2
+                OR (NOT remember_me AND last_used <= $3)
+                OR (remember_me AND last_used <= $4)"#,
+            now - self.cookies.max_refresh_duration,
+            now,
+            idle_since(self.cookies.standard.idle_timeout),
+            idle_since(self.cookies.remember_me.idle_timeout),
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("{:?}", e);
+            AppError::ServerError
+        })?;
+
+        Ok(result.rows_affected())
+    }
+
     /// removes all the expired sessions of User with `user_id`
-    pub async fn clear_expired_sessions(self: &Arc<Self>, user_id: Uuid) -> Result<(), AppError> {
-        sqlx::query!(r#"DELETE FROM sessions WHERE user_id = $1 AND expires_at <= NOW()"#, user_id)
+    pub async fn clear_expired_sessions(
+        self: &Arc<Self>,
+        user_id: Uuid,
+        now: OffsetDateTime,
+    ) -> Result<(), AppError> {
+        sqlx::query!("DELETE FROM sessions WHERE user_id = $1 AND expires_at <= $2", user_id, now)
             .execute(&self.pool)
             .await
             .map_err(|e| {
@@ -0,0 +1,216 @@
+use sqlx::{
+    Postgres, Transaction,
+    types::{Uuid, time::OffsetDateTime},
+};
+use std::sync::Arc;
+use util::AppError;
+
+/// who can register on a tenant
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RegistrationMode {
+    Open,
+    /// registrations need an invite code issued by an admin
+    InviteOnly,
+}
+
+impl RegistrationMode {
+    pub fn get_str(&self) -> &'static str {
+        match self {
+            RegistrationMode::Open => "open",
+            RegistrationMode::InviteOnly => "invite_only",
+        }
+    }
+}
+
+impl TryFrom<&str> for RegistrationMode {
+    type Error = AppError;
+
+    fn try_from(mode: &str) -> Result<Self, Self::Error> {
+        match mode {
+            "open" => Ok(RegistrationMode::Open),
+            "invite_only" => Ok(RegistrationMode::InviteOnly),
+            _ => Err(AppError::BadReq("Registration modes are open or invite_only")),
+        }
+    }
+}
+
+/// invite code that lets `max_uses` people register until `expires_at`
+#[derive(Clone, Debug, sqlx::FromRow)]
+pub struct Invite {
+    pub code: String,
+    pub tenant_id: String,
+    pub max_uses: i32,
+    pub uses: i32,
+    pub expires_at: Option<OffsetDateTime>,
+    pub created_by: Option<Uuid>,
+    pub created: OffsetDateTime,
+}
+
+// implementation block for the registration policy of the tenants
+// invite codes are checked when a registration starts and used up when the user is created
+impl crate::Db {
+    /// checks that `email` can register on the tenant with the (optional) invite code
+    pub async fn check_registration_policy(
+        self: &Arc<Self>,
+        tenant_id: &str,
+        email: &str,
+        invite_code: Option<&str>,
+    ) -> Result<(), AppError> {
+        let tenant = self.get_tenant(tenant_id).await?.ok_or(AppError::NotFound)?;
+        util::validation::is_email_domain_allowed(
+            email,
+            &tenant.allowed_email_domains,
+            &tenant.denied_email_domains,
+        )?;
+
+        match invite_code {
+            Some(code) => {
+                let valid = sqlx::query_scalar!(
+                    r#"SELECT EXISTS(
+                        SELECT 1 FROM registration_invites
+                        WHERE code = $1 AND tenant_id = $2 AND uses < max_uses
+                            AND (expires_at IS NULL OR expires_at > NOW())
+                    ) as "exists!""#,
+                    code,
+                    tenant_id
+                )
+                .fetch_one(&self.pool)
+                .await
+                .map_err(|e| {
+                    tracing::error!("{:?}", e);
+                    AppError::ServerError
+                })?;
+                if !valid {
+                    return Err(AppError::Forbidden("This invite code is no longer valid"));
+                }
+                Ok(())
+            }
+            None if tenant.registration_mode() == RegistrationMode::InviteOnly => {
+                Err(AppError::Forbidden("Registrations need an invite code"))
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// updates the registration mode and email domain lists of the tenant
+    pub async fn update_registration_policy(
+        self: &Arc<Self>,
+        tenant_id: &str,
+        mode: RegistrationMode,
+        allowed_email_domains: &[String],
+        denied_email_domains: &[String],
+    ) -> Result<(), AppError> {
+        sqlx::query!(
+            r#"UPDATE tenants SET registration_mode = $2, allowed_email_domains = $3,
+                denied_email_domains = $4
+            WHERE id = $1"#,
+            tenant_id,
+            mode.get_str(),
+            allowed_email_domains,
+            denied_email_domains
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("{:?}", e);
+            AppError::ServerError
+        })?;
+
+        self.tenants.invalidate_all();
+        tracing::info!("[Registration Policy] tenant: {tenant_id}, mode: {}", mode.get_str());
+        Ok(())
+    }
+
+    /// returns the invite codes of the tenant, newest first
+    pub async fn list_invites(self: &Arc<Self>, tenant_id: &str) -> Result<Vec<Invite>, AppError> {
+        sqlx::query_as!(
+            Invite,
+            "SELECT * FROM registration_invites WHERE tenant_id = $1 ORDER BY created DESC",
+            tenant_id
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("{:?}", e);
+            AppError::ServerError
+        })
+    }
+
+    /// issues a new invite code for the tenant
+    pub async fn create_invite(
+        self: &Arc<Self>,
+        tenant_id: &str,
+        max_uses: i32,
+        expires_at: Option<OffsetDateTime>,
+        created_by: Uuid,
+    ) -> Result<Invite, AppError> {
+        let invite = sqlx::query_as!(
+            Invite,
+            r#"INSERT INTO registration_invites (code, tenant_id, max_uses, expires_at, created_by)
+            VALUES ($1, $2, $3, $4, $5) RETURNING *"#,
+            util::generate::random_string(24),
+            tenant_id,
+            max_uses,
+            expires_at,
+            created_by
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("{:?}", e);
+            AppError::ServerError
+        })?;
+
+        tracing::info!("[Invite Created] tenant: {tenant_id}, max uses: {max_uses}");
+        Ok(invite)
+    }
+
+    /// deletes the invite code, the registrations that already started with it can't finish
+    pub async fn revoke_invite(
+        self: &Arc<Self>,
+        tenant_id: &str,
+        code: &str,
+    ) -> Result<(), AppError> {
+        let result = sqlx::query!(
+            "DELETE FROM registration_invites WHERE code = $1 AND tenant_id = $2",
+            code,
+            tenant_id
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("{:?}", e);
+            AppError::ServerError
+        })?;
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound);
+        }
+        Ok(())
+    }
+}
+
+/// uses the invite code once within `tx`, fails if it has been used up, revoked or has expired in
+/// the meantime
+pub(crate) async fn redeem_invite(
+    tx: &mut Transaction<'static, Postgres>,
+    tenant_id: &str,
+    code: &str,
+) -> Result<(), AppError> {
+    let result = sqlx::query!(
+        r#"UPDATE registration_invites SET uses = uses + 1
+        WHERE code = $1 AND tenant_id = $2 AND uses < max_uses
+            AND (expires_at IS NULL OR expires_at > NOW())"#,
+        code,
+        tenant_id
+    )
+    .execute(&mut **tx)
+    .await
+    .map_err(|e| {
+        tracing::error!("{:?}", e);
+        AppError::ServerError
+    })?;
+    if result.rows_affected() == 0 {
+        return Err(AppError::Forbidden("This invite code is no longer valid"));
+    }
+    Ok(())
+}
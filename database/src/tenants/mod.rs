@@ -0,0 +1,282 @@
+use sqlx::types::time::OffsetDateTime;
+
+pub mod invites;
+
+pub use invites::{Invite, RegistrationMode};
+use std::{collections::HashMap, sync::Arc};
+use util::{
+    AppError,
+    config::Config,
+    mail::{MailContent, MailKind, MailTemplates},
+    oauth::{OAuthConfig, OAuthProvider},
+};
+
+/// id of the tenant that existing users belong to, resolved when nothing else matches
+pub const DEFAULT_TENANT: &str = "default";
+
+/// a customer served by this deployment, with its own users, sessions and registrants
+#[derive(Clone, Debug, sqlx::FromRow)]
+pub struct Tenant {
+    pub id: String,
+    /// overrides `Config::service_name` when not empty
+    pub service_name: String,
+    /// overrides `Config::service_domain` when not empty
+    pub service_domain: String,
+    /// `Host` header (without port) that resolves to this tenant
+    pub host: Option<String>,
+    pub google_client_id: Option<String>,
+    pub google_client_secret: Option<String>,
+    /// json object of `MailKind` => { "subject", "body", "html" }
+    pub email_templates: String,
+    pub created: OffsetDateTime,
+    /// `open` or `invite_only`
+    pub registration_mode: String,
+    /// email domains that can register, every domain when empty
+    pub allowed_email_domains: Vec<String>,
+    /// email domains that can't register
+    pub denied_email_domains: Vec<String>,
+}
+
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+pub struct MailTemplate {
+    pub subject: String,
+    /// plain text
+    pub body: String,
+    /// alternative of `body`, inserted in the layout of the mail templates
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub html: Option<String>,
+}
+
+impl Tenant {
+    pub fn is_default(&self) -> bool {
+        self.id == DEFAULT_TENANT
+    }
+
+    /// tenant of the cookie names, the default tenant keeps the unsuffixed ones
+    pub fn cookie_scope(&self) -> Option<&str> {
+        (!self.is_default()).then_some(self.id.as_str())
+    }
+
+    pub fn service_name<'a>(&'a self, config: &'a Config) -> &'a str {
+        if self.service_name.is_empty() { &config.service_name } else { &self.service_name }
+    }
+
+    /// url that routes back to this tenant (the path prefix is used without a domain of its own)
+    pub fn base_url(&self, config: &Config) -> String {
+        match self.service_domain.as_str() {
+            "" if self.is_default() => config.service_domain.clone(),
+            "" => format!("{}/t/{}", config.service_domain, self.id),
+            domain => domain.to_string(),
+        }
+    }
+
+    /// returns the tenant's client of `provider`, or the one of `config`
+    pub fn oauth_config(
+        &self,
+        config: &Config,
+        provider: OAuthProvider,
+    ) -> Option<Arc<OAuthConfig<'static>>> {
+        match (provider, &self.google_client_id, &self.google_client_secret) {
+            (OAuthProvider::Google, Some(id), Some(secret)) => {
+                Some(Arc::new(OAuthConfig::google(id.clone(), secret.clone())))
+            }
+            _ => util::oauth::get_oauth_provider(config, provider),
+        }
+    }
+
+    pub fn registration_mode(&self) -> RegistrationMode {
+        RegistrationMode::try_from(self.registration_mode.as_str()).unwrap_or(RegistrationMode::Open)
+    }
+
+    /// the custom templates, none when they can't be parsed so the built-in ones are sent
+    pub fn mail_templates(&self) -> HashMap<String, MailTemplate> {
+        serde_json::from_str(&self.email_templates).unwrap_or_else(|e| {
+            tracing::error!("[Invalid Email Templates] tenant: {}, error: {e}", self.id);
+            HashMap::new()
+        })
+    }
+
+    /// renders the email of `kind` with the tenant's template, or with the one of `templates` in
+    /// the first of `locales` it is available in
+    pub fn mail(
+        &self,
+        config: &Config,
+        templates: &MailTemplates,
+        locales: &[String],
+        kind: MailKind,
+        vars: &[(&str, &str)],
+    ) -> MailContent {
+        let mut vars = vars.to_vec();
+        vars.push(("service_name", self.service_name(config)));
+        let base_url = self.base_url(config);
+        vars.push(("service_domain", &base_url));
+        match self.mail_templates().get(kind.get_str()) {
+            Some(t) => {
+                templates.render_custom(locales, &t.subject, &t.body, t.html.as_deref(), &vars)
+            }
+            None => templates.render(kind, locales, &vars),
+        }
+    }
+}
+
+// implementation block for resolving and managing tenants
+impl crate::Db {
+    /// returns the tenant with `id` (cached)
+    pub async fn get_tenant(self: &Arc<Self>, id: &str) -> Result<Option<Arc<Tenant>>, AppError> {
+        let key = format!("id:{id}");
+        if let Some(tenant) = self.cached_tenant(&key) {
+            return Ok(tenant);
+        }
+        let tenant = sqlx::query_as!(Tenant, "SELECT * FROM tenants WHERE id = $1", id)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| {
+                tracing::error!("{:?}", e);
+                AppError::ServerError
+            })?
+            .map(Arc::new);
+        self.cache_tenant(key, tenant.clone());
+        Ok(tenant)
+    }
+
+    /// returns the tenant served on `host` (cached)
+    pub async fn get_tenant_by_host(
+        self: &Arc<Self>,
+        host: &str,
+    ) -> Result<Option<Arc<Tenant>>, AppError> {
+        let key = format!("host:{host}");
+        if let Some(tenant) = self.cached_tenant(&key) {
+            return Ok(tenant);
+        }
+        let tenant = sqlx::query_as!(Tenant, "SELECT * FROM tenants WHERE host = $1", host)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| {
+                tracing::error!("{:?}", e);
+                AppError::ServerError
+            })?
+            .map(Arc::new);
+        self.cache_tenant(key, tenant.clone());
+        Ok(tenant)
+    }
+
+    /// `Some(None)` when the key is known not to match any tenant
+    fn cached_tenant(&self, key: &str) -> Option<Option<Arc<Tenant>>> {
+        if self.unknown_tenants.contains_key(key) {
+            return Some(None);
+        }
+        util::metrics::cache_lookup("tenants", self.tenants.get(key)).map(Some)
+    }
+
+    // unknown keys go to their own cache, so requests with random hosts can't evict the tenants
+    fn cache_tenant(&self, key: String, tenant: Option<Arc<Tenant>>) {
+        match tenant {
+            Some(tenant) => self.tenants.insert(key, tenant),
+            None => self.unknown_tenants.insert(key, ()),
+        }
+    }
+
+    pub async fn get_default_tenant(self: &Arc<Self>) -> Result<Arc<Tenant>, AppError> {
+        self.get_tenant(DEFAULT_TENANT).await?.ok_or_else(|| {
+            tracing::error!("The default tenant is missing");
+            AppError::ServerError
+        })
+    }
+
+    /// returns every tenant with its number of users
+    pub async fn list_tenants(self: &Arc<Self>) -> Result<Vec<(Tenant, i64)>, AppError> {
+        let tenants = sqlx::query_as!(Tenant, "SELECT * FROM tenants ORDER BY created, id")
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| {
+                tracing::error!("{:?}", e);
+                AppError::ServerError
+            })?;
+        let counts =
+            sqlx::query!(r#"SELECT tenant_id, COUNT(*) as "count!" FROM users GROUP BY tenant_id"#)
+                .fetch_all(&self.pool)
+                .await
+                .map_err(|e| {
+                    tracing::error!("{:?}", e);
+                    AppError::ServerError
+                })?
+                .into_iter()
+                .map(|r| (r.tenant_id, r.count))
+                .collect::<HashMap<_, _>>();
+
+        Ok(tenants
+            .into_iter()
+            .map(|t| {
+                let count = counts.get(&t.id).copied().unwrap_or(0);
+                (t, count)
+            })
+            .collect())
+    }
+
+    /// creates or replaces a tenant
+    pub async fn upsert_tenant(self: &Arc<Self>, tenant: &Tenant) -> Result<(), AppError> {
+        sqlx::query!(
+            r#"INSERT INTO tenants (
+                id, service_name, service_domain, host, google_client_id, google_client_secret,
+                email_templates
+            ) VALUES ($1, $2, $3, $4, $5, $6, $7)
+            ON CONFLICT (id) DO UPDATE SET
+                service_name = EXCLUDED.service_name,
+                service_domain = EXCLUDED.service_domain,
+                host = EXCLUDED.host,
+                google_client_id = EXCLUDED.google_client_id,
+                google_client_secret = EXCLUDED.google_client_secret,
+                email_templates = EXCLUDED.email_templates"#,
+            tenant.id,
+            tenant.service_name,
+            tenant.service_domain,
+            tenant.host,
+            tenant.google_client_id,
+            tenant.google_client_secret,
+            tenant.email_templates,
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| {
+            if let Some(db_err) = e.as_database_error()
+                && db_err.code() == Some(std::borrow::Cow::Borrowed("23505"))
+            {
+                return AppError::BadReq("Another tenant is served on this host");
+            }
+            tracing::error!("{:?}", e);
+            AppError::ServerError
+        })?;
+
+        self.tenants.invalidate_all();
+        self.unknown_tenants.invalidate_all();
+        tracing::info!("[Tenant Saved] id: {}", tenant.id);
+        Ok(())
+    }
+
+    /// deletes a tenant without users
+    pub async fn delete_tenant(self: &Arc<Self>, id: &str) -> Result<(), AppError> {
+        if id == DEFAULT_TENANT {
+            return Err(AppError::BadReq("The default tenant can't be deleted"));
+        }
+        let result = sqlx::query!("DELETE FROM tenants WHERE id = $1", id)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| {
+                if let Some(db_err) = e.as_database_error()
+                    && db_err.code() == Some(std::borrow::Cow::Borrowed("23503"))
+                {
+                    return AppError::BadReq("The tenant still has users");
+                }
+                tracing::error!("{:?}", e);
+                AppError::ServerError
+            })?;
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound);
+        }
+
+        self.tenants.invalidate_all();
+        self.unknown_tenants.invalidate_all();
+        tracing::info!("[Tenant Deleted] id: {id}");
+        Ok(())
+    }
+}
@@ -0,0 +1,308 @@
+use super::{AccountStatus, DeletedUser, User};
+use crate::events::SessionEvent;
+use sqlx::types::{Uuid, time::OffsetDateTime};
+use std::sync::Arc;
+use util::AppError;
+
+/// filters, ordering and page of an admin user search
+pub struct UserSearch {
+    pub tenant_id: String,
+    /// matched against email and username (case insensitive substring)
+    pub query: Option<String>,
+    pub status: Option<AccountStatus>,
+    pub created_after: Option<OffsetDateTime>,
+    pub created_before: Option<OffsetDateTime>,
+    pub sort: UserSort,
+    pub descending: bool,
+    pub limit: i64,
+    pub offset: i64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum UserSort {
+    Created,
+    Email,
+    Username,
+}
+
+impl UserSort {
+    fn get_str(&self) -> &'static str {
+        match self {
+            UserSort::Created => "created",
+            UserSort::Email => "email",
+            UserSort::Username => "username",
+        }
+    }
+}
+
+// implementation block for managing users as an admin
+impl crate::Db {
+    /// returns a page of users matching `search` and the total number of matching users
+    pub async fn search_users(
+        self: &Arc<Self>,
+        search: &UserSearch,
+    ) -> Result<(Vec<User>, i64), AppError> {
+        let pattern = search.query.as_deref().map(like_pattern);
+        let status = search.status.map(|s| s.get_str());
+
+        let users = sqlx::query_as!(
+            User,
+            r#"SELECT * FROM users
+            WHERE ($1::TEXT IS NULL OR email ILIKE $1 OR username ILIKE $1)
+                AND ($2::VARCHAR IS NULL OR status = $2)
+                AND ($3::TIMESTAMPTZ IS NULL OR created >= $3)
+                AND ($4::TIMESTAMPTZ IS NULL OR created < $4)
+                AND tenant_id = $9
+            ORDER BY
+                CASE WHEN $5 = 'email' AND NOT $6 THEN email END ASC,
+                CASE WHEN $5 = 'email' AND $6 THEN email END DESC,
+                CASE WHEN $5 = 'username' AND NOT $6 THEN username END ASC,
+                CASE WHEN $5 = 'username' AND $6 THEN username END DESC,
+                CASE WHEN $5 = 'created' AND NOT $6 THEN created END ASC,
+                created DESC, id
+            LIMIT $7 OFFSET $8"#,
+            pattern,
+            status,
+            search.created_after,
+            search.created_before,
+            search.sort.get_str(),
+            search.descending,
+            search.limit,
+            search.offset,
+            search.tenant_id,
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("{:?}", e);
+            AppError::ServerError
+        })?;
+
+        let total = sqlx::query_scalar!(
+            r#"SELECT COUNT(*) as "count!" FROM users
+            WHERE ($1::TEXT IS NULL OR email ILIKE $1 OR username ILIKE $1)
+                AND ($2::VARCHAR IS NULL OR status = $2)
+                AND ($3::TIMESTAMPTZ IS NULL OR created >= $3)
+                AND ($4::TIMESTAMPTZ IS NULL OR created < $4)
+                AND tenant_id = $5"#,
+            pattern,
+            status,
+            search.created_after,
+            search.created_before,
+            search.tenant_id,
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("{:?}", e);
+            AppError::ServerError
+        })?;
+
+        Ok((users, total))
+    }
+
+    /// returns a page of deleted users matching `query`, most recently deleted first
+    pub async fn search_deleted_users(
+        self: &Arc<Self>,
+        tenant_id: &str,
+        query: Option<&str>,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<DeletedUser>, AppError> {
+        sqlx::query_as!(
+            DeletedUser,
+            r#"SELECT
+                id, display_name, email, birth_date, password, username, banner,
+                icon, bio, legal_name, gender, phone, country,
+                COALESCE(oauth_provider, '') as "oauth_provider!", created,
+                bind_sessions, status, tenant_id, restricted, birth_date_changes, deleted
+            FROM deleted_users
+            WHERE ($1::TEXT IS NULL OR email ILIKE $1 OR username ILIKE $1) AND tenant_id = $4
+            ORDER BY deleted DESC, id
+            LIMIT $2 OFFSET $3"#,
+            query.map(like_pattern),
+            limit,
+            offset,
+            tenant_id,
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("{:?}", e);
+            AppError::ServerError
+        })
+    }
+
+    /// updates the account status of User with `user_id`
+    pub async fn set_user_status(
+        self: &Arc<Self>,
+        user_id: Uuid,
+        status: AccountStatus,
+    ) -> Result<(), AppError> {
+        let result =
+            sqlx::query!("UPDATE users SET status = $1 WHERE id = $2", status.get_str(), user_id)
+                .execute(&self.pool)
+                .await
+                .map_err(|e| {
+                    tracing::error!("{:?}", e);
+                    AppError::ServerError
+                })?;
+        if result.rows_affected() == 0 {
+            return Err(AppError::UserNotFound);
+        }
+
+        // the cached user would still carry the old status
+        self.active.invalidate(&user_id);
+        tracing::info!("[Account Status Updated] user_id: {user_id}, status: {}", status.get_str());
+        Ok(())
+    }
+
+    /// flags or unflags the account of User with `user_id` as restricted
+    pub async fn set_user_restricted(
+        self: &Arc<Self>,
+        user_id: Uuid,
+        restricted: bool,
+    ) -> Result<(), AppError> {
+        let result =
+            sqlx::query!("UPDATE users SET restricted = $1 WHERE id = $2", restricted, user_id)
+                .execute(&self.pool)
+                .await
+                .map_err(|e| {
+                    tracing::error!("{:?}", e);
+                    AppError::ServerError
+                })?;
+        if result.rows_affected() == 0 {
+            return Err(AppError::UserNotFound);
+        }
+
+        self.active.invalidate(&user_id);
+        tracing::info!("[Account Restriction Updated] user_id: {user_id}, restricted: {restricted}");
+        Ok(())
+    }
+
+    /// returns the id, birth date and country of the restricted users with a birth date
+    pub async fn list_restricted_users(
+        self: &Arc<Self>,
+    ) -> Result<Vec<(Uuid, OffsetDateTime, Option<String>)>, AppError> {
+        let rows = sqlx::query!(
+            r#"SELECT id, birth_date as "birth_date!", country FROM users
+            WHERE restricted AND birth_date IS NOT NULL"#
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("{:?}", e);
+            AppError::ServerError
+        })?;
+        Ok(rows.into_iter().map(|row| (row.id, row.birth_date, row.country)).collect())
+    }
+
+    /// removes every session of User with `user_id` (in both primary and in-memory database)
+    ///
+    /// returns the number of sessions removed
+    pub async fn revoke_user_sessions(self: &Arc<Self>, user_id: Uuid) -> Result<u64, AppError> {
+        let result = sqlx::query!("DELETE FROM sessions WHERE user_id = $1", user_id)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| {
+                tracing::error!("{:?}", e);
+                AppError::ServerError
+            })?;
+
+        self.active.invalidate(&user_id);
+        self.publish_event(user_id, SessionEvent::AllSessionsRevoked { except: Uuid::nil() });
+        tracing::info!("[All Sessions Revoked] user_id: {user_id}");
+        Ok(result.rows_affected())
+    }
+
+    /// moves the deleted user with `user_id` back to the users table
+    pub async fn restore_deleted_user(
+        self: &Arc<Self>,
+        tenant_id: &str,
+        user_id: Uuid,
+    ) -> Result<User, AppError> {
+        let mut tx = self.pool.begin().await.map_err(|e| {
+            tracing::error!("{:?}", e);
+            AppError::ServerError
+        })?;
+
+        let user = sqlx::query_as!(
+            User,
+            r#"INSERT INTO users (
+                id, display_name, email, birth_date, password, username, banner,
+                icon, bio, legal_name, gender, phone, country, oauth_provider, created,
+                bind_sessions, status, tenant_id, restricted, birth_date_changes
+            ) SELECT
+                id, display_name, email, birth_date, password, username, banner,
+                icon, bio, legal_name, gender, phone, country, COALESCE(oauth_provider, ''),
+                created, bind_sessions, status, tenant_id, restricted, birth_date_changes
+            FROM deleted_users WHERE id = $1 AND tenant_id = $2
+            RETURNING *"#,
+            user_id,
+            tenant_id
+        )
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|e| {
+            if let Some(db_err) = e.as_database_error()
+                && db_err.code() == Some(std::borrow::Cow::Borrowed("23505"))
+            {
+                return if db_err.message().contains("email") {
+                    AppError::EmailTaken
+                } else {
+                    AppError::UsernameTaken
+                };
+            }
+            tracing::error!("{:?}", e);
+            AppError::ServerError
+        })?
+        .ok_or(AppError::UserNotFound)?;
+
+        sqlx::query!("DELETE FROM deleted_users WHERE id = $1", user_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| {
+                tracing::error!("{:?}", e);
+                AppError::ServerError
+            })?;
+
+        tx.commit().await.map_err(|e| {
+            tracing::error!("{:?}", e);
+            AppError::ServerError
+        })?;
+
+        tracing::info!("[User Restored] Username: {}, Email: {}", user.username, user.email);
+        Ok(user)
+    }
+
+    /// permanently removes the deleted user with `user_id`
+    pub async fn purge_deleted_user(
+        self: &Arc<Self>,
+        tenant_id: &str,
+        user_id: Uuid,
+    ) -> Result<(), AppError> {
+        let result = sqlx::query!(
+            "DELETE FROM deleted_users WHERE id = $1 AND tenant_id = $2",
+            user_id,
+            tenant_id
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("{:?}", e);
+            AppError::ServerError
+        })?;
+        if result.rows_affected() == 0 {
+            return Err(AppError::UserNotFound);
+        }
+
+        tracing::info!("[Deleted User Purged] user_id: {user_id}");
+        Ok(())
+    }
+}
+
+/// returns a case insensitive substring pattern for `query` (with wildcards escaped)
+fn like_pattern(query: &str) -> String {
+    let escaped = query.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_");
+    format!("%{escaped}%")
+}
@@ -14,8 +14,12 @@ impl crate::Db {
         sqlx::query!(
             r#"INSERT INTO deleted_users (
                 id, display_name, email, birth_date, password, username, banner,
-                icon, bio, legal_name, gender, phone, country, oauth_provider, created
-            ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15)"#,
+                icon, bio, legal_name, gender, phone, country, oauth_provider, created,
+                bind_sessions, status, tenant_id, restricted, birth_date_changes
+            ) VALUES (
+                $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19,
+                $20
+            )"#,
             user.id,
             user.display_name,
             user.email,
@@ -31,6 +35,25 @@ impl crate::Db {
             user.country,
             user.oauth_provider.get_str(),
             user.created,
+            user.bind_sessions,
+            user.status.get_str(),
+            user.tenant_id,
+            user.restricted,
+            user.birth_date_changes,
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| {
+            tracing::error!("{:?}", e);
+            AppError::ServerError
+        })?;
+
+        // Delete the organizations that would be left without members
+        sqlx::query!(
+            r#"DELETE FROM organizations o USING memberships m
+            WHERE m.org_id = o.id AND m.user_id = $1
+                AND NOT EXISTS(SELECT 1 FROM memberships x WHERE x.org_id = o.id AND x.user_id <> $1)"#,
+            user.id
         )
         .execute(&mut *tx)
         .await
@@ -56,4 +79,22 @@ impl crate::Db {
         tracing::info!("[User Deleted] Username: {}, Email: {}", user.username, user.email);
         Ok(())
     }
+
+    /// permanently removes the deleted users that were deleted before `deleted_before`
+    ///
+    /// returns the number of deleted users removed
+    pub async fn purge_deleted_users(
+        self: &Arc<Self>,
+        deleted_before: sqlx::types::time::OffsetDateTime,
+    ) -> Result<u64, AppError> {
+        let result = sqlx::query!("DELETE FROM deleted_users WHERE deleted < $1", deleted_before)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| {
+                tracing::error!("{:?}", e);
+                AppError::ServerError
+            })?;
+
+        Ok(result.rows_affected())
+    }
 }
@@ -0,0 +1,557 @@
+use super::{AccountStatus, User};
+use sqlx::types::{Uuid, time::OffsetDateTime};
+use std::{collections::HashMap, sync::Arc};
+use time::format_description::well_known::Rfc3339;
+use util::{AppError, password::ForeignHash};
+
+/// columns of the csv format, `identities` holds `provider:subject` pairs separated by `;`
+pub const CSV_HEADERS: [&str; 14] = [
+    "id",
+    "email",
+    "username",
+    "display_name",
+    "password_hash",
+    "created",
+    "birth_date",
+    "bio",
+    "legal_name",
+    "gender",
+    "phone",
+    "country",
+    "status",
+    "identities",
+];
+
+/// a user as read by the importer and written by the exporter
+///
+/// dates are RFC 3339 strings, the password is only present as a foreign hash
+#[derive(Clone, Debug, Default, serde::Deserialize, serde::Serialize)]
+pub struct PortableUser {
+    pub id: Option<String>,
+    pub email: String,
+    pub username: String,
+    pub display_name: String,
+    pub password_hash: Option<String>,
+    pub created: Option<String>,
+    pub birth_date: Option<String>,
+    pub bio: Option<String>,
+    pub legal_name: Option<String>,
+    pub gender: Option<String>,
+    pub phone: Option<String>,
+    pub country: Option<String>,
+    pub status: Option<String>,
+    #[serde(default)]
+    pub identities: Vec<ExternalIdentity>,
+}
+
+/// account of a user at an external identity provider
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+pub struct ExternalIdentity {
+    pub provider: String,
+    pub subject: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PortableFormat {
+    Csv,
+    JsonLines,
+}
+
+impl TryFrom<&str> for PortableFormat {
+    type Error = AppError;
+
+    fn try_from(format: &str) -> Result<Self, Self::Error> {
+        match format {
+            "csv" => Ok(PortableFormat::Csv),
+            "jsonl" => Ok(PortableFormat::JsonLines),
+            _ => Err(AppError::InvalidData("Format should be `csv` or `jsonl`")),
+        }
+    }
+}
+
+impl PortableFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            PortableFormat::Csv => "text/csv",
+            PortableFormat::JsonLines => "application/x-ndjson",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            PortableFormat::Csv => "csv",
+            PortableFormat::JsonLines => "jsonl",
+        }
+    }
+
+    /// parses every row of `data`, keeping the row (line) number of each one
+    pub fn parse(&self, data: &[u8]) -> Vec<(u64, Result<PortableUser, ImportError>)> {
+        match self {
+            PortableFormat::JsonLines => String::from_utf8_lossy(data)
+                .lines()
+                .enumerate()
+                .filter(|(_, line)| !line.trim().is_empty())
+                .map(|(i, line)| {
+                    let user = serde_json::from_str(line)
+                        .map_err(|e| ImportError::Invalid(format!("Invalid JSON: {e}")));
+                    (i as u64 + 1, user)
+                })
+                .collect(),
+            PortableFormat::Csv => {
+                let mut reader = csv::ReaderBuilder::new().flexible(true).from_reader(data);
+                let headers = match reader.headers() {
+                    Ok(headers) => headers.clone(),
+                    Err(e) => {
+                        return vec![(1, Err(ImportError::Invalid(format!("Invalid CSV: {e}"))))];
+                    }
+                };
+                reader
+                    .records()
+                    .map(|record| {
+                        let record = match record {
+                            Ok(record) => record,
+                            Err(e) => {
+                                let row = e.position().map(|p| p.line()).unwrap_or_default();
+                                return (
+                                    row,
+                                    Err(ImportError::Invalid(format!("Invalid CSV: {e}"))),
+                                );
+                            }
+                        };
+                        let row = record.position().map(|p| p.line()).unwrap_or_default();
+                        (row, csv_row(&headers, &record))
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    /// encodes a page of users, starting with the csv header on the first page
+    pub fn encode(&self, users: &[PortableUser], first_page: bool) -> Vec<u8> {
+        match self {
+            PortableFormat::JsonLines => {
+                let mut out = Vec::new();
+                for user in users {
+                    serde_json::to_writer(&mut out, user).unwrap();
+                    out.push(b'\n');
+                }
+                out
+            }
+            PortableFormat::Csv => {
+                let mut writer = csv::Writer::from_writer(Vec::new());
+                if first_page {
+                    writer.write_record(CSV_HEADERS).unwrap();
+                }
+                for user in users {
+                    let identities = user
+                        .identities
+                        .iter()
+                        .map(|v| format!("{}:{}", v.provider, v.subject))
+                        .collect::<Vec<_>>()
+                        .join(";");
+                    let opt = |v: &Option<String>| v.clone().unwrap_or_default();
+                    writer
+                        .write_record([
+                            opt(&user.id),
+                            user.email.clone(),
+                            user.username.clone(),
+                            user.display_name.clone(),
+                            opt(&user.password_hash),
+                            opt(&user.created),
+                            opt(&user.birth_date),
+                            opt(&user.bio),
+                            opt(&user.legal_name),
+                            opt(&user.gender),
+                            opt(&user.phone),
+                            opt(&user.country),
+                            opt(&user.status),
+                            identities,
+                        ])
+                        .unwrap();
+                }
+                writer.into_inner().unwrap_or_default()
+            }
+        }
+    }
+}
+
+// maps a csv record to the json shape of `PortableUser`, empty cells are missing values
+fn csv_row(
+    headers: &csv::StringRecord,
+    record: &csv::StringRecord,
+) -> Result<PortableUser, ImportError> {
+    let mut object = serde_json::Map::new();
+    for (header, value) in headers.iter().zip(record.iter()) {
+        let value = value.trim();
+        if value.is_empty() {
+            continue;
+        }
+        if header == "identities" {
+            let identities = value
+                .split(';')
+                .filter(|v| !v.is_empty())
+                .map(|v| match v.split_once(':') {
+                    Some((provider, subject)) => {
+                        Ok(serde_json::json!({ "provider": provider, "subject": subject }))
+                    }
+                    None => Err(ImportError::Invalid(format!("Invalid identity `{v}`"))),
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            object.insert(header.to_string(), serde_json::Value::Array(identities));
+        } else {
+            object.insert(header.to_string(), serde_json::Value::String(value.to_string()));
+        }
+    }
+    serde_json::from_value(serde_json::Value::Object(object))
+        .map_err(|e| ImportError::Invalid(format!("Invalid row: {e}")))
+}
+
+/// reason a row couldn't be imported
+#[derive(Clone, Debug, PartialEq)]
+pub enum ImportError {
+    Invalid(String),
+    IdTaken,
+    EmailTaken,
+    UsernameTaken,
+    IdentityTaken,
+    ServerError,
+}
+
+impl ImportError {
+    pub fn get_str(&self) -> &'static str {
+        match self {
+            ImportError::Invalid(_) => "invalid",
+            ImportError::IdTaken => "id_taken",
+            ImportError::EmailTaken => "email_taken",
+            ImportError::UsernameTaken => "username_taken",
+            ImportError::IdentityTaken => "identity_taken",
+            ImportError::ServerError => "server_error",
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match self {
+            ImportError::Invalid(message) => message.clone(),
+            ImportError::IdTaken => "A user with this id already exists".to_string(),
+            ImportError::EmailTaken => "Email already taken".to_string(),
+            ImportError::UsernameTaken => "Username already taken".to_string(),
+            ImportError::IdentityTaken => "Identity already linked to another user".to_string(),
+            ImportError::ServerError => "Something went wrong".to_string(),
+        }
+    }
+}
+
+impl From<AppError> for ImportError {
+    fn from(e: AppError) -> Self {
+        match e {
+            AppError::InvalidData(message) | AppError::BadReq(message) => {
+                ImportError::Invalid(message.to_string())
+            }
+            AppError::InvalidDataFmt(message) => ImportError::Invalid(message),
+            AppError::InvalidEmailFormat => ImportError::Invalid("Invalid Email Format".to_string()),
+            AppError::EmailTaken => ImportError::EmailTaken,
+            AppError::UsernameTaken => ImportError::UsernameTaken,
+            _ => ImportError::ServerError,
+        }
+    }
+}
+
+/// outcome of importing one row
+pub struct ImportedRow {
+    pub row: u64,
+    pub result: Result<Uuid, ImportError>,
+}
+
+impl ImportedRow {
+    /// the row as reported by the api and the cli, with its new id or its error
+    pub fn to_json(&self) -> serde_json::Value {
+        match &self.result {
+            Ok(id) => serde_json::json!({ "row": self.row, "id": id.to_string() }),
+            Err(e) => serde_json::json!({
+                "row": self.row,
+                "error": e.get_str(),
+                "message": e.message(),
+            }),
+        }
+    }
+}
+
+fn parse_date(value: Option<&str>, field: &str) -> Result<Option<OffsetDateTime>, ImportError> {
+    value
+        .map(|v| OffsetDateTime::parse(v, &Rfc3339))
+        .transpose()
+        .map_err(|_| ImportError::Invalid(format!("`{field}` should be an RFC 3339 date")))
+}
+
+fn format_date(value: OffsetDateTime) -> Option<String> {
+    value.format(&Rfc3339).ok()
+}
+
+// implementation block for moving users in and out in bulk
+impl crate::Db {
+    /// imports every row of `data`, a failing row is reported without stopping the others
+    pub async fn import_users(
+        self: &Arc<Self>,
+        tenant_id: &str,
+        format: PortableFormat,
+        data: &[u8],
+    ) -> Vec<ImportedRow> {
+        let mut rows = Vec::new();
+        for (row, user) in format.parse(data) {
+            let result = match user {
+                Ok(user) => self.import_user(tenant_id, user).await,
+                Err(e) => Err(e),
+            };
+            rows.push(ImportedRow { row, result });
+        }
+        let imported = rows.iter().filter(|v| v.result.is_ok()).count();
+        tracing::info!(
+            "[Users Imported] tenant: {tenant_id}, imported: {imported}, failed: {}",
+            rows.len() - imported
+        );
+        rows
+    }
+
+    /// adds an imported user, with their foreign password hash and external identities
+    pub async fn import_user(
+        self: &Arc<Self>,
+        tenant_id: &str,
+        user: PortableUser,
+    ) -> Result<Uuid, ImportError> {
+        let id = match user.id.as_deref() {
+            Some(id) => {
+                Uuid::try_parse(id).map_err(|_| ImportError::Invalid("Invalid id".to_string()))?
+            }
+            None => Uuid::new_v4(),
+        };
+        util::validation::is_email_valid(&user.email)?;
+        util::validation::is_username_valid(&user.username)?;
+        util::validation::is_display_name_valid(&user.display_name)?;
+        if let Some(hash) = &user.password_hash {
+            hash.parse::<ForeignHash>().map_err(|e| ImportError::Invalid(e.to_string()))?;
+        }
+        let status = match user.status.as_deref() {
+            None => AccountStatus::Active,
+            Some(v @ ("active" | "suspended" | "password_reset_required")) => AccountStatus::from(v),
+            Some(_) => return Err(ImportError::Invalid("Invalid account status".to_string())),
+        };
+        if self.applications.is_email_present(tenant_id, &user.email) {
+            return Err(ImportError::EmailTaken);
+        }
+        let created =
+            parse_date(user.created.as_deref(), "created")?.unwrap_or_else(OffsetDateTime::now_utc);
+        let birth_date = parse_date(user.birth_date.as_deref(), "birth_date")?;
+
+        let db_error = |e: sqlx::Error| {
+            if let Some(db_err) = e.as_database_error()
+                && db_err.code() == Some(std::borrow::Cow::Borrowed("23505"))
+            {
+                return match db_err.constraint() {
+                    Some("users_pkey") => ImportError::IdTaken,
+                    Some("users_tenant_email_key") => ImportError::EmailTaken,
+                    Some("users_tenant_username_key") => ImportError::UsernameTaken,
+                    _ => ImportError::IdentityTaken,
+                };
+            }
+            tracing::error!("{:?}", e);
+            ImportError::ServerError
+        };
+
+        let mut tx = self.pool.begin().await.map_err(db_error)?;
+        sqlx::query!(
+            r#"INSERT INTO users (
+                id, display_name, email, birth_date, username, bio, legal_name,
+                gender, phone, country, oauth_provider, created, status, tenant_id
+            ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, '', $11, $12, $13)"#,
+            id,
+            user.display_name,
+            user.email,
+            birth_date,
+            user.username,
+            user.bio,
+            user.legal_name,
+            user.gender,
+            user.phone,
+            user.country,
+            created,
+            status.get_str(),
+            tenant_id,
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(db_error)?;
+
+        if let Some(hash) = &user.password_hash {
+            sqlx::query!("INSERT INTO imported_passwords (user_id, hash) VALUES ($1, $2)", id, hash)
+                .execute(&mut *tx)
+                .await
+                .map_err(db_error)?;
+        }
+        for identity in user.identities.iter() {
+            sqlx::query!(
+                r#"INSERT INTO user_identities (tenant_id, provider, subject, user_id)
+                VALUES ($1, $2, $3, $4)"#,
+                tenant_id,
+                identity.provider,
+                identity.subject,
+                id
+            )
+            .execute(&mut *tx)
+            .await
+            .map_err(db_error)?;
+        }
+
+        tx.commit().await.map_err(db_error)?;
+        Ok(id)
+    }
+
+    /// returns the users after `after_id` (ordered by id) in the export format
+    pub async fn export_users_page(
+        self: &Arc<Self>,
+        tenant_id: &str,
+        after_id: Option<Uuid>,
+        limit: i64,
+    ) -> Result<Vec<PortableUser>, AppError> {
+        let users = sqlx::query_as!(
+            User,
+            r#"SELECT * FROM users WHERE tenant_id = $1 AND ($2::UUID IS NULL OR id > $2)
+            ORDER BY id LIMIT $3"#,
+            tenant_id,
+            after_id,
+            limit
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("{:?}", e);
+            AppError::ServerError
+        })?;
+        let ids = users.iter().map(|u| u.id).collect::<Vec<_>>();
+
+        let mut hashes = sqlx::query!(
+            "SELECT user_id, hash FROM imported_passwords WHERE user_id = ANY($1)",
+            &ids
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("{:?}", e);
+            AppError::ServerError
+        })?
+        .into_iter()
+        .map(|r| (r.user_id, r.hash))
+        .collect::<HashMap<_, _>>();
+
+        let mut identities = HashMap::<Uuid, Vec<ExternalIdentity>>::new();
+        for r in sqlx::query!(
+            "SELECT user_id, provider, subject FROM user_identities WHERE user_id = ANY($1)",
+            &ids
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("{:?}", e);
+            AppError::ServerError
+        })? {
+            let identity = ExternalIdentity { provider: r.provider, subject: r.subject };
+            identities.entry(r.user_id).or_default().push(identity);
+        }
+
+        Ok(users
+            .into_iter()
+            .map(|user| PortableUser {
+                id: Some(user.id.to_string()),
+                password_hash: hashes.remove(&user.id),
+                created: format_date(user.created),
+                birth_date: user.birth_date.and_then(format_date),
+                status: Some(user.status.get_str().to_string()),
+                identities: identities.remove(&user.id).unwrap_or_default(),
+                email: user.email,
+                username: user.username,
+                display_name: user.display_name,
+                bio: user.bio,
+                legal_name: user.legal_name,
+                gender: user.gender,
+                phone: user.phone,
+                country: user.country,
+            })
+            .collect())
+    }
+
+    /// returns the user of the tenant linked to `subject` at `provider`
+    pub async fn get_user_by_identity(
+        self: &Arc<Self>,
+        tenant_id: &str,
+        provider: &str,
+        subject: &str,
+    ) -> Result<Option<User>, AppError> {
+        sqlx::query_as!(
+            User,
+            r#"SELECT u.* FROM users u
+            JOIN user_identities i ON i.user_id = u.id
+            WHERE i.tenant_id = $1 AND i.provider = $2 AND i.subject = $3"#,
+            tenant_id,
+            provider,
+            subject
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("{:?}", e);
+            AppError::ServerError
+        })
+    }
+
+    // returns the foreign hash of an imported user who hasn't logged in yet
+    pub(crate) async fn get_imported_password(
+        &self,
+        user_id: Uuid,
+    ) -> Result<Option<String>, AppError> {
+        sqlx::query_scalar!("SELECT hash FROM imported_passwords WHERE user_id = $1", user_id)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| {
+                tracing::error!("{:?}", e);
+                AppError::ServerError
+            })
+    }
+
+    // replaces the foreign hash of an imported user with their password
+    pub(crate) async fn replace_imported_password(
+        self: &Arc<Self>,
+        user_id: Uuid,
+        password: &str,
+    ) -> Result<(), AppError> {
+        let mut tx = self.pool.begin().await.map_err(|e| {
+            tracing::error!("{:?}", e);
+            AppError::ServerError
+        })?;
+        sqlx::query!(
+            "UPDATE users SET password = $1 WHERE id = $2 AND password IS NULL",
+            password,
+            user_id
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| {
+            tracing::error!("{:?}", e);
+            AppError::ServerError
+        })?;
+        sqlx::query!("DELETE FROM imported_passwords WHERE user_id = $1", user_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| {
+                tracing::error!("{:?}", e);
+                AppError::ServerError
+            })?;
+        tx.commit().await.map_err(|e| {
+            tracing::error!("{:?}", e);
+            AppError::ServerError
+        })?;
+
+        // other nodes may still cache the user without a password
+        self.notify_user_changed(user_id).await?;
+        tracing::info!("[Imported Password Replaced] user_id: {user_id}");
+        Ok(())
+    }
+}
@@ -1,11 +1,15 @@
 use sqlx::types::time::OffsetDateTime;
 
+pub mod admin;
 mod create;
 mod delete;
+pub mod import;
 mod read;
 mod update_by_email;
 mod update_by_username;
 
+pub(crate) use create::insert_user;
+
 macro_rules! user_struct {
     (
         $name:ident {
@@ -29,6 +33,13 @@ macro_rules! user_struct {
             pub country: Option<String>,
             pub oauth_provider: util::oauth::OAuthProvider,
             pub created: OffsetDateTime,
+            pub bind_sessions: bool,
+            pub status: AccountStatus,
+            pub tenant_id: String,
+            /// the user is under the age threshold of their country
+            pub restricted: bool,
+            /// number of times the user changed their birth date after it was first set
+            pub birth_date_changes: i32,
             $(pub $extra_field: $extra_type,)*
         }
     };
@@ -37,3 +48,47 @@ macro_rules! user_struct {
 user_struct!(User {});
 
 user_struct!(DeletedUser { deleted: OffsetDateTime });
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AccountStatus {
+    Active,
+    /// the user can't log in until an admin unsuspends the account
+    Suspended,
+    /// the user can't log in until they reset their password
+    PasswordResetRequired,
+}
+
+impl From<&str> for AccountStatus {
+    fn from(status: &str) -> Self {
+        match status {
+            "suspended" => AccountStatus::Suspended,
+            "password_reset_required" => AccountStatus::PasswordResetRequired,
+            _ => AccountStatus::Active,
+        }
+    }
+}
+
+impl From<String> for AccountStatus {
+    fn from(value: String) -> Self {
+        AccountStatus::from(value.as_str())
+    }
+}
+
+impl AccountStatus {
+    pub fn get_str(&self) -> &'static str {
+        match self {
+            AccountStatus::Active => "active",
+            AccountStatus::Suspended => "suspended",
+            AccountStatus::PasswordResetRequired => "password_reset_required",
+        }
+    }
+
+    /// returns an error if a user with this status isn't allowed to log in or use their sessions
+    pub fn ensure_active(&self) -> Result<(), util::AppError> {
+        match self {
+            AccountStatus::Active => Ok(()),
+            AccountStatus::Suspended => Err(util::AppError::AccountSuspended),
+            AccountStatus::PasswordResetRequired => Err(util::AppError::PasswordResetRequired),
+        }
+    }
+}
@@ -1,108 +1,197 @@
 use super::User;
+use crate::UserData;
+use sqlx::types::Uuid;
 use std::sync::Arc;
-use util::AppError;
+use util::{AppError, password::ForeignHash};
 
 // implementation block for checking user attributes
 impl crate::Db {
     // Check if email is available
-    pub async fn is_email_available(&self, email: &str) -> Result<(), AppError> {
-        if self.applications.is_email_present(email) {
+    pub async fn is_email_available(&self, tenant_id: &str, email: &str) -> Result<(), AppError> {
+        if self.applications.is_email_present(tenant_id, email) {
             return Err(AppError::EmailTaken);
         }
 
-        let exists =
-            sqlx::query_scalar!("SELECT EXISTS(SELECT 1 FROM users WHERE email = $1)", email)
-                .fetch_one(&self.pool)
-                .await
-                .map_err(|e| {
-                    tracing::error!("{:?}", e);
-                    AppError::ServerError
-                })?;
+        let exists = sqlx::query_scalar!(
+            "SELECT EXISTS(SELECT 1 FROM users WHERE tenant_id = $1 AND email = $2)",
+            tenant_id,
+            email
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("{:?}", e);
+            AppError::ServerError
+        })?;
 
         if exists.unwrap_or(false) { Err(AppError::EmailTaken) } else { Ok(()) }
     }
 
     // Check if username is available
-    pub async fn is_username_available(&self, username: &str) -> Result<(), AppError> {
-        let exists =
-            sqlx::query_scalar!("SELECT EXISTS(SELECT 1 FROM users WHERE username = $1)", username)
-                .fetch_one(&self.pool)
-                .await
-                .map_err(|e| {
-                    tracing::error!("{:?}", e);
-                    AppError::ServerError
-                })?;
+    pub async fn is_username_available(
+        &self,
+        tenant_id: &str,
+        username: &str,
+    ) -> Result<(), AppError> {
+        let exists = sqlx::query_scalar!(
+            "SELECT EXISTS(SELECT 1 FROM users WHERE tenant_id = $1 AND username = $2)",
+            tenant_id,
+            username
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("{:?}", e);
+            AppError::ServerError
+        })?;
 
         if exists.unwrap_or(false) { Err(AppError::UsernameTaken) } else { Ok(()) }
     }
 
     // Authenticate user by email
     pub async fn authenticate_user_by_email(
-        &self,
+        self: &Arc<Self>,
+        tenant_id: &str,
         email: &str,
         password: &str,
     ) -> Result<User, AppError> {
-        let user = sqlx::query_as!(User, "SELECT * FROM users WHERE email = $1", email)
-            .fetch_optional(&self.pool)
-            .await
-            .map_err(|e| {
-                tracing::error!("{:?}", e);
-                AppError::ServerError
-            })?
-            .ok_or(AppError::UserNotFound)?;
+        let user = sqlx::query_as!(
+            User,
+            "SELECT * FROM users WHERE tenant_id = $1 AND email = $2",
+            tenant_id,
+            email
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("{:?}", e);
+            AppError::ServerError
+        })?
+        .ok_or(AppError::UserNotFound)?;
 
-        match &user.password {
-            Some(db_password) if db_password == password => Ok(user),
-            Some(_) => Err(AppError::PasswordMismatch),
-            None => Err(AppError::BadReq("Password not set")),
-        }
+        self.check_password(user, password).await
     }
 
     // Authenticate user by username
     pub async fn authenticate_user_by_username(
-        &self,
+        self: &Arc<Self>,
+        tenant_id: &str,
         username: &str,
         password: &str,
     ) -> Result<User, AppError> {
-        let user = sqlx::query_as!(User, "SELECT * FROM users WHERE username = $1", username)
-            .fetch_optional(&self.pool)
-            .await
-            .map_err(|e| {
-                tracing::error!("{:?}", e);
-                AppError::ServerError
-            })?
-            .ok_or(AppError::UserNotFound)?;
+        let user = sqlx::query_as!(
+            User,
+            "SELECT * FROM users WHERE tenant_id = $1 AND username = $2",
+            tenant_id,
+            username
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("{:?}", e);
+            AppError::ServerError
+        })?
+        .ok_or(AppError::UserNotFound)?;
+
+        self.check_password(user, password).await
+    }
+
+    async fn check_password(
+        self: &Arc<Self>,
+        mut user: User,
+        password: &str,
+    ) -> Result<User, AppError> {
+        self.verify_password(user.id, user.password.as_deref(), password).await?;
+        user.password.get_or_insert_with(|| password.to_string());
+        Ok(user)
+    }
 
-        match &user.password {
-            Some(db_password) if db_password == password => Ok(user),
+    /// checks `password` against the password of the user, or the foreign hash it was imported
+    /// with, which is then replaced by `password`
+    pub async fn verify_user_password(
+        self: &Arc<Self>,
+        user: &UserData,
+        password: &str,
+    ) -> Result<(), AppError> {
+        let (user_id, stored) = {
+            let guard = user.lock().unwrap();
+            (guard.0.id, guard.0.password.clone())
+        };
+        self.verify_password(user_id, stored.as_deref(), password).await?;
+        user.lock().unwrap().0.password.get_or_insert_with(|| password.to_string());
+        Ok(())
+    }
+
+    async fn verify_password(
+        self: &Arc<Self>,
+        user_id: Uuid,
+        stored: Option<&str>,
+        password: &str,
+    ) -> Result<(), AppError> {
+        match stored {
+            Some(db_password) if db_password == password => Ok(()),
             Some(_) => Err(AppError::PasswordMismatch),
-            None => Err(AppError::BadReq("Password not set")),
+            None => {
+                // imported users keep the hash of their previous system until they log in
+                let hash = self
+                    .get_imported_password(user_id)
+                    .await?
+                    .ok_or(AppError::BadReq("Password not set"))?;
+                let given = password.to_string();
+                let matched = tokio::task::spawn_blocking(move || {
+                    hash.parse::<ForeignHash>().is_ok_and(|h| h.verify(&given))
+                })
+                .await
+                .unwrap_or(false);
+                if !matched {
+                    return Err(AppError::PasswordMismatch);
+                }
+                self.replace_imported_password(user_id, password).await
+            }
         }
     }
 
-    pub async fn get_user_by_email(self: &Arc<Self>, email: &str) -> Result<User, AppError> {
-        sqlx::query_as!(User, "SELECT * FROM users WHERE email = $1", email)
-            .fetch_one(&self.pool)
-            .await
-            .map_err(|e| match e {
-                sqlx::Error::RowNotFound => AppError::UserNotFound,
-                _ => {
-                    tracing::error!("{:?}", e);
-                    AppError::ServerError
-                }
-            })
+    pub async fn get_user_by_email(
+        self: &Arc<Self>,
+        tenant_id: &str,
+        email: &str,
+    ) -> Result<User, AppError> {
+        sqlx::query_as!(
+            User,
+            "SELECT * FROM users WHERE tenant_id = $1 AND email = $2",
+            tenant_id,
+            email
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::RowNotFound => AppError::UserNotFound,
+            _ => {
+                tracing::error!("{:?}", e);
+                AppError::ServerError
+            }
+        })
     }
 
-    pub async fn get_user_by_username(self: &Arc<Self>, username: &str) -> Result<User, AppError> {
-        sqlx::query_as!(User, "SELECT * FROM users WHERE username = $1", username)
-            .fetch_one(&self.pool)
-            .await
-            .map_err(|e| match e {
-                sqlx::Error::RowNotFound => AppError::UserNotFound,
-                _ => {
-                    tracing::error!("{:?}", e);
-                    AppError::ServerError
-                }
-            })
+    pub async fn get_user_by_username(
+        self: &Arc<Self>,
+        tenant_id: &str,
+        username: &str,
+    ) -> Result<User, AppError> {
+        sqlx::query_as!(
+            User,
+            "SELECT * FROM users WHERE tenant_id = $1 AND username = $2",
+            tenant_id,
+            username
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::RowNotFound => AppError::UserNotFound,
+            _ => {
+                tracing::error!("{:?}", e);
+                AppError::ServerError
+            }
+        })
     }
 }
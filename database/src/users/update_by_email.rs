@@ -6,19 +6,41 @@ impl crate::Db {
     // updates password of the given user
     pub async fn update_password(
         self: &Arc<Self>,
+        tenant_id: &str,
         email: &str,
         password: &str,
     ) -> Result<(), AppError> {
-        sqlx::query_as!(User, "UPDATE users SET password = $1 WHERE email = $2", password, email)
-            .fetch_one(&self.pool)
-            .await
-            .map_err(|e| match e {
-                sqlx::Error::RowNotFound => AppError::UserNotFound,
-                _ => {
-                    tracing::error!("{:?}", e);
-                    AppError::ServerError
-                }
-            })?;
+        // a new password also fulfills a reset required by an admin
+        let result = sqlx::query!(
+            r#"UPDATE users SET password = $1,
+                status = CASE WHEN status = 'password_reset_required' THEN 'active' ELSE status END
+            WHERE tenant_id = $2 AND email = $3"#,
+            password,
+            tenant_id,
+            email
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("{:?}", e);
+            AppError::ServerError
+        })?;
+        if result.rows_affected() == 0 {
+            return Err(AppError::UserNotFound);
+        }
+        // the foreign hash of an imported user is outdated now
+        sqlx::query!(
+            r#"DELETE FROM imported_passwords
+            WHERE user_id = (SELECT id FROM users WHERE tenant_id = $1 AND email = $2)"#,
+            tenant_id,
+            email
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("{:?}", e);
+            AppError::ServerError
+        })?;
 
         tracing::info!("[Password Updated] Email: {email}");
         Ok(())
@@ -26,13 +48,15 @@ impl crate::Db {
 
     pub async fn update_oauth_provider(
         self: &Arc<Self>,
+        tenant_id: &str,
         email: &str,
         oauth_provider: OAuthProvider,
     ) -> Result<(), AppError> {
         sqlx::query_as!(
             User,
-            "UPDATE users SET oauth_provider = $1 WHERE email = $2",
+            "UPDATE users SET oauth_provider = $1 WHERE tenant_id = $2 AND email = $3",
             oauth_provider.get_str(),
+            tenant_id,
             email
         )
         .fetch_one(&self.pool)
@@ -5,16 +5,22 @@ use util::AppError;
 impl crate::Db {
     pub async fn check_and_update_username(
         self: &Arc<Self>,
+        tenant_id: &str,
         username: &str,
         new_username: &str,
     ) -> Result<(), AppError> {
-        sqlx::query!("UPDATE users SET username = $1 WHERE username = $2", new_username, username)
-            .execute(&self.pool)
-            .await
-            .map_err(|e| {
-                tracing::error!("{:?}", e);
-                AppError::ServerError
-            })?;
+        sqlx::query!(
+            "UPDATE users SET username = $1 WHERE tenant_id = $2 AND username = $3",
+            new_username,
+            tenant_id,
+            username
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("{:?}", e);
+            AppError::ServerError
+        })?;
 
         tracing::info!(
             "[Username Updated] Old Username: @{username}, New Username: @{new_username}"
@@ -24,33 +30,60 @@ impl crate::Db {
 
     pub async fn update_legal_name(
         self: &Arc<Self>,
+        tenant_id: &str,
         username: &str,
         legal_name: &str,
     ) -> Result<(), AppError> {
-        sqlx::query!("UPDATE users SET legal_name = $1 WHERE username = $2", legal_name, username)
-            .execute(&self.pool)
-            .await
-            .map_err(|e| {
-                tracing::error!("{:?}", e);
-                AppError::ServerError
-            })?;
+        sqlx::query!(
+            "UPDATE users SET legal_name = $1 WHERE tenant_id = $2 AND username = $3",
+            legal_name,
+            tenant_id,
+            username
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("{:?}", e);
+            AppError::ServerError
+        })?;
 
         tracing::info!("[Legal Name Updated] @{username}, Legal Name: {legal_name}");
         Ok(())
     }
 
+    /// sets the birth date, every change after the first one counts against `max_changes`
+    ///
+    /// the account stays restricted if it already was
     pub async fn update_birth_date(
         self: &Arc<Self>,
+        tenant_id: &str,
         username: &str,
         birth_date: sqlx::types::time::OffsetDateTime,
+        restricted: bool,
+        max_changes: i32,
     ) -> Result<(), AppError> {
-        sqlx::query!("UPDATE users SET birth_date = $1 WHERE username = $2", birth_date, username)
-            .execute(&self.pool)
-            .await
-            .map_err(|e| {
-                tracing::error!("{:?}", e);
-                AppError::ServerError
-            })?;
+        let result = sqlx::query!(
+            r#"UPDATE users SET birth_date = $1, restricted = restricted OR $2,
+                birth_date_changes = birth_date_changes + (birth_date IS NOT NULL)::INT
+            WHERE tenant_id = $3 AND username = $4
+                AND (birth_date IS NULL OR birth_date_changes < $5)"#,
+            birth_date,
+            restricted,
+            tenant_id,
+            username,
+            max_changes
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("{:?}", e);
+            AppError::ServerError
+        })?;
+        if result.rows_affected() == 0 {
+            return Err(AppError::Forbidden(
+                "Your birth date can't be changed anymore, please contact support",
+            ));
+        }
 
         tracing::info!("[Birth Date Updated] @{username}, Birth Date: {birth_date}");
         Ok(())
@@ -58,16 +91,22 @@ impl crate::Db {
 
     pub async fn update_gender(
         self: &Arc<Self>,
+        tenant_id: &str,
         username: &str,
         gender: &str,
     ) -> Result<(), AppError> {
-        sqlx::query!("UPDATE users SET gender = $1 WHERE username = $2", gender, username)
-            .execute(&self.pool)
-            .await
-            .map_err(|e| {
-                tracing::error!("{:?}", e);
-                AppError::ServerError
-            })?;
+        sqlx::query!(
+            "UPDATE users SET gender = $1 WHERE tenant_id = $2 AND username = $3",
+            gender,
+            tenant_id,
+            username
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("{:?}", e);
+            AppError::ServerError
+        })?;
 
         tracing::info!("[Gender Updated] @{username}, Gender: {gender}");
         Ok(())
@@ -75,24 +114,57 @@ impl crate::Db {
 
     pub async fn update_country(
         self: &Arc<Self>,
+        tenant_id: &str,
         username: &str,
         country: &str,
+        restricted: bool,
     ) -> Result<(), AppError> {
-        sqlx::query!("UPDATE users SET country = $1 WHERE username = $2", country, username)
-            .execute(&self.pool)
-            .await
-            .map_err(|e| {
-                tracing::error!("{:?}", e);
-                AppError::ServerError
-            })?;
+        sqlx::query!(
+            r#"UPDATE users SET country = $1, restricted = restricted OR $2
+            WHERE tenant_id = $3 AND username = $4"#,
+            country,
+            restricted,
+            tenant_id,
+            username
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("{:?}", e);
+            AppError::ServerError
+        })?;
 
         tracing::info!("[Country Updated] @{username}, Country: {country}");
         Ok(())
     }
 
+    pub async fn update_session_binding(
+        self: &Arc<Self>,
+        tenant_id: &str,
+        username: &str,
+        bind_sessions: bool,
+    ) -> Result<(), AppError> {
+        sqlx::query!(
+            "UPDATE users SET bind_sessions = $1 WHERE tenant_id = $2 AND username = $3",
+            bind_sessions,
+            tenant_id,
+            username
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("{:?}", e);
+            AppError::ServerError
+        })?;
+
+        tracing::info!("[Session Binding Updated] @{username}, Enabled: {bind_sessions}");
+        Ok(())
+    }
+
     // Update profile (dynamic fields)
     pub async fn update_profile(
         &self,
+        tenant_id: &str,
         username: &str,
         banner: &Option<String>,
         icon: &Option<String>,
@@ -101,8 +173,8 @@ impl crate::Db {
     ) -> Result<(), AppError> {
         // Build update query dynamically
         let mut updates = Vec::new();
-        let mut params: Vec<String> = vec![username.to_string()];
-        let mut param_index = 2;
+        let mut params: Vec<String> = vec![tenant_id.to_string(), username.to_string()];
+        let mut param_index = 3;
 
         if let Some(val) = banner {
             updates.push(format!("banner = ${param_index}"));
@@ -128,7 +200,10 @@ impl crate::Db {
             return Ok(());
         }
 
-        let query_str = format!("UPDATE users SET {} WHERE username = $1", updates.join(", "));
+        let query_str = format!(
+            "UPDATE users SET {} WHERE tenant_id = $1 AND username = $2",
+            updates.join(", ")
+        );
 
         let mut query = sqlx::query(&query_str);
         for param in params {
@@ -0,0 +1,64 @@
+use axum::{
+    Extension,
+    extract::{Query, State},
+};
+use axum_extra::{json, response::ErasedJson};
+use database::{
+    Db,
+    audit::{AuditKind, AuditQuery},
+    tenants::Tenant,
+};
+use std::sync::Arc;
+use util::AppError;
+
+const DEFAULT_PAGE_SIZE: i64 = 50;
+const MAX_PAGE_SIZE: i64 = 200;
+
+#[derive(serde::Deserialize)]
+pub struct AuditLogQuery {
+    /// events about this user
+    username: Option<String>,
+    /// events caused by this user (like an admin)
+    actor: Option<String>,
+    kind: Option<String>,
+    /// id of the last event of the previous page
+    before: Option<i64>,
+    limit: Option<i64>,
+}
+
+pub async fn list_audit_events(
+    State(db): State<Arc<Db>>,
+    Extension(tenant): Extension<Arc<Tenant>>,
+    Query(q): Query<AuditLogQuery>,
+) -> Result<ErasedJson, AppError> {
+    let user_id = match q.username.as_deref().filter(|v| !v.is_empty()) {
+        Some(username) => Some(db.get_user_by_username(&tenant.id, username).await?.id),
+        None => None,
+    };
+    let actor_id = match q.actor.as_deref().filter(|v| !v.is_empty()) {
+        Some(username) => Some(db.get_user_by_username(&tenant.id, username).await?.id),
+        None => None,
+    };
+    let kind = match q.kind.as_deref().filter(|v| !v.is_empty()) {
+        Some(kind) => {
+            Some(AuditKind::from_name(kind).ok_or(AppError::InvalidData("Invalid event kind"))?)
+        }
+        None => None,
+    };
+
+    let events = db
+        .list_audit_events(&AuditQuery {
+            user_id,
+            actor_id,
+            // the default tenant's admins keep seeing every event
+            tenant_id: (!tenant.is_default()).then(|| tenant.id.clone()),
+            kind,
+            before_id: q.before,
+            limit: q.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE),
+        })
+        .await?;
+    Ok(json!({
+        "events": events.iter().map(crate::user_data::audit_record).collect::<Vec<_>>(),
+        "next_before": events.last().map(|e| e.id),
+    }))
+}
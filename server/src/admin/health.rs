@@ -1,114 +1,338 @@
-use axum::extract::{State, WebSocketUpgrade, ws::Message};
+use axum::extract::{
+    State, WebSocketUpgrade,
+    ws::{Message, WebSocket},
+};
 use database::Db;
-use std::{sync::Arc, time::Duration};
+use std::{
+    collections::VecDeque,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::Duration,
+};
+use sysinfo::{Disks, Networks, Pid, ProcessRefreshKind, ProcessesToUpdate, System};
 
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+/// disks change slowly, so they're refreshed once every this many samples
+const DISK_REFRESH_SAMPLES: u64 = 30;
+const MAX_INTERVAL_SECS: u64 = 60;
+
+/// metric groups a client can subscribe to
+const GROUPS: [&str; 7] = ["cpu", "memory", "storage", "network", "process", "app", "maintenance"];
+
+/// message sent by the client to change what it receives
+///
+/// `history` asks for that many past samples (spaced by `interval`) before the live ones
+#[derive(serde::Deserialize)]
+struct Subscription {
+    groups: Option<Vec<String>>,
+    interval: Option<u64>,
+    history: Option<usize>,
+}
+
+/// streams health samples to an admin
+///
+/// on connect the client receives the whole history and then every sample with all the groups,
+/// until it sends a `Subscription`
 pub async fn health_handler(
-    State(db): State<Arc<Db>>,
+    State(state): State<crate::AppState>,
     ws: WebSocketUpgrade,
 ) -> impl axum::response::IntoResponse {
-    let state = super::AdminState::new().await;
-    ws.on_upgrade(|mut socket| async move {
-        let mut interval = tokio::time::interval(Duration::from_secs(1));
-        loop {
-            interval.tick().await;
-            let metrics = collect_metrics(&state, &db).await;
+    let history = state.admin.history;
+    let subscribers = state.admin.subscribers;
+    ws.on_upgrade(move |socket| stream_health(socket, history, subscribers))
+}
+
+/// counts an open health socket until it's dropped
+struct Subscriber(Arc<AtomicUsize>);
 
-            match serde_json::to_string(&metrics) {
-                Ok(json) => {
-                    if socket.send(Message::Text(json.into())).await.is_err() {
-                        break;
+impl Drop for Subscriber {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+async fn stream_health(
+    mut socket: WebSocket,
+    history: Arc<Mutex<VecDeque<Arc<serde_json::Value>>>>,
+    subscribers: Arc<AtomicUsize>,
+) {
+    subscribers.fetch_add(1, Ordering::Relaxed);
+    let _subscriber = Subscriber(subscribers);
+    let mut groups = GROUPS.map(str::to_owned).to_vec();
+    let mut interval = tokio::time::interval(SAMPLE_INTERVAL);
+
+    let backfill = past_samples(&history, &groups, 1, usize::MAX);
+    if send(&mut socket, serde_json::json!({ "type": "history", "samples": backfill }))
+        .await
+        .is_err()
+    {
+        return;
+    }
+
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {
+                let Some(sample) = history.lock().unwrap().back().cloned() else { continue };
+                let msg = serde_json::json!({ "type": "sample", "sample": filter(&sample, &groups) });
+                if send(&mut socket, msg).await.is_err() {
+                    break;
+                }
+            }
+            msg = socket.recv() => {
+                let text = match msg {
+                    Some(Ok(Message::Text(text))) => text,
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                    Some(Ok(_)) => continue,
+                };
+                let reply = match parse_subscription(&text) {
+                    Ok((new_groups, new_interval, history_len)) => {
+                        groups = new_groups;
+                        interval = tokio::time::interval(Duration::from_secs(new_interval));
+                        let samples = past_samples(&history, &groups, new_interval, history_len);
+                        serde_json::json!({
+                            "type": "subscribed",
+                            "groups": groups,
+                            "interval": new_interval,
+                            "samples": samples,
+                        })
                     }
+                    Err(message) => serde_json::json!({ "type": "error", "message": message }),
+                };
+                if send(&mut socket, reply).await.is_err() {
+                    break;
                 }
-                Err(_) => break,
             }
         }
-    })
+    }
 }
 
-async fn collect_metrics(state: &super::AdminState, db: &Arc<Db>) -> HealthMetrics {
-    // Refresh system info
-    let mut sys = state.sys.lock().await;
-    sys.refresh_all();
-
-    let mut networks = state.networks.lock().await;
-    networks.refresh(true);
-
-    let mut disks = state.disks.lock().await;
-    disks.refresh(true);
-
-    // CPU metrics
-    let cpu_metrics = CpuMetrics {
-        usage_percent: sys.global_cpu_usage(),
-        cores: sys.cpus().len(),
-        per_core_usage: sys.cpus().iter().map(|cpu| cpu.cpu_usage()).collect(),
+/// returns the groups, interval (in seconds) and number of past samples of a subscription
+fn parse_subscription(text: &str) -> Result<(Vec<String>, u64, usize), &'static str> {
+    let sub: Subscription = serde_json::from_str(text).map_err(|_| "Invalid subscription")?;
+    let groups = match sub.groups {
+        Some(groups) if groups.iter().all(|g| GROUPS.contains(&g.as_str())) => groups,
+        Some(_) => return Err("Unknown metric group"),
+        None => GROUPS.map(str::to_owned).to_vec(),
     };
-
-    // Memory metrics
-    let total_mem = sys.total_memory();
-    let used_mem = sys.used_memory();
-    let memory_metrics = MemoryMetrics {
-        total_mb: total_mem / 1024 / 1024,
-        used_mb: used_mem / 1024 / 1024,
-        available_mb: (total_mem - used_mem) / 1024 / 1024,
-        usage_percent: (used_mem as f32 / total_mem as f32) * 100.0,
-        swap_total_mb: sys.total_swap() / 1024 / 1024,
-        swap_used_mb: sys.used_swap() / 1024 / 1024,
+    let interval = match sub.interval {
+        Some(v @ 1..=MAX_INTERVAL_SECS) => v,
+        Some(_) => return Err("The interval must be between 1 and 60 seconds"),
+        None => 1,
     };
+    Ok((groups, interval, sub.history.unwrap_or(0)))
+}
 
-    // Storage metrics
-    let storage_metrics: Vec<StorageMetrics> = disks
-        .list()
+/// returns up to `count` of the most recent samples, `step` samples apart (oldest first)
+fn past_samples(
+    history: &Mutex<VecDeque<Arc<serde_json::Value>>>,
+    groups: &[String],
+    step: u64,
+    count: usize,
+) -> Vec<serde_json::Value> {
+    let history = history.lock().unwrap();
+    let mut samples = history
         .iter()
-        .map(|disk| {
-            let total = disk.total_space();
-            let available = disk.available_space();
-            StorageMetrics {
-                name: disk.name().to_string_lossy().to_string(),
-                mount_point: disk.mount_point().to_string_lossy().to_string(),
-                total_gb: total / 1024 / 1024 / 1024,
-                available_gb: available / 1024 / 1024 / 1024,
-                usage_percent: ((total - available) as f32 / total as f32) * 100.0,
+        .rev()
+        .step_by(step as usize)
+        .take(count)
+        .map(|s| filter(s, groups))
+        .collect::<Vec<_>>();
+    samples.reverse();
+    samples
+}
+
+/// keeps the timestamp and `groups` of `sample`
+fn filter(sample: &serde_json::Value, groups: &[String]) -> serde_json::Value {
+    let mut filtered = serde_json::Map::new();
+    if let Some(sample) = sample.as_object() {
+        for (key, value) in sample {
+            if key == "timestamp" || groups.contains(key) {
+                filtered.insert(key.clone(), value.clone());
             }
-        })
-        .collect();
-
-    // Network metrics
-    let mut total_rx = 0;
-    let mut total_tx = 0;
-    let interface_metrics: Vec<InterfaceMetrics> = networks
-        .list()
-        .iter()
-        .map(|(name, data)| {
-            let rx = data.total_received();
-            let tx = data.total_transmitted();
-            total_rx += rx;
-            total_tx += tx;
-            InterfaceMetrics {
-                name: name.clone(),
-                received_mb: rx / 1024 / 1024,
-                transmitted_mb: tx / 1024 / 1024,
+        }
+    }
+    serde_json::Value::Object(filtered)
+}
+
+async fn send(socket: &mut WebSocket, msg: serde_json::Value) -> Result<(), axum::Error> {
+    socket.send(Message::Text(msg.to_string().into())).await
+}
+
+/// spawns the task that records a health sample every second into `history`, which keeps the
+/// last `history_size` ones
+///
+/// the app metrics lock every cached user, so they're left out while `subscribers` is 0
+pub(super) fn spawn_sampler(
+    db: Arc<Db>,
+    history: Arc<Mutex<VecDeque<Arc<serde_json::Value>>>>,
+    last_maintenance: crate::maintenance::LastRun,
+    subscribers: Arc<AtomicUsize>,
+    history_size: usize,
+) {
+    tokio::spawn(async move {
+        let mut sampler = Sampler {
+            sys: System::new(),
+            networks: Networks::new_with_refreshed_list(),
+            disks: Disks::new_with_refreshed_list(),
+            pid: sysinfo::get_current_pid().ok(),
+            samples: 0,
+        };
+        let mut interval = tokio::time::interval(SAMPLE_INTERVAL);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            let with_app = subscribers.load(Ordering::Relaxed) > 0;
+            let metrics = sampler.collect(&db, &last_maintenance, with_app);
+            let sample = match serde_json::to_value(&metrics) {
+                Ok(v) => Arc::new(v),
+                Err(e) => {
+                    tracing::error!("{e:?}");
+                    continue;
+                }
+            };
+            let mut history = history.lock().unwrap();
+            history.push_back(sample);
+            while history.len() > history_size {
+                history.pop_front();
             }
-        })
-        .collect();
+        }
+    });
+}
 
-    let network_metrics = NetworkMetrics {
-        total_received_mb: total_rx / 1024 / 1024,
-        total_transmitted_mb: total_tx / 1024 / 1024,
-        interfaces: interface_metrics,
-    };
+struct Sampler {
+    sys: System,
+    networks: Networks,
+    disks: Disks,
+    pid: Option<Pid>,
+    samples: u64,
+}
 
-    let database_metrics = DatabaseMetrics { logged_users_count: db.logged_users_count() };
-
-    HealthMetrics {
-        timestamp: std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_secs(),
-        cpu: cpu_metrics,
-        memory: memory_metrics,
-        storage: storage_metrics,
-        network: network_metrics,
-        database: database_metrics,
+impl Sampler {
+    fn collect(
+        &mut self,
+        db: &Arc<Db>,
+        last_maintenance: &crate::maintenance::LastRun,
+        with_app: bool,
+    ) -> HealthMetrics {
+        // refreshing only what the sample reads
+        self.sys.refresh_cpu_usage();
+        self.sys.refresh_memory();
+        self.networks.refresh(true);
+        if self.samples.is_multiple_of(DISK_REFRESH_SAMPLES) {
+            self.disks.refresh(true);
+        }
+        if let Some(pid) = self.pid {
+            self.sys.refresh_processes_specifics(
+                ProcessesToUpdate::Some(&[pid]),
+                true,
+                ProcessRefreshKind::nothing().with_cpu().with_memory().with_tasks(),
+            );
+        }
+        self.samples += 1;
+        let sys = &self.sys;
+
+        // CPU metrics
+        let cpu_metrics = CpuMetrics {
+            usage_percent: sys.global_cpu_usage(),
+            cores: sys.cpus().len(),
+            per_core_usage: sys.cpus().iter().map(|cpu| cpu.cpu_usage()).collect(),
+        };
+
+        // Memory metrics
+        let total_mem = sys.total_memory();
+        let used_mem = sys.used_memory();
+        let memory_metrics = MemoryMetrics {
+            total_mb: total_mem / 1024 / 1024,
+            used_mb: used_mem / 1024 / 1024,
+            available_mb: (total_mem - used_mem) / 1024 / 1024,
+            usage_percent: (used_mem as f32 / total_mem as f32) * 100.0,
+            swap_total_mb: sys.total_swap() / 1024 / 1024,
+            swap_used_mb: sys.used_swap() / 1024 / 1024,
+        };
+
+        // Storage metrics
+        let storage_metrics: Vec<StorageMetrics> = self
+            .disks
+            .list()
+            .iter()
+            .map(|disk| {
+                let total = disk.total_space();
+                let available = disk.available_space();
+                StorageMetrics {
+                    name: disk.name().to_string_lossy().to_string(),
+                    mount_point: disk.mount_point().to_string_lossy().to_string(),
+                    total_gb: total / 1024 / 1024 / 1024,
+                    available_gb: available / 1024 / 1024 / 1024,
+                    usage_percent: ((total - available) as f32 / total as f32) * 100.0,
+                }
+            })
+            .collect();
+
+        // Network metrics
+        let mut total_rx = 0;
+        let mut total_tx = 0;
+        let interface_metrics: Vec<InterfaceMetrics> = self
+            .networks
+            .list()
+            .iter()
+            .map(|(name, data)| {
+                let rx = data.total_received();
+                let tx = data.total_transmitted();
+                total_rx += rx;
+                total_tx += tx;
+                InterfaceMetrics {
+                    name: name.clone(),
+                    received_mb: rx / 1024 / 1024,
+                    transmitted_mb: tx / 1024 / 1024,
+                }
+            })
+            .collect();
+
+        let network_metrics = NetworkMetrics {
+            total_received_mb: total_rx / 1024 / 1024,
+            total_transmitted_mb: total_tx / 1024 / 1024,
+            interfaces: interface_metrics,
+        };
+
+        // Process metrics (of this server)
+        let runtime = tokio::runtime::Handle::current().metrics();
+        let process = self.pid.and_then(|pid| sys.process(pid));
+        let process_metrics = ProcessMetrics {
+            pid: self.pid.map(|p| p.as_u32()).unwrap_or_default(),
+            uptime_secs: process.map(|p| p.run_time()).unwrap_or_default(),
+            cpu_usage_percent: process.map(|p| p.cpu_usage()).unwrap_or_default(),
+            rss_mb: process.map(|p| p.memory()).unwrap_or_default() / 1024 / 1024,
+            virtual_mb: process.map(|p| p.virtual_memory()).unwrap_or_default() / 1024 / 1024,
+            threads: process.and_then(|p| p.tasks()).map(|t| t.len()),
+            open_fds: process.and_then(|p| p.open_files()),
+            open_fds_limit: process.and_then(|p| p.open_files_limit()),
+            tokio_workers: runtime.num_workers(),
+            tokio_alive_tasks: runtime.num_alive_tasks(),
+            tokio_global_queue_depth: runtime.global_queue_depth(),
+        };
+
+        let app_metrics = with_app.then(|| AppMetrics {
+            logged_users_count: db.logged_users_count(),
+            active_sessions: db.active_sessions_count(),
+            pending_registrants: db.pending_registrants_count(),
+            pending_password_resets: db.pending_password_resets_count(),
+        });
+
+        HealthMetrics {
+            timestamp: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_secs(),
+            cpu: cpu_metrics,
+            memory: memory_metrics,
+            storage: storage_metrics,
+            network: network_metrics,
+            process: process_metrics,
+            app: app_metrics,
+            maintenance: last_maintenance.lock().unwrap().clone(),
+        }
     }
 }
 
@@ -119,7 +343,10 @@ struct HealthMetrics {
     memory: MemoryMetrics,
     storage: Vec<StorageMetrics>,
     network: NetworkMetrics,
-    database: DatabaseMetrics,
+    process: ProcessMetrics,
+    /// `None` in the samples taken while no one was subscribed
+    app: Option<AppMetrics>,
+    maintenance: Option<crate::maintenance::MaintenanceReport>,
 }
 
 #[derive(serde::Serialize)]
@@ -163,6 +390,102 @@ struct InterfaceMetrics {
 }
 
 #[derive(serde::Serialize)]
-struct DatabaseMetrics {
+struct ProcessMetrics {
+    pid: u32,
+    uptime_secs: u64,
+    cpu_usage_percent: f32,
+    rss_mb: u64,
+    virtual_mb: u64,
+    threads: Option<usize>,
+    open_fds: Option<usize>,
+    open_fds_limit: Option<usize>,
+    tokio_workers: usize,
+    tokio_alive_tasks: usize,
+    tokio_global_queue_depth: usize,
+}
+
+#[derive(serde::Serialize)]
+struct AppMetrics {
     logged_users_count: u64,
+    active_sessions: u64,
+    pending_registrants: u64,
+    pending_password_resets: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn history(len: u64) -> Mutex<VecDeque<Arc<serde_json::Value>>> {
+        let samples = (0..len).map(|t| Arc::new(json!({ "timestamp": t, "cpu": t, "app": t })));
+        Mutex::new(samples.collect())
+    }
+
+    fn groups(groups: &[&str]) -> Vec<String> {
+        groups.iter().map(|g| g.to_string()).collect()
+    }
+
+    #[test]
+    fn subscription_defaults() {
+        let (groups, interval, history) = parse_subscription("{}").unwrap();
+        assert_eq!(groups, GROUPS.map(str::to_owned).to_vec());
+        assert_eq!(interval, 1);
+        assert_eq!(history, 0);
+    }
+
+    #[test]
+    fn subscription_with_every_field() {
+        let text = r#"{"groups":["cpu","app"],"interval":5,"history":60}"#;
+        let (groups, interval, history) = parse_subscription(text).unwrap();
+        assert_eq!(groups, ["cpu", "app"]);
+        assert_eq!(interval, 5);
+        assert_eq!(history, 60);
+    }
+
+    #[test]
+    fn invalid_subscriptions() {
+        assert_eq!(parse_subscription("cpu"), Err("Invalid subscription"));
+        assert_eq!(parse_subscription(r#"{"interval":-1}"#), Err("Invalid subscription"));
+        assert_eq!(parse_subscription(r#"{"groups":["cpu","gpu"]}"#), Err("Unknown metric group"));
+        for interval in [0, MAX_INTERVAL_SECS + 1] {
+            let text = format!(r#"{{"interval":{interval}}}"#);
+            assert_eq!(
+                parse_subscription(&text),
+                Err("The interval must be between 1 and 60 seconds")
+            );
+        }
+        assert!(parse_subscription(r#"{"interval":60}"#).is_ok());
+    }
+
+    #[test]
+    fn past_samples_are_oldest_first() {
+        let samples = past_samples(&history(5), &groups(&["cpu"]), 1, usize::MAX);
+        let timestamps = samples.iter().map(|s| s["timestamp"].as_u64().unwrap());
+        assert_eq!(timestamps.collect::<Vec<_>>(), [0, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn past_samples_are_spaced_from_the_latest() {
+        let samples = past_samples(&history(10), &groups(&["cpu"]), 3, 3);
+        let timestamps = samples.iter().map(|s| s["timestamp"].as_u64().unwrap());
+        assert_eq!(timestamps.collect::<Vec<_>>(), [3, 6, 9]);
+    }
+
+    #[test]
+    fn past_samples_of_an_empty_history() {
+        assert!(past_samples(&history(0), &groups(&["cpu"]), 1, 10).is_empty());
+        assert!(past_samples(&history(5), &groups(&["cpu"]), 1, 0).is_empty());
+    }
+
+    #[test]
+    fn filter_keeps_the_timestamp_and_groups() {
+        let sample = json!({ "timestamp": 1, "cpu": 2, "memory": 3, "app": null });
+        assert_eq!(
+            filter(&sample, &groups(&["cpu", "app"])),
+            json!({ "timestamp": 1, "cpu": 2, "app": null })
+        );
+        assert_eq!(filter(&sample, &[]), json!({ "timestamp": 1 }));
+        assert_eq!(filter(&json!([1, 2]), &groups(&["cpu"])), json!({}));
+    }
 }
@@ -0,0 +1,53 @@
+use axum::{
+    Extension, Json,
+    extract::{ConnectInfo, State},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+};
+use database::{Db, UserData, tenants::Tenant};
+use std::sync::Arc;
+use util::{AppError, clock::Clock, config::Config};
+
+#[derive(serde::Deserialize)]
+pub struct ImpersonateRequest {
+    username: String,
+    reason: Option<String>,
+}
+
+/// replaces the admin's session cookie with a short lived session of the target user
+#[allow(clippy::too_many_arguments)]
+pub async fn impersonate(
+    State(db): State<Arc<Db>>,
+    State(config): State<Arc<Config>>,
+    State(clock): State<Arc<dyn Clock>>,
+    Extension(tenant): Extension<Arc<Tenant>>,
+    ConnectInfo(conn_info): ConnectInfo<crate::ClientSocket>,
+    Extension(admin): Extension<UserData>,
+    headers: HeaderMap,
+    Json(body): Json<ImpersonateRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let admin_id = admin.lock().unwrap().0.id;
+    let target = db.get_user_by_username(&tenant.id, &body.username).await?;
+    if target.id == admin_id {
+        return Err(AppError::BadReq("You cannot impersonate yourself"));
+    }
+
+    let (new_session, _, set_cookie_headermap) = util::session::create_impersonation_session(
+        &config,
+        clock.now(),
+        tenant.cookie_scope(),
+        target.id,
+        admin_id,
+        &headers,
+        *conn_info,
+        config.impersonation_duration,
+    );
+    let res_body = crate::user_data::arrange(&target, &[&new_session]);
+    db.start_impersonation(admin_id, target.id, new_session, body.reason.as_deref()).await?;
+    let event =
+        super::admin_action(admin_id, Some(target.id), "impersonate", conn_info.ip(), &headers)
+            .details(serde_json::json!({ "action": "impersonate", "reason": body.reason }));
+    db.record_audit(event).await;
+
+    Ok((StatusCode::CREATED, set_cookie_headermap, res_body))
+}
@@ -0,0 +1,74 @@
+use axum::{
+    Extension, Json,
+    extract::{ConnectInfo, State},
+    http::HeaderMap,
+};
+use axum_extra::{json, response::ErasedJson};
+use database::{Db, UserData, legal::LegalKind, tenants::Tenant};
+use std::sync::Arc;
+use util::AppError;
+
+pub async fn list_documents(
+    State(db): State<Arc<Db>>,
+    Extension(tenant): Extension<Arc<Tenant>>,
+) -> Result<ErasedJson, AppError> {
+    let documents = db
+        .list_legal_documents(&tenant.id)
+        .await?
+        .iter()
+        .map(|(d, acceptances)| {
+            serde_json::json!({
+                "kind": d.kind,
+                "version": d.version,
+                "url": d.url,
+                "published_at": d.published_at.to_string(),
+                "acceptances": acceptances,
+            })
+        })
+        .collect::<Vec<_>>();
+    let kinds = LegalKind::ALL.iter().map(|k| k.get_str()).collect::<Vec<_>>();
+    Ok(json!({
+        "documents": documents,
+        "kinds": kinds,
+    }))
+}
+
+#[derive(serde::Deserialize)]
+pub struct PublishDocumentRequest {
+    kind: String,
+    version: String,
+    url: String,
+}
+
+pub async fn publish_document(
+    State(db): State<Arc<Db>>,
+    Extension(tenant): Extension<Arc<Tenant>>,
+    ConnectInfo(conn_info): ConnectInfo<crate::ClientSocket>,
+    headers: HeaderMap,
+    Extension(admin): Extension<UserData>,
+    Json(body): Json<PublishDocumentRequest>,
+) -> Result<ErasedJson, AppError> {
+    let kind = LegalKind::try_from(body.kind.as_str())?;
+    let version = body.version.trim();
+    if version.is_empty() || version.len() > 32 {
+        return Err(AppError::InvalidData("Versions must be 1-32 characters long"));
+    }
+    if body.url.len() > 511 || !(body.url.starts_with("https://") || body.url.starts_with("http://"))
+    {
+        return Err(AppError::InvalidData("The document must be linked with an http(s) url"));
+    }
+
+    db.publish_legal_document(&tenant.id, kind, version, &body.url).await?;
+    let admin_id = admin.lock().unwrap().0.id;
+    let event =
+        super::admin_action(admin_id, None, "publish_legal_document", conn_info.ip(), &headers)
+            .details(serde_json::json!({
+                "action": "publish_legal_document",
+                "kind": kind.get_str(),
+                "version": version,
+            }));
+    db.record_audit(event).await;
+    Ok(json!({
+        "message": "The document has been published, users have to accept it to continue"
+    }))
+}
@@ -1,39 +1,103 @@
-use axum::{Router, routing::get};
-use std::sync::Arc;
-use sysinfo::{Disks, Networks, System};
-use tokio::sync::{Mutex, OnceCell};
+use crate::middleware::{default_tenant_only, permission_middleware};
+use axum::{
+    Router,
+    extract::DefaultBodyLimit,
+    handler::Handler,
+    middleware::{from_fn, from_fn_with_state},
+    routing::{get, post},
+};
+use database::audit::{AuditEvent, AuditKind};
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex, atomic::AtomicUsize},
+};
+use util::permission::Permission;
 
+mod audit;
 mod health;
+mod impersonate;
+mod legal;
+mod registration;
+mod roles;
+mod tenants;
+mod transfer;
+mod users;
 
 #[rustfmt::skip]
-pub async fn admin_routes() -> Router {
+pub fn admin_routes(state: &crate::AppState) -> Router {
+    // sampling starts with the server so that the health history is available on connect
+    health::spawn_sampler(
+        state.db.clone(),
+        state.admin.history.clone(),
+        state.last_maintenance.clone(),
+        state.admin.subscribers.clone(),
+        state.config.health_history_size,
+    );
+    let require = |permission: Permission| {
+        from_fn_with_state((state.db.clone(), permission), permission_middleware)
+    };
+
     Router::new()
-        .route("/api/health", get(health::health_handler))
-        .layer(axum::middleware::from_fn(crate::middleware::admin_middleware))
-        .layer(axum::middleware::from_fn(crate::middleware::auth_middleware))
-        .with_state(database::Db::new().await)
+        .route("/api/health", get(health::health_handler).layer(require(Permission::HealthRead)))
+        .route("/api/admin/impersonate", post(impersonate::impersonate).layer(require(Permission::UsersImpersonate)))
+        .route("/api/admin/roles", get(roles::list_roles).post(roles::upsert_role.layer(from_fn(default_tenant_only))).layer(require(Permission::RolesManage)))
+        .route("/api/admin/roles/delete", post(roles::delete_role).layer(from_fn(default_tenant_only)).layer(require(Permission::RolesManage)))
+        .route("/api/admin/roles/assign", post(roles::assign_role).layer(require(Permission::RolesManage)))
+        .route("/api/admin/roles/revoke", post(roles::revoke_role).layer(require(Permission::RolesManage)))
+        .route("/api/admin/users", get(users::search_users).layer(require(Permission::UsersRead)))
+        .route("/api/admin/users/@{username}", get(users::get_user).layer(require(Permission::UsersRead)))
+        .route("/api/admin/users/export", get(transfer::export_users).layer(require(Permission::UsersManage)))
+        .route("/api/admin/users/import", post(transfer::import_users).layer(DefaultBodyLimit::max(transfer::IMPORT_MAX_SIZE)).layer(require(Permission::UsersManage)))
+        .route("/api/admin/deleted_users", get(users::search_deleted_users).layer(require(Permission::UsersRead)))
+        .route("/api/admin/users/suspend", post(users::suspend_user).layer(require(Permission::UsersManage)))
+        .route("/api/admin/users/unsuspend", post(users::unsuspend_user).layer(require(Permission::UsersManage)))
+        .route("/api/admin/users/force_password_reset", post(users::force_password_reset).layer(require(Permission::UsersManage)))
+        .route("/api/admin/users/lift_restriction", post(users::lift_restriction).layer(require(Permission::UsersManage)))
+        .route("/api/admin/users/revoke_sessions", post(users::revoke_sessions).layer(require(Permission::UsersManage)))
+        .route("/api/admin/deleted_users/restore", post(users::restore_user).layer(require(Permission::UsersManage)))
+        .route("/api/admin/deleted_users/purge", post(users::purge_user).layer(require(Permission::UsersManage)))
+        .route("/api/admin/audit", get(audit::list_audit_events).layer(require(Permission::AuditRead)))
+        .route("/api/admin/tenants", get(tenants::list_tenants).post(tenants::upsert_tenant).layer(from_fn(default_tenant_only)).layer(require(Permission::TenantsManage)))
+        .route("/api/admin/tenants/delete", post(tenants::delete_tenant).layer(from_fn(default_tenant_only)).layer(require(Permission::TenantsManage)))
+        .route("/api/admin/registration", get(registration::get_registration_policy).post(registration::update_registration_policy).layer(require(Permission::RegistrationManage)))
+        .route("/api/admin/invites", post(registration::create_invite).layer(require(Permission::RegistrationManage)))
+        .route("/api/admin/invites/revoke", post(registration::revoke_invite).layer(require(Permission::RegistrationManage)))
+        .route("/api/admin/legal", get(legal::list_documents).post(legal::publish_document).layer(require(Permission::LegalManage)))
+        .route("/api/admin/users/@{username}/roles", get(roles::get_user_roles).layer(require(Permission::RolesManage)))
+        .route_layer(axum::middleware::from_fn_with_state(
+            state.config.clone(),
+            crate::middleware::csrf_middleware,
+        ))
+        .route_layer(axum::middleware::from_fn_with_state(state.clone(), crate::middleware::auth_middleware))
+        .with_state(state.clone())
 }
 
-#[derive(Clone)]
-struct AdminState {
-    sys: Arc<Mutex<System>>,
-    networks: Arc<Mutex<Networks>>,
-    disks: Arc<Mutex<Disks>>,
+/// path of the routes of a single user, a struct so that the `{tenant}` of the prefixed routes
+/// is ignored
+#[derive(serde::Deserialize)]
+struct UserPath {
+    username: String,
 }
 
-static ADMIN_STATE: OnceCell<AdminState> = OnceCell::const_new();
+/// audit event for `action` taken by the admin with `admin_id` (on the user with `user_id`)
+fn admin_action(
+    admin_id: uuid::Uuid,
+    user_id: Option<uuid::Uuid>,
+    action: &str,
+    ip: std::net::IpAddr,
+    headers: &axum::http::HeaderMap,
+) -> AuditEvent {
+    AuditEvent::new(AuditKind::AdminAction, user_id)
+        .actor(admin_id)
+        .client(ip, headers)
+        .details(serde_json::json!({ "action": action }))
+}
 
-impl AdminState {
-    pub async fn new() -> Self {
-        ADMIN_STATE
-            .get_or_init(|| async {
-                AdminState {
-                    sys: Arc::new(Mutex::new(System::new_all())),
-                    networks: Arc::new(Mutex::new(Networks::new_with_refreshed_list())),
-                    disks: Arc::new(Mutex::new(Disks::new_with_refreshed_list())),
-                }
-            })
-            .await
-            .clone()
-    }
+/// part of `crate::AppState` only used by the admin routes
+#[derive(Clone, Default)]
+pub(crate) struct AdminState {
+    /// recent health samples (oldest first), filled by `health::spawn_sampler`
+    history: Arc<Mutex<VecDeque<Arc<serde_json::Value>>>>,
+    /// number of open health sockets, the app metrics are only sampled while there's one
+    subscribers: Arc<AtomicUsize>,
 }
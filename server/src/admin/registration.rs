@@ -0,0 +1,145 @@
+use axum::{
+    Extension, Json,
+    extract::{ConnectInfo, State},
+    http::HeaderMap,
+};
+use axum_extra::{json, response::ErasedJson};
+use database::{
+    Db, UserData,
+    tenants::{RegistrationMode, Tenant},
+};
+use std::{sync::Arc, time::Duration};
+use util::{AppError, clock::Clock};
+
+/// the registration policy of the admin's tenant along with its invite codes
+pub async fn get_registration_policy(
+    State(db): State<Arc<Db>>,
+    Extension(tenant): Extension<Arc<Tenant>>,
+) -> Result<ErasedJson, AppError> {
+    let invites = db
+        .list_invites(&tenant.id)
+        .await?
+        .iter()
+        .map(|invite| {
+            serde_json::json!({
+                "code": invite.code,
+                "max_uses": invite.max_uses,
+                "uses": invite.uses,
+                "expires_at": invite.expires_at.map(|v| v.to_string()),
+                "created_by": invite.created_by.map(|v| v.to_string()),
+                "created": invite.created.to_string(),
+            })
+        })
+        .collect::<Vec<_>>();
+    Ok(json!({
+        "mode": tenant.registration_mode().get_str(),
+        "allowed_email_domains": tenant.allowed_email_domains.clone(),
+        "denied_email_domains": tenant.denied_email_domains.clone(),
+        "invites": invites,
+    }))
+}
+
+#[derive(serde::Deserialize)]
+pub struct UpdatePolicyRequest {
+    mode: String,
+    #[serde(default)]
+    allowed_email_domains: Vec<String>,
+    #[serde(default)]
+    denied_email_domains: Vec<String>,
+}
+
+pub async fn update_registration_policy(
+    State(db): State<Arc<Db>>,
+    Extension(tenant): Extension<Arc<Tenant>>,
+    ConnectInfo(conn_info): ConnectInfo<crate::ClientSocket>,
+    headers: HeaderMap,
+    Extension(admin): Extension<UserData>,
+    Json(body): Json<UpdatePolicyRequest>,
+) -> Result<ErasedJson, AppError> {
+    let mode = RegistrationMode::try_from(body.mode.as_str())?;
+    let normalize = |domains: Vec<String>| {
+        domains
+            .into_iter()
+            .map(|v| v.trim().trim_start_matches('@').to_ascii_lowercase())
+            .map(|v| util::validation::is_email_domain_valid(&v).map(|_| v))
+            .collect::<Result<Vec<_>, _>>()
+    };
+    let allowed = normalize(body.allowed_email_domains)?;
+    let denied = normalize(body.denied_email_domains)?;
+
+    db.update_registration_policy(&tenant.id, mode, &allowed, &denied).await?;
+    let admin_id = admin.lock().unwrap().0.id;
+    let event = super::admin_action(admin_id, None, "registration_policy", conn_info.ip(), &headers)
+        .details(serde_json::json!({
+            "action": "registration_policy",
+            "mode": mode.get_str(),
+            "allowed_email_domains": allowed,
+            "denied_email_domains": denied,
+        }));
+    db.record_audit(event).await;
+    Ok(json!({
+        "message": "The registration policy has been saved"
+    }))
+}
+
+#[derive(serde::Deserialize)]
+pub struct CreateInviteRequest {
+    /// number of registrations the code allows, defaults to a single one
+    max_uses: Option<i32>,
+    /// lifetime of the code in seconds, the code never expires without it
+    expires_in: Option<u64>,
+}
+
+pub async fn create_invite(
+    State(db): State<Arc<Db>>,
+    State(clock): State<Arc<dyn Clock>>,
+    Extension(tenant): Extension<Arc<Tenant>>,
+    ConnectInfo(conn_info): ConnectInfo<crate::ClientSocket>,
+    headers: HeaderMap,
+    Extension(admin): Extension<UserData>,
+    Json(body): Json<CreateInviteRequest>,
+) -> Result<ErasedJson, AppError> {
+    let max_uses = body.max_uses.unwrap_or(1);
+    if max_uses < 1 {
+        return Err(AppError::InvalidData("Invite codes need at least one use"));
+    }
+    let expires_at = body.expires_in.map(|secs| clock.now() + Duration::from_secs(secs));
+
+    let admin_id = admin.lock().unwrap().0.id;
+    let invite = db.create_invite(&tenant.id, max_uses, expires_at, admin_id).await?;
+    let event = super::admin_action(admin_id, None, "create_invite", conn_info.ip(), &headers)
+        .details(serde_json::json!({
+            "action": "create_invite",
+            "max_uses": max_uses,
+            "expires_at": expires_at.map(|v| v.to_string()),
+        }));
+    db.record_audit(event).await;
+    Ok(json!({
+        "code": invite.code,
+        "max_uses": invite.max_uses,
+        "expires_at": invite.expires_at.map(|v| v.to_string()),
+        "message": "The invite code has been created",
+    }))
+}
+
+#[derive(serde::Deserialize)]
+pub struct RevokeInviteRequest {
+    code: String,
+}
+
+pub async fn revoke_invite(
+    State(db): State<Arc<Db>>,
+    Extension(tenant): Extension<Arc<Tenant>>,
+    ConnectInfo(conn_info): ConnectInfo<crate::ClientSocket>,
+    headers: HeaderMap,
+    Extension(admin): Extension<UserData>,
+    Json(body): Json<RevokeInviteRequest>,
+) -> Result<ErasedJson, AppError> {
+    db.revoke_invite(&tenant.id, &body.code).await?;
+    let admin_id = admin.lock().unwrap().0.id;
+    let event = super::admin_action(admin_id, None, "revoke_invite", conn_info.ip(), &headers);
+    db.record_audit(event).await;
+    Ok(json!({
+        "message": "The invite code has been revoked"
+    }))
+}
@@ -0,0 +1,163 @@
+use axum::{
+    Extension, Json,
+    extract::{ConnectInfo, Path, State},
+    http::HeaderMap,
+};
+use axum_extra::{json, response::ErasedJson};
+use database::{Db, UserData, tenants::Tenant};
+use std::sync::Arc;
+use util::{AppError, permission::Permission};
+
+/// the built-in role that can't be changed or deleted, so that admins can't lock themselves out
+const BUILTIN_ROLE: &str = "admin";
+
+pub async fn list_roles(State(db): State<Arc<Db>>) -> Result<ErasedJson, AppError> {
+    let roles = db.list_roles().await?;
+    let permissions = Permission::ALL.iter().map(|p| p.get_str()).collect::<Vec<_>>();
+    Ok(json!({
+        "roles": roles,
+        "permissions": permissions,
+    }))
+}
+
+#[derive(serde::Deserialize)]
+pub struct UpsertRoleRequest {
+    name: String,
+    #[serde(default)]
+    description: String,
+    permissions: Vec<String>,
+}
+
+pub async fn upsert_role(
+    State(db): State<Arc<Db>>,
+    ConnectInfo(conn_info): ConnectInfo<crate::ClientSocket>,
+    headers: HeaderMap,
+    Extension(admin): Extension<UserData>,
+    Json(body): Json<UpsertRoleRequest>,
+) -> Result<ErasedJson, AppError> {
+    is_role_name_valid(&body.name)?;
+    if body.name == BUILTIN_ROLE {
+        return Err(AppError::BadReq("The admin role cannot be changed"));
+    }
+    let permissions = body
+        .permissions
+        .iter()
+        .map(|p| Permission::try_from(p.as_str()))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    db.upsert_role(&body.name, &body.description, &permissions).await?;
+    let admin_id = admin.lock().unwrap().0.id;
+    let event = super::admin_action(admin_id, None, "upsert_role", conn_info.ip(), &headers)
+        .details(serde_json::json!({
+            "action": "upsert_role",
+            "role": body.name,
+            "permissions": body.permissions,
+        }));
+    db.record_audit(event).await;
+    Ok(json!({
+        "message": "The role has been saved"
+    }))
+}
+
+#[derive(serde::Deserialize)]
+pub struct DeleteRoleRequest {
+    name: String,
+}
+
+pub async fn delete_role(
+    State(db): State<Arc<Db>>,
+    ConnectInfo(conn_info): ConnectInfo<crate::ClientSocket>,
+    headers: HeaderMap,
+    Extension(admin): Extension<UserData>,
+    Json(body): Json<DeleteRoleRequest>,
+) -> Result<ErasedJson, AppError> {
+    if body.name == BUILTIN_ROLE {
+        return Err(AppError::BadReq("The admin role cannot be deleted"));
+    }
+    db.delete_role(&body.name).await?;
+    let admin_id = admin.lock().unwrap().0.id;
+    let event = super::admin_action(admin_id, None, "delete_role", conn_info.ip(), &headers)
+        .details(serde_json::json!({ "action": "delete_role", "role": body.name }));
+    db.record_audit(event).await;
+    Ok(json!({
+        "message": "The role has been deleted"
+    }))
+}
+
+pub async fn get_user_roles(
+    State(db): State<Arc<Db>>,
+    Extension(tenant): Extension<Arc<Tenant>>,
+    Path(path): Path<super::UserPath>,
+) -> Result<ErasedJson, AppError> {
+    let user = db.get_user_by_username(&tenant.id, &path.username).await?;
+    let roles = db.get_user_roles(user.id).await?;
+    Ok(json!({
+        "username": user.username,
+        "roles": roles,
+    }))
+}
+
+#[derive(serde::Deserialize)]
+pub struct RoleAssignmentRequest {
+    username: String,
+    role: String,
+}
+
+pub async fn assign_role(
+    State(db): State<Arc<Db>>,
+    Extension(tenant): Extension<Arc<Tenant>>,
+    ConnectInfo(conn_info): ConnectInfo<crate::ClientSocket>,
+    headers: HeaderMap,
+    Extension(admin): Extension<UserData>,
+    Json(body): Json<RoleAssignmentRequest>,
+) -> Result<ErasedJson, AppError> {
+    let admin_id = admin.lock().unwrap().0.id;
+    let user = db.get_user_by_username(&tenant.id, &body.username).await?;
+    db.assign_role(user.id, &body.role, Some(admin_id)).await?;
+    let event =
+        super::admin_action(admin_id, Some(user.id), "assign_role", conn_info.ip(), &headers)
+            .details(serde_json::json!({ "action": "assign_role", "role": body.role }));
+    db.record_audit(event).await;
+    Ok(json!({
+        "roles": db.get_user_roles(user.id).await?,
+        "message": "The role has been assigned"
+    }))
+}
+
+pub async fn revoke_role(
+    State(db): State<Arc<Db>>,
+    Extension(tenant): Extension<Arc<Tenant>>,
+    ConnectInfo(conn_info): ConnectInfo<crate::ClientSocket>,
+    headers: HeaderMap,
+    Extension(admin): Extension<UserData>,
+    Json(body): Json<RoleAssignmentRequest>,
+) -> Result<ErasedJson, AppError> {
+    let admin_id = admin.lock().unwrap().0.id;
+    let user = db.get_user_by_username(&tenant.id, &body.username).await?;
+    if user.id == admin_id && body.role == BUILTIN_ROLE {
+        return Err(AppError::BadReq("You cannot revoke your own admin role"));
+    }
+    db.revoke_role(user.id, &body.role).await?;
+    let event =
+        super::admin_action(admin_id, Some(user.id), "revoke_role", conn_info.ip(), &headers)
+            .details(serde_json::json!({ "action": "revoke_role", "role": body.role }));
+    db.record_audit(event).await;
+    Ok(json!({
+        "roles": db.get_user_roles(user.id).await?,
+        "message": "The role has been revoked"
+    }))
+}
+
+fn is_role_name_valid(name: &str) -> Result<(), AppError> {
+    if name.is_empty()
+        || name.len() > 64
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '-')
+    {
+        return Err(AppError::InvalidData(
+            "Role names must be 1-64 lowercase letters, digits, `_` or `-`",
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,137 @@
+use axum::{
+    Extension, Json,
+    extract::{ConnectInfo, State},
+    http::HeaderMap,
+};
+use axum_extra::{json, response::ErasedJson};
+use database::{
+    Db, UserData,
+    tenants::{MailTemplate, Tenant},
+};
+use std::{collections::HashMap, sync::Arc};
+use util::{AppError, clock::Clock, mail::MailKind};
+
+pub async fn list_tenants(State(db): State<Arc<Db>>) -> Result<ErasedJson, AppError> {
+    let tenants = db.list_tenants().await?;
+    let tenant_list = tenants
+        .iter()
+        .map(|(t, users)| {
+            serde_json::json!({
+                "id": t.id,
+                "service_name": t.service_name,
+                "service_domain": t.service_domain,
+                "host": t.host,
+                "google_client_id": t.google_client_id,
+                // the secret never leaves the server
+                "google_client_secret": t.google_client_secret.is_some(),
+                "email_templates": t.mail_templates(),
+                "created": t.created.to_string(),
+                "users": users,
+            })
+        })
+        .collect::<Vec<_>>();
+    let mail_kinds = MailKind::ALL.iter().map(|k| k.get_str()).collect::<Vec<_>>();
+    Ok(json!({
+        "tenants": tenant_list,
+        "mail_kinds": mail_kinds,
+    }))
+}
+
+#[derive(serde::Deserialize)]
+pub struct UpsertTenantRequest {
+    id: String,
+    #[serde(default)]
+    service_name: String,
+    #[serde(default)]
+    service_domain: String,
+    host: Option<String>,
+    google_client_id: Option<String>,
+    google_client_secret: Option<String>,
+    #[serde(default)]
+    email_templates: HashMap<String, MailTemplate>,
+}
+
+pub async fn upsert_tenant(
+    State(db): State<Arc<Db>>,
+    State(clock): State<Arc<dyn Clock>>,
+    ConnectInfo(conn_info): ConnectInfo<crate::ClientSocket>,
+    headers: HeaderMap,
+    Extension(admin): Extension<UserData>,
+    Json(body): Json<UpsertTenantRequest>,
+) -> Result<ErasedJson, AppError> {
+    is_tenant_id_valid(&body.id)?;
+    for kind in body.email_templates.keys() {
+        MailKind::try_from(kind.as_str())?;
+    }
+    if body.google_client_id.is_some() != body.google_client_secret.is_some() {
+        return Err(AppError::BadReq("Both the Google client id and secret are required"));
+    }
+    let host = body.host.map(|v| v.trim().to_ascii_lowercase()).filter(|v| !v.is_empty());
+    let service_domain = body.service_domain.trim_end_matches('/').to_string();
+    let email_templates = serde_json::to_string(&body.email_templates).map_err(|e| {
+        tracing::error!("{:?}", e);
+        AppError::ServerError
+    })?;
+
+    let tenant = Tenant {
+        id: body.id,
+        service_name: body.service_name,
+        service_domain,
+        host,
+        google_client_id: body.google_client_id,
+        google_client_secret: body.google_client_secret,
+        email_templates,
+        created: clock.now(),
+        // the registration policy is changed with `/api/admin/registration`
+        registration_mode: String::new(),
+        allowed_email_domains: vec![],
+        denied_email_domains: vec![],
+    };
+    db.upsert_tenant(&tenant).await?;
+    let admin_id = admin.lock().unwrap().0.id;
+    let event = super::admin_action(admin_id, None, "upsert_tenant", conn_info.ip(), &headers)
+        .details(serde_json::json!({
+            "action": "upsert_tenant",
+            "tenant": tenant.id,
+            "host": tenant.host,
+        }));
+    db.record_audit(event).await;
+    Ok(json!({
+        "message": "The tenant has been saved"
+    }))
+}
+
+#[derive(serde::Deserialize)]
+pub struct DeleteTenantRequest {
+    id: String,
+}
+
+pub async fn delete_tenant(
+    State(db): State<Arc<Db>>,
+    ConnectInfo(conn_info): ConnectInfo<crate::ClientSocket>,
+    headers: HeaderMap,
+    Extension(admin): Extension<UserData>,
+    Json(body): Json<DeleteTenantRequest>,
+) -> Result<ErasedJson, AppError> {
+    db.delete_tenant(&body.id).await?;
+    let admin_id = admin.lock().unwrap().0.id;
+    let event = super::admin_action(admin_id, None, "delete_tenant", conn_info.ip(), &headers)
+        .details(serde_json::json!({ "action": "delete_tenant", "tenant": body.id }));
+    db.record_audit(event).await;
+    Ok(json!({
+        "message": "The tenant has been deleted"
+    }))
+}
+
+// tenant ids are part of urls (`/t/{tenant}`)
+fn is_tenant_id_valid(id: &str) -> Result<(), AppError> {
+    if id.is_empty()
+        || id.len() > 64
+        || !id.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+    {
+        return Err(AppError::InvalidData(
+            "Tenant ids must be 1-64 lowercase letters, digits or `-`",
+        ));
+    }
+    Ok(())
+}
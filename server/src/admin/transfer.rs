@@ -0,0 +1,109 @@
+use axum::{
+    Extension,
+    body::{Body, Bytes},
+    extract::{ConnectInfo, Query, State},
+    http::{HeaderMap, header},
+    response::IntoResponse,
+};
+use axum_extra::{json, response::ErasedJson};
+use database::{
+    Db, UserData,
+    tenants::Tenant,
+    users::import::{ImportedRow, PortableFormat},
+};
+use std::sync::Arc;
+use util::AppError;
+
+/// largest file accepted by `import_users`, bigger ones can be imported with the cli
+pub const IMPORT_MAX_SIZE: usize = 32 * 1024 * 1024;
+
+const EXPORT_PAGE_SIZE: i64 = 500;
+
+#[derive(serde::Deserialize)]
+pub struct FormatQuery {
+    /// `csv` or `jsonl`
+    format: Option<String>,
+}
+
+/// imports users from a csv or json lines body, reporting the outcome of every row
+pub async fn import_users(
+    State(db): State<Arc<Db>>,
+    Extension(tenant): Extension<Arc<Tenant>>,
+    ConnectInfo(conn_info): ConnectInfo<crate::ClientSocket>,
+    headers: HeaderMap,
+    Extension(admin): Extension<UserData>,
+    Query(q): Query<FormatQuery>,
+    body: Bytes,
+) -> Result<ErasedJson, AppError> {
+    let format = match q.format.as_deref() {
+        Some(format) => PortableFormat::try_from(format)?,
+        // the format can also be given with the content type
+        None => match headers.get(header::CONTENT_TYPE).and_then(|v| v.to_str().ok()) {
+            Some(v) if v.starts_with("text/csv") => PortableFormat::Csv,
+            _ => PortableFormat::JsonLines,
+        },
+    };
+
+    let rows = db.import_users(&tenant.id, format, &body).await;
+    let imported = rows.iter().filter(|v| v.result.is_ok()).count();
+    let admin_id = admin.lock().unwrap().0.id;
+    let event = super::admin_action(admin_id, None, "import_users", conn_info.ip(), &headers)
+        .details(serde_json::json!({
+            "action": "import_users",
+            "imported": imported,
+            "failed": rows.len() - imported,
+        }));
+    db.record_audit(event).await;
+
+    Ok(json!({
+        "imported": imported,
+        "failed": rows.len() - imported,
+        "rows": rows.iter().map(ImportedRow::to_json).collect::<Vec<_>>(),
+    }))
+}
+
+/// streams every user in the import format, page by page
+pub async fn export_users(
+    State(db): State<Arc<Db>>,
+    Extension(tenant): Extension<Arc<Tenant>>,
+    ConnectInfo(conn_info): ConnectInfo<crate::ClientSocket>,
+    headers: HeaderMap,
+    Extension(admin): Extension<UserData>,
+    Query(q): Query<FormatQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let format = PortableFormat::try_from(q.format.as_deref().unwrap_or("jsonl"))?;
+    let admin_id = admin.lock().unwrap().0.id;
+    db.record_audit(super::admin_action(admin_id, None, "export_users", conn_info.ip(), &headers))
+        .await;
+
+    // state is the last exported id and whether the csv header is still to be written
+    let pages = futures_util::stream::unfold(Some((None, true)), move |state| {
+        let (db, tenant) = (db.clone(), tenant.clone());
+        async move {
+            let (after_id, first_page) = state?;
+            match db.export_users_page(&tenant.id, after_id, EXPORT_PAGE_SIZE).await {
+                Ok(users) => {
+                    let next = (users.len() as i64 == EXPORT_PAGE_SIZE)
+                        .then(|| users.last().and_then(|u| u.id.as_deref()))
+                        .flatten()
+                        .and_then(|id| uuid::Uuid::try_parse(id).ok())
+                        .map(|id| (Some(id), false));
+                    let chunk = Bytes::from(format.encode(&users, first_page));
+                    Some((Ok(chunk), next))
+                }
+                Err(_) => Some((Err(std::io::Error::other("failed to read users")), None)),
+            }
+        }
+    });
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, format.content_type().to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"users.{}\"", format.extension()),
+            ),
+        ],
+        Body::from_stream(pages),
+    ))
+}
@@ -0,0 +1,400 @@
+use crate::outbox::Outbox;
+use axum::{
+    Extension, Json,
+    extract::{ConnectInfo, Path, Query, State},
+    http::HeaderMap,
+};
+use axum_extra::{json, response::ErasedJson};
+use database::{
+    Db, UserData,
+    tenants::Tenant,
+    users::{
+        AccountStatus, User,
+        admin::{UserSearch, UserSort},
+    },
+};
+use std::sync::Arc;
+use util::{AppError, clock::Clock, config::Config, mail::MailKind};
+
+const DEFAULT_PAGE_SIZE: i64 = 20;
+const MAX_PAGE_SIZE: i64 = 100;
+
+#[derive(serde::Deserialize)]
+pub struct SearchUsersQuery {
+    q: Option<String>,
+    status: Option<String>,
+    /// unix timestamp (inclusive)
+    created_after: Option<i64>,
+    /// unix timestamp (exclusive)
+    created_before: Option<i64>,
+    /// `created` (default), `email` or `username`
+    sort: Option<String>,
+    /// `asc` or `desc` (default)
+    order: Option<String>,
+    page: Option<i64>,
+    per_page: Option<i64>,
+}
+
+pub async fn search_users(
+    State(db): State<Arc<Db>>,
+    Extension(tenant): Extension<Arc<Tenant>>,
+    Query(q): Query<SearchUsersQuery>,
+) -> Result<ErasedJson, AppError> {
+    let (page, per_page) = page_bounds(q.page, q.per_page);
+    let search = UserSearch {
+        tenant_id: tenant.id.clone(),
+        query: q.q.filter(|v| !v.is_empty()),
+        status: match q.status.as_deref() {
+            None | Some("") => None,
+            Some(v @ ("active" | "suspended" | "password_reset_required")) => {
+                Some(AccountStatus::from(v))
+            }
+            Some(_) => return Err(AppError::InvalidData("Invalid account status")),
+        },
+        created_after: q.created_after.map(timestamp).transpose()?,
+        created_before: q.created_before.map(timestamp).transpose()?,
+        sort: match q.sort.as_deref() {
+            None | Some("created") => UserSort::Created,
+            Some("email") => UserSort::Email,
+            Some("username") => UserSort::Username,
+            Some(_) => return Err(AppError::InvalidData("Invalid sort field")),
+        },
+        descending: match q.order.as_deref() {
+            None | Some("desc") => true,
+            Some("asc") => false,
+            Some(_) => return Err(AppError::InvalidData("Invalid sort order")),
+        },
+        limit: per_page,
+        offset: (page - 1) * per_page,
+    };
+
+    let (users, total) = db.search_users(&search).await?;
+    Ok(json!({
+        "users": users.iter().map(user_summary).collect::<Vec<_>>(),
+        "page": page,
+        "per_page": per_page,
+        "total": total,
+    }))
+}
+
+pub async fn get_user(
+    State(db): State<Arc<Db>>,
+    State(clock): State<Arc<dyn Clock>>,
+    Extension(tenant): Extension<Arc<Tenant>>,
+    Path(path): Path<super::UserPath>,
+) -> Result<ErasedJson, AppError> {
+    let user = db.get_user_by_username(&tenant.id, &path.username).await?;
+    let sessions = db.get_user_sessions(user.id, clock.now()).await?;
+    let roles = db.get_user_roles(user.id).await?;
+    let session_list = sessions
+        .iter()
+        .map(|s| {
+            serde_json::json!({
+                "unsigned_ssid": s.unsigned_ssid.to_string(),
+                "user_agent": s.user_agent,
+                "ip_address": s.ip_address.to_string(),
+                "created_at": s.created_at.to_string(),
+                "last_used": s.last_used.to_string(),
+                "expires_at": s.expires_at.to_string(),
+                "impersonated": s.impersonated_by.is_some(),
+            })
+        })
+        .collect::<Vec<_>>();
+
+    Ok(json!({
+        "user": user_summary(&user),
+        "legal_name": user.legal_name,
+        "phone": user.phone,
+        "country": user.country,
+        "bind_sessions": user.bind_sessions,
+        "roles": roles,
+        "sessions": session_list,
+    }))
+}
+
+#[derive(serde::Deserialize)]
+pub struct DeletedUsersQuery {
+    q: Option<String>,
+    page: Option<i64>,
+    per_page: Option<i64>,
+}
+
+pub async fn search_deleted_users(
+    State(db): State<Arc<Db>>,
+    Extension(tenant): Extension<Arc<Tenant>>,
+    Query(q): Query<DeletedUsersQuery>,
+) -> Result<ErasedJson, AppError> {
+    let (page, per_page) = page_bounds(q.page, q.per_page);
+    let query = q.q.as_deref().filter(|v| !v.is_empty());
+    let users = db.search_deleted_users(&tenant.id, query, per_page, (page - 1) * per_page).await?;
+    let user_list = users
+        .iter()
+        .map(|u| {
+            serde_json::json!({
+                "id": u.id.to_string(),
+                "username": u.username,
+                "email": u.email,
+                "display_name": u.display_name,
+                "created": u.created.to_string(),
+                "deleted": u.deleted.to_string(),
+            })
+        })
+        .collect::<Vec<_>>();
+
+    Ok(json!({
+        "users": user_list,
+        "page": page,
+        "per_page": per_page,
+    }))
+}
+
+#[derive(serde::Deserialize)]
+pub struct UsernameRequest {
+    username: String,
+}
+
+pub async fn suspend_user(
+    State(db): State<Arc<Db>>,
+    ConnectInfo(conn_info): ConnectInfo<crate::ClientSocket>,
+    headers: HeaderMap,
+    Extension(admin): Extension<UserData>,
+    Json(body): Json<UsernameRequest>,
+) -> Result<ErasedJson, AppError> {
+    let user = other_user(&db, &admin, &body.username).await?;
+    db.set_user_status(user.id, AccountStatus::Suspended).await?;
+    db.revoke_user_sessions(user.id).await?;
+    let admin_id = admin.lock().unwrap().0.id;
+    db.record_audit(super::admin_action(
+        admin_id,
+        Some(user.id),
+        "suspend",
+        conn_info.ip(),
+        &headers,
+    ))
+    .await;
+    Ok(json!({
+        "status": AccountStatus::Suspended.get_str(),
+        "message": format!("@{} has been suspended", user.username)
+    }))
+}
+
+pub async fn unsuspend_user(
+    State(db): State<Arc<Db>>,
+    Extension(tenant): Extension<Arc<Tenant>>,
+    ConnectInfo(conn_info): ConnectInfo<crate::ClientSocket>,
+    headers: HeaderMap,
+    Extension(admin): Extension<UserData>,
+    Json(body): Json<UsernameRequest>,
+) -> Result<ErasedJson, AppError> {
+    let user = db.get_user_by_username(&tenant.id, &body.username).await?;
+    if user.status != AccountStatus::Suspended {
+        return Err(AppError::BadReq("The user is not suspended"));
+    }
+    db.set_user_status(user.id, AccountStatus::Active).await?;
+    let admin_id = admin.lock().unwrap().0.id;
+    db.record_audit(super::admin_action(
+        admin_id,
+        Some(user.id),
+        "unsuspend",
+        conn_info.ip(),
+        &headers,
+    ))
+    .await;
+    Ok(json!({
+        "status": AccountStatus::Active.get_str(),
+        "message": format!("@{} has been unsuspended", user.username)
+    }))
+}
+
+/// logs the user out everywhere and blocks their login until they reset their password
+#[allow(clippy::too_many_arguments)]
+pub async fn force_password_reset(
+    State(db): State<Arc<Db>>,
+    State(config): State<Arc<Config>>,
+    outbox: Outbox,
+    Extension(tenant): Extension<Arc<Tenant>>,
+    ConnectInfo(conn_info): ConnectInfo<crate::ClientSocket>,
+    headers: HeaderMap,
+    Extension(admin): Extension<UserData>,
+    Json(body): Json<UsernameRequest>,
+) -> Result<ErasedJson, AppError> {
+    let user = other_user(&db, &admin, &body.username).await?;
+    if user.status == AccountStatus::Suspended {
+        return Err(AppError::BadReq("The user is suspended"));
+    }
+    db.set_user_status(user.id, AccountStatus::PasswordResetRequired).await?;
+    db.revoke_user_sessions(user.id).await?;
+    let admin_id = admin.lock().unwrap().0.id;
+    db.record_audit(super::admin_action(
+        admin_id,
+        Some(user.id),
+        "force_password_reset",
+        conn_info.ip(),
+        &headers,
+    ))
+    .await;
+
+    let code = util::generate::hex_64(&user.email);
+    db.request_password_reset(&tenant.id, *conn_info, user.email.clone(), code.clone());
+    util::metrics::VERIFICATION_CODES.with_label_values(&["password_reset"]).inc();
+    let link = format!("{}/api/reset_password?code={code}", tenant.base_url(&config));
+    outbox
+        .send(
+            user.email.clone(),
+            MailKind::PasswordResetRequired,
+            &[("email", &user.email), ("link", &link)],
+        )
+        .await?;
+
+    Ok(json!({
+        "status": AccountStatus::PasswordResetRequired.get_str(),
+        "message": format!("@{} has to reset their password", user.username)
+    }))
+}
+
+pub async fn revoke_sessions(
+    State(db): State<Arc<Db>>,
+    ConnectInfo(conn_info): ConnectInfo<crate::ClientSocket>,
+    headers: HeaderMap,
+    Extension(admin): Extension<UserData>,
+    Json(body): Json<UsernameRequest>,
+) -> Result<ErasedJson, AppError> {
+    let user = other_user(&db, &admin, &body.username).await?;
+    let count = db.revoke_user_sessions(user.id).await?;
+    let admin_id = admin.lock().unwrap().0.id;
+    db.record_audit(super::admin_action(
+        admin_id,
+        Some(user.id),
+        "revoke_sessions",
+        conn_info.ip(),
+        &headers,
+    ))
+    .await;
+    Ok(json!({
+        "revoked": count,
+        "message": format!("All sessions of @{} have been revoked", user.username)
+    }))
+}
+
+/// lifts the age restriction of the user, once an admin has verified their age
+pub async fn lift_restriction(
+    State(db): State<Arc<Db>>,
+    Extension(tenant): Extension<Arc<Tenant>>,
+    ConnectInfo(conn_info): ConnectInfo<crate::ClientSocket>,
+    headers: HeaderMap,
+    Extension(admin): Extension<UserData>,
+    Json(body): Json<UsernameRequest>,
+) -> Result<ErasedJson, AppError> {
+    let user = db.get_user_by_username(&tenant.id, &body.username).await?;
+    if !user.restricted {
+        return Err(AppError::BadReq("The user is not restricted"));
+    }
+    db.set_user_restricted(user.id, false).await?;
+    let admin_id = admin.lock().unwrap().0.id;
+    db.record_audit(super::admin_action(
+        admin_id,
+        Some(user.id),
+        "lift_restriction",
+        conn_info.ip(),
+        &headers,
+    ))
+    .await;
+    Ok(json!({
+        "restricted": false,
+        "message": format!("The restriction of @{} has been lifted", user.username)
+    }))
+}
+
+#[derive(serde::Deserialize)]
+pub struct DeletedUserRequest {
+    id: String,
+}
+
+pub async fn restore_user(
+    State(db): State<Arc<Db>>,
+    Extension(tenant): Extension<Arc<Tenant>>,
+    ConnectInfo(conn_info): ConnectInfo<crate::ClientSocket>,
+    headers: HeaderMap,
+    Extension(admin): Extension<UserData>,
+    Json(body): Json<DeletedUserRequest>,
+) -> Result<ErasedJson, AppError> {
+    let user_id = parse_user_id(&body.id)?;
+    let user = db.restore_deleted_user(&tenant.id, user_id).await?;
+    let admin_id = admin.lock().unwrap().0.id;
+    db.record_audit(super::admin_action(
+        admin_id,
+        Some(user.id),
+        "restore_user",
+        conn_info.ip(),
+        &headers,
+    ))
+    .await;
+    Ok(json!({
+        "user": user_summary(&user),
+        "message": format!("@{} has been restored", user.username)
+    }))
+}
+
+pub async fn purge_user(
+    State(db): State<Arc<Db>>,
+    Extension(tenant): Extension<Arc<Tenant>>,
+    ConnectInfo(conn_info): ConnectInfo<crate::ClientSocket>,
+    headers: HeaderMap,
+    Extension(admin): Extension<UserData>,
+    Json(body): Json<DeletedUserRequest>,
+) -> Result<ErasedJson, AppError> {
+    let user_id = parse_user_id(&body.id)?;
+    db.purge_deleted_user(&tenant.id, user_id).await?;
+    let admin_id = admin.lock().unwrap().0.id;
+    db.record_audit(super::admin_action(
+        admin_id,
+        Some(user_id),
+        "purge_user",
+        conn_info.ip(),
+        &headers,
+    ))
+    .await;
+    Ok(json!({
+        "message": "The user has been permanently deleted"
+    }))
+}
+
+/// returns the user with `username` of the admin's tenant, refusing the admin's own account
+async fn other_user(db: &Arc<Db>, admin: &UserData, username: &str) -> Result<User, AppError> {
+    let (admin_id, tenant_id) = {
+        let guard = admin.lock().unwrap();
+        (guard.0.id, guard.0.tenant_id.clone())
+    };
+    let user = db.get_user_by_username(&tenant_id, username).await?;
+    if user.id == admin_id {
+        return Err(AppError::BadReq("You cannot do this to your own account"));
+    }
+    Ok(user)
+}
+
+fn user_summary(user: &User) -> serde_json::Value {
+    serde_json::json!({
+        "id": user.id.to_string(),
+        "username": user.username,
+        "email": user.email,
+        "display_name": user.display_name,
+        "oauth_provider": user.oauth_provider.get_str(),
+        "status": user.status.get_str(),
+        "restricted": user.restricted,
+        "created": user.created.to_string(),
+    })
+}
+
+/// returns the 1-based page and the page size
+fn page_bounds(page: Option<i64>, per_page: Option<i64>) -> (i64, i64) {
+    (page.unwrap_or(1).max(1), per_page.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE))
+}
+
+fn timestamp(unix: i64) -> Result<time::OffsetDateTime, AppError> {
+    time::OffsetDateTime::from_unix_timestamp(unix)
+        .map_err(|_| AppError::InvalidData("Invalid timestamp"))
+}
+
+fn parse_user_id(id: &str) -> Result<uuid::Uuid, AppError> {
+    uuid::Uuid::try_from(id).map_err(|_| AppError::InvalidData("Invalid user id"))
+}
@@ -0,0 +1,20 @@
+use axum::{Extension, extract::State, response::IntoResponse};
+use axum_extra::json;
+use database::tenants::Tenant;
+use std::sync::Arc;
+use util::{config::Config, session::ParsedSession};
+
+pub async fn csrf_token(
+    State(config): State<Arc<Config>>,
+    Extension(tenant): Extension<Arc<Tenant>>,
+    Extension(parsed_session): Extension<ParsedSession>,
+) -> impl IntoResponse {
+    let (token, set_cookie_headermap) =
+        util::session::issue_csrf_token(&config, tenant.cookie_scope(), &parsed_session);
+    (
+        set_cookie_headermap,
+        json!({
+            "csrf_token": token,
+        }),
+    )
+}
@@ -0,0 +1,69 @@
+use database::{Db, events::SessionEvent, users::User};
+use std::sync::Arc;
+use util::{
+    AppError, SessionInfo,
+    config::Config,
+    session::{LimitStrategy, SessionStatus},
+};
+
+/// makes room for a new session of `user` according to the configured session limit
+///
+/// the sessions in `revoke_sessions` (chosen by the user after a rejected login)
+/// are removed before the limit is checked, `None` means the client can't choose them
+/// (e.g. the oidc callback) so the least recently used sessions are evicted instead of
+/// rejecting the login
+pub async fn make_room_for_session(
+    db: &Arc<Db>,
+    config: &Config,
+    user: &User,
+    revoke_sessions: Option<&[String]>,
+    now: time::OffsetDateTime,
+) -> Result<(), AppError> {
+    // users with any permission count as admins
+    let is_admin = !db.get_user_permissions(user.id).await?.is_empty();
+    let Some(max_sessions) = config.session_limit.max_for(is_admin) else {
+        return Ok(());
+    };
+
+    // idle sessions and the sessions of impersonating admins don't count
+    let mut sessions = db.get_user_sessions(user.id, now).await?;
+    sessions.retain(|s| {
+        s.impersonated_by.is_none()
+            && !matches!(s.session_status(&config.cookies, now), SessionStatus::Invalid)
+    });
+    let mut revoked = vec![];
+    for device in revoke_sessions.unwrap_or_default() {
+        let uid = uuid::Uuid::try_from(device.as_str())
+            .map_err(|_| AppError::InvalidData("Invalid Session found"))?;
+        if sessions.iter().any(|s| s.unsigned_ssid == uid) {
+            sessions.retain(|s| s.unsigned_ssid != uid);
+            revoked.push(uid);
+        }
+    }
+
+    // `sessions` is ordered by least recently used first
+    if sessions.len() >= max_sessions {
+        let strategy = match revoke_sessions {
+            Some(_) => config.session_limit.strategy,
+            None => LimitStrategy::EvictLru,
+        };
+        match strategy {
+            LimitStrategy::EvictLru => {
+                let excess = sessions.len() + 1 - max_sessions;
+                revoked.extend(sessions[..excess].iter().map(|s| s.unsigned_ssid));
+            }
+            LimitStrategy::Reject => {
+                return Err(AppError::SessionLimitReached(
+                    sessions.iter().map(SessionInfo::from).collect(),
+                ));
+            }
+        }
+    }
+
+    if !revoked.is_empty() {
+        db.remove_selected_sessions(user.id, &revoked).await?;
+        db.remove_active_sessions(user.id, &revoked);
+        db.publish_event(user.id, SessionEvent::SessionsRevoked { sessions: revoked });
+    }
+    Ok(())
+}
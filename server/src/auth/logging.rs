@@ -1,3 +1,4 @@
+use crate::outbox::Outbox;
 use axum::{
     Extension, Json,
     extract::{ConnectInfo, State},
@@ -5,36 +6,123 @@ use axum::{
     response::IntoResponse,
 };
 use axum_extra::{json, response::ErasedJson};
-use database::{Db, UserData};
+use database::{
+    Db, UserData,
+    audit::{AuditEvent, AuditKind},
+    events::SessionEvent,
+    tenants::Tenant,
+};
 use std::sync::Arc;
-use util::{AppError, session::ParsedSession};
+use util::{AppError, clock::Clock, config::Config, session::ParsedSession};
 
 #[derive(serde::Deserialize)]
 pub struct LoginRequest {
     email: Option<String>,
     username: Option<String>,
     password: String,
+    #[serde(default)]
+    remember_me: bool,
+    /// sessions to log out when the session limit has been reached
+    #[serde(default)]
+    revoke_sessions: Vec<String>,
 }
 
+#[allow(clippy::too_many_arguments)]
 pub async fn login(
     State(db): State<Arc<Db>>,
+    State(config): State<Arc<Config>>,
+    State(clock): State<Arc<dyn Clock>>,
+    Extension(tenant): Extension<Arc<Tenant>>,
     ConnectInfo(conn_info): ConnectInfo<crate::ClientSocket>,
+    outbox: Outbox,
     headers: HeaderMap,
     Json(body): Json<LoginRequest>,
 ) -> Result<impl IntoResponse, AppError> {
-    let user = match (&body.email, &body.username) {
-        (Some(email), None) => db.authenticate_user_by_email(email, &body.password).await?,
-        (None, Some(username)) => db.authenticate_user_by_username(username, &body.password).await?,
+    let authenticated = match (&body.email, &body.username) {
+        (Some(email), None) => {
+            db.authenticate_user_by_email(&tenant.id, email, &body.password).await
+        }
+        (None, Some(username)) => {
+            db.authenticate_user_by_username(&tenant.id, username, &body.password).await
+        }
         (Some(_), Some(_)) => return Err(AppError::BadReq("Either email or username is allowed")),
         (None, None) => return Err(AppError::BadReq("No email or username found")),
     };
+    let user = match authenticated {
+        Ok(user) => user,
+        Err(AppError::PasswordMismatch) => {
+            util::metrics::LOGINS.with_label_values(&["password", "failure"]).inc();
+            // the user exists, so the failed attempt shows up in their activity
+            let user = match (&body.email, &body.username) {
+                (Some(email), _) => db.get_user_by_email(&tenant.id, email).await.ok(),
+                (_, Some(username)) => db.get_user_by_username(&tenant.id, username).await.ok(),
+                _ => None,
+            };
+            let event = AuditEvent::new(AuditKind::LoginFailed, user.map(|u| u.id))
+                .client(conn_info.ip(), &headers)
+                .details(serde_json::json!({ "reason": "password_mismatch" }));
+            db.record_audit(event).await;
+            return Err(AppError::PasswordMismatch);
+        }
+        Err(AppError::UserNotFound) => {
+            util::metrics::LOGINS.with_label_values(&["password", "failure"]).inc();
+            // no account to attach it to, admins find it by the identifier that was tried
+            let identifier = body.email.as_deref().or(body.username.as_deref());
+            let event = AuditEvent::new(AuditKind::LoginFailed, None)
+                .client(conn_info.ip(), &headers)
+                .details(serde_json::json!({
+                    "reason": "user_not_found",
+                    "identifier": identifier,
+                    "tenant": tenant.id,
+                }));
+            db.record_audit(event).await;
+            return Err(AppError::UserNotFound);
+        }
+        Err(e) => {
+            util::metrics::LOGINS.with_label_values(&["password", "failure"]).inc();
+            return Err(e);
+        }
+    };
+    if let Err(e) = user.status.ensure_active() {
+        util::metrics::LOGINS.with_label_values(&["password", "failure"]).inc();
+        let event = AuditEvent::new(AuditKind::LoginFailed, Some(user.id))
+            .client(conn_info.ip(), &headers)
+            .details(serde_json::json!({ "reason": user.status.get_str() }));
+        db.record_audit(event).await;
+        return Err(e);
+    }
+    let now = clock.now();
+    super::new_device::notify_new_device(
+        &db,
+        &config,
+        &outbox,
+        &user,
+        conn_info.ip(),
+        &headers,
+        now,
+    )
+    .await?;
+    super::limit::make_room_for_session(&db, &config, &user, Some(&body.revoke_sessions), now)
+        .await?;
 
-    let (new_session, parsed_session, set_cookie_headermap) =
-        util::session::create_session(user.id, &headers, *conn_info);
+    let (new_session, parsed_session, set_cookie_headermap) = util::session::create_session(
+        &config,
+        now,
+        tenant.cookie_scope(),
+        user.id,
+        &headers,
+        *conn_info,
+        body.remember_me,
+    );
     let res_body = crate::user_data::arrange(&user, &vec![&new_session]);
 
     // adding `Session` to primary database
     db.add_session(user.id, new_session.clone()).await?;
+    let event = AuditEvent::new(AuditKind::LoginSucceeded, Some(user.id))
+        .client(conn_info.ip(), &headers)
+        .details(serde_json::json!({ "method": "password" }));
+    db.record_audit(event).await;
+    util::metrics::LOGINS.with_label_values(&["password", "success"]).inc();
 
     // activating session by adding it to `Db::active`
     if let Some((arc_wrapped, is_session_present)) = db.get_active_user(&parsed_session)
@@ -50,17 +138,36 @@ pub async fn login(
 
 pub async fn logout(
     State(db): State<Arc<Db>>,
+    State(config): State<Arc<Config>>,
+    ConnectInfo(conn_info): ConnectInfo<crate::ClientSocket>,
+    Extension(tenant): Extension<Arc<Tenant>>,
+    headers: HeaderMap,
     Extension(parsed_session): Extension<ParsedSession>,
     Extension(user): Extension<UserData>,
 ) -> Result<impl IntoResponse, AppError> {
-    let user_id = user.lock().unwrap().0.id;
+    let (user_id, is_impersonation) = {
+        let guard = user.lock().unwrap();
+        let session = guard.1.iter().find(|s| s.unsigned_ssid == parsed_session.unsigned_ssid);
+        (guard.0.id, session.is_some_and(|s| s.impersonated_by.is_some()))
+    };
 
     db.remove_session(user_id, parsed_session.unsigned_ssid).await?;
+    if is_impersonation {
+        db.end_impersonation(parsed_session.unsigned_ssid).await?;
+    }
+    let event = AuditEvent::new(AuditKind::Logout, Some(user_id))
+        .client(conn_info.ip(), &headers)
+        .details(serde_json::json!({ "impersonation": is_impersonation }));
+    db.record_audit(event).await;
     db.remove_active_user(&parsed_session);
+    db.publish_event(
+        user_id,
+        SessionEvent::SessionsRevoked { sessions: vec![parsed_session.unsigned_ssid] },
+    );
 
     Ok((
         StatusCode::CREATED,
-        util::session::expire_session(),
+        util::session::expire_session(&config, tenant.cookie_scope()),
         json!({
             "message": "Logout Successful"
         }),
@@ -75,15 +182,15 @@ pub struct LogoutDevicesRequest {
 
 pub async fn logout_devices(
     State(db): State<Arc<Db>>,
+    ConnectInfo(conn_info): ConnectInfo<crate::ClientSocket>,
+    headers: HeaderMap,
     Extension(parsed_session): Extension<ParsedSession>,
     Extension(user): Extension<UserData>,
     Json(body): Json<LogoutDevicesRequest>,
 ) -> Result<impl IntoResponse, AppError> {
+    db.verify_user_password(&user, &body.password).await?;
     let (user_id, mut session_list) = {
         let guard = user.lock().unwrap();
-        if guard.0.password.as_ref().is_none_or(|v| v != &body.password) {
-            return Err(AppError::PasswordMismatch);
-        }
         (guard.0.id, guard.1.clone())
     };
 
@@ -102,6 +209,11 @@ pub async fn logout_devices(
     // updating primary and in-memory database with the only session
     db.remove_selected_sessions(user_id, &mapped_unsigned_ssids).await.unwrap();
     user.lock().unwrap().1 = session_list;
+    let event = AuditEvent::new(AuditKind::SessionsRevoked, Some(user_id))
+        .client(conn_info.ip(), &headers)
+        .details(serde_json::json!({ "count": mapped_unsigned_ssids.len() }));
+    db.record_audit(event).await;
+    db.publish_event(user_id, SessionEvent::SessionsRevoked { sessions: mapped_unsigned_ssids });
 
     Ok(json!({
         "message": "Your sessions has been updated"
@@ -115,15 +227,15 @@ pub struct LogoutAllRequest {
 
 pub async fn logout_all(
     State(db): State<Arc<Db>>,
+    ConnectInfo(conn_info): ConnectInfo<crate::ClientSocket>,
+    headers: HeaderMap,
     Extension(parsed_session): Extension<ParsedSession>,
     Extension(user): Extension<UserData>,
     Json(body): Json<LogoutAllRequest>,
 ) -> Result<ErasedJson, AppError> {
+    db.verify_user_password(&user, &body.password).await?;
     let (user_id, mut session_list) = {
         let guard = user.lock().unwrap();
-        if guard.0.password.as_ref().is_none_or(|v| v != &body.password) {
-            return Err(AppError::PasswordMismatch);
-        }
         (guard.0.id, guard.1.clone())
     };
 
@@ -133,6 +245,14 @@ pub async fn logout_all(
     // updating primary and in-memory database with the only session
     db.remove_all_sessions(user_id, parsed_session.unsigned_ssid).await?;
     user.lock().unwrap().1 = session_list;
+    let event = AuditEvent::new(AuditKind::SessionsRevoked, Some(user_id))
+        .client(conn_info.ip(), &headers)
+        .details(serde_json::json!({ "all_other_sessions": true }));
+    db.record_audit(event).await;
+    db.publish_event(
+        user_id,
+        SessionEvent::AllSessionsRevoked { except: parsed_session.unsigned_ssid },
+    );
 
     Ok(json!({
         "message": "Your all other sessions has been deleted"
@@ -1,17 +1,29 @@
-use axum::routing::{get, post};
+use crate::middleware::deny_impersonation;
+use axum::{
+    middleware::from_fn,
+    routing::{get, post},
+};
 
+mod csrf;
+mod limit;
 mod logging;
+mod new_device;
 mod oidc;
 mod recovery;
 mod register;
 
 #[rustfmt::skip]
-pub async fn auth_routes() -> axum::Router {
+pub fn auth_routes(state: &crate::AppState) -> axum::Router {
     axum::Router::new()
-        .route("/api/logout_all", post(logging::logout_all))
-        .route("/api/logout_devices", post(logging::logout_devices))
+        .route("/api/logout_all", post(logging::logout_all).layer(from_fn(deny_impersonation)))
+        .route("/api/logout_devices", post(logging::logout_devices).layer(from_fn(deny_impersonation)))
         .route("/api/logout", post(logging::logout))
-        .layer(axum::middleware::from_fn(crate::middleware::auth_middleware))
+        .route("/api/csrf", get(csrf::csrf_token))
+        .route_layer(axum::middleware::from_fn_with_state(
+            state.config.clone(),
+            crate::middleware::csrf_middleware,
+        ))
+        .route_layer(axum::middleware::from_fn_with_state(state.clone(), crate::middleware::auth_middleware))
         .route("/api/login", post(logging::login))
         .route("/api/forgot_password", post(recovery::forgot_password))
         .route("/api/reset_password", post(recovery::reset_password))
@@ -23,5 +35,5 @@ pub async fn auth_routes() -> axum::Router {
         .route("/api/register/verify_email", post(register::verify_email))
         .route("/api/register/set_password", post(register::set_password))
         .route("/api/register/set_username", post(register::set_username))
-        .with_state(database::Db::new().await)
+        .with_state(state.clone())
 }
@@ -0,0 +1,54 @@
+use crate::outbox::Outbox;
+use axum::http::{HeaderMap, header};
+use database::{
+    Db,
+    audit::{AuditKind, AuditQuery},
+    users::User,
+};
+use std::{net::IpAddr, sync::Arc};
+use util::{AppError, config::Config, mail::MailKind};
+
+/// number of the latest logins a new login is compared with
+const KNOWN_LOGINS: i64 = 50;
+
+/// emails `user` when they log in from a client that none of their sessions and latest logins
+/// came from (compared like bound sessions, see `BindingPolicy::same_client`)
+///
+/// users without any session or login to compare with aren't notified, it has to run before the
+/// new session and login are recorded
+pub async fn notify_new_device(
+    db: &Arc<Db>,
+    config: &Config,
+    outbox: &Outbox,
+    user: &User,
+    ip: IpAddr,
+    headers: &HeaderMap,
+    now: time::OffsetDateTime,
+) -> Result<(), AppError> {
+    let user_agent = headers.get(header::USER_AGENT).and_then(|v| v.to_str().ok());
+    let sessions = db.get_user_sessions(user.id, now).await?;
+    let query = AuditQuery {
+        user_id: Some(user.id),
+        kind: Some(AuditKind::LoginSucceeded),
+        limit: KNOWN_LOGINS,
+        ..Default::default()
+    };
+    let logins = db.list_audit_events(&query).await?;
+
+    // the sessions of impersonating admins were never used by the user
+    let mut known = sessions
+        .iter()
+        .filter(|s| s.impersonated_by.is_none())
+        .map(|s| (s.ip_address, s.user_agent.as_deref()))
+        .chain(logins.iter().filter_map(|l| Some((l.ip_address?, l.user_agent.as_deref()))))
+        .peekable();
+    if known.peek().is_none() || known.any(|v| config.binding.same_client(v, ip, user_agent)) {
+        return Ok(());
+    }
+
+    let family = util::session::user_agent_family(user_agent.unwrap_or_default());
+    let ip = ip.to_string();
+    let vars = [("ip", ip.as_str()), ("client", family)];
+    outbox.send_later(user.email.clone(), MailKind::NewActivity, &vars);
+    Ok(())
+}
@@ -1,21 +1,30 @@
-use crate::ClientSocket;
+use crate::{ClientSocket, outbox::Outbox};
 use axum::{
+    Extension,
     extract::{ConnectInfo, Query, State},
     http::HeaderMap,
     response::{IntoResponse, Redirect},
 };
 use base64::Engine;
-use database::Db;
+use database::{
+    Db,
+    audit::{AuditEvent, AuditKind},
+    tenants::Tenant,
+};
 use std::sync::Arc;
-use util::{AppError, oauth::OAuthProvider};
+use util::{AppError, clock::Clock, config::Config, oauth::OAuthProvider};
 
 #[derive(serde::Deserialize)]
 pub struct ProviderQuery {
     by: String,
+    /// invite code for the registration, if the user doesn't have an account yet
+    invite: Option<String>,
 }
 
 pub async fn login(
     State(db): State<Arc<Db>>,
+    State(config): State<Arc<Config>>,
+    Extension(tenant): Extension<Arc<Tenant>>,
     ConnectInfo(conn_info): ConnectInfo<ClientSocket>,
     Query(q): Query<ProviderQuery>,
 ) -> Result<Redirect, AppError> {
@@ -23,18 +32,21 @@ pub async fn login(
     let csrf_state = util::generate::random_string(32);
     let nonce = util::generate::random_string(32);
     let (code_verifier, code_challenge) = util::generate::pkce();
-    let oauth_cfg = util::oauth::get_oauth_provider(OAuthProvider::from(q.by))
+    let oauth_cfg = tenant
+        .oauth_config(&config, OAuthProvider::from(q.by))
         .ok_or(AppError::InvalidOAuthProvider)?;
 
     db.add_oidc_info(
+        &tenant.id,
         *conn_info,
         csrf_state.clone(),
         code_verifier,
         nonce.clone(),
         oauth_cfg.provider,
+        q.invite,
     );
 
-    let redirect_uri = format!("{}/api/oauth2/callback", &*util::SERVICE_DOMAIN);
+    let redirect_uri = format!("{}/api/oauth2/callback", tenant.base_url(&config));
     let mut request_uri = oauth_cfg.authorization_endpoint.clone();
     request_uri
         .query_pairs_mut()
@@ -70,19 +82,25 @@ struct TokenResponse {
     refresh_token: Option<String>,
 }
 
+#[allow(clippy::too_many_arguments)]
 pub async fn callback(
     State(db): State<Arc<Db>>,
+    State(config): State<Arc<Config>>,
+    State(clock): State<Arc<dyn Clock>>,
+    Extension(tenant): Extension<Arc<Tenant>>,
     ConnectInfo(conn_info): ConnectInfo<ClientSocket>,
+    outbox: Outbox,
     headers: HeaderMap,
     Query(q): Query<ProviderRedirect>,
 ) -> Result<impl IntoResponse, AppError> {
-    let oidc_info =
-        db.get_oidc_info(&q.csrf_state).ok_or(AppError::BadReq("CSRF state didn't match"))?;
+    let oidc_info = db
+        .get_oidc_info(&tenant.id, &q.csrf_state)
+        .ok_or(AppError::BadReq("CSRF state didn't match"))?;
 
     let oauth_cfg =
-        util::oauth::get_oauth_provider(oidc_info.provider).ok_or(AppError::InvalidOAuthProvider)?;
+        tenant.oauth_config(&config, oidc_info.provider).ok_or(AppError::InvalidOAuthProvider)?;
     let client = reqwest::Client::new();
-    let redirect_uri = format!("{}/api/oauth2/callback", &*util::SERVICE_DOMAIN);
+    let redirect_uri = format!("{}/api/oauth2/callback", tenant.base_url(&config));
 
     // Exchange authorization code for tokens
     let token_response = match client
@@ -150,18 +168,57 @@ pub async fn callback(
         fetch_user_info().await?
     };
 
-    match db.get_user_by_email(&user_info.email).await {
+    // an identity linked on import takes precedence over the email
+    let identity =
+        db.get_user_by_identity(&tenant.id, oidc_info.provider.get_str(), &user_info.sub).await?;
+    let found = match identity {
+        Some(user) => Ok((user, true)),
+        None => db.get_user_by_email(&tenant.id, &user_info.email).await.map(|user| (user, false)),
+    };
+
+    match found {
         // if the user found inside database
-        Ok(user) => match user.oauth_provider {
+        Ok((user, linked)) => match user.oauth_provider {
             // return error if the user have already registered with password
-            OAuthProvider::None => Err(AppError::BadReq(
+            OAuthProvider::None if !linked => Err(AppError::BadReq(
                 "Your account with this email already exists. Please login to link your account.",
             )),
             // login if the user is already registered with OIDC
             _ => {
+                if let Err(e) = user.status.ensure_active() {
+                    util::metrics::LOGINS.with_label_values(&["oidc", "failure"]).inc();
+                    let event = AuditEvent::new(AuditKind::LoginFailed, Some(user.id))
+                        .client(conn_info.ip(), &headers)
+                        .details(serde_json::json!({ "reason": user.status.get_str() }));
+                    db.record_audit(event).await;
+                    return Err(e);
+                }
+                let now = clock.now();
+                let ip = conn_info.ip();
+                super::new_device::notify_new_device(
+                    &db, &config, &outbox, &user, ip, &headers, now,
+                )
+                .await?;
+                super::limit::make_room_for_session(&db, &config, &user, None, now).await?;
                 let (new_session, parsed_session, set_cookie_headermap) =
-                    util::session::create_session(user.id, &headers, *conn_info);
+                    util::session::create_session(
+                        &config,
+                        now,
+                        tenant.cookie_scope(),
+                        user.id,
+                        &headers,
+                        *conn_info,
+                        false,
+                    );
                 db.add_session(user.id, new_session.clone()).await?;
+                let event = AuditEvent::new(AuditKind::LoginSucceeded, Some(user.id))
+                    .client(conn_info.ip(), &headers)
+                    .details(serde_json::json!({
+                        "method": "oidc",
+                        "provider": oidc_info.provider.get_str(),
+                    }));
+                db.record_audit(event).await;
+                util::metrics::LOGINS.with_label_values(&["oidc", "success"]).inc();
                 // activating session by adding it to `Db::active`
                 if let Some((arc_wrapped, is_session_present)) = db.get_active_user(&parsed_session)
                     && !is_session_present
@@ -171,21 +228,23 @@ pub async fn callback(
                 } else {
                     db.make_user_active(user, new_session);
                 }
-                db.remove_oidc_info(&q.csrf_state);
+                db.remove_oidc_info(&tenant.id, &q.csrf_state);
                 Ok((set_cookie_headermap, Redirect::to("/")).into_response()) // REDIRECT ENDPOINT NEEDS TO BE CHECKED
             }
         },
         // create registrant if the user is trying to register using open id connect
         Err(AppError::UserNotFound) => {
             db.create_registrant_oidc(
+                &tenant.id,
                 *conn_info,
                 user_info.name,
                 user_info.email,
                 user_info.picture,
                 oidc_info.provider,
+                oidc_info.invite_code,
             )
             .await?;
-            db.remove_oidc_info(&q.csrf_state);
+            db.remove_oidc_info(&tenant.id, &q.csrf_state);
             Ok(Redirect::to("/register/finish_oidc").into_response())
         }
         Err(e) => Err(e),
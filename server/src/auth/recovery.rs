@@ -1,12 +1,17 @@
-use crate::ClientSocket;
+use crate::{ClientSocket, outbox::Outbox};
 use axum::{
-    Json,
+    Extension, Json,
     extract::{ConnectInfo, Query, State},
+    http::HeaderMap,
 };
 use axum_extra::{json, response::ErasedJson};
-use database::Db;
+use database::{
+    Db,
+    audit::{AuditEvent, AuditKind},
+    tenants::Tenant,
+};
 use std::sync::Arc;
-use util::AppError;
+use util::{AppError, config::Config, mail::MailKind};
 
 #[derive(serde::Deserialize)]
 pub struct ForgotPasswordRequest {
@@ -16,26 +21,28 @@ pub struct ForgotPasswordRequest {
 // improve this route such that it can reset password using username and phone also
 pub async fn forgot_password(
     State(db): State<Arc<Db>>,
+    State(config): State<Arc<Config>>,
+    outbox: Outbox,
+    Extension(tenant): Extension<Arc<Tenant>>,
     ConnectInfo(conn_info): ConnectInfo<ClientSocket>,
     Json(body): Json<ForgotPasswordRequest>,
 ) -> Result<ErasedJson, AppError> {
     util::validation::is_email_valid(&body.email)?;
     let code = util::generate::hex_64(&body.email);
-    db.request_password_reset(*conn_info, body.email.clone(), code.clone());
+    db.request_password_reset(&tenant.id, *conn_info, body.email.clone(), code.clone());
+    util::metrics::VERIFICATION_CODES.with_label_values(&["password_reset"]).inc();
 
-    util::mail::send(
-        body.email.clone(),
-        format!("{} password reset request", &*util::SERVICE_NAME),
-        format!(
-            "<h1>Reset your password?</h1>\nIf you requested a password reset for {} press on this link {}\nIf you didn't make the request, please ignore this email.\nThanks, {}\n",
-            body.email,
-            format_args!("{}/api/reset_password?code={code}", &*util::SERVICE_DOMAIN),
-            &*util::SERVICE_NAME
-        ),
-    ).await?;
+    let link = format!("{}/api/reset_password?code={code}", tenant.base_url(&config));
+    outbox
+        .send(
+            body.email.clone(),
+            MailKind::PasswordReset,
+            &[("email", &body.email), ("link", &link)],
+        )
+        .await?;
 
     Ok(json!({
-        "message": format!("Check your email to reset password")
+        "message": "Check your email to reset password"
     }))
 }
 
@@ -51,19 +58,22 @@ pub struct ResetPasswordRequest {
 
 pub async fn reset_password(
     State(db): State<Arc<Db>>,
+    outbox: Outbox,
+    Extension(tenant): Extension<Arc<Tenant>>,
     ConnectInfo(conn_info): ConnectInfo<ClientSocket>,
+    headers: HeaderMap,
     Query(q): Query<ResetPasswordQuery>,
     Json(body): Json<ResetPasswordRequest>,
 ) -> Result<ErasedJson, AppError> {
     util::validation::is_password_strong(&body.password)?;
-    let email = db.reset_password(*conn_info, &q.code, &body.password).await?;
-
-    util::mail::send(
-        email.clone(),
-        format!("Your {} password has been changed", &*util::SERVICE_NAME),
-        format!("Your password for {} has been changed.\nThanks, {}\n", email, &*util::SERVICE_NAME),
+    let email = db.reset_password(&tenant.id, *conn_info, &q.code, &body.password).await?;
+    let user_id = db.get_user_by_email(&tenant.id, &email).await.ok().map(|u| u.id);
+    db.record_audit(
+        AuditEvent::new(AuditKind::PasswordReset, user_id).client(conn_info.ip(), &headers),
     )
-    .await?;
+    .await;
+
+    outbox.send(email.clone(), MailKind::PasswordChanged, &[("email", &email)]).await?;
 
     Ok(json!({
         "message": format!("Your password for {email} has been changed")
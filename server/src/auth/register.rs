@@ -1,20 +1,25 @@
-use crate::ClientSocket;
+use crate::settings::BirthDateRequest;
+use crate::{ClientSocket, outbox::Outbox};
 use axum::extract::ConnectInfo;
 use axum::http::{StatusCode, header::HeaderMap};
-use axum::{Json, extract::State, response::IntoResponse};
+use axum::{Extension, Json, extract::State, response::IntoResponse};
 use axum_extra::{json, response::ErasedJson};
-use database::Db;
-use std::sync::Arc;
-use util::AppError;
+use database::{Db, legal::AcceptedDocuments, tenants::Tenant};
+use std::{collections::HashMap, sync::Arc};
+use util::{AppError, clock::Clock, config::Config, mail::MailKind};
 
 #[derive(serde::Deserialize)]
 pub struct CreateUserRequest {
     name: String,
     email: String,
+    /// required when the tenant only accepts invited users
+    invite_code: Option<String>,
 }
 
 pub async fn start(
     State(db): State<Arc<Db>>,
+    outbox: Outbox,
+    Extension(tenant): Extension<Arc<Tenant>>,
     ConnectInfo(conn_info): ConnectInfo<ClientSocket>,
     Json(body): Json<CreateUserRequest>,
 ) -> Result<ErasedJson, AppError> {
@@ -23,17 +28,20 @@ pub async fn start(
     util::validation::is_email_valid(&body.email)?;
 
     let otp = util::generate::otp(&body.email);
-    tracing::info!("Email: {}, OTP: {}", body.email, otp);
 
-    db.create_registrant(*conn_info, body.name, body.email.clone(), otp.clone()).await?;
-
-    // sending otp to the email
-    util::mail::send(
-        body.email,
-        format!("{otp} is your {} verification code", &*util::SERVICE_NAME),
-        format!("Confirm your email address\n {otp}\n Thanks,\n {}", &*util::SERVICE_NAME),
+    db.create_registrant(
+        &tenant.id,
+        *conn_info,
+        body.name,
+        body.email.clone(),
+        otp.clone(),
+        body.invite_code,
     )
     .await?;
+    util::metrics::VERIFICATION_CODES.with_label_values(&["registration"]).inc();
+
+    // sending otp to the email
+    outbox.send(body.email, MailKind::VerificationCode, &[("code", &otp)]).await?;
 
     Ok(json!({
         "message": "Your information has been accepted"
@@ -47,18 +55,16 @@ pub struct ResendOtpRequest {
 
 pub async fn resend_otp(
     State(db): State<Arc<Db>>,
+    outbox: Outbox,
+    Extension(tenant): Extension<Arc<Tenant>>,
     Json(body): Json<ResendOtpRequest>,
 ) -> Result<ErasedJson, AppError> {
     let otp = util::generate::otp(&body.email);
-    db.update_registrant_otp(&body.email, otp.clone()).await?;
+    db.update_registrant_otp(&tenant.id, &body.email, otp.clone()).await?;
+    util::metrics::VERIFICATION_CODES.with_label_values(&["registration"]).inc();
 
     // resending otp to the email
-    util::mail::send(
-        body.email,
-        format!("{otp} is your {} verification code", &*util::SERVICE_NAME),
-        format!("Confirm your email address\n {otp}\n Thanks,\n {}", &*util::SERVICE_NAME),
-    )
-    .await?;
+    outbox.send(body.email, MailKind::VerificationCode, &[("code", &otp)]).await?;
 
     Ok(json!({
         "message": "The email has been sent"
@@ -73,22 +79,15 @@ pub struct VerifyEmailRequest {
 
 pub async fn verify_email(
     State(db): State<Arc<Db>>,
+    outbox: Outbox,
+    Extension(tenant): Extension<Arc<Tenant>>,
     Json(body): Json<VerifyEmailRequest>,
 ) -> Result<ErasedJson, AppError> {
     // verifying email by checking if the otp sent by user matches the original one
-    db.verify_registrant_email(&body.email, &body.otp).await?;
+    db.verify_registrant_email(&tenant.id, &body.email, &body.otp).await?;
 
     // sending email verification success
-    util::mail::send(
-        body.email.clone(),
-        format!("Your email {} has been verified successfully", body.email),
-        format!(
-            "Your email {} has been verified successfully\n Thanks,\n {}",
-            body.email,
-            &*util::SERVICE_NAME
-        ),
-    )
-    .await?;
+    outbox.send(body.email.clone(), MailKind::EmailVerified, &[("email", &body.email)]).await?;
 
     Ok(json!({
         "message": "Email Verification successful"
@@ -103,11 +102,12 @@ pub struct SetPasswordRequest {
 
 pub async fn set_password(
     State(db): State<Arc<Db>>,
+    Extension(tenant): Extension<Arc<Tenant>>,
     Json(body): Json<SetPasswordRequest>,
 ) -> Result<ErasedJson, AppError> {
     util::validation::is_password_strong(&body.password)?;
 
-    db.set_registrant_password(&body.email, body.password).await?;
+    db.set_registrant_password(&tenant.id, &body.email, body.password).await?;
 
     Ok(json!({
         "message": format!("Your password for email {} has been set", body.email)
@@ -118,21 +118,57 @@ pub async fn set_password(
 pub struct SetUsernameRequest {
     email: String,
     username: String,
+    /// required when a minimum age is configured
+    birth_date: Option<BirthDateRequest>,
+    country: Option<String>,
+    /// versions of the current legal documents accepted by the user (kind => version)
+    #[serde(default)]
+    accepted_documents: HashMap<String, String>,
 }
 
 pub async fn set_username(
     State(db): State<Arc<Db>>,
+    State(config): State<Arc<Config>>,
+    State(clock): State<Arc<dyn Clock>>,
+    Extension(tenant): Extension<Arc<Tenant>>,
     ConnectInfo(conn_info): ConnectInfo<ClientSocket>,
     headers: HeaderMap,
     Json(body): Json<SetUsernameRequest>,
 ) -> Result<impl IntoResponse, AppError> {
     util::validation::is_username_valid(&body.username)?;
+    let documents = db.check_legal_acceptance(&tenant.id, &body.accepted_documents).await?;
+    let now = clock.now();
+    let (birth_date, country, restricted) =
+        check_age(&config.age, body.birth_date, body.country, now)?;
+    let accepted = AcceptedDocuments {
+        documents: &documents,
+        ip_address: conn_info.ip(),
+        user_agent: user_agent(&headers),
+    };
 
-    // registering user to primary database
-    let user = db.set_registrant_username(body.email, body.username).await?;
+    // registering user to primary database along with the accepted documents
+    let user = db
+        .set_registrant_username(
+            &tenant.id,
+            body.email,
+            body.username,
+            birth_date,
+            country,
+            restricted,
+            &accepted,
+        )
+        .await?;
 
-    let (new_session, _, set_cookie_headermap) =
-        util::session::create_session(user.id, &headers, *conn_info);
+    super::limit::make_room_for_session(&db, &config, &user, None, now).await?;
+    let (new_session, _, set_cookie_headermap) = util::session::create_session(
+        &config,
+        now,
+        tenant.cookie_scope(),
+        user.id,
+        &headers,
+        *conn_info,
+        false,
+    );
 
     let res_body = crate::user_data::arrange(&user, &vec![&new_session]);
     db.add_session(user.id, new_session.clone()).await?;
@@ -145,21 +181,57 @@ pub async fn set_username(
 pub struct FinishOidcRequest {
     email: String,
     username: String,
+    /// required when a minimum age is configured
+    birth_date: Option<BirthDateRequest>,
+    country: Option<String>,
+    /// versions of the current legal documents accepted by the user (kind => version)
+    #[serde(default)]
+    accepted_documents: HashMap<String, String>,
 }
 
 pub async fn finish_oidc(
     State(db): State<Arc<Db>>,
+    State(config): State<Arc<Config>>,
+    State(clock): State<Arc<dyn Clock>>,
+    Extension(tenant): Extension<Arc<Tenant>>,
     ConnectInfo(conn_info): ConnectInfo<ClientSocket>,
     headers: HeaderMap,
     Json(body): Json<FinishOidcRequest>,
 ) -> Result<impl IntoResponse, AppError> {
     util::validation::is_username_valid(&body.username)?;
+    let documents = db.check_legal_acceptance(&tenant.id, &body.accepted_documents).await?;
+    let now = clock.now();
+    let (birth_date, country, restricted) =
+        check_age(&config.age, body.birth_date, body.country, now)?;
+    let accepted = AcceptedDocuments {
+        documents: &documents,
+        ip_address: conn_info.ip(),
+        user_agent: user_agent(&headers),
+    };
 
-    // registering user to primary database
-    let user = db.finish_oidc_application(body.email, body.username).await?;
+    // registering user to primary database along with the accepted documents
+    let user = db
+        .finish_oidc_application(
+            &tenant.id,
+            body.email,
+            body.username,
+            birth_date,
+            country,
+            restricted,
+            &accepted,
+        )
+        .await?;
 
-    let (new_session, _, set_cookie_headermap) =
-        util::session::create_session(user.id, &headers, *conn_info);
+    super::limit::make_room_for_session(&db, &config, &user, None, now).await?;
+    let (new_session, _, set_cookie_headermap) = util::session::create_session(
+        &config,
+        now,
+        tenant.cookie_scope(),
+        user.id,
+        &headers,
+        *conn_info,
+        false,
+    );
 
     let res_body = crate::user_data::arrange(&user, &vec![&new_session]);
     db.add_session(user.id, new_session.clone()).await?;
@@ -167,3 +239,28 @@ pub async fn finish_oidc(
 
     Ok((StatusCode::CREATED, set_cookie_headermap, res_body))
 }
+
+/// validates the birth date and country of the registrant against the age policy
+///
+/// returns them along with whether the account has to be restricted
+fn check_age(
+    policy: &util::age::AgePolicy,
+    birth_date: Option<BirthDateRequest>,
+    country: Option<String>,
+    now: time::OffsetDateTime,
+) -> Result<(Option<time::OffsetDateTime>, Option<String>, bool), AppError> {
+    let country = country.map(|v| util::validation::is_country_valid(&v)).transpose()?;
+    let Some(birth_date) = birth_date else {
+        if policy.is_enforced() {
+            return Err(AppError::InvalidData("Please enter your birth date"));
+        }
+        return Ok((None, country, false));
+    };
+    let birth_date = birth_date.birth_date()?;
+    let status = policy.check(birth_date, country.as_deref(), now)?;
+    Ok((Some(birth_date), country, status == util::age::AgeStatus::Restricted))
+}
+
+fn user_agent(headers: &HeaderMap) -> Option<&str> {
+    headers.get(axum::http::header::USER_AGENT).and_then(|v| v.to_str().ok())
+}
@@ -0,0 +1,112 @@
+use axum::{
+    Extension,
+    extract::{
+        State, WebSocketUpgrade,
+        ws::{Message, WebSocket},
+    },
+    http::{HeaderMap, header},
+    response::IntoResponse,
+    routing::get,
+};
+use database::{Db, UserData, events::SessionEvent};
+use serde_json::json;
+use std::sync::Arc;
+use tokio::sync::broadcast::error::RecvError;
+use util::{AppError, session::ParsedSession};
+
+pub fn events_routes(state: &crate::AppState) -> axum::Router {
+    axum::Router::new()
+        .route("/api/events", get(events_handler))
+        .route_layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            crate::middleware::auth_middleware,
+        ))
+        .with_state(state.clone())
+}
+
+/// pushes the events of the logged in user to the client, until its session is revoked
+pub async fn events_handler(
+    State(db): State<Arc<Db>>,
+    Extension(parsed_session): Extension<ParsedSession>,
+    Extension(user): Extension<UserData>,
+    headers: HeaderMap,
+    ws: WebSocketUpgrade,
+) -> Result<impl IntoResponse, AppError> {
+    // browsers send cookies with cross-site websocket handshakes, so the origin is checked here
+    if !is_same_origin(&headers) {
+        return Err(AppError::Forbidden("Cross-origin event streams are not allowed"));
+    }
+    let user_id = user.lock().unwrap().0.id;
+    let receiver = db.subscribe_events(user_id);
+
+    Ok(ws.on_upgrade(move |socket| stream_events(socket, receiver, parsed_session.unsigned_ssid)))
+}
+
+async fn stream_events(
+    mut socket: WebSocket,
+    mut receiver: tokio::sync::broadcast::Receiver<SessionEvent>,
+    unsigned_ssid: uuid::Uuid,
+) {
+    loop {
+        tokio::select! {
+            event = receiver.recv() => {
+                let (message, is_final) = match event {
+                    Ok(event) => outgoing_message(event, unsigned_ssid),
+                    // the client missed some events, so it should reload everything
+                    Err(RecvError::Lagged(_)) => (r#"{"type":"resync"}"#.to_string(), false),
+                    Err(RecvError::Closed) => break,
+                };
+                if socket.send(Message::Text(message.into())).await.is_err() || is_final {
+                    break;
+                }
+            }
+            incoming = socket.recv() => match incoming {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => continue,
+            },
+        }
+    }
+    let _ = socket.send(Message::Close(None)).await;
+}
+
+/// translates `event` into the message for the client of session `unsigned_ssid`
+///
+/// returns true as second element if the session has ended
+fn outgoing_message(event: SessionEvent, unsigned_ssid: uuid::Uuid) -> (String, bool) {
+    let (message, ended) = match event {
+        SessionEvent::SessionsRevoked { sessions } if sessions.contains(&unsigned_ssid) => {
+            (json!({ "type": "session_revoked" }), true)
+        }
+        SessionEvent::SessionsRevoked { sessions } => (
+            json!({
+                "type": "sessions_revoked",
+                "sessions": sessions.iter().map(|v| v.to_string()).collect::<Vec<_>>(),
+            }),
+            false,
+        ),
+        SessionEvent::AllSessionsRevoked { except } if except != unsigned_ssid => {
+            (json!({ "type": "session_revoked" }), true)
+        }
+        SessionEvent::AllSessionsRevoked { .. } => {
+            (json!({ "type": "all_sessions_revoked" }), false)
+        }
+        SessionEvent::ProfileChanged => (json!({ "type": "profile_changed" }), false),
+        SessionEvent::EmailChanged { email } => {
+            (json!({ "type": "email_changed", "email": email }), false)
+        }
+        SessionEvent::UsernameChanged { username } => {
+            (json!({ "type": "username_changed", "username": username }), false)
+        }
+        SessionEvent::AccountDeleted => (json!({ "type": "account_deleted" }), true),
+    };
+    (message.to_string(), ended)
+}
+
+fn is_same_origin(headers: &HeaderMap) -> bool {
+    let Some(origin) = headers.get(header::ORIGIN).and_then(|v| v.to_str().ok()) else {
+        // non-browser clients don't send an origin
+        return true;
+    };
+    let host = headers.get(header::HOST).and_then(|v| v.to_str().ok()).unwrap_or_default();
+    origin.split_once("://").is_some_and(|(_, origin_host)| origin_host == host)
+}
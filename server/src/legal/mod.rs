@@ -0,0 +1,98 @@
+use crate::middleware::deny_impersonation;
+use axum::{
+    Extension, Json,
+    extract::{ConnectInfo, State},
+    http::{HeaderMap, header},
+    middleware::from_fn,
+    routing::{get, post},
+};
+use axum_extra::{json, response::ErasedJson};
+use database::{Db, UserData, legal::AcceptedDocuments, tenants::Tenant};
+use std::{collections::HashMap, sync::Arc};
+use util::AppError;
+
+#[rustfmt::skip]
+pub fn legal_routes(state: &crate::AppState) -> axum::Router {
+    axum::Router::new()
+        .route("/api/legal/accept", post(accept_documents).layer(from_fn(deny_impersonation)))
+        .route("/api/legal/acceptances", get(list_acceptances))
+        .route_layer(axum::middleware::from_fn_with_state(
+            state.config.clone(),
+            crate::middleware::csrf_middleware,
+        ))
+        .route_layer(axum::middleware::from_fn_with_state(state.clone(), crate::middleware::auth_middleware))
+        .route("/api/legal", get(current_documents))
+        .with_state(state.clone())
+}
+
+/// the versions users have to accept, to show them before the registration
+pub async fn current_documents(
+    State(db): State<Arc<Db>>,
+    Extension(tenant): Extension<Arc<Tenant>>,
+) -> Result<ErasedJson, AppError> {
+    let documents = db
+        .current_legal_documents(&tenant.id)
+        .await?
+        .iter()
+        .map(|d| {
+            serde_json::json!({
+                "kind": d.kind,
+                "version": d.version,
+                "url": d.url,
+                "published_at": d.published_at.to_string(),
+            })
+        })
+        .collect::<Vec<_>>();
+    Ok(json!({
+        "documents": documents,
+    }))
+}
+
+#[derive(serde::Deserialize)]
+pub struct AcceptDocumentsRequest {
+    /// kind => version, the current version of every pending document has to be accepted
+    documents: HashMap<String, String>,
+}
+
+pub async fn accept_documents(
+    State(db): State<Arc<Db>>,
+    Extension(tenant): Extension<Arc<Tenant>>,
+    ConnectInfo(conn_info): ConnectInfo<crate::ClientSocket>,
+    headers: HeaderMap,
+    Extension(user): Extension<UserData>,
+    Json(body): Json<AcceptDocumentsRequest>,
+) -> Result<ErasedJson, AppError> {
+    let user_id = user.lock().unwrap().0.id;
+    let documents = db.check_legal_acceptance(&tenant.id, &body.documents).await?;
+    let accepted = AcceptedDocuments {
+        documents: &documents,
+        ip_address: conn_info.ip(),
+        user_agent: headers.get(header::USER_AGENT).and_then(|v| v.to_str().ok()),
+    };
+    db.record_legal_acceptances(&tenant.id, user_id, &accepted).await?;
+    Ok(json!({
+        "message": "Thank you for accepting the terms"
+    }))
+}
+
+pub async fn list_acceptances(
+    State(db): State<Arc<Db>>,
+    Extension(user): Extension<UserData>,
+) -> Result<ErasedJson, AppError> {
+    let user_id = user.lock().unwrap().0.id;
+    let acceptances = db
+        .list_legal_acceptances(user_id)
+        .await?
+        .iter()
+        .map(|a| {
+            serde_json::json!({
+                "kind": a.kind,
+                "version": a.version,
+                "accepted_at": a.accepted_at.to_string(),
+            })
+        })
+        .collect::<Vec<_>>();
+    Ok(json!({
+        "acceptances": acceptances,
+    }))
+}
@@ -1,27 +1,63 @@
 mod admin;
 mod auth;
 mod connection;
+mod events;
+mod legal;
+mod maintenance;
+mod metrics;
 mod middleware;
+mod organizations;
+mod outbox;
+mod probes;
 mod settings;
+mod state;
 mod stream_drop;
 mod user;
 mod user_data;
 
 pub use connection::ClientSocket;
+pub use state::AppState;
+use std::sync::Arc;
+use util::config::Config;
 
-/// main router for server routes
-pub async fn routes() -> axum::Router {
+/// main router for server routes, the background tasks of `state` start with it
+pub fn routes(state: AppState) -> axum::Router {
+    maintenance::spawn(
+        state.db.clone(),
+        state.clock.clone(),
+        state.config.clone(),
+        state.last_maintenance.clone(),
+    );
+    state.db.spawn_change_listener();
+
+    let app = axum::Router::new()
+        .merge(admin::admin_routes(&state))
+        .merge(auth::auth_routes(&state))
+        .merge(events::events_routes(&state))
+        .merge(legal::legal_routes(&state))
+        .merge(organizations::organizations_routes(&state))
+        .merge(settings::settings_routes(&state))
+        .merge(user::user_routes(&state));
+
+    // every route is also served under `/t/{tenant}` for tenants without their own host
     axum::Router::new()
-        .merge(admin::admin_routes().await)
-        .merge(auth::auth_routes().await)
-        .merge(settings::settings_routes().await)
-        .merge(user::user_routes().await)
+        .merge(app.clone())
+        .nest("/t/{tenant}", app)
+        .layer(axum::middleware::from_fn_with_state(state.db.clone(), middleware::tenant_middleware))
+        // the probes and metrics don't belong to a tenant, resolving one could need the database
+        .merge(metrics::metrics_routes(&state))
+        .merge(probes::probes_routes(&state))
+        .layer(axum::middleware::from_fn(middleware::metrics_middleware))
 }
 
-pub async fn get_custom_listener() -> stream_drop::CustomListener {
+/// binds the socket of `config`, the applications of the closed connections are dropped from `db`
+pub async fn get_custom_listener(
+    config: &Config,
+    db: Arc<database::Db>,
+) -> std::io::Result<stream_drop::CustomListener> {
     use axum::serve::Listener;
-    let listener = tokio::net::TcpListener::bind(std::env::var("SOCKET").unwrap()).await.unwrap();
-    let custom_listener = stream_drop::CustomListener::from(listener);
+    let listener = tokio::net::TcpListener::bind(config.socket).await?;
+    let custom_listener = stream_drop::CustomListener::new(listener, db);
     tracing::info!("[+] listening on {}", custom_listener.local_addr().unwrap());
-    custom_listener
+    Ok(custom_listener)
 }
@@ -0,0 +1,106 @@
+use database::Db;
+use std::{
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+use util::{age::AgePolicy, clock::Clock, config::Config};
+
+/// report of the last completed maintenance run (exposed in admin health metrics)
+pub(crate) type LastRun = Arc<Mutex<Option<MaintenanceReport>>>;
+
+#[derive(Clone, Debug, Default, serde::Serialize)]
+pub struct MaintenanceReport {
+    pub finished_at: u64,
+    pub duration_ms: u64,
+    pub expired_sessions: u64,
+    pub purged_deleted_users: u64,
+    pub pruned_registrants: u64,
+    pub pruned_event_channels: u64,
+    pub closed_impersonations: u64,
+    pub pruned_audit_events: u64,
+    pub expired_invitations: u64,
+    pub lifted_restrictions: u64,
+    pub errors: Vec<String>,
+}
+
+/// spawns the task that periodically removes stale data from the primary and in-memory database,
+/// the report of every run is stored in `last_run`
+pub(crate) fn spawn(db: Arc<Db>, clock: Arc<dyn Clock>, config: Arc<Config>, last_run: LastRun) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(config.maintenance.interval);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            let report = run(&db, clock.as_ref(), &config).await;
+            tracing::info!(
+                "[Maintenance] expired sessions: {}, purged deleted users: {}, pruned registrants: {}, took {}ms",
+                report.expired_sessions,
+                report.purged_deleted_users,
+                report.pruned_registrants,
+                report.duration_ms
+            );
+            *last_run.lock().unwrap() = Some(report);
+        }
+    });
+}
+
+async fn run(db: &Arc<Db>, clock: &dyn Clock, config: &Config) -> MaintenanceReport {
+    let start = Instant::now();
+    let mut report = MaintenanceReport::default();
+
+    match db.clear_all_expired_sessions(clock.now()).await {
+        Ok(count) => report.expired_sessions = count,
+        Err(e) => report.errors.push(format!("expired sessions: {e:?}")),
+    }
+
+    match db.close_finished_impersonations().await {
+        Ok(count) => report.closed_impersonations = count,
+        Err(e) => report.errors.push(format!("impersonations: {e:?}")),
+    }
+
+    let deleted_before = clock.now() - config.maintenance.deleted_users_retention;
+    match db.purge_deleted_users(deleted_before).await {
+        Ok(count) => report.purged_deleted_users = count,
+        Err(e) => report.errors.push(format!("deleted users: {e:?}")),
+    }
+
+    let occurred_before = clock.now() - config.maintenance.audit_retention;
+    match db.prune_audit_events(occurred_before).await {
+        Ok(count) => report.pruned_audit_events = count,
+        Err(e) => report.errors.push(format!("audit events: {e:?}")),
+    }
+
+    match db.prune_expired_invitations().await {
+        Ok(count) => report.expired_invitations = count,
+        Err(e) => report.errors.push(format!("invitations: {e:?}")),
+    }
+
+    match lift_age_restrictions(db, &config.age, clock.now()).await {
+        Ok(count) => report.lifted_restrictions = count,
+        Err(e) => report.errors.push(format!("age restrictions: {e:?}")),
+    }
+
+    report.pruned_registrants =
+        db.prune_unverified_registrants(config.maintenance.registrant_max_age);
+    report.pruned_event_channels = db.prune_event_channels();
+
+    report.duration_ms = start.elapsed().as_millis() as u64;
+    report.finished_at = clock.now().unix_timestamp() as u64;
+    report
+}
+
+/// lifts the restriction of the users who have come of age since they were restricted
+async fn lift_age_restrictions(
+    db: &Arc<Db>,
+    policy: &AgePolicy,
+    now: time::OffsetDateTime,
+) -> Result<u64, util::AppError> {
+    let mut count = 0;
+    for (user_id, birth_date, country) in db.list_restricted_users().await? {
+        if !policy.restricts(birth_date, country.as_deref(), now) {
+            db.set_user_restricted(user_id, false).await?;
+            count += 1;
+        }
+    }
+    Ok(count)
+}
@@ -0,0 +1,41 @@
+use axum::{
+    extract::State,
+    http::{HeaderMap, header},
+    response::IntoResponse,
+    routing::get,
+};
+use database::Db;
+use std::sync::Arc;
+use util::{AppError, config::Config};
+
+pub fn metrics_routes(state: &crate::AppState) -> axum::Router {
+    if state.config.metrics_token.is_none() {
+        tracing::warn!("[Metrics] /metrics is public, set METRICS_TOKEN to require a token");
+    }
+    axum::Router::new().route("/metrics", get(metrics_handler)).with_state(state.clone())
+}
+
+/// exposes the metrics of `util::metrics` in the prometheus text format
+pub async fn metrics_handler(
+    State(db): State<Arc<Db>>,
+    State(config): State<Arc<Config>>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, AppError> {
+    // the endpoint is public when no token is configured
+    if let Some(token) = config.metrics_token.as_deref() {
+        let bearer = headers
+            .get(header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "));
+        if !bearer.is_some_and(|v| constant_time_eq(v.as_bytes(), token.as_bytes())) {
+            return Err(AppError::Unauthorized("Invalid metrics token"));
+        }
+    }
+
+    db.observe_metrics();
+    Ok(([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], util::metrics::render()))
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
@@ -1,20 +1,55 @@
+use crate::outbox::Outbox;
 use axum::{
-    extract::{ConnectInfo, Request},
+    Extension,
+    extract::{ConnectInfo, Request, State},
+    http::HeaderMap,
     middleware::Next,
     response::{IntoResponse, Response},
 };
+use database::{
+    Db,
+    audit::{AuditEvent, AuditKind},
+    events::SessionEvent,
+    tenants::Tenant,
+};
+use std::{net::IpAddr, sync::Arc};
 use util::{
     AppError,
-    session::{ParsedSession, SessionStatus},
+    mail::MailKind,
+    session::{MismatchAction, ParsedSession, Session, SessionStatus},
 };
 
+/// the only route a session waiting for step-up authentication can access
+pub const STEP_UP_PATH: &str = "/api/settings/verify_password";
+
+/// the routes a user who has to accept new legal documents can still access
+pub const LEGAL_EXEMPT_PATHS: [&str; 4] =
+    ["/api/legal/accept", "/api/legal/acceptances", "/api/csrf", "/api/logout"];
+
+/// the routes a restricted account (under the age threshold of its country) can't access
+pub const RESTRICTED_PATH_PREFIXES: [&str; 1] = ["/api/organizations"];
+
+/// the cookies of a tenant are also sent to the other tenants served on the same host
+const OTHER_TENANT: AppError = AppError::Unauthorized("This session belongs to another tenant");
+
 pub async fn auth_middleware(
+    State(state): State<crate::AppState>,
     ConnectInfo(conn_info): ConnectInfo<crate::ClientSocket>,
+    Extension(tenant): Extension<Arc<Tenant>>,
     mut req: Request,
     next: Next,
 ) -> Result<Response, AppError> {
-    let parsed_session = ParsedSession::parse_and_verify_from_headers(req.headers())?;
-    let db = database::Db::new().await;
+    let config = state.config.clone();
+    // every expiry check of this request uses the same time
+    let now = state.clock.now();
+    let scope = tenant.cookie_scope();
+    let parsed_session = ParsedSession::parse_and_verify_from_headers(&config, scope, req.headers())
+        .map_err(|e| e.into_app_error(&config, scope))?;
+    let db = state.db.clone();
+    let user_agent = req
+        .headers()
+        .get(axum::http::header::USER_AGENT)
+        .map(|v| v.to_str().unwrap_or_default().to_owned());
 
     // Check if user is already in cache (found inside `Db::active`)
     if let Some((arc_wrapped, is_session_present)) = db.get_active_user(&parsed_session) {
@@ -24,30 +59,122 @@ pub async fn auth_middleware(
             let mut guard = arc_wrapped.lock().unwrap();
             guard.1.push(session);
         }
+
+        // enforcing idle timeout and absolute lifetime on the cached session
+        let (user_id, status, touched_at, mismatch, impersonated, refreshable) = {
+            let mut guard = arc_wrapped.lock().unwrap();
+            if guard.0.tenant_id != tenant.id {
+                return Err(OTHER_TENANT);
+            }
+            guard.0.status.ensure_active()?;
+            ensure_unrestricted(guard.0.restricted, req.uri().path())?;
+            let (user_id, bind_sessions) = (guard.0.id, guard.0.bind_sessions);
+            let email = guard.0.email.clone();
+            let session = guard
+                .1
+                .iter_mut()
+                .find(|s| s.unsigned_ssid == parsed_session.unsigned_ssid)
+                .ok_or(AppError::SessionExpired)?;
+            let status = session.session_status(&config.cookies, now);
+            let touched_at = (!matches!(status, SessionStatus::Invalid) && session.needs_touch(now))
+                .then_some(now);
+            if let Some(now) = touched_at {
+                session.last_used = now;
+            }
+            // checking whether the session is used by the client it is bound to
+            let mismatch = (config.binding.applies_to(bind_sessions)
+                && !config.binding.matches(session, conn_info.ip(), user_agent.as_deref()))
+            .then_some(email);
+            let refreshable =
+                matches!(status, SessionStatus::Expiring(_) | SessionStatus::Refreshable(_))
+                    .then(|| session.clone());
+            (user_id, status, touched_at, mismatch, session.impersonated_by.is_some(), refreshable)
+        };
+        if let SessionStatus::Invalid = status {
+            db.remove_active_user(&parsed_session);
+            db.remove_session(user_id, parsed_session.unsigned_ssid).await?;
+            let sessions = vec![parsed_session.unsigned_ssid];
+            db.publish_event(user_id, SessionEvent::SessionsRevoked { sessions });
+            return Err(AppError::InvalidSession(util::session::expire_session(&config, scope)));
+        }
+        if let Some(email) = mismatch {
+            let path = req.uri().path().to_owned();
+            on_binding_mismatch(
+                &state,
+                Outbox::new(&state, tenant.clone(), req.headers()),
+                user_id,
+                email,
+                &parsed_session,
+                conn_info.ip(),
+                user_agent,
+                &path,
+            )
+            .await?;
+        }
+        if let Some(session) = refreshable {
+            let (new_session, set_cookie_headermap) =
+                refresh(&state, now, scope, &session, user_id, req.headers(), *conn_info).await?;
+            // replacing the old session with the new session inside `Db::active`
+            let mut guard = arc_wrapped.lock().unwrap();
+            guard.1.retain(|s| s.unsigned_ssid != session.unsigned_ssid);
+            guard.1.push(new_session);
+            return Ok(set_cookie_headermap.into_response());
+        }
+        if let Some(last_used) = touched_at {
+            db.touch_session(parsed_session.unsigned_ssid, last_used).await?;
+        }
+        ensure_legal_accepted(&db, &tenant, user_id, impersonated, req.uri().path()).await?;
+
         req.extensions_mut().insert(parsed_session);
         req.extensions_mut().insert(arc_wrapped);
         return Ok(next.run(req).await);
     }
 
     // User not cached, fetch from database (not found inside `Db::active`)
-    let (user, session) = db.get_all_by_parsed_session(&parsed_session).await?;
+    let (user, mut session) = db.get_all_by_parsed_session(&parsed_session).await?;
+    if user.tenant_id != tenant.id {
+        return Err(OTHER_TENANT);
+    }
+    user.status.ensure_active()?;
+    ensure_unrestricted(user.restricted, req.uri().path())?;
+
+    // checking whether the session is used by the client it is bound to
+    if config.binding.applies_to(user.bind_sessions)
+        && !config.binding.matches(&session, conn_info.ip(), user_agent.as_deref())
+        && on_binding_mismatch(
+            &state,
+            Outbox::new(&state, tenant.clone(), req.headers()),
+            user.id,
+            user.email.clone(),
+            &parsed_session,
+            conn_info.ip(),
+            user_agent.clone(),
+            req.uri().path(),
+        )
+        .await?
+    {
+        session.ip_address = conn_info.ip();
+        session.user_agent = user_agent;
+    }
 
-    match session.session_status() {
+    match session.session_status(&config.cookies, now) {
         SessionStatus::Valid(_) => {
+            // recording that the session has been used
+            if session.needs_touch(now) {
+                session.last_used = now;
+                db.touch_session(session.unsigned_ssid, session.last_used).await?;
+            }
             // adding session and `User` to `Db::active`
+            let (user_id, impersonated) = (user.id, session.impersonated_by.is_some());
             let arc_wrapped = db.make_user_active(user, session);
+            ensure_legal_accepted(&db, &tenant, user_id, impersonated, req.uri().path()).await?;
             req.extensions_mut().insert(parsed_session);
             req.extensions_mut().insert(arc_wrapped);
         }
 
         SessionStatus::Expiring(_) | SessionStatus::Refreshable(_) => {
-            // automatic session refresh code block
-            let (new_session, _, set_cookie_headermap) =
-                util::session::create_session(user.id, req.headers(), *conn_info);
-
-            // replacing the old session with new session
-            db.add_session(user.id, new_session.clone()).await?;
-            db.remove_session(user.id, session.unsigned_ssid).await?;
+            let (new_session, set_cookie_headermap) =
+                refresh(&state, now, scope, &session, user.id, req.headers(), *conn_info).await?;
             db.make_user_active(user, new_session);
 
             // in the case of `Expiring` the new ssid will override the old one
@@ -55,11 +182,113 @@ pub async fn auth_middleware(
         }
 
         SessionStatus::Invalid => {
-            db.clear_expired_sessions(user.id).await?;
+            db.clear_expired_sessions(user.id, now).await?;
+            let sessions = vec![parsed_session.unsigned_ssid];
+            db.publish_event(user.id, SessionEvent::SessionsRevoked { sessions });
 
-            return Err(AppError::InvalidSession(util::session::expire_session()));
+            return Err(AppError::InvalidSession(util::session::expire_session(&config, scope)));
         }
     }
 
     Ok(next.run(req).await)
 }
+
+/// automatic session refresh (bounded by the absolute expiry of `session`), the new session
+/// replaces `session` in the primary database
+///
+/// returns the new session along with the headers setting its cookies
+async fn refresh(
+    state: &crate::AppState,
+    now: time::OffsetDateTime,
+    scope: Option<&str>,
+    session: &Session,
+    user_id: uuid::Uuid,
+    headers: &HeaderMap,
+    socket_addr: std::net::SocketAddr,
+) -> Result<(Session, HeaderMap), AppError> {
+    let (new_session, _, set_cookie_headermap) = util::session::refresh_session(
+        &state.config,
+        now,
+        scope,
+        session,
+        user_id,
+        headers,
+        socket_addr,
+    );
+
+    state.db.add_session(user_id, new_session.clone()).await?;
+    state.db.remove_session(user_id, session.unsigned_ssid).await?;
+    let event = AuditEvent::new(AuditKind::SessionRefreshed, Some(user_id))
+        .client(socket_addr.ip(), headers);
+    state.db.record_audit(event).await;
+    Ok((new_session, set_cookie_headermap))
+}
+
+/// gates the api until the user has accepted the current legal documents of the tenant
+///
+/// impersonation sessions aren't gated, only the user can accept the documents
+async fn ensure_legal_accepted(
+    db: &Arc<Db>,
+    tenant: &Tenant,
+    user_id: uuid::Uuid,
+    impersonated: bool,
+    path: &str,
+) -> Result<(), AppError> {
+    if impersonated || LEGAL_EXEMPT_PATHS.contains(&path) {
+        return Ok(());
+    }
+    let pending = db.pending_legal_documents(&tenant.id, user_id).await?;
+    if !pending.is_empty() {
+        return Err(AppError::LegalAcceptanceRequired(pending.iter().map(Into::into).collect()));
+    }
+    Ok(())
+}
+
+fn ensure_unrestricted(restricted: bool, path: &str) -> Result<(), AppError> {
+    if restricted && RESTRICTED_PATH_PREFIXES.iter().any(|prefix| path.starts_with(prefix)) {
+        return Err(AppError::Forbidden("This feature isn't available to restricted accounts"));
+    }
+    Ok(())
+}
+
+/// applies `Config::binding` to a session used by a client it isn't bound to
+///
+/// returns true if the session has been rebound to the new client
+#[allow(clippy::too_many_arguments)]
+async fn on_binding_mismatch(
+    state: &crate::AppState,
+    outbox: Outbox,
+    user_id: uuid::Uuid,
+    email: String,
+    parsed_session: &ParsedSession,
+    ip: IpAddr,
+    user_agent: Option<String>,
+    path: &str,
+) -> Result<bool, AppError> {
+    tracing::warn!(
+        "[Session Binding Mismatch] user_id: {user_id}, session_id: {}, ip: {ip}",
+        parsed_session.unsigned_ssid
+    );
+    let db = &state.db;
+    match state.config.binding.action {
+        MismatchAction::Reauthenticate => {
+            db.remove_active_user(parsed_session);
+            db.remove_session(user_id, parsed_session.unsigned_ssid).await?;
+            let sessions = vec![parsed_session.unsigned_ssid];
+            db.publish_event(user_id, SessionEvent::SessionsRevoked { sessions });
+            let scope = outbox.tenant().cookie_scope();
+            Err(AppError::InvalidSession(util::session::expire_session(&state.config, scope)))
+        }
+        // the session is rebound by `verify_password` once the password is confirmed
+        MismatchAction::StepUp if path == STEP_UP_PATH => Ok(false),
+        MismatchAction::StepUp => Err(AppError::StepUpRequired),
+        MismatchAction::Notify => {
+            let family = util::session::user_agent_family(user_agent.as_deref().unwrap_or_default());
+            db.rebind_session(user_id, parsed_session.unsigned_ssid, ip, user_agent).await?;
+            let ip = ip.to_string();
+            let vars = [("ip", ip.as_str()), ("client", family)];
+            outbox.send_later(email, MailKind::NewActivity, &vars);
+            Ok(true)
+        }
+    }
+}
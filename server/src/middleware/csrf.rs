@@ -0,0 +1,54 @@
+use axum::{
+    Extension,
+    extract::{Request, State},
+    http::{HeaderMap, header},
+    middleware::Next,
+    response::Response,
+};
+use database::tenants::Tenant;
+use std::sync::Arc;
+use util::{AppError, config::Config, session::ParsedSession};
+
+/// rejects state-changing requests of cookie authenticated clients without a valid csrf token
+///
+/// must be layered inside `auth_middleware` as it needs the `ParsedSession` of the request
+pub async fn csrf_middleware(
+    State(config): State<Arc<Config>>,
+    Extension(tenant): Extension<Arc<Tenant>>,
+    req: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    if req.method().is_safe() || is_bearer_client(req.headers()) {
+        return Ok(next.run(req).await);
+    }
+
+    let parsed_session =
+        req.extensions().get::<ParsedSession>().ok_or(AppError::Unauthorized("No session found"))?;
+    let header_token = req
+        .headers()
+        .get(util::session::CSRF_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .ok_or(AppError::InvalidCsrfToken)?;
+    let cookie_token =
+        util::session::csrf_token_from_cookies(&config, tenant.cookie_scope(), req.headers())
+            .ok_or(AppError::InvalidCsrfToken)?;
+
+    // double submit: the header must match the cookie and be signed for this session
+    if header_token != cookie_token
+        || !util::session::verify_csrf_token(&config, header_token, &parsed_session.unsigned_ssid)
+    {
+        return Err(AppError::InvalidCsrfToken);
+    }
+
+    Ok(next.run(req).await)
+}
+
+/// clients authenticating with a bearer token don't send cookies implicitly,
+/// so their requests can't be forged by another site
+fn is_bearer_client(headers: &HeaderMap) -> bool {
+    !headers.contains_key(header::COOKIE)
+        && headers
+            .get(header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| v.starts_with("Bearer "))
+}
@@ -0,0 +1,25 @@
+use axum::{extract::Request, middleware::Next, response::Response};
+use database::UserData;
+use util::{AppError, session::ParsedSession};
+
+/// rejects requests made with an impersonation session
+///
+/// must be layered inside `auth_middleware` as it needs the session of the request
+pub async fn deny_impersonation(req: Request, next: Next) -> Result<Response, AppError> {
+    if impersonated_by(&req).is_some() {
+        return Err(AppError::Forbidden("This action is not allowed while impersonating"));
+    }
+    Ok(next.run(req).await)
+}
+
+/// returns the id of the admin impersonating the user, if the request uses such a session
+pub fn impersonated_by(req: &Request) -> Option<uuid::Uuid> {
+    let parsed_session = req.extensions().get::<ParsedSession>()?;
+    let user = req.extensions().get::<UserData>()?;
+    let guard = user.lock().unwrap();
+    guard
+        .1
+        .iter()
+        .find(|s| s.unsigned_ssid == parsed_session.unsigned_ssid)
+        .and_then(|s| s.impersonated_by)
+}
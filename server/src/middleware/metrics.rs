@@ -0,0 +1,25 @@
+use axum::{
+    extract::{MatchedPath, Request},
+    middleware::Next,
+    response::Response,
+};
+use std::time::Instant;
+use util::metrics::{HTTP_REQUEST_DURATION, HTTP_REQUESTS};
+
+/// records the count and latency of requests per route and status
+///
+/// must be added with `Router::layer` so that it also runs for the fallback, whose requests are
+/// counted as `unmatched`
+pub async fn metrics_middleware(req: Request, next: Next) -> Response {
+    let start = Instant::now();
+    let method = req.method().clone();
+    let route = req.extensions().get::<MatchedPath>().map(|v| v.as_str().to_owned());
+
+    let res = next.run(req).await;
+
+    let status = res.status();
+    let labels = [method.as_str(), route.as_deref().unwrap_or("unmatched"), status.as_str()];
+    HTTP_REQUESTS.with_label_values(&labels).inc();
+    HTTP_REQUEST_DURATION.with_label_values(&labels).observe(start.elapsed().as_secs_f64());
+    res
+}
@@ -1,5 +1,13 @@
-mod admin;
 mod auth;
+mod csrf;
+mod impersonation;
+mod metrics;
+mod permission;
+mod tenant;
 
-pub use admin::admin_middleware;
 pub use auth::auth_middleware;
+pub use csrf::csrf_middleware;
+pub use impersonation::{deny_impersonation, impersonated_by};
+pub use metrics::metrics_middleware;
+pub use permission::permission_middleware;
+pub use tenant::{default_tenant_only, tenant_middleware};
@@ -0,0 +1,33 @@
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::Response,
+};
+use database::{Db, UserData};
+use std::sync::Arc;
+use util::{AppError, permission::Permission};
+
+/// allows the request only if the user has `permission` through their roles
+///
+/// must be layered inside `auth_middleware` with the database and the permission as state, e.g.
+/// `from_fn_with_state((db, Permission::HealthRead), permission_middleware)`
+pub async fn permission_middleware(
+    State((db, permission)): State<(Arc<Db>, Permission)>,
+    req: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    // impersonation sessions never get any permission, even if the impersonated user has them
+    if super::impersonated_by(&req).is_some() {
+        return Err(AppError::NotFound);
+    }
+    let Some(user_id) = req.extensions().get::<UserData>().map(|u| u.lock().unwrap().0.id) else {
+        return Err(AppError::NotFound);
+    };
+
+    if db.has_permission(user_id, permission).await? {
+        Ok(next.run(req).await)
+    } else {
+        // admin routes stay hidden from users without the permission
+        Err(AppError::NotFound)
+    }
+}
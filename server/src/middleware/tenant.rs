@@ -0,0 +1,65 @@
+use axum::{
+    Extension,
+    extract::{OriginalUri, Request, State},
+    http::{header, uri::Authority},
+    middleware::Next,
+    response::Response,
+};
+use database::{Db, tenants::Tenant};
+use std::sync::Arc;
+use util::AppError;
+
+/// path prefix that selects a tenant explicitly (`/t/{tenant}/api/...`)
+pub const TENANT_PREFIX: &str = "/t/";
+
+/// resolves the tenant of the request and inserts it as `Extension<Arc<Tenant>>`
+///
+/// the tenant is taken from the path prefix, then from the `Host` header,
+/// otherwise the default tenant serves the request
+pub async fn tenant_middleware(
+    State(db): State<Arc<Db>>,
+    mut req: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let path = match req.extensions().get::<OriginalUri>() {
+        Some(uri) => uri.path().to_owned(),
+        None => req.uri().path().to_owned(),
+    };
+
+    let tenant = if let Some(rest) = path.strip_prefix(TENANT_PREFIX) {
+        let id = rest.split('/').next().unwrap_or_default();
+        // an unknown prefix must not fall back to another tenant
+        db.get_tenant(id).await?.ok_or(AppError::NotFound)?
+    } else {
+        let host = req
+            .headers()
+            .get(header::HOST)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse::<Authority>().ok())
+            .or_else(|| req.uri().authority().cloned())
+            .map(|v| v.host().to_ascii_lowercase());
+        let tenant = match host {
+            Some(host) => db.get_tenant_by_host(&host).await?,
+            None => None,
+        };
+        match tenant {
+            Some(tenant) => tenant,
+            None => db.get_default_tenant().await?,
+        }
+    };
+
+    req.extensions_mut().insert(tenant);
+    Ok(next.run(req).await)
+}
+
+/// hides routes that change every tenant from the admins of the other tenants
+pub async fn default_tenant_only(
+    Extension(tenant): Extension<Arc<Tenant>>,
+    req: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    if !tenant.is_default() {
+        return Err(AppError::NotFound);
+    }
+    Ok(next.run(req).await)
+}
@@ -0,0 +1,98 @@
+use super::{OrgPath, parse_id};
+use crate::outbox::Outbox;
+use axum::{
+    Extension, Json,
+    extract::{Path, State},
+};
+use axum_extra::{json, response::ErasedJson};
+use database::{Db, UserData, organizations::OrgRole, tenants::Tenant};
+use serde::Deserialize;
+use std::{sync::Arc, time::Duration};
+use util::{AppError, clock::Clock, mail::MailKind};
+
+/// invitations that aren't answered in time have to be sent again
+const INVITATION_LIFETIME: Duration = Duration::from_secs(7 * 86400);
+
+// a struct so that the `{tenant}` of the prefixed routes is ignored
+#[derive(Deserialize)]
+pub struct InvitationPath {
+    id: String,
+}
+
+#[derive(Deserialize)]
+pub struct InviteMemberRequest {
+    email: String,
+    role: Option<String>,
+}
+
+pub async fn invite_member(
+    State(db): State<Arc<Db>>,
+    outbox: Outbox,
+    State(clock): State<Arc<dyn Clock>>,
+    Extension(user): Extension<UserData>,
+    Path(p): Path<OrgPath>,
+    Json(body): Json<InviteMemberRequest>,
+) -> Result<ErasedJson, AppError> {
+    let org_id = p.id()?;
+    let role = match &body.role {
+        Some(role) => OrgRole::try_from(role.as_str())?,
+        None => OrgRole::Member,
+    };
+    util::validation::is_email_valid(&body.email)?;
+    let (user_id, inviter) = {
+        let guard = user.lock().unwrap();
+        (guard.0.id, guard.0.display_name.clone())
+    };
+
+    let expires_at = clock.now() + INVITATION_LIFETIME;
+    let (id, org_name) = db.invite_member(org_id, user_id, &body.email, role, expires_at).await?;
+
+    // the invitation is also listed to the invitee, so it stands when the email can't be sent
+    outbox.send_later(
+        body.email.clone(),
+        MailKind::OrganizationInvite,
+        &[("organization", &org_name), ("inviter", &inviter), ("role", role.get_str())],
+    );
+
+    Ok(json!({
+        "id": id.to_string(),
+        "email": body.email,
+        "role": role.get_str(),
+        "expires_at": expires_at.to_string(),
+        "message": "The invitation has been sent",
+    }))
+}
+
+pub async fn accept_invitation(
+    State(db): State<Arc<Db>>,
+    Extension(tenant): Extension<Arc<Tenant>>,
+    Extension(user): Extension<UserData>,
+    Path(p): Path<InvitationPath>,
+) -> Result<ErasedJson, AppError> {
+    let id = parse_id(&p.id)?;
+    let (user_id, email) = {
+        let guard = user.lock().unwrap();
+        (guard.0.id, guard.0.email.clone())
+    };
+    let org_id = db.accept_invitation(id, &tenant.id, user_id, &email).await?;
+    let (org, role) = db.get_membership(org_id, user_id).await?;
+    Ok(json!({
+        "id": org.id.to_string(),
+        "name": org.name,
+        "role": role.get_str(),
+        "message": "You have joined the organization",
+    }))
+}
+
+pub async fn decline_invitation(
+    State(db): State<Arc<Db>>,
+    Extension(user): Extension<UserData>,
+    Path(p): Path<InvitationPath>,
+) -> Result<ErasedJson, AppError> {
+    let id = parse_id(&p.id)?;
+    let email = user.lock().unwrap().0.email.clone();
+    db.decline_invitation(id, &email).await?;
+    Ok(json!({
+        "message": "The invitation has been declined",
+    }))
+}
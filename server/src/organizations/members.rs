@@ -0,0 +1,152 @@
+use super::OrgPath;
+use axum::{
+    Extension, Json,
+    extract::{Path, State},
+};
+use axum_extra::{json, response::ErasedJson};
+use database::{Db, UserData, organizations::OrgRole, tenants::Tenant};
+use serde::Deserialize;
+use std::sync::Arc;
+use util::AppError;
+
+/// organizations of the user and the invitations they received
+pub async fn list_organizations(
+    State(db): State<Arc<Db>>,
+    Extension(tenant): Extension<Arc<Tenant>>,
+    Extension(user): Extension<UserData>,
+) -> Result<ErasedJson, AppError> {
+    let (user_id, email) = {
+        let guard = user.lock().unwrap();
+        (guard.0.id, guard.0.email.clone())
+    };
+    let organizations = db.list_user_organizations(user_id).await?;
+    let invitations = db.list_user_invitations(&tenant.id, &email).await?;
+    Ok(json!({
+        "organizations": organizations.iter().map(crate::user_data::membership).collect::<Vec<_>>(),
+        "invitations": invitations.iter().map(crate::user_data::invitation).collect::<Vec<_>>(),
+    }))
+}
+
+#[derive(Deserialize)]
+pub struct CreateOrganizationRequest {
+    name: String,
+}
+
+pub async fn create_organization(
+    State(db): State<Arc<Db>>,
+    Extension(tenant): Extension<Arc<Tenant>>,
+    Extension(user): Extension<UserData>,
+    Json(body): Json<CreateOrganizationRequest>,
+) -> Result<ErasedJson, AppError> {
+    let name = body.name.trim();
+    if name.is_empty() || name.chars().count() > 64 {
+        return Err(AppError::InvalidData("Organization names must be 1-64 characters long"));
+    }
+    let user_id = user.lock().unwrap().0.id;
+    let org = db.create_organization(&tenant.id, user_id, name).await?;
+    Ok(json!({
+        "id": org.id.to_string(),
+        "name": org.name,
+        "role": OrgRole::Owner.get_str(),
+        "created": org.created.to_string(),
+        "message": "The organization has been created",
+    }))
+}
+
+/// the organization with its members, admins also see the pending invitations
+pub async fn get_organization(
+    State(db): State<Arc<Db>>,
+    Extension(user): Extension<UserData>,
+    Path(p): Path<OrgPath>,
+) -> Result<ErasedJson, AppError> {
+    let org_id = p.id()?;
+    let user_id = user.lock().unwrap().0.id;
+    let (org, role) = db.get_membership(org_id, user_id).await?;
+    let members = db
+        .list_members(org_id)
+        .await?
+        .iter()
+        .map(|m| {
+            serde_json::json!({
+                "username": m.username,
+                "display_name": m.display_name,
+                "icon": m.icon,
+                "role": m.role.get_str(),
+                "joined_at": m.joined_at.to_string(),
+            })
+        })
+        .collect::<Vec<_>>();
+    let invitations = if role.can_manage() {
+        db.list_org_invitations(org_id).await?.iter().map(crate::user_data::invitation).collect()
+    } else {
+        vec![]
+    };
+    Ok(json!({
+        "id": org.id.to_string(),
+        "name": org.name,
+        "created": org.created.to_string(),
+        "role": role.get_str(),
+        "members": members,
+        "invitations": invitations,
+    }))
+}
+
+#[derive(Deserialize)]
+pub struct UpdateMemberRoleRequest {
+    username: String,
+    role: String,
+}
+
+pub async fn update_member_role(
+    State(db): State<Arc<Db>>,
+    Extension(user): Extension<UserData>,
+    Path(p): Path<OrgPath>,
+    Json(body): Json<UpdateMemberRoleRequest>,
+) -> Result<ErasedJson, AppError> {
+    let org_id = p.id()?;
+    let role = OrgRole::try_from(body.role.as_str())?;
+    let user_id = user.lock().unwrap().0.id;
+    db.update_member_role(org_id, user_id, &body.username, role).await?;
+    Ok(json!({
+        "username": body.username,
+        "role": role.get_str(),
+        "message": "The role has been updated",
+    }))
+}
+
+#[derive(Deserialize)]
+pub struct RemoveMemberRequest {
+    username: String,
+}
+
+pub async fn remove_member(
+    State(db): State<Arc<Db>>,
+    Extension(user): Extension<UserData>,
+    Path(p): Path<OrgPath>,
+    Json(body): Json<RemoveMemberRequest>,
+) -> Result<ErasedJson, AppError> {
+    let org_id = p.id()?;
+    let user_id = user.lock().unwrap().0.id;
+    db.remove_member(org_id, user_id, &body.username).await?;
+    Ok(json!({
+        "message": "The member has been removed",
+    }))
+}
+
+pub async fn leave_organization(
+    State(db): State<Arc<Db>>,
+    Extension(user): Extension<UserData>,
+    Path(p): Path<OrgPath>,
+) -> Result<ErasedJson, AppError> {
+    let org_id = p.id()?;
+    let user_id = user.lock().unwrap().0.id;
+    let deleted = db.leave_organization(org_id, user_id).await?;
+    Ok(json!({
+        "deleted": deleted,
+        "message": if deleted {
+            "You were the last member, the organization has been deleted"
+        } else {
+            "You have left the organization"
+        },
+    }))
+}
@@ -0,0 +1,40 @@
+use axum::routing::{get, post};
+
+mod invitations;
+mod members;
+
+#[rustfmt::skip]
+pub fn organizations_routes(state: &crate::AppState) -> axum::Router {
+    axum::Router::new()
+        .route("/api/organizations", get(members::list_organizations).post(members::create_organization))
+        .route("/api/organizations/{org}", get(members::get_organization))
+        .route("/api/organizations/{org}/invite", post(invitations::invite_member))
+        .route("/api/organizations/{org}/role", post(members::update_member_role))
+        .route("/api/organizations/{org}/remove", post(members::remove_member))
+        .route("/api/organizations/{org}/leave", post(members::leave_organization))
+        .route("/api/organizations/invitations/{id}/accept", post(invitations::accept_invitation))
+        .route("/api/organizations/invitations/{id}/decline", post(invitations::decline_invitation))
+        .route_layer(axum::middleware::from_fn_with_state(
+            state.config.clone(),
+            crate::middleware::csrf_middleware,
+        ))
+        .route_layer(axum::middleware::from_fn_with_state(state.clone(), crate::middleware::auth_middleware))
+        .with_state(state.clone())
+}
+
+/// path of the routes of a single organization, a struct so that the `{tenant}` of the prefixed
+/// routes is ignored
+#[derive(serde::Deserialize)]
+struct OrgPath {
+    org: String,
+}
+
+impl OrgPath {
+    fn id(&self) -> Result<uuid::Uuid, util::AppError> {
+        parse_id(&self.org)
+    }
+}
+
+fn parse_id(id: &str) -> Result<uuid::Uuid, util::AppError> {
+    uuid::Uuid::try_parse(id).map_err(|_| util::AppError::NotFound)
+}
@@ -0,0 +1,81 @@
+use axum::{
+    extract::{FromRef, FromRequestParts},
+    http::{HeaderMap, header, request::Parts},
+};
+use database::tenants::Tenant;
+use std::sync::Arc;
+use util::{
+    AppError,
+    config::Config,
+    mail::{MailKind, MailTemplates, Mailer},
+};
+
+/// sends the emails of a request, rendered with the templates of its tenant in the languages
+/// accepted by its client (`Accept-Language`)
+pub struct Outbox {
+    config: Arc<Config>,
+    mailer: Arc<dyn Mailer>,
+    templates: Arc<MailTemplates>,
+    tenant: Arc<Tenant>,
+    locales: Vec<String>,
+}
+
+impl Outbox {
+    pub fn new(state: &crate::AppState, tenant: Arc<Tenant>, headers: &HeaderMap) -> Self {
+        let (config, mailer) = (state.config.clone(), state.mailer.clone());
+        Self::from_parts(config, mailer, state.templates.clone(), tenant, headers)
+    }
+
+    /// the tenant the emails are sent for
+    pub fn tenant(&self) -> &Tenant {
+        &self.tenant
+    }
+
+    fn from_parts(
+        config: Arc<Config>,
+        mailer: Arc<dyn Mailer>,
+        templates: Arc<MailTemplates>,
+        tenant: Arc<Tenant>,
+        headers: &HeaderMap,
+    ) -> Self {
+        let header = headers.get(header::ACCEPT_LANGUAGE).and_then(|v| v.to_str().ok());
+        let locales = header.map(util::mail::accepted_locales).unwrap_or_default();
+        Self { config, mailer, templates, tenant, locales }
+    }
+
+    /// sends the email of `kind` to `to` and waits for its delivery
+    pub async fn send(
+        &self,
+        to: String,
+        kind: MailKind,
+        vars: &[(&str, &str)],
+    ) -> Result<(), AppError> {
+        let mail = self.tenant.mail(&self.config, &self.templates, &self.locales, kind, vars);
+        self.mailer.send(to, mail).await
+    }
+
+    /// sends the email of `kind` to `to` without waiting, for the notifications that mustn't
+    /// fail the request
+    pub fn send_later(&self, to: String, kind: MailKind, vars: &[(&str, &str)]) {
+        let mail = self.tenant.mail(&self.config, &self.templates, &self.locales, kind, vars);
+        let mailer = self.mailer.clone();
+        tokio::spawn(async move { mailer.send(to, mail).await });
+    }
+}
+
+impl<S> FromRequestParts<S> for Outbox
+where
+    S: Send + Sync,
+    Arc<Config>: FromRef<S>,
+    Arc<dyn Mailer>: FromRef<S>,
+    Arc<MailTemplates>: FromRef<S>,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        // inserted by `tenant_middleware`
+        let tenant = parts.extensions.get::<Arc<Tenant>>().cloned().ok_or(AppError::ServerError)?;
+        let (config, mailer) = (FromRef::from_ref(state), FromRef::from_ref(state));
+        Ok(Self::from_parts(config, mailer, FromRef::from_ref(state), tenant, &parts.headers))
+    }
+}
@@ -0,0 +1,95 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, routing::get};
+use axum_extra::json;
+use database::Db;
+use std::{
+    future::Future,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::sync::Mutex;
+use util::mail::Mailer;
+
+/// time after which a single readiness check counts as failed
+const CHECK_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// the last readiness result, held locked while checking so concurrent probes share one run
+pub(crate) type LastReadiness = Arc<Mutex<Option<(Instant, Readiness)>>>;
+
+#[derive(Clone)]
+pub(crate) struct Readiness {
+    ready: bool,
+    checks: serde_json::Value,
+}
+
+pub fn probes_routes(state: &crate::AppState) -> axum::Router {
+    axum::Router::new()
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz))
+        .with_state(state.clone())
+}
+
+/// liveness: the process is up and serving requests
+pub async fn healthz() -> impl IntoResponse {
+    json!({ "status": "ok" })
+}
+
+/// readiness: postgres (with migrations), the bucket and the mail transport are reachable
+pub async fn readyz(State(state): State<crate::AppState>) -> impl IntoResponse {
+    let mut last = state.last_readiness.lock().await;
+    let readiness = match &*last {
+        Some((checked_at, readiness))
+            if checked_at.elapsed() < state.config.readiness_cache_duration =>
+        {
+            readiness.clone()
+        }
+        _ => {
+            let readiness = check_dependencies(&state.db, state.mailer.as_ref()).await;
+            *last = Some((Instant::now(), readiness.clone()));
+            readiness
+        }
+    };
+    drop(last);
+
+    let status = if readiness.ready { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+    (
+        status,
+        json!({
+            "status": if readiness.ready { "ready" } else { "not_ready" },
+            "checks": readiness.checks,
+        }),
+    )
+}
+
+async fn check_dependencies(db: &Arc<Db>, mailer: &dyn Mailer) -> Readiness {
+    let (postgres, bucket, mail) = tokio::join!(
+        timed(db.check_database()),
+        timed(db.check_bucket()),
+        timed(async { mailer.test_connection().await.map_err(|_| "mail unreachable".into()) }),
+    );
+    let ready = [&postgres, &bucket, &mail].iter().all(|(ok, _)| *ok);
+
+    Readiness {
+        ready,
+        checks: serde_json::json!({
+            "postgres": postgres.1,
+            "bucket": bucket.1,
+            "mail": mail.1,
+        }),
+    }
+}
+
+/// runs `check` with `CHECK_TIMEOUT` and returns whether it passed along with its status
+async fn timed(check: impl Future<Output = Result<(), String>>) -> (bool, serde_json::Value) {
+    let start = Instant::now();
+    let result = tokio::time::timeout(CHECK_TIMEOUT, check)
+        .await
+        .unwrap_or_else(|_| Err("timed out".to_string()));
+    let duration_ms = start.elapsed().as_millis() as u64;
+
+    match result {
+        Ok(()) => (true, serde_json::json!({ "status": "ok", "duration_ms": duration_ms })),
+        Err(e) => {
+            (false, serde_json::json!({ "status": "error", "duration_ms": duration_ms, "error": e }))
+        }
+    }
+}
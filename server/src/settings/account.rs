@@ -1,17 +1,38 @@
-use axum::{Extension, extract::State};
+use axum::{
+    Extension,
+    extract::{ConnectInfo, State},
+    http::HeaderMap,
+};
 use axum_extra::{json, response::ErasedJson};
-use database::{Db, UserData};
+use database::{
+    Db, UserData,
+    audit::{AuditEvent, AuditKind},
+    events::SessionEvent,
+};
 use std::sync::Arc;
 use util::{AppError, session::ParsedSession};
 
 pub async fn delete_account(
     State(db): State<Arc<Db>>,
+    ConnectInfo(conn_info): ConnectInfo<crate::ClientSocket>,
+    headers: HeaderMap,
     Extension(parsed_session): Extension<ParsedSession>,
     Extension(user): Extension<UserData>,
 ) -> Result<ErasedJson, AppError> {
+    let user_id = user.lock().unwrap().0.id;
+    if db.is_sole_owner(user_id).await? {
+        return Err(AppError::BadReq(
+            "Transfer the ownership of your organizations before deleting your account",
+        ));
+    }
     db.remove_active_user(&parsed_session);
     let u = user.lock().unwrap().0.clone(); // this clone can be avoided
     db.delete_user(u).await?;
+    db.record_audit(
+        AuditEvent::new(AuditKind::AccountDeleted, Some(user_id)).client(conn_info.ip(), &headers),
+    )
+    .await;
+    db.publish_event(user_id, SessionEvent::AccountDeleted);
     Ok(json!({
         "message": "Your account has been deleted"
     }))
@@ -0,0 +1,39 @@
+use axum::{
+    Extension,
+    extract::{Query, State},
+};
+use axum_extra::{json, response::ErasedJson};
+use database::{Db, UserData, audit::AuditQuery};
+use std::sync::Arc;
+use util::AppError;
+
+const DEFAULT_PAGE_SIZE: i64 = 20;
+const MAX_PAGE_SIZE: i64 = 100;
+
+#[derive(serde::Deserialize)]
+pub struct ActivityQuery {
+    /// id of the last event of the previous page
+    before: Option<i64>,
+    limit: Option<i64>,
+}
+
+/// security relevant activity on the user's own account, newest first
+pub async fn account_activity(
+    State(db): State<Arc<Db>>,
+    Extension(user): Extension<UserData>,
+    Query(q): Query<ActivityQuery>,
+) -> Result<ErasedJson, AppError> {
+    let user_id = user.lock().unwrap().0.id;
+    let events = db
+        .list_audit_events(&AuditQuery {
+            user_id: Some(user_id),
+            before_id: q.before,
+            limit: q.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE),
+            ..Default::default()
+        })
+        .await?;
+    Ok(json!({
+        "events": events.iter().map(crate::user_data::audit_record).collect::<Vec<_>>(),
+        "next_before": events.last().map(|e| e.id),
+    }))
+}
@@ -0,0 +1,54 @@
+use axum::{
+    Extension, Json,
+    extract::{ConnectInfo, State},
+    http::HeaderMap,
+};
+use axum_extra::{json, response::ErasedJson};
+use database::{
+    Db, UserData,
+    audit::{AuditEvent, AuditKind},
+    tenants::Tenant,
+};
+use std::sync::Arc;
+use util::{AppError, config::Config, session::BindingMode};
+
+#[derive(serde::Deserialize)]
+pub struct UpdateSessionBindingRequest {
+    enabled: bool,
+    password: String,
+}
+
+pub async fn update_session_binding(
+    State(db): State<Arc<Db>>,
+    State(config): State<Arc<Config>>,
+    Extension(tenant): Extension<Arc<Tenant>>,
+    ConnectInfo(conn_info): ConnectInfo<crate::ClientSocket>,
+    headers: HeaderMap,
+    Extension(user): Extension<UserData>,
+    Json(body): Json<UpdateSessionBindingRequest>,
+) -> Result<ErasedJson, AppError> {
+    if config.binding.mode != BindingMode::OptIn {
+        return Err(AppError::BadReq("Session binding is managed by the server"));
+    }
+    db.verify_user_password(&user, &body.password).await?;
+    let (user_id, username) = {
+        let guard = user.lock().unwrap();
+        (guard.0.id, guard.0.username.clone())
+    };
+    db.update_session_binding(&tenant.id, &username, body.enabled).await?;
+    user.lock().unwrap().0.bind_sessions = body.enabled;
+    // the other servers drop their cached copy of the user, every client is told as well
+    db.notify_user_changed(user_id).await?;
+    let event = AuditEvent::new(AuditKind::SessionBindingChanged, Some(user_id))
+        .client(conn_info.ip(), &headers)
+        .details(serde_json::json!({ "enabled": body.enabled }));
+    db.record_audit(event).await;
+    Ok(json!({
+        "bind_sessions": body.enabled,
+        "message": if body.enabled {
+            "Your sessions are now bound to the device they were created on"
+        } else {
+            "Your sessions are no longer bound to a device"
+        }
+    }))
+}
@@ -1,13 +1,19 @@
-use crate::ClientSocket;
+use crate::{ClientSocket, outbox::Outbox};
 use axum::{
     Extension, Json,
     extract::{ConnectInfo, State},
+    http::HeaderMap,
 };
 use axum_extra::{json, response::ErasedJson};
-use database::{Db, UserData};
+use database::{
+    Db, UserData,
+    audit::{AuditEvent, AuditKind},
+    events::SessionEvent,
+    tenants::Tenant,
+};
 use serde::Deserialize;
 use std::sync::Arc;
-use util::{AppError, oauth::OAuthProvider};
+use util::{AppError, mail::MailKind, oauth::OAuthProvider};
 
 #[derive(Deserialize)]
 pub struct UpdateEmailRequest {
@@ -17,15 +23,15 @@ pub struct UpdateEmailRequest {
 
 pub async fn update_email(
     State(db): State<Arc<Db>>,
+    outbox: Outbox,
+    Extension(tenant): Extension<Arc<Tenant>>,
     ConnectInfo(conn_info): ConnectInfo<ClientSocket>,
     Extension(user): Extension<UserData>,
     Json(body): Json<UpdateEmailRequest>,
 ) -> Result<ErasedJson, AppError> {
+    db.verify_user_password(&user, &body.password).await?;
     let email = {
         let guard = user.lock().unwrap();
-        if guard.0.password.as_ref().is_none_or(|v| v != &body.password) {
-            return Err(AppError::PasswordMismatch);
-        }
         guard.0.email.clone()
     };
     // checking whether the new email is same as original email or not
@@ -33,20 +39,21 @@ pub async fn update_email(
         return Err(AppError::BadReq("Your new email cannot be same as of your original email"));
     }
     util::validation::is_email_valid(&body.new_email)?;
+    util::validation::is_email_domain_allowed(
+        &body.new_email,
+        &tenant.allowed_email_domains,
+        &tenant.denied_email_domains,
+    )?;
 
     let otp = util::generate::otp(&body.new_email);
-    tracing::info!("Email: {}, OTP: {}", email, otp);
 
     // adding an entry to database for further checking
-    db.request_email_update(*conn_info, email, body.new_email.clone(), otp.clone()).await?;
+    db.request_email_update(&tenant.id, *conn_info, email, body.new_email.clone(), otp.clone())
+        .await?;
+    util::metrics::VERIFICATION_CODES.with_label_values(&["email_update"]).inc();
 
     // sending mail to the new email for verification
-    util::mail::send(
-        body.new_email,
-        format!("{otp} is your {} verification code", &*util::SERVICE_NAME),
-        format!("Confirm your email address\n {otp}\n Thanks,\n {}", &*util::SERVICE_NAME),
-    )
-    .await?;
+    outbox.send(body.new_email, MailKind::VerificationCode, &[("code", &otp)]).await?;
 
     Ok(json!({
         "message": "Please verify your email",
@@ -61,12 +68,28 @@ pub struct VerifyEmailRequest {
 
 pub async fn verify_email(
     State(db): State<Arc<Db>>,
+    outbox: Outbox,
+    Extension(tenant): Extension<Arc<Tenant>>,
+    ConnectInfo(conn_info): ConnectInfo<ClientSocket>,
+    headers: HeaderMap,
     Extension(user): Extension<UserData>,
     Json(body): Json<VerifyEmailRequest>,
 ) -> Result<ErasedJson, AppError> {
-    let old_email = user.lock().unwrap().0.email.clone();
-    db.update_email(&old_email, body.new_email.clone(), &body.otp).await?;
+    let (user_id, old_email) = {
+        let guard = user.lock().unwrap();
+        (guard.0.id, guard.0.email.clone())
+    };
+    db.update_email(&tenant.id, &old_email, body.new_email.clone(), &body.otp).await?;
     user.lock().unwrap().0.email = body.new_email.clone();
+    let event = AuditEvent::new(AuditKind::EmailChanged, Some(user_id))
+        .client(conn_info.ip(), &headers)
+        .details(serde_json::json!({ "old_email": old_email, "new_email": body.new_email }));
+    db.record_audit(event).await;
+    db.publish_event(user_id, SessionEvent::EmailChanged { email: body.new_email.clone() });
+
+    // the previous address is told about the change, which is already done if it fails
+    let vars = [("email", old_email.as_str()), ("new_email", body.new_email.as_str())];
+    outbox.send_later(old_email.clone(), MailKind::EmailChanged, &vars);
     Ok(json!({
         "email": body.new_email,
         "message": "Your email has been verified",
@@ -75,15 +98,25 @@ pub async fn verify_email(
 
 pub async fn connect_email(
     State(db): State<Arc<Db>>,
+    Extension(tenant): Extension<Arc<Tenant>>,
+    ConnectInfo(conn_info): ConnectInfo<ClientSocket>,
+    headers: HeaderMap,
     Extension(user): Extension<UserData>,
 ) -> Result<ErasedJson, AppError> {
-    let email = user.lock().unwrap().0.email.clone();
+    let (user_id, email) = {
+        let guard = user.lock().unwrap();
+        (guard.0.id, guard.0.email.clone())
+    };
     let domain = unsafe { email.split('@').next_back().unwrap_unchecked() };
     let provider = OAuthProvider::from_domain(domain);
     match provider {
         OAuthProvider::Google => {
-            db.update_oauth_provider(&email, provider).await?;
+            db.update_oauth_provider(&tenant.id, &email, provider).await?;
             user.lock().unwrap().0.oauth_provider = provider;
+            let event = AuditEvent::new(AuditKind::OidcLinked, Some(user_id))
+                .client(conn_info.ip(), &headers)
+                .details(serde_json::json!({ "provider": provider.get_str() }));
+            db.record_audit(event).await;
             Ok(json!({
                 "oauth_provider": provider.get_str(),
                 "message": format!("Your email is now connected with {}", provider.get_str()),
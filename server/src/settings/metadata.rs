@@ -1,8 +1,8 @@
 use axum::{Extension, Json, extract::State};
 use axum_extra::{json, response::ErasedJson};
-use database::{Db, UserData};
+use database::{Db, UserData, events::SessionEvent, tenants::Tenant};
 use std::sync::Arc;
-use util::AppError;
+use util::{AppError, clock::Clock, config::Config};
 
 #[derive(serde::Deserialize)]
 pub struct UpdateLegalNameRequest {
@@ -11,13 +11,19 @@ pub struct UpdateLegalNameRequest {
 
 pub async fn update_legal_name(
     State(db): State<Arc<Db>>,
+    Extension(tenant): Extension<Arc<Tenant>>,
     Extension(user): Extension<UserData>,
     Json(body): Json<UpdateLegalNameRequest>,
 ) -> Result<ErasedJson, AppError> {
     util::validation::is_legal_name_valid(&body.legal_name)?;
     let username = user.lock().unwrap().0.username.clone();
-    db.update_legal_name(&username, &body.legal_name).await?;
-    user.lock().unwrap().0.legal_name = Some(body.legal_name.clone());
+    db.update_legal_name(&tenant.id, &username, &body.legal_name).await?;
+    let user_id = {
+        let mut guard = user.lock().unwrap();
+        guard.0.legal_name = Some(body.legal_name.clone());
+        guard.0.id
+    };
+    db.publish_event(user_id, SessionEvent::ProfileChanged);
     Ok(json!({
         "legal_name": body.legal_name,
         "message": "Your legal name has been updated"
@@ -25,7 +31,7 @@ pub async fn update_legal_name(
 }
 
 #[derive(serde::Deserialize)]
-pub struct UpdateBirthDateRequest {
+pub struct BirthDateRequest {
     year: u32,
     month: u8,
     day: u8,
@@ -34,20 +40,54 @@ pub struct UpdateBirthDateRequest {
     offset_seconds: i8,
 }
 
+impl BirthDateRequest {
+    pub fn birth_date(&self) -> Result<time::OffsetDateTime, AppError> {
+        let offset =
+            time::UtcOffset::from_hms(self.offset_hours, self.offset_minutes, self.offset_seconds)
+                .map_err(|_| AppError::InvalidData("Invalid UTC Offset"))?;
+        util::validation::is_birth_date_valid(self.year, self.month, self.day, offset)
+    }
+}
+
 pub async fn update_birth_date(
     State(db): State<Arc<Db>>,
+    State(config): State<Arc<Config>>,
+    State(clock): State<Arc<dyn Clock>>,
+    Extension(tenant): Extension<Arc<Tenant>>,
     Extension(user): Extension<UserData>,
-    Json(body): Json<UpdateBirthDateRequest>,
+    Json(body): Json<BirthDateRequest>,
 ) -> Result<ErasedJson, AppError> {
-    let offset =
-        time::UtcOffset::from_hms(body.offset_hours, body.offset_minutes, body.offset_seconds)
-            .map_err(|_| AppError::InvalidData("Invalid UTC Offset"))?;
-    let birth_date = util::validation::is_birth_date_valid(body.year, body.month, body.day, offset)?;
-    let username = user.lock().unwrap().0.username.clone();
-    db.update_birth_date(&username, birth_date).await?;
-    user.lock().unwrap().0.birth_date = Some(birth_date);
+    let birth_date = body.birth_date()?;
+    let (username, country, was_restricted) = {
+        let guard = user.lock().unwrap();
+        (guard.0.username.clone(), guard.0.country.clone(), guard.0.restricted)
+    };
+    let restricted = config.age.restricts(birth_date, country.as_deref(), clock.now());
+    // otherwise lifting the restriction would only be a request away
+    if was_restricted && !restricted {
+        return Err(AppError::Forbidden(
+            "Restricted accounts can't change their birth date, please contact support",
+        ));
+    }
+    db.update_birth_date(
+        &tenant.id,
+        &username,
+        birth_date,
+        restricted,
+        config.age.max_birth_date_changes,
+    )
+    .await?;
+
+    let mut guard = user.lock().unwrap();
+    if guard.0.birth_date.is_some() {
+        guard.0.birth_date_changes += 1;
+    }
+    guard.0.birth_date = Some(birth_date);
+    guard.0.restricted |= restricted;
+    db.publish_event(guard.0.id, SessionEvent::ProfileChanged);
     Ok(json!({
         "birth_date": birth_date.to_string(),
+        "restricted": guard.0.restricted,
         "message": "Your birth date has been updated"
     }))
 }
@@ -59,13 +99,19 @@ pub struct UpdateGenderRequest {
 
 pub async fn update_gender(
     State(db): State<Arc<Db>>,
+    Extension(tenant): Extension<Arc<Tenant>>,
     Extension(user): Extension<UserData>,
     Json(body): Json<UpdateGenderRequest>,
 ) -> Result<ErasedJson, AppError> {
     util::validation::is_gender_valid(&body.gender)?;
     let username = user.lock().unwrap().0.username.clone();
-    db.update_gender(&username, &body.gender).await?;
-    user.lock().unwrap().0.gender = Some(body.gender.clone());
+    db.update_gender(&tenant.id, &username, &body.gender).await?;
+    let user_id = {
+        let mut guard = user.lock().unwrap();
+        guard.0.gender = Some(body.gender.clone());
+        guard.0.id
+    };
+    db.publish_event(user_id, SessionEvent::ProfileChanged);
     Ok(json!({
         "gender": body.gender,
         "message": "Your gender has been updated"
@@ -79,15 +125,29 @@ pub struct UpdateCountryRequest {
 
 pub async fn update_country(
     State(db): State<Arc<Db>>,
+    State(config): State<Arc<Config>>,
+    State(clock): State<Arc<dyn Clock>>,
+    Extension(tenant): Extension<Arc<Tenant>>,
     Extension(user): Extension<UserData>,
     Json(body): Json<UpdateCountryRequest>,
 ) -> Result<ErasedJson, AppError> {
     let country = util::validation::is_country_valid(&body.country)?;
-    let username = user.lock().unwrap().0.username.clone();
-    db.update_country(&username, &country).await?;
-    user.lock().unwrap().0.country = Some(country.clone());
+    let (username, birth_date) = {
+        let guard = user.lock().unwrap();
+        (guard.0.username.clone(), guard.0.birth_date)
+    };
+    // moving to a country with stricter rules restricts the account, the opposite doesn't lift it
+    let restricted =
+        birth_date.is_some_and(|v| config.age.restricts(v, Some(&country), clock.now()));
+    db.update_country(&tenant.id, &username, &country, restricted).await?;
+
+    let mut guard = user.lock().unwrap();
+    guard.0.country = Some(country.clone());
+    guard.0.restricted |= restricted;
+    db.publish_event(guard.0.id, SessionEvent::ProfileChanged);
     Ok(json!({
         "country": country,
+        "restricted": guard.0.restricted,
         "message": "Your country has been updated"
     }))
 }
@@ -1,21 +1,29 @@
-use axum::routing::{get, post};
+use crate::middleware::deny_impersonation;
+use axum::{
+    middleware::from_fn,
+    routing::{get, post},
+};
 
 mod account;
+mod activity;
+mod binding;
 mod email;
 mod metadata;
 mod password;
 mod phone;
 mod username;
 
+pub(crate) use metadata::BirthDateRequest;
+
 #[rustfmt::skip]
-pub async fn settings_routes() -> axum::Router {
+pub fn settings_routes(state: &crate::AppState) -> axum::Router {
     axum::Router::new()
         .route("/api/settings", get(fetch_settings))
-        .route("/api/settings/email", post(email::update_email))
-        .route("/api/settings/verify_email", post(email::verify_email))
-        .route("/api/settings/connect_email", post(email::connect_email))
+        .route("/api/settings/email", post(email::update_email).layer(from_fn(deny_impersonation)))
+        .route("/api/settings/verify_email", post(email::verify_email).layer(from_fn(deny_impersonation)))
+        .route("/api/settings/connect_email", post(email::connect_email).layer(from_fn(deny_impersonation)))
         .route("/api/settings/username", post(username::update_username))
-        .route("/api/settings/password", post(password::update_password))
+        .route("/api/settings/password", post(password::update_password).layer(from_fn(deny_impersonation)))
         .route("/api/settings/verify_password", post(password::verify_password))
         .route("/api/settings/legal_name", post(metadata::update_legal_name))
         .route("/api/settings/birth_date", post(metadata::update_birth_date))
@@ -23,16 +31,36 @@ pub async fn settings_routes() -> axum::Router {
         .route("/api/settings/phone", post(phone::update_phone))
         .route("/api/settings/verify_phone", post(phone::verify_phone))
         .route("/api/settings/country", post(metadata::update_country))
-        .route("/api/settings/delete_account", post(account::delete_account))
-        .layer(axum::middleware::from_fn(crate::middleware::auth_middleware))
+        .route("/api/settings/activity", get(activity::account_activity))
+        .route("/api/settings/session_binding", post(binding::update_session_binding))
+        .route("/api/settings/delete_account", post(account::delete_account).layer(from_fn(deny_impersonation)))
+        .route_layer(axum::middleware::from_fn_with_state(
+            state.config.clone(),
+            crate::middleware::csrf_middleware,
+        ))
+        .route_layer(axum::middleware::from_fn_with_state(state.clone(), crate::middleware::auth_middleware))
         .route("/api/settings/is_username_available", get(username::validate_username))
-        .with_state(database::Db::new().await)
+        .with_state(state.clone())
 }
 
 pub async fn fetch_settings(
+    axum::extract::State(db): axum::extract::State<std::sync::Arc<database::Db>>,
+    axum::extract::State(config): axum::extract::State<std::sync::Arc<util::config::Config>>,
+    axum::Extension(tenant): axum::Extension<std::sync::Arc<database::tenants::Tenant>>,
+    axum::Extension(parsed_session): axum::Extension<util::session::ParsedSession>,
     axum::Extension(user): axum::Extension<database::UserData>,
-) -> axum_extra::response::ErasedJson {
-    let guard = user.lock().unwrap();
+) -> Result<impl axum::response::IntoResponse, util::AppError> {
+    // every settings fetch hands out a fresh csrf token for the following mutations
+    let (_, set_cookie_headermap) =
+        util::session::issue_csrf_token(&config, tenant.cookie_scope(), &parsed_session);
+    let user_id = user.lock().unwrap().0.id;
+    let organizations = db.list_user_organizations(user_id).await?;
 
-    crate::user_data::arrange(&guard.0, &guard.1)
+    let mut body = {
+        let guard = user.lock().unwrap();
+        crate::user_data::user_json(&guard.0, &guard.1)
+    };
+    body["organizations"] =
+        organizations.iter().map(crate::user_data::membership).collect::<Vec<_>>().into();
+    Ok((set_cookie_headermap, axum_extra::response::ErasedJson::new(body)))
 }
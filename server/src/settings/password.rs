@@ -1,8 +1,16 @@
-use axum::{Extension, Json, extract::State};
+use axum::{
+    Extension, Json,
+    extract::{ConnectInfo, State},
+    http::{HeaderMap, header},
+};
 use axum_extra::{json, response::ErasedJson};
-use database::{Db, UserData};
+use database::{
+    Db, UserData,
+    audit::{AuditEvent, AuditKind},
+    tenants::Tenant,
+};
 use std::sync::Arc;
-use util::AppError;
+use util::{AppError, config::Config, session::ParsedSession};
 
 #[derive(serde::Deserialize)]
 pub struct UpdatePasswordRequest {
@@ -12,19 +20,24 @@ pub struct UpdatePasswordRequest {
 
 pub async fn update_password(
     State(db): State<Arc<Db>>,
+    Extension(tenant): Extension<Arc<Tenant>>,
+    ConnectInfo(conn_info): ConnectInfo<crate::ClientSocket>,
+    headers: HeaderMap,
     Extension(user): Extension<UserData>,
     Json(body): Json<UpdatePasswordRequest>,
 ) -> Result<ErasedJson, AppError> {
-    let email = {
+    db.verify_user_password(&user, &body.old_password).await?;
+    let (user_id, email) = {
         let guard = user.lock().unwrap();
-        if guard.0.password.as_ref().is_none_or(|v| v != &body.old_password) {
-            return Err(AppError::PasswordMismatch);
-        }
-        guard.0.email.clone()
+        (guard.0.id, guard.0.email.clone())
     };
     util::validation::is_password_strong(&body.new_password)?;
-    db.update_password(&email, &body.new_password).await?;
+    db.update_password(&tenant.id, &email, &body.new_password).await?;
     user.lock().unwrap().0.password = Some(body.new_password);
+    db.record_audit(
+        AuditEvent::new(AuditKind::PasswordChanged, Some(user_id)).client(conn_info.ip(), &headers),
+    )
+    .await;
     Ok(json!({
         "message": "Your password has been changed"
     }))
@@ -36,15 +49,40 @@ pub struct VerifyPasswordRequest {
 }
 
 pub async fn verify_password(
+    State(db): State<Arc<Db>>,
+    State(config): State<Arc<Config>>,
+    ConnectInfo(conn_info): ConnectInfo<crate::ClientSocket>,
+    Extension(parsed_session): Extension<ParsedSession>,
     Extension(user): Extension<UserData>,
+    headers: HeaderMap,
     Json(body): Json<VerifyPasswordRequest>,
 ) -> Result<ErasedJson, AppError> {
-    let guard = user.lock().unwrap();
-    if guard.0.password.as_ref().is_none_or(|v| v != &body.password) {
-        Err(AppError::PasswordMismatch)
-    } else {
-        Ok(json!({
-            "success": "Password correct"
-        }))
+    let user_agent = headers.get(header::USER_AGENT).map(|v| v.to_str().unwrap_or_default());
+    db.verify_user_password(&user, &body.password).await?;
+    let (user_id, rebind) = {
+        let guard = user.lock().unwrap();
+        // a confirmed password completes the step-up of a session used by a new client
+        let rebind = config.binding.applies_to(guard.0.bind_sessions)
+            && guard.1.iter().any(|s| {
+                s.unsigned_ssid == parsed_session.unsigned_ssid
+                    && !config.binding.matches(s, conn_info.ip(), user_agent)
+            });
+        (guard.0.id, rebind)
+    };
+    if rebind {
+        db.rebind_session(
+            user_id,
+            parsed_session.unsigned_ssid,
+            conn_info.ip(),
+            user_agent.map(str::to_owned),
+        )
+        .await?;
+        let event = AuditEvent::new(AuditKind::SessionBindingChanged, Some(user_id))
+            .client(conn_info.ip(), &headers)
+            .details(serde_json::json!({ "rebound": parsed_session.unsigned_ssid.to_string() }));
+        db.record_audit(event).await;
     }
+    Ok(json!({
+        "success": "Password correct"
+    }))
 }
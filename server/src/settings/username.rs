@@ -1,6 +1,15 @@
-use axum::{Extension, Json, extract::State};
+use axum::{
+    Extension, Json,
+    extract::{ConnectInfo, State},
+    http::HeaderMap,
+};
 use axum_extra::{json, response::ErasedJson};
-use database::{Db, UserData};
+use database::{
+    Db, UserData,
+    audit::{AuditEvent, AuditKind},
+    events::SessionEvent,
+    tenants::Tenant,
+};
 use std::sync::Arc;
 use util::AppError;
 
@@ -12,15 +21,16 @@ pub struct UpdateUsernameRequest {
 
 pub async fn update_username(
     State(db): State<Arc<Db>>,
+    Extension(tenant): Extension<Arc<Tenant>>,
+    ConnectInfo(conn_info): ConnectInfo<crate::ClientSocket>,
+    headers: HeaderMap,
     Extension(user): Extension<UserData>,
     Json(body): Json<UpdateUsernameRequest>,
 ) -> Result<ErasedJson, AppError> {
-    let username = {
+    db.verify_user_password(&user, &body.password).await?;
+    let (user_id, username) = {
         let guard = user.lock().unwrap();
-        if guard.0.password.as_ref().is_none_or(|v| v != &body.password) {
-            return Err(AppError::PasswordMismatch);
-        }
-        guard.0.username.clone()
+        (guard.0.id, guard.0.username.clone())
     };
     // checking if the new username is valid or not
     util::validation::is_username_valid(&body.new_username)?;
@@ -31,8 +41,13 @@ pub async fn update_username(
         ));
     }
     // updating username in the primary database
-    db.check_and_update_username(&username, &body.new_username).await?;
+    db.check_and_update_username(&tenant.id, &username, &body.new_username).await?;
     user.lock().unwrap().0.username = body.new_username.clone();
+    let event = AuditEvent::new(AuditKind::UsernameChanged, Some(user_id))
+        .client(conn_info.ip(), &headers)
+        .details(serde_json::json!({ "old_username": username, "new_username": body.new_username }));
+    db.record_audit(event).await;
+    db.publish_event(user_id, SessionEvent::UsernameChanged { username: body.new_username.clone() });
     Ok(json!({
         "username": body.new_username,
         "message": "Your username has been updated"
@@ -46,6 +61,7 @@ pub struct ValidateUsernameRequest {
 
 pub async fn validate_username(
     State(db): State<Arc<Db>>,
+    Extension(tenant): Extension<Arc<Tenant>>,
     Extension(user): Extension<UserData>,
     Json(body): Json<ValidateUsernameRequest>,
 ) -> Result<ErasedJson, AppError> {
@@ -58,6 +74,6 @@ pub async fn validate_username(
             "Your new username cannot be same as of your original username",
         ));
     }
-    db.is_username_available(&username).await?;
+    db.is_username_available(&tenant.id, &username).await?;
     Ok(json!({ "available": true }))
 }
@@ -0,0 +1,91 @@
+use axum::extract::FromRef;
+use database::{Db, bucket::BlackBlazeB2};
+use std::sync::Arc;
+use util::{
+    clock::{Clock, SystemClock},
+    config::Config,
+    mail::{MailTemplates, Mailer},
+};
+
+/// everything the routes depend on, built by the binary and given to `crate::routes`
+///
+/// handlers extract the parts they need, e.g. `State(db): State<Arc<Db>>`
+#[derive(Clone)]
+pub struct AppState {
+    pub db: Arc<Db>,
+    pub mailer: Arc<dyn Mailer>,
+    pub storage: Arc<BlackBlazeB2>,
+    pub config: Arc<Config>,
+    pub clock: Arc<dyn Clock>,
+    pub templates: Arc<MailTemplates>,
+    pub(crate) admin: crate::admin::AdminState,
+    /// report of the last maintenance run
+    pub(crate) last_maintenance: crate::maintenance::LastRun,
+    /// cached readiness result of `/readyz`
+    pub(crate) last_readiness: crate::probes::LastReadiness,
+}
+
+impl AppState {
+    /// `db` has to store its files in `storage`, the clock is the system one and the emails are
+    /// rendered with the built-in templates
+    pub fn new(
+        db: Arc<Db>,
+        mailer: Arc<dyn Mailer>,
+        storage: Arc<BlackBlazeB2>,
+        config: Arc<Config>,
+    ) -> Self {
+        Self {
+            db,
+            mailer,
+            storage,
+            config,
+            clock: Arc::new(SystemClock),
+            templates: Arc::new(MailTemplates::default()),
+            admin: crate::admin::AdminState::default(),
+            last_maintenance: Default::default(),
+            last_readiness: Default::default(),
+        }
+    }
+
+    /// replaces the clock used by the routes
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// replaces the templates of the emails sent by the routes
+    pub fn with_templates(mut self, templates: Arc<MailTemplates>) -> Self {
+        self.templates = templates;
+        self
+    }
+}
+
+impl FromRef<AppState> for Arc<Db> {
+    fn from_ref(state: &AppState) -> Self {
+        state.db.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<dyn Mailer> {
+    fn from_ref(state: &AppState) -> Self {
+        state.mailer.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<Config> {
+    fn from_ref(state: &AppState) -> Self {
+        state.config.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<MailTemplates> {
+    fn from_ref(state: &AppState) -> Self {
+        state.templates.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<dyn Clock> {
+    fn from_ref(state: &AppState) -> Self {
+        state.clock.clone()
+    }
+}
@@ -1,6 +1,8 @@
+use database::Db;
+use std::sync::Arc;
 use tokio::net::TcpListener;
 
-pub struct CustomStream(tokio::net::TcpStream);
+pub struct CustomStream(tokio::net::TcpStream, Arc<Db>);
 
 impl tokio::io::AsyncRead for CustomStream {
     fn poll_read(
@@ -43,14 +45,11 @@ impl Drop for CustomStream {
             Err(_) => return, // might cause problem
         };
 
-        tokio::spawn(async move {
-            let db = database::Db::new().await;
-            db.drop_application(&socket_addr);
-        });
+        self.1.drop_application(&socket_addr);
     }
 }
 
-pub struct CustomListener(TcpListener);
+pub struct CustomListener(TcpListener, Arc<Db>);
 
 impl axum::serve::Listener for CustomListener {
     type Io = CustomStream;
@@ -58,7 +57,7 @@ impl axum::serve::Listener for CustomListener {
 
     async fn accept(&mut self) -> (Self::Io, Self::Addr) {
         let (io, addr) = self.0.accept().await.unwrap();
-        (CustomStream(io), addr)
+        (CustomStream(io, self.1.clone()), addr)
     }
 
     fn local_addr(&self) -> tokio::io::Result<Self::Addr> {
@@ -66,8 +65,8 @@ impl axum::serve::Listener for CustomListener {
     }
 }
 
-impl From<TcpListener> for CustomListener {
-    fn from(value: TcpListener) -> Self {
-        Self(value)
+impl CustomListener {
+    pub fn new(listener: TcpListener, db: Arc<Db>) -> Self {
+        Self(listener, db)
     }
 }
@@ -3,22 +3,29 @@ use axum::{
     extract::{Multipart, Path, State},
 };
 use axum_extra::{json, response::ErasedJson};
-use database::{Db, users::User};
-use std::sync::{Arc, Mutex};
+use database::{Db, UserData, events::SessionEvent, tenants::Tenant};
+use std::sync::Arc;
 use util::AppError;
 
+// a struct so that the `{tenant}` of the prefixed routes is ignored
+#[derive(serde::Deserialize)]
+pub struct ProfilePath {
+    id: String,
+}
+
 pub async fn get_user_profile(
     State(db): State<Arc<Db>>,
-    Extension(user): Extension<Arc<Mutex<User>>>,
-    Path(p): Path<String>,
+    Extension(tenant): Extension<Arc<Tenant>>,
+    Extension(user): Extension<UserData>,
+    Path(p): Path<ProfilePath>,
 ) -> Result<ErasedJson, AppError> {
     let res = {
         let guard = user.lock().unwrap();
-        if guard.username == p {
+        if guard.0.username == p.id {
             Some(json!({
-                "username": guard.username.clone(),
-                "display_name": guard.display_name.clone(),
-                "bio": guard.bio.clone(),
+                "username": guard.0.username.clone(),
+                "display_name": guard.0.display_name.clone(),
+                "bio": guard.0.bio.clone(),
             }))
         } else {
             None
@@ -28,7 +35,7 @@ pub async fn get_user_profile(
     if let Some(res) = res {
         Ok(res)
     } else {
-        let u = db.get_user_by_username(&p).await?;
+        let u = db.get_user_by_username(&tenant.id, &p.id).await?;
         Ok(json!({
             "username": u.username,
             "display_name": u.display_name,
@@ -39,13 +46,15 @@ pub async fn get_user_profile(
 
 pub async fn update_profile(
     State(db): State<Arc<Db>>,
-    Extension(user): Extension<Arc<Mutex<User>>>,
+    Extension(tenant): Extension<Arc<Tenant>>,
+    Extension(user): Extension<UserData>,
     mut multipart: Multipart,
 ) -> Result<ErasedJson, AppError> {
-    let (username, _id) = {
+    let (username, user_id) = {
         let guard = user.lock().unwrap();
-        (guard.username.clone(), guard.id.clone().to_string())
+        (guard.0.username.clone(), guard.0.id)
     };
+    let _id = user_id.to_string();
 
     let (mut banner, mut icon, mut display_name, mut bio) = (None, None, None, None);
 
@@ -108,24 +117,25 @@ pub async fn update_profile(
     }
 
     // update user profile in database
-    db.update_profile(&username, &banner, &icon, &display_name, &bio).await?;
+    db.update_profile(&tenant.id, &username, &banner, &icon, &display_name, &bio).await?;
     let res = {
         let mut guard = user.lock().unwrap();
         if icon.is_some() {
-            guard.icon = icon;
+            guard.0.icon = icon;
         }
         if let Some(display_name) = display_name {
-            guard.display_name = display_name;
+            guard.0.display_name = display_name;
         }
         if bio.is_some() {
-            guard.bio = bio;
+            guard.0.bio = bio;
         }
         json!({
-            "icon": guard.icon.clone(),
-            "display_name": guard.display_name.clone(),
-            "bio": guard.bio.clone(),
+            "icon": guard.0.icon.clone(),
+            "display_name": guard.0.display_name.clone(),
+            "bio": guard.0.bio.clone(),
         })
     };
+    db.publish_event(user_id, SessionEvent::ProfileChanged);
 
     Ok(res)
 }
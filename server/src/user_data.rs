@@ -1,4 +1,12 @@
 pub fn arrange<S>(user: &database::users::User, sessions: &[S]) -> axum_extra::response::ErasedJson
+where
+    S: AsRef<util::session::Session>,
+{
+    axum_extra::response::ErasedJson::new(user_json(user, sessions))
+}
+
+/// json representation of the user and their sessions
+pub fn user_json<S>(user: &database::users::User, sessions: &[S]) -> serde_json::Value
 where
     S: AsRef<util::session::Session>,
 {
@@ -11,13 +19,14 @@ where
                 "user_agent": session.user_agent,
                 "created_at": session.created_at.to_string(),
                 "last_used": session.last_used.to_string(),
+                "impersonated": session.impersonated_by.is_some(),
             })
         })
         .collect::<Vec<_>>();
 
     let birth_date = if let Some(v) = &user.birth_date { v.to_string() } else { "".to_string() };
 
-    axum_extra::json!({
+    serde_json::json!({
         "email": &user.email,
         "birth_date": birth_date,
         "username": &user.username,
@@ -29,7 +38,47 @@ where
         "gender": &user.gender,
         "phone": &user.phone,
         "country": &user.country,
+        "restricted": user.restricted,
+        "bind_sessions": user.bind_sessions,
         "created": user.created.to_string(),
         "sessions": session_list,
     })
 }
+
+/// json representation of an organization the user is a member of
+pub fn membership(membership: &database::organizations::Membership) -> serde_json::Value {
+    serde_json::json!({
+        "id": membership.org_id.to_string(),
+        "name": membership.name,
+        "role": membership.role.get_str(),
+        "joined_at": membership.joined_at.to_string(),
+    })
+}
+
+/// json representation of a pending organization invitation
+pub fn invitation(invitation: &database::organizations::Invitation) -> serde_json::Value {
+    serde_json::json!({
+        "id": invitation.id.to_string(),
+        "organization_id": invitation.org_id.to_string(),
+        "organization": invitation.org_name,
+        "email": invitation.email,
+        "role": invitation.role.get_str(),
+        "invited_by": invitation.invited_by,
+        "created": invitation.created.to_string(),
+        "expires_at": invitation.expires_at.to_string(),
+    })
+}
+
+/// json representation of an audit log entry
+pub fn audit_record(record: &database::audit::AuditRecord) -> serde_json::Value {
+    serde_json::json!({
+        "id": record.id,
+        "occurred_at": record.occurred_at.to_string(),
+        "kind": record.kind,
+        "user_id": record.user_id.map(|v| v.to_string()),
+        "actor_id": record.actor_id.map(|v| v.to_string()),
+        "ip_address": record.ip_address.map(|v| v.to_string()),
+        "user_agent": record.user_agent,
+        "details": record.details,
+    })
+}
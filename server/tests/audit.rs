@@ -0,0 +1,58 @@
+mod common;
+
+use common::{Account, PASSWORD, TestServer};
+use reqwest::StatusCode;
+use serde_json::{Value, json};
+
+#[tokio::test]
+async fn activity_lists_the_account_events_by_page() {
+    let server = TestServer::start().await;
+    let (client, account) = server.register().await;
+    server.client().login("username", &account.username, "Wr0ngPassword!").await;
+    server.client().login("username", &account.username, PASSWORD).await;
+    client
+        .post(
+            "/api/settings/password",
+            json!({ "old_password": PASSWORD, "new_password": "N3wPassword!x" }),
+        )
+        .await;
+
+    let res = client.get("/api/settings/activity?limit=2").await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.body);
+    let first = res.body["events"].as_array().unwrap().clone();
+    assert_eq!(kinds(&first), ["password_changed", "login_succeeded"]);
+
+    let before = res.body["next_before"].as_i64().unwrap();
+    let res = client.get(&format!("/api/settings/activity?limit=2&before={before}")).await;
+    let second = res.body["events"].as_array().unwrap().clone();
+    assert_eq!(kinds(&second), ["login_failed"]);
+    assert_eq!(second[0]["details"]["reason"], "password_mismatch");
+    assert!(second[0]["id"].as_i64().unwrap() < before);
+}
+
+#[tokio::test]
+async fn failed_logins_of_unknown_users_are_audited() {
+    let server = TestServer::start().await;
+    let (admin, admin_account) = server.register().await;
+    server.grant_role(&admin_account, "admin").await;
+    let unknown = Account::random().username;
+
+    let res = server.client().login("username", &unknown, PASSWORD).await;
+    assert!(res.status.is_client_error(), "{}", res.body);
+
+    let res = admin.get("/api/admin/audit?kind=login_failed&limit=200").await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.body);
+    let event = res.body["events"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|e| e["details"]["identifier"] == unknown.as_str())
+        .cloned()
+        .expect("the failed login wasn't audited");
+    assert_eq!(event["user_id"], Value::Null);
+    assert_eq!(event["details"]["reason"], "user_not_found");
+}
+
+fn kinds(events: &[Value]) -> Vec<&str> {
+    events.iter().map(|e| e["kind"].as_str().unwrap()).collect()
+}
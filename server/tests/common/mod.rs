@@ -1,5 +1,291 @@
-mod scanner;
-pub use scanner::Scanner;
+#![allow(dead_code)]
+//! in-process server for the integration tests
+//!
+//! the configuration is loaded like the binary does (`CONFIG_FILE`, `config.toml` or the
+//! environment), so its database has to be reachable. The server listens on a random port and
+//! keeps the emails it sends in memory, where the tests read the codes from.
 
-mod printer;
-pub use printer::Printer;
+use axum::serve::Listener;
+use database::{
+    Db,
+    bucket::BlackBlazeB2,
+    tenants::{DEFAULT_TENANT, Tenant},
+};
+use reqwest::{StatusCode, header};
+use serde_json::{Value, json};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+use util::{config::Config, mail::MemoryMailer};
+
+pub const PASSWORD: &str = "Passw0rd!x";
+
+pub struct TestServer {
+    pub url: String,
+    pub db: Arc<Db>,
+    pub mailer: Arc<MemoryMailer>,
+    pub config: Arc<Config>,
+}
+
+impl TestServer {
+    pub async fn start() -> Self {
+        Self::start_with(|_| {}).await
+    }
+
+    /// starts a server with the configuration changed by `configure`
+    pub async fn start_with(configure: impl FnOnce(&mut Config)) -> Self {
+        let mut config = Config::load().unwrap_or_else(|errors| {
+            panic!("invalid configuration:\n  {}", errors.join("\n  "));
+        });
+        config.socket = ([127, 0, 0, 1], 0).into();
+        configure(&mut config);
+        let config = Arc::new(config);
+
+        let storage = Arc::new(BlackBlazeB2::from(&config.bucket));
+        let db = Db::connect(&config, storage.clone()).await.unwrap();
+        let mailer = Arc::new(MemoryMailer::default());
+        let state = server::AppState::new(db.clone(), mailer.clone(), storage, config.clone());
+
+        let listener = server::get_custom_listener(&config, db.clone()).await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            axum::serve(
+                listener,
+                server::routes(state).into_make_service_with_connect_info::<server::ClientSocket>(),
+            )
+            .await
+            .unwrap();
+        });
+        Self { url, db, mailer, config }
+    }
+
+    pub fn client(&self) -> Client {
+        Client {
+            http: reqwest::Client::builder().user_agent("integration-tests").build().unwrap(),
+            url: self.url.clone(),
+            cookies: Arc::default(),
+            csrf_cookie: self.config.cookies.csrf_cookie(None),
+            host: None,
+        }
+    }
+
+    /// a client of `tenant` selected by the `/t/{tenant}` prefix
+    pub fn tenant_client(&self, tenant: &str) -> Client {
+        self.client().in_tenant(&self.config, tenant)
+    }
+
+    /// creates a tenant with a random id served on `host` and returns its id
+    pub async fn create_tenant(&self, host: Option<&str>) -> String {
+        let id = format!("t{}", &uuid::Uuid::new_v4().simple().to_string()[..12]);
+        let tenant = Tenant {
+            id: id.clone(),
+            service_name: format!("Service {id}"),
+            service_domain: String::new(),
+            host: host.map(str::to_owned),
+            google_client_id: None,
+            google_client_secret: None,
+            email_templates: "{}".to_owned(),
+            created: time::OffsetDateTime::now_utc(),
+            registration_mode: String::new(),
+            allowed_email_domains: vec![],
+            denied_email_domains: vec![],
+        };
+        self.db.upsert_tenant(&tenant).await.unwrap();
+        id
+    }
+
+    /// the six digit code of the last email sent to `to`
+    pub fn code_sent_to(&self, to: &str) -> String {
+        let email = self.mailer.last_sent_to(to).expect("no email was sent");
+        email
+            .text
+            .split_whitespace()
+            .find(|w| w.len() == 6 && w.chars().all(|c| c.is_ascii_digit()))
+            .expect("the email has no code")
+            .to_owned()
+    }
+
+    /// the `code` query parameter of the link in the last email sent to `to`
+    pub fn link_code_sent_to(&self, to: &str) -> String {
+        let email = self.mailer.last_sent_to(to).expect("no email was sent");
+        let link = email.text.split_whitespace().find(|w| w.contains("code=")).unwrap();
+        link[link.find("code=").unwrap() + 5..].to_owned()
+    }
+
+    /// gives the role `role` to the user of `account`
+    pub async fn grant_role(&self, account: &Account, role: &str) {
+        let user = self.db.get_user_by_username(DEFAULT_TENANT, &account.username).await.unwrap();
+        self.db.assign_role(user.id, role, None).await.unwrap();
+    }
+
+    /// registers a new user and returns a client logged in with its session
+    pub async fn register(&self) -> (Client, Account) {
+        let account = Account::random();
+        let client = self.client();
+        self.register_with(&client, &account).await;
+        (client, account)
+    }
+
+    /// registers `account` in the tenant of `client`, which is then logged in with its session
+    pub async fn register_with(&self, client: &Client, account: &Account) {
+        let res = client
+            .post("/api/register", json!({ "name": "Test User", "email": account.email }))
+            .await;
+        assert!(res.status.is_success(), "{}", res.body);
+
+        let otp = self.code_sent_to(&account.email);
+        let res = client
+            .post("/api/register/verify_email", json!({ "email": account.email, "otp": otp }))
+            .await;
+        assert!(res.status.is_success(), "{}", res.body);
+
+        let res = client
+            .post(
+                "/api/register/set_password",
+                json!({ "email": account.email, "password": PASSWORD }),
+            )
+            .await;
+        assert!(res.status.is_success(), "{}", res.body);
+
+        let accepted_documents = client.current_documents().await;
+        let res = client
+            .post(
+                "/api/register/set_username",
+                json!({
+                    "email": account.email,
+                    "username": account.username,
+                    "accepted_documents": accepted_documents,
+                }),
+            )
+            .await;
+        assert_eq!(res.status, StatusCode::CREATED, "{}", res.body);
+
+        client.fetch_csrf().await;
+    }
+}
+
+pub struct Account {
+    pub email: String,
+    pub username: String,
+}
+
+impl Account {
+    pub fn random() -> Self {
+        let username = format!("u{}", &uuid::Uuid::new_v4().simple().to_string()[..12]);
+        Self { email: format!("{username}@example.com"), username }
+    }
+}
+
+#[derive(Debug)]
+pub struct Response {
+    pub status: StatusCode,
+    pub body: Value,
+}
+
+/// http client keeping its cookies and sending the csrf token of its cookie with every request
+pub struct Client {
+    http: reqwest::Client,
+    url: String,
+    /// shared by the clients of every tenant made from the same one, like a browser
+    cookies: Arc<Mutex<HashMap<String, String>>>,
+    csrf_cookie: String,
+    host: Option<String>,
+}
+
+impl Client {
+    /// a client of `tenant` selected by the `/t/{tenant}` prefix, sharing the cookies of `self`
+    pub fn in_tenant(&self, config: &Config, tenant: &str) -> Client {
+        Client {
+            http: self.http.clone(),
+            url: format!("{}/t/{tenant}", self.url),
+            cookies: self.cookies.clone(),
+            csrf_cookie: config.cookies.csrf_cookie(Some(tenant)),
+            host: self.host.clone(),
+        }
+    }
+
+    /// sends the requests with the `Host` header `host`, which serves the tenant `tenant`
+    pub fn with_host(mut self, config: &Config, host: &str, tenant: &str) -> Client {
+        self.host = Some(host.to_owned());
+        self.csrf_cookie = config.cookies.csrf_cookie(Some(tenant));
+        self
+    }
+
+    /// sends the requests with the `User-Agent` header `user_agent`, like another browser would
+    pub fn with_user_agent(mut self, user_agent: &str) -> Client {
+        self.http = reqwest::Client::builder().user_agent(user_agent).build().unwrap();
+        self
+    }
+
+    /// names of the cookies the client holds
+    pub fn cookie_names(&self) -> Vec<String> {
+        self.cookies.lock().unwrap().keys().cloned().collect()
+    }
+
+    pub async fn get(&self, path: &str) -> Response {
+        self.send(self.http.get(format!("{}{path}", self.url))).await
+    }
+
+    pub async fn post(&self, path: &str, body: Value) -> Response {
+        self.send(self.http.post(format!("{}{path}", self.url)).json(&body)).await
+    }
+
+    pub async fn post_multipart(&self, path: &str, form: reqwest::multipart::Form) -> Response {
+        self.send(self.http.post(format!("{}{path}", self.url)).multipart(form)).await
+    }
+
+    pub async fn send(&self, mut req: reqwest::RequestBuilder) -> Response {
+        let jar = self.cookies.lock().unwrap().clone();
+        let cookies = jar.iter().map(|(k, v)| format!("{k}={v}")).collect::<Vec<_>>().join("; ");
+        if !cookies.is_empty() {
+            req = req.header(header::COOKIE, cookies);
+        }
+        if let Some(token) = jar.get(&self.csrf_cookie) {
+            req = req.header(util::session::CSRF_HEADER, token);
+        }
+        if let Some(host) = &self.host {
+            req = req.header(header::HOST, host);
+        }
+
+        let res = req.send().await.unwrap();
+        for value in res.headers().get_all(header::SET_COOKIE) {
+            let value = value.to_str().unwrap();
+            let pair = value.split(';').next().unwrap();
+            let (name, content) = pair.split_once('=').unwrap();
+            let mut cookies = self.cookies.lock().unwrap();
+            if content.is_empty() || value.contains("Max-Age=0") {
+                cookies.remove(name);
+            } else {
+                cookies.insert(name.to_owned(), content.to_owned());
+            }
+        }
+        let status = res.status();
+        let text = res.text().await.unwrap();
+        Response { status, body: serde_json::from_str(&text).unwrap_or(Value::String(text)) }
+    }
+
+    /// logs in with `login` ("email" or "username") and fetches a csrf token for the session
+    pub async fn login(&self, login: &str, value: &str, password: &str) -> Response {
+        let res = self.post("/api/login", json!({ login: value, "password": password })).await;
+        if res.status.is_success() {
+            self.fetch_csrf().await;
+        }
+        res
+    }
+
+    pub async fn fetch_csrf(&self) {
+        let res = self.get("/api/csrf").await;
+        assert!(res.status.is_success(), "{}", res.body);
+    }
+
+    /// the versions of the current legal documents (kind => version)
+    pub async fn current_documents(&self) -> Value {
+        let res = self.get("/api/legal").await;
+        let documents = res.body["documents"].as_array().cloned().unwrap_or_default();
+        documents
+            .iter()
+            .map(|d| (d["kind"].as_str().unwrap().to_owned(), d["version"].clone()))
+            .collect()
+    }
+}
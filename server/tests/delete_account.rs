@@ -1,46 +1,17 @@
-#![allow(unused_must_use)]
 mod common;
 
-use common::{Printer, Scanner};
-use fake::Fake;
-use reqwest::header;
-use std::io::Write;
+use common::{PASSWORD, TestServer};
+use reqwest::StatusCode;
+use serde_json::json;
 
-#[test]
-fn main() -> Result<(), reqwest::Error> {
-    const SOCKET: &str = "http://127.0.0.1:8080";
-    let client = reqwest::blocking::Client::builder()
-        .user_agent(fake::faker::internet::en::UserAgent().fake::<String>())
-        .build()
-        .unwrap_or_default();
+#[tokio::test]
+async fn deletes_the_account() {
+    let server = TestServer::start().await;
+    let (client, account) = server.register().await;
 
-    // for io
-    let mut token = Scanner::new(std::io::stdin().lock());
-    let mut out = Printer::new();
-
-    out.write("Enter cookie: ");
-    let cookies = token.next_line::<String>();
-
-    loop {
-        let endpoint1 = format!("{}/api/settings/delete_account", SOCKET);
-        let res1 = client
-            .post(&endpoint1)
-            .header(header::CONTENT_TYPE, "application/json")
-            .header(header::COOKIE, &cookies)
-            .send();
-        match res1 {
-            Ok(v) => {
-                if v.status().is_client_error() {
-                    writeln!(out.inner, "{:?}", v.text()?);
-                } else {
-                    break;
-                }
-            }
-            Err(e) => {
-                writeln!(out.inner, "{e:?}");
-            }
-        }
-    }
-
-    Ok(())
+    let res = client.post("/api/settings/delete_account", json!({})).await;
+    assert!(res.status.is_success(), "{}", res.body);
+    assert_eq!(client.get("/api/settings").await.status, StatusCode::UNAUTHORIZED);
+    let res = server.client().login("email", &account.email, PASSWORD).await;
+    assert!(res.status.is_client_error(), "{}", res.body);
 }
@@ -1,30 +1,25 @@
-#![allow(unused_must_use)]
 mod common;
 
-use common::{Printer, Scanner};
-use fake::Fake;
-use reqwest::header;
-use std::io::Write;
+use common::{Account, TestServer};
+use reqwest::StatusCode;
 
-#[test]
-fn main() -> Result<(), reqwest::Error> {
-    const SOCKET: &str = "http://127.0.0.1:8080";
-    let client = reqwest::blocking::Client::builder()
-        .user_agent(fake::faker::internet::en::UserAgent().fake::<String>())
-        .build()
-        .unwrap_or_default();
+#[tokio::test]
+async fn returns_the_profile_of_another_user() {
+    let server = TestServer::start().await;
+    let (client, _) = server.register().await;
+    let (_, other) = server.register().await;
 
-    // for io
-    let mut token = Scanner::new(std::io::stdin().lock());
-    let mut out = Printer::new();
+    let res = client.get(&format!("/api/user/@{}", other.username)).await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.body);
+    assert_eq!(res.body["username"], other.username);
+    assert_eq!(res.body["display_name"], "Test User");
+}
 
-    out.write("Enter cookie: ");
-    let cookies = token.next_line::<String>();
-    out.write("Enter username: ");
-    let username = token.next::<String>();
-    let endpoint = format!("{}/api/user/@{}", SOCKET, username);
-    let res = client.get(&endpoint).header(header::COOKIE, cookies).send()?;
-    writeln!(out.inner, "{:?}", res.text()?);
+#[tokio::test]
+async fn rejects_an_unknown_user() {
+    let server = TestServer::start().await;
+    let (client, _) = server.register().await;
 
-    Ok(())
+    let res = client.get(&format!("/api/user/@{}", Account::random().username)).await;
+    assert!(res.status.is_client_error(), "{}", res.body);
 }
@@ -0,0 +1,26 @@
+mod common;
+
+use common::{PASSWORD, TestServer};
+use reqwest::StatusCode;
+use serde_json::json;
+
+#[tokio::test]
+async fn impersonation_sessions_cant_log_out_the_other_sessions() {
+    let server = TestServer::start().await;
+    let (admin, admin_account) = server.register().await;
+    server.grant_role(&admin_account, "admin").await;
+    let (user, account) = server.register().await;
+
+    let res = admin.post("/api/admin/impersonate", json!({ "username": account.username })).await;
+    assert!(res.status.is_success(), "{}", res.body);
+    admin.fetch_csrf().await;
+    assert_eq!(admin.get("/api/settings").await.body["username"], account.username);
+
+    let res = admin.post("/api/logout_all", json!({ "password": PASSWORD })).await;
+    assert_eq!(res.status, StatusCode::FORBIDDEN, "{}", res.body);
+    let ssid = user.get("/api/settings").await.body["sessions"][0]["unsigned_ssid"].clone();
+    let res =
+        admin.post("/api/logout_devices", json!({ "sessions": [ssid], "password": PASSWORD })).await;
+    assert_eq!(res.status, StatusCode::FORBIDDEN, "{}", res.body);
+    assert_eq!(user.get("/api/settings").await.status, StatusCode::OK);
+}
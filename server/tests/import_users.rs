@@ -0,0 +1,85 @@
+mod common;
+
+use common::{Account, PASSWORD, TestServer};
+use database::tenants::DEFAULT_TENANT;
+use reqwest::StatusCode;
+use serde_json::{Value, json};
+
+/// django hash of `PASSWORD`
+const DJANGO_HASH: &str = "pbkdf2_sha256$1000$somesalt$L7Ss6xrcdjMwTK/nNLBHjcGNSE3Yoo1+igSuBPGNk5o=";
+
+async fn import(server: &TestServer, rows: &[Value]) -> Value {
+    let (admin, account) = server.register().await;
+    server.grant_role(&account, "admin").await;
+    admin.fetch_csrf().await;
+
+    let body = rows.iter().map(Value::to_string).collect::<Vec<_>>().join("\n");
+    let req = reqwest::Client::new()
+        .post(format!("{}/api/admin/users/import?format=jsonl", server.url))
+        .body(body);
+    let res = admin.send(req).await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.body);
+    res.body
+}
+
+fn row(account: &Account, hash: &str) -> Value {
+    json!({
+        "email": account.email,
+        "username": account.username,
+        "display_name": "Imported",
+        "password_hash": hash,
+    })
+}
+
+#[tokio::test]
+async fn the_first_login_replaces_the_foreign_hash() {
+    let server = TestServer::start().await;
+    let account = Account::random();
+    let imported = import(&server, &[row(&account, DJANGO_HASH)]).await;
+    assert_eq!(imported["imported"], 1, "{imported}");
+
+    let res = server.client().login("username", &account.username, "Wr0ngPassw0rd!").await;
+    assert!(!res.status.is_success());
+    let user = server.db.get_user_by_username(DEFAULT_TENANT, &account.username).await.unwrap();
+    assert_eq!(user.password, None);
+
+    for _ in 0..2 {
+        let res = server.client().login("username", &account.username, PASSWORD).await;
+        assert!(res.status.is_success(), "{}", res.body);
+    }
+    let user = server.db.get_user_by_username(DEFAULT_TENANT, &account.username).await.unwrap();
+    assert_eq!(user.password.as_deref(), Some(PASSWORD));
+}
+
+#[tokio::test]
+async fn imported_users_confirm_their_password_in_the_settings() {
+    let server = TestServer::start().await;
+    let account = Account::random();
+    import(&server, &[row(&account, DJANGO_HASH)]).await;
+    let client = server.client();
+    let res = client.login("username", &account.username, PASSWORD).await;
+    assert!(res.status.is_success(), "{}", res.body);
+    let documents = client.current_documents().await;
+    client.post("/api/legal/accept", json!({ "documents": documents })).await;
+
+    let new_username = Account::random().username;
+    let body = json!({ "new_username": new_username, "password": PASSWORD });
+    let res = client.post("/api/settings/username", body).await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.body);
+    let body = json!({ "old_password": PASSWORD, "new_password": "N3wPassword!x" });
+    let res = client.post("/api/settings/password", body).await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.body);
+
+    let res = server.client().login("username", &new_username, "N3wPassword!x").await;
+    assert!(res.status.is_success(), "{}", res.body);
+}
+
+#[tokio::test]
+async fn rejects_hashes_with_too_many_iterations() {
+    let server = TestServer::start().await;
+    let account = Account::random();
+    let slow = "pbkdf2_sha256$2000000$somesalt$L7Ss6xrcdjMwTK/nNLBHjcGNSE3Yoo1+igSuBPGNk5o=";
+    let imported = import(&server, &[row(&account, slow)]).await;
+    assert_eq!(imported["failed"], 1, "{imported}");
+    assert_eq!(imported["rows"][0]["error"], "invalid");
+}
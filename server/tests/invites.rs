@@ -0,0 +1,126 @@
+mod common;
+
+use common::{Account, Client, PASSWORD, Response, TestServer};
+use reqwest::StatusCode;
+use serde_json::json;
+use std::time::Duration;
+
+/// a new invite only tenant and a client of its admin
+async fn invite_only_tenant(server: &TestServer) -> (String, Client) {
+    let tenant = server.create_tenant(None).await;
+    let admin = server.tenant_client(&tenant);
+    let account = Account::random();
+    server.register_with(&admin, &account).await;
+    let user = server.db.get_user_by_username(&tenant, &account.username).await.unwrap();
+    server.db.assign_role(user.id, "admin", None).await.unwrap();
+
+    let res = admin.post("/api/admin/registration", json!({ "mode": "invite_only" })).await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.body);
+    (tenant, admin)
+}
+
+async fn create_invite(admin: &Client, body: serde_json::Value) -> String {
+    let res = admin.post("/api/admin/invites", body).await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.body);
+    res.body["code"].as_str().unwrap().to_owned()
+}
+
+/// registers `account` with `code` up to its username, returns the response of `/api/register`
+/// if it fails
+async fn apply(server: &TestServer, client: &Client, account: &Account, code: &str) -> Response {
+    let body = json!({ "name": "Test User", "email": account.email, "invite_code": code });
+    let res = client.post("/api/register", body).await;
+    if !res.status.is_success() {
+        return res;
+    }
+    let otp = server.code_sent_to(&account.email);
+    let res = client
+        .post("/api/register/verify_email", json!({ "email": account.email, "otp": otp }))
+        .await;
+    assert!(res.status.is_success(), "{}", res.body);
+    let res = client
+        .post("/api/register/set_password", json!({ "email": account.email, "password": PASSWORD }))
+        .await;
+    assert!(res.status.is_success(), "{}", res.body);
+    res
+}
+
+async fn set_username(client: &Client, account: &Account) -> Response {
+    let accepted_documents = client.current_documents().await;
+    let body = json!({
+        "email": account.email,
+        "username": account.username,
+        "accepted_documents": accepted_documents,
+    });
+    client.post("/api/register/set_username", body).await
+}
+
+#[tokio::test]
+async fn invite_codes_allow_max_uses_registrations() {
+    let server = TestServer::start().await;
+    let (tenant, admin) = invite_only_tenant(&server).await;
+    let code = create_invite(&admin, json!({ "max_uses": 2 })).await;
+
+    for _ in 0..2 {
+        let (client, account) = (server.tenant_client(&tenant), Account::random());
+        let res = apply(&server, &client, &account, &code).await;
+        assert!(res.status.is_success(), "{}", res.body);
+        assert_eq!(set_username(&client, &account).await.status, StatusCode::CREATED);
+    }
+
+    let res = apply(&server, &server.tenant_client(&tenant), &Account::random(), &code).await;
+    assert_eq!(res.status, StatusCode::FORBIDDEN, "{}", res.body);
+}
+
+#[tokio::test]
+async fn registrants_sharing_a_used_up_code_are_not_created() {
+    let server = TestServer::start().await;
+    let (tenant, admin) = invite_only_tenant(&server).await;
+    let code = create_invite(&admin, json!({ "max_uses": 1 })).await;
+
+    // both start while the code still has a use left
+    let (first, first_account) = (server.tenant_client(&tenant), Account::random());
+    let (second, second_account) = (server.tenant_client(&tenant), Account::random());
+    assert!(apply(&server, &first, &first_account, &code).await.status.is_success());
+    assert!(apply(&server, &second, &second_account, &code).await.status.is_success());
+
+    assert_eq!(set_username(&first, &first_account).await.status, StatusCode::CREATED);
+    let res = set_username(&second, &second_account).await;
+    assert_eq!(res.status, StatusCode::FORBIDDEN, "{}", res.body);
+    assert!(server.db.get_user_by_username(&tenant, &second_account.username).await.is_err());
+
+    let res = admin.get("/api/admin/registration").await;
+    let invite = &res.body["invites"].as_array().unwrap()[0];
+    assert_eq!(invite["uses"], 1);
+}
+
+#[tokio::test]
+async fn expired_invite_codes_are_rejected() {
+    let server = TestServer::start().await;
+    let (tenant, admin) = invite_only_tenant(&server).await;
+    let code = create_invite(&admin, json!({ "expires_in": 1 })).await;
+    tokio::time::sleep(Duration::from_millis(1500)).await;
+
+    let res = apply(&server, &server.tenant_client(&tenant), &Account::random(), &code).await;
+    assert_eq!(res.status, StatusCode::FORBIDDEN, "{}", res.body);
+}
+
+#[tokio::test]
+async fn revoked_invite_codes_are_rejected() {
+    let server = TestServer::start().await;
+    let (tenant, admin) = invite_only_tenant(&server).await;
+    let code = create_invite(&admin, json!({})).await;
+
+    // revoked between the start of the registration and its end
+    let (client, account) = (server.tenant_client(&tenant), Account::random());
+    assert!(apply(&server, &client, &account, &code).await.status.is_success());
+    let res = admin.post("/api/admin/invites/revoke", json!({ "code": code })).await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.body);
+
+    let res = set_username(&client, &account).await;
+    assert_eq!(res.status, StatusCode::FORBIDDEN, "{}", res.body);
+    assert!(server.db.get_user_by_username(&tenant, &account.username).await.is_err());
+
+    let res = apply(&server, &server.tenant_client(&tenant), &Account::random(), &code).await;
+    assert_eq!(res.status, StatusCode::FORBIDDEN, "{}", res.body);
+}
@@ -0,0 +1,121 @@
+mod common;
+
+use common::{Account, Client, TestServer};
+use reqwest::StatusCode;
+use serde_json::json;
+
+/// a new tenant and a client of its admin
+async fn tenant_with_admin(server: &TestServer) -> (String, Client) {
+    let tenant = server.create_tenant(None).await;
+    let admin = server.tenant_client(&tenant);
+    let account = Account::random();
+    server.register_with(&admin, &account).await;
+    let user = server.db.get_user_by_username(&tenant, &account.username).await.unwrap();
+    server.db.assign_role(user.id, "admin", None).await.unwrap();
+    (tenant, admin)
+}
+
+/// publishes `version` of the terms of service, which the admin accepts right away
+async fn publish_terms(admin: &Client, version: &str) {
+    let body = json!({
+        "kind": "terms_of_service",
+        "version": version,
+        "url": format!("https://example.com/terms/{version}"),
+    });
+    let res = admin.post("/api/admin/legal", body).await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.body);
+    let documents = admin.current_documents().await;
+    let res = admin.post("/api/legal/accept", json!({ "documents": documents })).await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.body);
+}
+
+#[tokio::test]
+async fn registration_records_the_accepted_documents() {
+    let server = TestServer::start().await;
+    let (tenant, admin) = tenant_with_admin(&server).await;
+    publish_terms(&admin, "2024-01").await;
+
+    let client = server.tenant_client(&tenant);
+    server.register_with(&client, &Account::random()).await;
+    let res = client.get("/api/legal/acceptances").await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.body);
+    let acceptances = res.body["acceptances"].as_array().unwrap();
+    assert_eq!(acceptances.len(), 1);
+    assert_eq!(acceptances[0]["kind"], "terms_of_service");
+    assert_eq!(acceptances[0]["version"], "2024-01");
+}
+
+#[tokio::test]
+async fn registration_needs_the_current_documents() {
+    let server = TestServer::start().await;
+    let (tenant, admin) = tenant_with_admin(&server).await;
+    publish_terms(&admin, "2024-01").await;
+
+    let (client, account) = (server.tenant_client(&tenant), Account::random());
+    client.post("/api/register", json!({ "name": "Test User", "email": account.email })).await;
+    let otp = server.code_sent_to(&account.email);
+    client.post("/api/register/verify_email", json!({ "email": account.email, "otp": otp })).await;
+    client
+        .post(
+            "/api/register/set_password",
+            json!({ "email": account.email, "password": common::PASSWORD }),
+        )
+        .await;
+
+    let body = json!({
+        "email": account.email,
+        "username": account.username,
+        "accepted_documents": { "terms_of_service": "2023-01" },
+    });
+    let res = client.post("/api/register/set_username", body).await;
+    assert_eq!(res.status, StatusCode::FORBIDDEN, "{}", res.body);
+    assert_eq!(res.body["documents"][0]["version"], "2024-01");
+    assert!(server.db.get_user_by_username(&tenant, &account.username).await.is_err());
+}
+
+#[tokio::test]
+async fn publishing_a_version_asks_signed_in_users_to_accept_it() {
+    let server = TestServer::start().await;
+    let (tenant, admin) = tenant_with_admin(&server).await;
+    publish_terms(&admin, "2024-01").await;
+
+    // the documents the user has to accept are cached by this request
+    let client = server.tenant_client(&tenant);
+    server.register_with(&client, &Account::random()).await;
+    let res = client.get("/api/settings/activity").await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.body);
+
+    publish_terms(&admin, "2024-06").await;
+    let res = client.get("/api/settings/activity").await;
+    assert_eq!(res.status, StatusCode::FORBIDDEN, "{}", res.body);
+    assert_eq!(res.body["documents"][0]["version"], "2024-06");
+
+    let documents = client.current_documents().await;
+    let res = client.post("/api/legal/accept", json!({ "documents": documents })).await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.body);
+    let res = client.get("/api/settings/activity").await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.body);
+}
+
+#[tokio::test]
+async fn impersonation_sessions_arent_gated_and_cant_accept() {
+    let server = TestServer::start().await;
+    let (tenant, admin) = tenant_with_admin(&server).await;
+    let (client, account) = (server.tenant_client(&tenant), Account::random());
+    server.register_with(&client, &account).await;
+    publish_terms(&admin, "2024-01").await;
+
+    let res = admin.post("/api/admin/impersonate", json!({ "username": account.username })).await;
+    assert!(res.status.is_success(), "{}", res.body);
+    admin.fetch_csrf().await;
+    let res = admin.get("/api/settings").await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.body);
+    assert_eq!(res.body["username"], account.username);
+
+    let documents = admin.current_documents().await;
+    let res = admin.post("/api/legal/accept", json!({ "documents": documents })).await;
+    assert_eq!(res.status, StatusCode::FORBIDDEN, "{}", res.body);
+    let res = client.get("/api/settings").await;
+    assert_eq!(res.status, StatusCode::FORBIDDEN, "{}", res.body);
+    assert_eq!(res.body["documents"][0]["version"], "2024-01");
+}
@@ -1,46 +1,27 @@
-#![allow(unused_must_use)]
 mod common;
 
-use common::{Printer, Scanner};
-use fake::Fake;
-use reqwest::header;
-use std::io::Write;
+use common::{PASSWORD, TestServer};
+use reqwest::StatusCode;
 
-#[test]
-fn main() -> Result<(), reqwest::Error> {
-    const SOCKET: &str = "http://127.0.0.1:8080";
-    let client = reqwest::blocking::Client::builder()
-        .user_agent(fake::faker::internet::en::UserAgent().fake::<String>())
-        .build()
-        .unwrap_or_default();
+#[tokio::test]
+async fn logs_in_with_the_email() {
+    let server = TestServer::start().await;
+    let (_, account) = server.register().await;
 
-    // for io
-    let mut token = Scanner::new(std::io::stdin().lock());
-    let mut out = Printer::new();
-
-    out.write("Enter email: ");
-    let email = token.next_line::<String>();
-    out.write("Enter password: ");
-    let password = token.next_line::<String>();
+    let client = server.client();
+    let res = client.login("email", &account.email, PASSWORD).await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.body);
+    assert_eq!(res.body["username"], account.username);
+    assert_eq!(client.get("/api/settings").await.status, StatusCode::OK);
+}
 
-    let body = format!(r#"{{"email": "{email}", "password": "{password}"}}"#);
-    let res = client
-        .post(format!("{}/api/login", SOCKET))
-        .header(header::CONTENT_TYPE, "application/json")
-        .body(body)
-        .send()?;
-    let cookies = res
-        .headers()
-        .get_all(reqwest::header::SET_COOKIE)
-        .into_iter()
-        .map(|s| {
-            let v = s.to_str().unwrap();
-            v[..v.find(';').unwrap()].to_string()
-        })
-        .collect::<Vec<String>>()
-        .join(";");
-    writeln!(out.inner, "{cookies}");
-    writeln!(out.inner, "{:?}", res.text()?);
+#[tokio::test]
+async fn rejects_a_wrong_password() {
+    let server = TestServer::start().await;
+    let (_, account) = server.register().await;
 
-    Ok(())
+    let client = server.client();
+    let res = client.login("email", &account.email, "Wr0ngPassword!").await;
+    assert!(res.status.is_client_error(), "{}", res.body);
+    assert!(client.get("/api/settings").await.status.is_client_error());
 }
@@ -1,28 +1,15 @@
-#![allow(unused_must_use)]
 mod common;
 
-use common::{Printer, Scanner};
-use fake::Fake;
-use reqwest::header;
-use std::io::Write;
+use common::TestServer;
+use reqwest::StatusCode;
+use serde_json::json;
 
-#[test]
-fn main() -> Result<(), reqwest::Error> {
-    const SOCKET: &str = "http://127.0.0.1:8080";
-    let client = reqwest::blocking::Client::builder()
-        .user_agent(fake::faker::internet::en::UserAgent().fake::<String>())
-        .build()
-        .unwrap_or_default();
+#[tokio::test]
+async fn ends_the_session() {
+    let server = TestServer::start().await;
+    let (client, _) = server.register().await;
 
-    // for io
-    let mut token = Scanner::new(std::io::stdin().lock());
-    let mut out = Printer::new();
-
-    out.write("Enter cookie: ");
-    let cookies = token.next_line::<String>();
-    let res =
-        client.post(format!("{}/api/logout", SOCKET)).header(header::COOKIE, cookies).send()?;
-    writeln!(out.inner, "{:?}", res.text()?);
-
-    Ok(())
+    let res = client.post("/api/logout", json!({})).await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.body);
+    assert_eq!(client.get("/api/settings").await.status, StatusCode::UNAUTHORIZED);
 }
@@ -1,36 +1,28 @@
-#![allow(unused_must_use)]
 mod common;
 
-use common::{Printer, Scanner};
-use fake::Fake;
-use reqwest::header;
-use std::io::Write;
+use common::{PASSWORD, TestServer};
+use reqwest::StatusCode;
+use serde_json::json;
 
-#[test]
-fn main() -> Result<(), reqwest::Error> {
-    const SOCKET: &str = "http://127.0.0.1:8080";
-    let client = reqwest::blocking::Client::builder()
-        .user_agent(fake::faker::internet::en::UserAgent().fake::<String>())
-        .build()
-        .unwrap_or_default();
+#[tokio::test]
+async fn ends_every_other_session() {
+    let server = TestServer::start().await;
+    let (client, account) = server.register().await;
+    let other = server.client();
+    other.login("email", &account.email, PASSWORD).await;
 
-    // for io
-    let mut token = Scanner::new(std::io::stdin().lock());
-    let mut out = Printer::new();
-
-    out.write("Enter cookie: ");
-    let cookies = token.next_line::<String>();
-
-    out.write("Enter password: ");
-    let password = token.next_line::<String>();
+    let res = client.post("/api/logout_all", json!({ "password": PASSWORD })).await;
+    assert!(res.status.is_success(), "{}", res.body);
+    assert_eq!(client.get("/api/settings").await.status, StatusCode::OK);
+    assert_eq!(other.get("/api/settings").await.status, StatusCode::UNAUTHORIZED);
+}
 
-    let body = format!(r#"{{"password": "{password}"}}"#);
-    let res = client
-        .post(format!("{}/api/logout_all", SOCKET))
-        .header(header::COOKIE, cookies)
-        .body(body)
-        .send()?;
-    writeln!(out.inner, "{:?}", res.text()?);
+#[tokio::test]
+async fn requires_the_password() {
+    let server = TestServer::start().await;
+    let (client, _) = server.register().await;
 
-    Ok(())
+    let res = client.post("/api/logout_all", json!({ "password": "Wr0ngPassword!" })).await;
+    assert!(res.status.is_client_error(), "{}", res.body);
+    assert_eq!(client.get("/api/settings").await.status, StatusCode::OK);
 }
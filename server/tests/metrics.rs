@@ -0,0 +1,27 @@
+mod common;
+
+use common::TestServer;
+use reqwest::StatusCode;
+
+#[tokio::test]
+async fn counts_the_requests_without_a_route() {
+    let server = TestServer::start().await;
+    let client = server.client();
+
+    let res = client.get("/api/does_not_exist").await;
+    assert_eq!(res.status, StatusCode::NOT_FOUND);
+    client.get("/healthz").await;
+
+    let res = client.get("/metrics").await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.body);
+    let text = res.body.as_str().unwrap();
+    let counted = |route: &str, status: &str| {
+        text.lines().any(|l| {
+            l.starts_with("http_requests_total{")
+                && l.contains(&format!("route=\"{route}\""))
+                && l.contains(&format!("status=\"{status}\""))
+        })
+    };
+    assert!(counted("unmatched", "404"), "{text}");
+    assert!(counted("/healthz", "200"), "{text}");
+}
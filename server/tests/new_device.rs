@@ -0,0 +1,35 @@
+mod common;
+
+use common::{PASSWORD, TestServer};
+use reqwest::StatusCode;
+use std::time::Duration;
+
+const FIREFOX: &str = "Mozilla/5.0 (X11; Linux x86_64; rv:128.0) Gecko/20100101 Firefox/128.0";
+
+#[tokio::test]
+async fn logins_from_a_new_device_are_notified() {
+    let server = TestServer::start().await;
+    let (_, account) = server.register().await;
+    // the notifications are sent without waiting, so they are counted a bit later
+    let notifications = || async {
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        let sent = server.mailer.sent();
+        sent.iter().filter(|e| e.to == account.email && e.subject.contains("New activity")).count()
+    };
+
+    // the browser the account was registered with
+    let res = server.client().login("username", &account.username, PASSWORD).await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.body);
+    assert_eq!(notifications().await, 0);
+
+    let client = server.client().with_user_agent(FIREFOX);
+    let res = client.login("username", &account.username, PASSWORD).await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.body);
+    assert_eq!(notifications().await, 1);
+
+    // firefox is known from now on
+    let client = server.client().with_user_agent(FIREFOX);
+    let res = client.login("username", &account.username, PASSWORD).await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.body);
+    assert_eq!(notifications().await, 1);
+}
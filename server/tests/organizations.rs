@@ -0,0 +1,154 @@
+mod common;
+
+use common::{Account, Client, Response, TestServer};
+use reqwest::StatusCode;
+use serde_json::json;
+
+async fn create_organization(client: &Client) -> String {
+    let res = client.post("/api/organizations", json!({ "name": "Acme" })).await;
+    assert!(res.status.is_success(), "{}", res.body);
+    res.body["id"].as_str().unwrap().to_owned()
+}
+
+async fn invite(client: &Client, org: &str, email: &str, role: &str) -> Response {
+    let body = json!({ "email": email, "role": role });
+    client.post(&format!("/api/organizations/{org}/invite"), body).await
+}
+
+async fn update_role(client: &Client, org: &str, account: &Account, role: &str) -> Response {
+    let body = json!({ "username": account.username, "role": role });
+    client.post(&format!("/api/organizations/{org}/role"), body).await
+}
+
+async fn remove(client: &Client, org: &str, account: &Account) -> Response {
+    let body = json!({ "username": account.username });
+    client.post(&format!("/api/organizations/{org}/remove"), body).await
+}
+
+async fn leave(client: &Client, org: &str) -> Response {
+    client.post(&format!("/api/organizations/{org}/leave"), json!({})).await
+}
+
+/// registers a user who joins `org` as `role` on the invitation of `owner`
+async fn join(server: &TestServer, owner: &Client, org: &str, role: &str) -> (Client, Account) {
+    let (client, account) = server.register().await;
+    let res = invite(owner, org, &account.email, role).await;
+    assert!(res.status.is_success(), "{}", res.body);
+
+    let res = client.get("/api/organizations").await;
+    let invitation = res.body["invitations"][0]["id"].as_str().unwrap().to_owned();
+    let res =
+        client.post(&format!("/api/organizations/invitations/{invitation}/accept"), json!({})).await;
+    assert!(res.status.is_success(), "{}", res.body);
+    assert_eq!(res.body["role"], role);
+    (client, account)
+}
+
+#[tokio::test]
+async fn invites_a_member_by_email() {
+    let server = TestServer::start().await;
+    let (owner, _) = server.register().await;
+    let org = create_organization(&owner).await;
+    let invitee = Account::random();
+
+    let res = invite(&owner, &org, &invitee.email, "member").await;
+    assert!(res.status.is_success(), "{}", res.body);
+
+    // the email is sent in the background
+    let mut sent = None;
+    for _ in 0..50 {
+        sent = server.mailer.last_sent_to(&invitee.email);
+        if sent.is_some() {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+    assert!(sent.expect("no invitation was sent").text.contains("Acme"));
+
+    let (member, member_account) = join(&server, &owner, &org, "member").await;
+    let res = member.get(&format!("/api/organizations/{org}")).await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.body);
+    let usernames = res.body["members"].as_array().unwrap().iter().map(|m| m["username"].clone());
+    assert!(usernames.collect::<Vec<_>>().contains(&json!(member_account.username)));
+    // only the admins see the pending invitations
+    assert_eq!(res.body["invitations"], json!([]));
+    let res = owner.get(&format!("/api/organizations/{org}")).await;
+    assert_eq!(res.body["invitations"][0]["email"], invitee.email);
+}
+
+#[tokio::test]
+async fn only_admins_invite_up_to_their_role() {
+    let server = TestServer::start().await;
+    let (owner, _) = server.register().await;
+    let org = create_organization(&owner).await;
+    let (member, _) = join(&server, &owner, &org, "member").await;
+    let (admin, _) = join(&server, &owner, &org, "admin").await;
+    let email = Account::random().email;
+
+    assert_eq!(invite(&member, &org, &email, "member").await.status, StatusCode::FORBIDDEN);
+    assert_eq!(invite(&admin, &org, &email, "owner").await.status, StatusCode::FORBIDDEN);
+    assert!(invite(&admin, &org, &email, "admin").await.status.is_success());
+    assert!(invite(&owner, &org, &email, "owner").await.status.is_success());
+
+    // the members of other organizations can't invite
+    let (outsider, _) = server.register().await;
+    assert_eq!(invite(&outsider, &org, &email, "member").await.status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn only_admins_change_roles_up_to_their_own() {
+    let server = TestServer::start().await;
+    let (owner, owner_account) = server.register().await;
+    let org = create_organization(&owner).await;
+    let (member, member_account) = join(&server, &owner, &org, "member").await;
+    let (admin, admin_account) = join(&server, &owner, &org, "admin").await;
+
+    let res = update_role(&member, &org, &member_account, "admin").await;
+    assert_eq!(res.status, StatusCode::FORBIDDEN);
+    let res = update_role(&admin, &org, &member_account, "owner").await;
+    assert_eq!(res.status, StatusCode::FORBIDDEN);
+    let res = update_role(&admin, &org, &owner_account, "member").await;
+    assert_eq!(res.status, StatusCode::FORBIDDEN);
+
+    assert!(update_role(&admin, &org, &member_account, "admin").await.status.is_success());
+    assert!(update_role(&owner, &org, &admin_account, "owner").await.status.is_success());
+}
+
+#[tokio::test]
+async fn only_admins_remove_members_up_to_their_role() {
+    let server = TestServer::start().await;
+    let (owner, owner_account) = server.register().await;
+    let org = create_organization(&owner).await;
+    let (member, member_account) = join(&server, &owner, &org, "member").await;
+    let (admin, admin_account) = join(&server, &owner, &org, "admin").await;
+
+    assert_eq!(remove(&member, &org, &admin_account).await.status, StatusCode::FORBIDDEN);
+    assert_eq!(remove(&admin, &org, &owner_account).await.status, StatusCode::FORBIDDEN);
+    assert_eq!(remove(&admin, &org, &admin_account).await.status, StatusCode::BAD_REQUEST);
+
+    assert!(remove(&admin, &org, &member_account).await.status.is_success());
+    let res = member.get(&format!("/api/organizations/{org}")).await;
+    assert_eq!(res.status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn keeps_an_owner() {
+    let server = TestServer::start().await;
+    let (owner, owner_account) = server.register().await;
+    let org = create_organization(&owner).await;
+    let (admin, admin_account) = join(&server, &owner, &org, "admin").await;
+
+    // the last owner can't step down nor leave while there are other members
+    let res = update_role(&owner, &org, &owner_account, "admin").await;
+    assert_eq!(res.status, StatusCode::BAD_REQUEST, "{}", res.body);
+    assert_eq!(leave(&owner, &org).await.status, StatusCode::BAD_REQUEST);
+
+    assert!(update_role(&owner, &org, &admin_account, "owner").await.status.is_success());
+    assert!(update_role(&owner, &org, &owner_account, "admin").await.status.is_success());
+    assert!(leave(&owner, &org).await.status.is_success());
+
+    // the last member deletes the organization by leaving
+    let res = leave(&admin, &org).await;
+    assert!(res.status.is_success(), "{}", res.body);
+    assert_eq!(res.body["deleted"], true);
+}
@@ -0,0 +1,134 @@
+mod common;
+
+use axum::{Json, Router, extract::State, http::StatusCode, routing::post};
+use common::TestServer;
+use serde_json::{Value, json};
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::Duration,
+};
+
+/// b2 api counting the authorizations, whose tokens are accepted `uses_per_token` times
+#[derive(Clone, Default)]
+struct FakeB2 {
+    url: Arc<std::sync::OnceLock<String>>,
+    authorizations: Arc<AtomicUsize>,
+    upload_urls: Arc<AtomicUsize>,
+    token_uses: Arc<AtomicUsize>,
+    uses_per_token: usize,
+}
+
+impl FakeB2 {
+    async fn start(uses_per_token: usize) -> Self {
+        let b2 = Self { uses_per_token, ..Self::default() };
+        let app = Router::new()
+            .route("/b2api/v2/b2_authorize_account", axum::routing::get(authorize_account))
+            .route("/b2api/v2/b2_get_upload_url", post(get_upload_url))
+            .with_state(b2.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        b2.url.set(format!("http://{}", listener.local_addr().unwrap())).unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        b2
+    }
+
+    fn url(&self) -> String {
+        self.url.get().unwrap().clone()
+    }
+}
+
+async fn authorize_account(State(b2): State<FakeB2>) -> Json<Value> {
+    let n = b2.authorizations.fetch_add(1, Ordering::SeqCst);
+    b2.token_uses.store(0, Ordering::SeqCst);
+    Json(json!({ "authorizationToken": format!("token-{n}"), "apiUrl": b2.url() }))
+}
+
+async fn get_upload_url(
+    State(b2): State<FakeB2>,
+    headers: axum::http::HeaderMap,
+) -> Result<Json<Value>, StatusCode> {
+    b2.upload_urls.fetch_add(1, Ordering::SeqCst);
+    let token = headers["authorization"].to_str().unwrap();
+    let current = format!("token-{}", b2.authorizations.load(Ordering::SeqCst) - 1);
+    if token != current || b2.token_uses.fetch_add(1, Ordering::SeqCst) >= b2.uses_per_token {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+    Ok(Json(json!({ "uploadUrl": format!("{}/upload", b2.url()), "authorizationToken": "u" })))
+}
+
+async fn start_with(b2: &FakeB2, cache: Duration) -> TestServer {
+    TestServer::start_with(|config| {
+        config.bucket.endpoint = b2.url();
+        config.readiness_cache_duration = cache;
+    })
+    .await
+}
+
+#[tokio::test]
+async fn reports_the_dependencies() {
+    let b2 = FakeB2::start(usize::MAX).await;
+    let server = start_with(&b2, Duration::ZERO).await;
+
+    let res = server.client().get("/readyz").await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.body);
+    assert_eq!(res.body["status"], "ready");
+    for check in ["postgres", "bucket", "mail"] {
+        assert_eq!(res.body["checks"][check]["status"], "ok", "{}", res.body);
+    }
+}
+
+#[tokio::test]
+async fn reuses_the_bucket_authorization() {
+    let b2 = FakeB2::start(usize::MAX).await;
+    let server = start_with(&b2, Duration::ZERO).await;
+    let client = server.client();
+
+    for _ in 0..3 {
+        let res = client.get("/readyz").await;
+        assert_eq!(res.status, StatusCode::OK, "{}", res.body);
+    }
+    assert_eq!(b2.authorizations.load(Ordering::SeqCst), 1);
+    assert_eq!(b2.upload_urls.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn renews_an_expired_bucket_authorization() {
+    let b2 = FakeB2::start(1).await;
+    let server = start_with(&b2, Duration::ZERO).await;
+    let client = server.client();
+
+    for _ in 0..2 {
+        let res = client.get("/readyz").await;
+        assert_eq!(res.status, StatusCode::OK, "{}", res.body);
+    }
+    assert_eq!(b2.authorizations.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn caches_the_result() {
+    let b2 = FakeB2::start(usize::MAX).await;
+    let server = start_with(&b2, Duration::from_secs(60)).await;
+    let client = server.client();
+
+    let first = client.get("/readyz").await;
+    let second = client.get("/readyz").await;
+    assert_eq!(first.body, second.body);
+    assert_eq!(b2.upload_urls.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn fails_when_the_bucket_is_unreachable() {
+    let server = TestServer::start_with(|config| {
+        config.bucket.endpoint = "http://127.0.0.1:9".into();
+        config.readiness_cache_duration = Duration::ZERO;
+    })
+    .await;
+
+    let res = server.client().get("/readyz").await;
+    assert_eq!(res.status, StatusCode::SERVICE_UNAVAILABLE, "{}", res.body);
+    assert_eq!(res.body["status"], "not_ready");
+    assert_eq!(res.body["checks"]["bucket"]["status"], "error");
+    assert_eq!(res.body["checks"]["postgres"]["status"], "ok");
+}
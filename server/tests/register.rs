@@ -1,183 +1,46 @@
-#![allow(unused_must_use, unused_parens)]
 mod common;
 
-use common::{Printer, Scanner};
-use fake::Fake;
-use fake::faker::internet::en::{FreeEmail, Password, Username};
-use fake::faker::name::en::{FirstName, LastName};
-use reqwest::header;
-use std::io::Write;
+use common::{Account, TestServer};
+use reqwest::StatusCode;
+use serde_json::json;
 
-#[test]
-fn main() -> Result<(), reqwest::Error> {
-    const SOCKET: &str = "http://127.0.0.1:8080";
-    let is_auto = std::env::var("AUTO").ok().map(|v| v.as_str() == "true").unwrap_or(false);
-    let client = reqwest::blocking::Client::builder()
-        .user_agent(fake::faker::internet::en::UserAgent().fake::<String>())
-        .build()
-        .unwrap_or_default();
+#[tokio::test]
+async fn registers_with_the_mailed_code() {
+    let server = TestServer::start().await;
+    let (client, account) = server.register().await;
 
-    // for io
-    let mut token = Scanner::new(std::io::stdin().lock());
-    let mut out = Printer::new();
-    let mut email: String;
-
-    // first step of registering
-    loop {
-        let (name) = if is_auto {
-            email = FreeEmail().fake();
-            (
-                format!("{} {}", FirstName().fake::<String>(), LastName().fake::<String>())
-                // (1950..=2024).fake::<u32>(),
-                // (1..=12).fake::<u8>(),
-                // (1..=28).fake::<u8>(),
-                // (1..=11).fake::<u8>(),
-                // (1..=59).fake::<u8>(),
-                // (1..=59).fake::<u8>(),
-            )
-        } else {
-            out.write("Enter name: ");
-            let name = token.next_line::<String>();
-            out.write("Enter email: ");
-            email = token.next_line::<String>();
-            // out.write("Enter year of birth: ");
-            // let year = token.next::<u32>();
-            // out.write("Enter month of birth: ");
-            // let month = token.next::<u8>();
-            // out.write("Enter day of birth: ");
-            // let day = token.next::<u8>();
-            // let offset_hours = (1..=11).fake::<u8>();
-            // let offset_minutes = (1..=59).fake::<u8>();
-            // let offset_seconds = (1..=59).fake::<u8>();
-            (name)
-        };
-
-        let body1 = format!(r#"{{"name": "{name}", "email": "{email}"}}"#);
-        if is_auto {
-            out.write(&body1);
-            out.write("\n");
-        }
-        let res1 = client
-            .post(format!("{}/api/register", SOCKET))
-            .header(header::CONTENT_TYPE, "application/json")
-            .body(body1)
-            .send();
-        match res1 {
-            Ok(v) => {
-                if v.status().is_client_error() {
-                    writeln!(out.inner, "{:?}", v.text()?);
-                } else {
-                    break;
-                }
-            }
-            Err(e) => {
-                writeln!(out.inner, "{e:?}");
-            }
-        }
-    }
-
-    // second step of registering
-    loop {
-        out.write("Enter your otp: ");
-        let otp = token.next::<String>();
-
-        let body2 = format!(r#"{{"email": "{email}", "otp": "{otp}"}}"#);
-        let res2 = client
-            .post(format!("{}/api/register/verify_email", SOCKET))
-            .header(header::CONTENT_TYPE, "application/json")
-            .body(body2)
-            .send();
-        match res2 {
-            Ok(v) => {
-                if v.status().is_client_error() {
-                    writeln!(out.inner, "{:?}", v.text()?);
-                } else {
-                    break;
-                }
-            }
-            Err(e) => {
-                writeln!(out.inner, "{e:?}");
-            }
-        }
-    }
-
-    // third step of registering
-    loop {
-        let password = if is_auto {
-            Password(8..128).fake::<String>()
-        } else {
-            out.write("Enter your password: ");
-            token.next_line::<String>()
-        };
-        let body3 = format!(r#"{{"email": "{email}", "password": "{password}"}}"#);
-        if is_auto {
-            out.write(&body3);
-            out.write("\n");
-        }
-
-        let res3 = client
-            .post(format!("{}/api/register/set_password", SOCKET))
-            .header(header::CONTENT_TYPE, "application/json")
-            .body(body3)
-            .send();
-        match res3 {
-            Ok(v) => {
-                if v.status().is_client_error() {
-                    writeln!(out.inner, "{:?}", v.text()?);
-                } else {
-                    break;
-                }
-            }
-            Err(e) => {
-                writeln!(out.inner, "{e:?}");
-            }
-        }
-    }
+    let res = client.get("/api/settings").await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.body);
+    assert_eq!(res.body["email"], account.email);
+    assert_eq!(res.body["username"], account.username);
+}
 
-    // fourth step of registering
-    loop {
-        let username = if is_auto {
-            Username().fake::<String>().replace("_", ".")
-        } else {
-            out.write("Enter your username: ");
-            token.next::<String>()
-        };
+#[tokio::test]
+async fn rejects_a_wrong_code() {
+    let server = TestServer::start().await;
+    let client = server.client();
+    let account = Account::random();
+
+    let res =
+        client.post("/api/register", json!({ "name": "Test User", "email": account.email })).await;
+    assert!(res.status.is_success(), "{}", res.body);
+    let otp = server.code_sent_to(&account.email);
+    let wrong = if otp == "000000" { "111111" } else { "000000" };
+
+    let res = client
+        .post("/api/register/verify_email", json!({ "email": account.email, "otp": wrong }))
+        .await;
+    assert!(res.status.is_client_error(), "{}", res.body);
+}
 
-        let body4 = format!(r#"{{"email": "{email}", "username": "{username}"}}"#);
-        if is_auto {
-            out.write(&body4);
-            out.write("\n");
-        }
-        let res4 = client
-            .post(format!("{}/api/register/set_username", SOCKET))
-            .header(header::CONTENT_TYPE, "application/json")
-            .body(body4)
-            .send();
-        match res4 {
-            Ok(v) => {
-                if v.status().is_client_error() {
-                    writeln!(out.inner, "{:?}", v.text()?);
-                } else {
-                    let cookies = v
-                        .headers()
-                        .get_all(reqwest::header::SET_COOKIE)
-                        .into_iter()
-                        .map(|s| {
-                            let v = s.to_str().unwrap();
-                            v[..v.find(';').unwrap()].to_string()
-                        })
-                        .collect::<Vec<String>>()
-                        .join("; ");
-                    writeln!(out.inner, "{cookies}");
-                    writeln!(out.inner, "{:?}", v.text()?);
-                    break;
-                }
-            }
-            Err(e) => {
-                writeln!(out.inner, "{e:?}");
-            }
-        }
-    }
+#[tokio::test]
+async fn resends_the_code() {
+    let server = TestServer::start().await;
+    let client = server.client();
+    let account = Account::random();
 
-    Ok(())
+    client.post("/api/register", json!({ "name": "Test User", "email": account.email })).await;
+    let res = client.post("/api/register/resend_otp", json!({ "email": account.email })).await;
+    assert!(res.status.is_success(), "{}", res.body);
+    assert_eq!(server.mailer.sent().iter().filter(|e| e.to == account.email).count(), 2);
 }
@@ -1,74 +1,38 @@
-#![allow(unused_must_use)]
 mod common;
 
-use common::{Printer, Scanner};
-use fake::Fake;
-use reqwest::header;
-use std::io::Write;
+use common::{PASSWORD, TestServer};
+use reqwest::StatusCode;
+use serde_json::json;
 
-#[test]
-fn main() -> Result<(), reqwest::Error> {
-    const SOCKET: &str = "http://127.0.0.1:8080";
-    let client = reqwest::blocking::Client::builder()
-        .user_agent(fake::faker::internet::en::UserAgent().fake::<String>())
-        .build()
-        .unwrap_or_default();
+#[tokio::test]
+async fn resets_the_password_with_the_mailed_link() {
+    let server = TestServer::start().await;
+    let (_, account) = server.register().await;
+    let client = server.client();
 
-    // for io
-    let mut token = Scanner::new(std::io::stdin().lock());
-    let mut out = Printer::new();
+    let res = client.post("/api/forgot_password", json!({ "email": account.email })).await;
+    assert!(res.status.is_success(), "{}", res.body);
+    let code = server.link_code_sent_to(&account.email);
 
-    let mut email: String;
-    let endpoint1 = format!("{}/api/forgot_password", SOCKET);
-
-    loop {
-        out.write("Enter email: ");
-        email = token.next_line::<String>();
-        let body1 = format!(r#"{{"email":"{email}"}}"#);
-        let res1 = client
-            .post(&endpoint1)
-            .header(header::CONTENT_TYPE, "application/json")
-            .body(body1)
-            .send();
-        match res1 {
-            Ok(v) => {
-                if v.status().is_client_error() {
-                    writeln!(out.inner, "{:?}", v.text()?);
-                } else {
-                    break;
-                }
-            }
-            Err(e) => {
-                writeln!(out.inner, "{e:?}");
-            }
-        }
-    }
+    let new_password = "N3wPassword!x";
+    let res = client
+        .post(&format!("/api/reset_password?code={code}"), json!({ "password": new_password }))
+        .await;
+    assert!(res.status.is_success(), "{}", res.body);
+    assert!(server.client().login("email", &account.email, PASSWORD).await.status.is_client_error());
+    let res = server.client().login("email", &account.email, new_password).await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.body);
+}
 
-    loop {
-        out.write("Enter your code: ");
-        let code = token.next::<String>();
-        out.write("Enter your password: ");
-        let password = token.next_line::<String>();
-        let body2 = format!(r#"{{"password": "{password}"}}"#);
-        let endpoint2 = format!(r#"{}/api/reset_password?code={}"#, SOCKET, code);
-        let res2 = client
-            .post(&endpoint2)
-            .header(header::CONTENT_TYPE, "application/json")
-            .body(body2)
-            .send();
-        match res2 {
-            Ok(v) => {
-                if v.status().is_client_error() {
-                    writeln!(out.inner, "{:?}", v.text()?);
-                } else {
-                    break;
-                }
-            }
-            Err(e) => {
-                writeln!(out.inner, "{e:?}");
-            }
-        }
-    }
+#[tokio::test]
+async fn rejects_an_unknown_code() {
+    let server = TestServer::start().await;
+    let (_, account) = server.register().await;
+    let client = server.client();
+    client.post("/api/forgot_password", json!({ "email": account.email })).await;
 
-    Ok(())
+    let res = client
+        .post("/api/reset_password?code=0123456789abcdef", json!({ "password": "N3wPassword!x" }))
+        .await;
+    assert!(res.status.is_client_error(), "{}", res.body);
 }
@@ -0,0 +1,66 @@
+mod common;
+
+use common::{Account, Client, PASSWORD, Response, TestServer};
+use reqwest::StatusCode;
+use serde_json::json;
+use util::age::AgeRule;
+
+/// registers `account` in Korea, born on the first of january of `year`
+async fn register(server: &TestServer, client: &Client, account: &Account, year: i32) -> Response {
+    client.post("/api/register", json!({ "name": "Test User", "email": account.email })).await;
+    let otp = server.code_sent_to(&account.email);
+    client.post("/api/register/verify_email", json!({ "email": account.email, "otp": otp })).await;
+    let body = json!({ "email": account.email, "password": PASSWORD });
+    client.post("/api/register/set_password", body).await;
+
+    let body = json!({
+        "email": account.email,
+        "username": account.username,
+        "birth_date": {
+            "year": year,
+            "month": 1,
+            "day": 1,
+            "offset_hours": 0,
+            "offset_minutes": 0,
+            "offset_seconds": 0,
+        },
+        "country": "KR",
+        "accepted_documents": client.current_documents().await,
+    });
+    let res = client.post("/api/register/set_username", body).await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.body);
+    client.fetch_csrf().await;
+    res
+}
+
+#[tokio::test]
+async fn restricted_accounts_cant_use_organizations() {
+    let server = TestServer::start_with(|config| {
+        let rule = AgeRule { minimum_age: None, restricted_age: Some(19) };
+        config.age.countries.insert("KR".to_owned(), rule);
+    })
+    .await;
+    let (client, account) = (server.client(), Account::random());
+    let admin = server.client();
+    let admin_account = Account::random();
+    register(&server, &admin, &admin_account, 1990).await;
+    server.grant_role(&admin_account, "admin").await;
+
+    let year = time::OffsetDateTime::now_utc().year() - 15;
+    let res = register(&server, &client, &account, year).await;
+    assert_eq!(res.body["restricted"], true, "{}", res.body);
+
+    let res = client.post("/api/organizations", json!({ "name": "Acme" })).await;
+    assert_eq!(res.status, StatusCode::FORBIDDEN, "{}", res.body);
+    let res = client.get("/api/organizations").await;
+    assert_eq!(res.status, StatusCode::FORBIDDEN, "{}", res.body);
+    // the rest of the account stays available
+    let res = client.get("/api/settings").await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.body);
+
+    let body = json!({ "username": account.username });
+    let res = admin.post("/api/admin/users/lift_restriction", body).await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.body);
+    let res = client.get("/api/organizations").await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.body);
+}
@@ -0,0 +1,73 @@
+mod common;
+
+use common::{Account, TestServer};
+use database::{events::SessionEvent, tenants::DEFAULT_TENANT};
+use reqwest::StatusCode;
+use serde_json::json;
+use std::time::Duration;
+use tokio::sync::broadcast::Receiver;
+
+async fn next_event(receiver: &mut Receiver<SessionEvent>) -> SessionEvent {
+    tokio::time::timeout(Duration::from_secs(5), receiver.recv()).await.unwrap().unwrap()
+}
+
+#[tokio::test]
+async fn metadata_changes_are_published() {
+    let server = TestServer::start().await;
+    let (client, account) = server.register().await;
+    let user = server.db.get_user_by_username(DEFAULT_TENANT, &account.username).await.unwrap();
+    let mut receiver = server.db.subscribe_events(user.id);
+
+    let res = client.post("/api/settings/gender", json!({ "gender": "female" })).await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.body);
+    assert!(matches!(next_event(&mut receiver).await, SessionEvent::ProfileChanged));
+}
+
+#[tokio::test]
+async fn binding_changes_reach_every_server() {
+    let server = TestServer::start().await;
+    let (client, account) = server.register().await;
+    let user = server.db.get_user_by_username(DEFAULT_TENANT, &account.username).await.unwrap();
+    let mut receiver = server.db.subscribe_events(user.id);
+
+    let body = json!({ "enabled": true, "password": common::PASSWORD });
+    let res = client.post("/api/settings/session_binding", body).await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.body);
+    // sent through the database like to the other servers
+    assert!(matches!(next_event(&mut receiver).await, SessionEvent::ProfileChanged));
+    let res = client.get("/api/settings").await;
+    assert_eq!(res.body["bind_sessions"], true, "{}", res.body);
+}
+
+#[tokio::test]
+async fn idle_sessions_are_published_as_revoked() {
+    let server = TestServer::start_with(|config| {
+        config.cookies.standard.idle_timeout = Duration::from_secs(1);
+    })
+    .await;
+    let (client, account) = (server.client(), Account::random());
+    server.register_with(&client, &account).await;
+    let user = server.db.get_user_by_username(DEFAULT_TENANT, &account.username).await.unwrap();
+    let mut receiver = server.db.subscribe_events(user.id);
+
+    tokio::time::sleep(Duration::from_millis(1500)).await;
+    let res = client.get("/api/settings").await;
+    assert_eq!(res.status, StatusCode::UNAUTHORIZED, "{}", res.body);
+    let SessionEvent::SessionsRevoked { sessions } = next_event(&mut receiver).await else {
+        panic!("expected the session to be revoked");
+    };
+    assert_eq!(sessions.len(), 1);
+}
+
+#[tokio::test]
+async fn changes_made_by_other_processes_are_published() {
+    let server = TestServer::start().await;
+    let (_, account) = server.register().await;
+    let user = server.db.get_user_by_username(DEFAULT_TENANT, &account.username).await.unwrap();
+    let mut receiver = server.db.subscribe_events(user.id);
+
+    server.db.notify_user_changed(user.id).await.unwrap();
+    assert!(matches!(next_event(&mut receiver).await, SessionEvent::ProfileChanged));
+    server.db.notify_sessions_revoked(user.id).await.unwrap();
+    assert!(matches!(next_event(&mut receiver).await, SessionEvent::AllSessionsRevoked { .. }));
+}
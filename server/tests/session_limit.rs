@@ -0,0 +1,67 @@
+mod common;
+
+use common::{Account, PASSWORD, TestServer};
+use reqwest::StatusCode;
+use serde_json::json;
+use std::time::Duration;
+use util::session::LimitStrategy;
+
+/// a server rejecting logins over `max_sessions` sessions
+async fn rejecting_server(
+    max_sessions: usize,
+    configure: impl FnOnce(&mut util::config::Config),
+) -> TestServer {
+    TestServer::start_with(|config| {
+        config.session_limit.max_sessions = Some(max_sessions);
+        config.session_limit.strategy = LimitStrategy::Reject;
+        configure(config);
+    })
+    .await
+}
+
+#[tokio::test]
+async fn logins_over_the_limit_are_rejected() {
+    let server = rejecting_server(1, |_| {}).await;
+    let (_, account) = server.register().await;
+
+    let client = server.client();
+    let res = client.login("username", &account.username, PASSWORD).await;
+    assert_eq!(res.status, StatusCode::CONFLICT, "{}", res.body);
+    let sessions = res.body["sessions"].as_array().unwrap();
+    assert_eq!(sessions.len(), 1, "{}", res.body);
+
+    let body = json!({
+        "username": account.username,
+        "password": PASSWORD,
+        "revoke_sessions": [sessions[0]["unsigned_ssid"]],
+    });
+    let res = client.post("/api/login", body).await;
+    assert!(res.status.is_success(), "{}", res.body);
+}
+
+#[tokio::test]
+async fn impersonation_sessions_dont_count() {
+    let server = rejecting_server(2, |_| {}).await;
+    let (_, account) = server.register().await;
+    let (admin, admin_account) = server.register().await;
+    server.grant_role(&admin_account, "admin").await;
+
+    let res = admin.post("/api/admin/impersonate", json!({ "username": account.username })).await;
+    assert!(res.status.is_success(), "{}", res.body);
+    let res = server.client().login("username", &account.username, PASSWORD).await;
+    assert!(res.status.is_success(), "{}", res.body);
+}
+
+#[tokio::test]
+async fn idle_sessions_dont_count() {
+    let server = rejecting_server(1, |config| {
+        config.cookies.standard.idle_timeout = Duration::from_secs(1);
+    })
+    .await;
+    let account = Account::random();
+    server.register_with(&server.client(), &account).await;
+
+    tokio::time::sleep(Duration::from_millis(1500)).await;
+    let res = server.client().login("username", &account.username, PASSWORD).await;
+    assert!(res.status.is_success(), "{}", res.body);
+}
@@ -0,0 +1,35 @@
+mod common;
+
+use common::TestServer;
+use database::tenants::DEFAULT_TENANT;
+use reqwest::StatusCode;
+use std::time::Duration;
+
+#[tokio::test]
+async fn cached_sessions_are_refreshed_before_they_expire() {
+    // sessions are valid for 2 seconds before they start expiring
+    let server = TestServer::start_with(|config| {
+        config.cookies.mem_cache_duration = Duration::from_secs(3600);
+        config.cookies.standard.lifetime = Duration::from_secs(3602);
+    })
+    .await;
+    let (client, account) = server.register().await;
+    let user = server.db.get_user_by_username(DEFAULT_TENANT, &account.username).await.unwrap();
+    let sessions = |now| server.db.get_user_sessions(user.id, now);
+    let old = sessions(time::OffsetDateTime::now_utc()).await.unwrap();
+    assert_eq!(old.len(), 1);
+
+    tokio::time::sleep(Duration::from_millis(2500)).await;
+    let res = client.get("/api/settings").await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.body);
+    let new = sessions(time::OffsetDateTime::now_utc()).await.unwrap();
+    assert_eq!(new.len(), 1);
+    assert_ne!(new[0].unsigned_ssid, old[0].unsigned_ssid);
+    assert_eq!(new[0].absolute_expires_at, old[0].absolute_expires_at);
+
+    // the client carries on with the new session
+    client.fetch_csrf().await;
+    let res = client.get("/api/settings").await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.body);
+    assert_eq!(res.body["username"], account.username);
+}
@@ -0,0 +1,133 @@
+mod common;
+
+use common::{Account, PASSWORD, TestServer};
+use reqwest::StatusCode;
+
+#[tokio::test]
+async fn resolves_the_tenant_from_the_path_prefix() {
+    let server = TestServer::start().await;
+    let tenant = server.create_tenant(None).await;
+    let client = server.tenant_client(&tenant);
+    let account = Account::random();
+    server.register_with(&client, &account).await;
+
+    let res = client.get("/api/settings").await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.body);
+    assert_eq!(res.body["username"], account.username);
+
+    // the user doesn't exist in the default tenant
+    let res = server.client().login("username", &account.username, PASSWORD).await;
+    assert!(!res.status.is_success(), "{}", res.body);
+    let res = server.tenant_client(&tenant).login("username", &account.username, PASSWORD).await;
+    assert!(res.status.is_success(), "{}", res.body);
+}
+
+#[tokio::test]
+async fn resolves_the_tenant_from_the_host() {
+    let server = TestServer::start().await;
+    let host = format!("{}.example.test", uuid::Uuid::new_v4().simple());
+    let tenant = server.create_tenant(Some(&host)).await;
+    let account = Account::random();
+    server.register_with(&server.tenant_client(&tenant), &account).await;
+
+    let client = server.client().with_host(&server.config, &host, &tenant);
+    let res = client.login("username", &account.username, PASSWORD).await;
+    assert!(res.status.is_success(), "{}", res.body);
+    let res = client.get("/api/settings").await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.body);
+    assert_eq!(res.body["username"], account.username);
+
+    // the host is matched without its port and case
+    let client =
+        server.client().with_host(&server.config, &format!("{}:443", host.to_uppercase()), &tenant);
+    let res = client.login("username", &account.username, PASSWORD).await;
+    assert!(res.status.is_success(), "{}", res.body);
+}
+
+#[tokio::test]
+async fn an_unknown_tenant_is_not_found() {
+    let server = TestServer::start().await;
+    let res = server.client().get("/t/no-such-tenant/api/legal").await;
+    assert_eq!(res.status, StatusCode::NOT_FOUND);
+
+    // an unknown host is served by the default tenant
+    let client = server.client().with_host(&server.config, "unknown.example.test", "default");
+    assert_eq!(client.get("/api/legal").await.status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn a_host_is_resolved_once_a_tenant_is_served_on_it() {
+    let server = TestServer::start().await;
+    let host = format!("{}.example.test", uuid::Uuid::new_v4().simple());
+    let client = server.client().with_host(&server.config, &host, "default");
+    assert_eq!(client.get("/api/legal").await.status, StatusCode::OK);
+
+    let tenant = server.create_tenant(Some(&host)).await;
+    let account = Account::random();
+    server.register_with(&server.tenant_client(&tenant), &account).await;
+    let client = server.client().with_host(&server.config, &host, &tenant);
+    let res = client.login("username", &account.username, PASSWORD).await;
+    assert!(res.status.is_success(), "{}", res.body);
+}
+
+#[tokio::test]
+async fn probes_are_served_without_a_tenant() {
+    let server = TestServer::start().await;
+    let res = server.client().get("/healthz").await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.body);
+    // they aren't served per tenant
+    let tenant = server.create_tenant(None).await;
+    let res = server.tenant_client(&tenant).get("/healthz").await;
+    assert_eq!(res.status, StatusCode::NOT_FOUND, "{}", res.body);
+}
+
+#[tokio::test]
+async fn emails_and_usernames_are_unique_per_tenant() {
+    let server = TestServer::start().await;
+    let tenant = server.create_tenant(None).await;
+    let account = Account::random();
+
+    server.register_with(&server.client(), &account).await;
+    server.register_with(&server.tenant_client(&tenant), &account).await;
+}
+
+#[tokio::test]
+async fn keeps_a_session_per_tenant_on_the_same_host() {
+    let server = TestServer::start().await;
+    let tenant = server.create_tenant(None).await;
+    let (client, account) = server.register().await;
+    let tenant_client = client.in_tenant(&server.config, &tenant);
+    let tenant_account = Account::random();
+    server.register_with(&tenant_client, &tenant_account).await;
+
+    let names = client.cookie_names();
+    let policy = &server.config.cookies;
+    for scope in [None, Some(tenant.as_str())] {
+        for cookie in
+            [policy.ssid_cookie(scope), policy.uuid_cookie(scope), policy.csrf_cookie(scope)]
+        {
+            assert!(names.contains(&cookie), "{cookie} not in {names:?}");
+        }
+    }
+
+    assert_eq!(client.get("/api/settings").await.body["username"], account.username);
+    let res = tenant_client.get("/api/settings").await;
+    assert_eq!(res.body["username"], tenant_account.username);
+
+    let res = tenant_client.post("/api/logout", serde_json::json!({})).await;
+    assert!(res.status.is_success(), "{}", res.body);
+    assert_eq!(tenant_client.get("/api/settings").await.status, StatusCode::UNAUTHORIZED);
+    assert_eq!(client.get("/api/settings").await.body["username"], account.username);
+}
+
+#[tokio::test]
+async fn sends_the_builtin_emails_when_the_templates_are_invalid() {
+    let server = TestServer::start().await;
+    let id = server.create_tenant(None).await;
+    let mut tenant = (*server.db.get_tenant(&id).await.unwrap().unwrap()).clone();
+    tenant.email_templates = "not json".to_owned();
+    server.db.upsert_tenant(&tenant).await.unwrap();
+
+    let client = server.tenant_client(&id);
+    server.register_with(&client, &Account::random()).await;
+}
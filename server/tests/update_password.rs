@@ -1,58 +1,35 @@
-#![allow(unused_must_use)]
 mod common;
 
-use common::{Printer, Scanner};
-use fake::{Fake, faker::internet::en::Password};
-use reqwest::header;
-use std::io::Write;
+use common::{PASSWORD, TestServer};
+use reqwest::StatusCode;
+use serde_json::json;
 
-#[test]
-fn main() -> Result<(), reqwest::Error> {
-    const SOCKET: &str = "http://127.0.0.1:8080";
-    let is_auto = std::env::var("AUTO").ok().map(|v| v.as_str() == "true").unwrap_or(false);
-    let client = reqwest::blocking::Client::builder()
-        .user_agent(fake::faker::internet::en::UserAgent().fake::<String>())
-        .build()
-        .unwrap_or_default();
+#[tokio::test]
+async fn updates_the_password() {
+    let server = TestServer::start().await;
+    let (client, account) = server.register().await;
 
-    // for io
-    let mut token = Scanner::new(std::io::stdin().lock());
-    let mut out = Printer::new();
-
-    out.write("Enter cookie: ");
-    let cookies = token.next_line::<String>();
-
-    loop {
-        out.write("Enter old password: ");
-        let old_password = token.next_line::<String>();
-        let new_password = if is_auto {
-            Password(8..128).fake::<String>()
-        } else {
-            out.write("Enter new password: ");
-            token.next_line::<String>()
-        };
+    let new_password = "N3wPassword!x";
+    let res = client
+        .post(
+            "/api/settings/password",
+            json!({ "old_password": PASSWORD, "new_password": new_password }),
+        )
+        .await;
+    assert!(res.status.is_success(), "{}", res.body);
+    let res = server.client().login("username", &account.username, new_password).await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.body);
+}
 
-        let body1 =
-            format!(r#"{{"old_password": "{old_password}", "new_password": "{new_password}"}}"#);
-        let res1 = client
-            .post(format!("{}/api/settings/password", SOCKET))
-            .header(header::CONTENT_TYPE, "application/json")
-            .header(header::COOKIE, &cookies)
-            .body(body1)
-            .send();
-        match res1 {
-            Ok(v) => {
-                if v.status().is_client_error() {
-                    writeln!(out.inner, "{:?}", v.text()?);
-                } else {
-                    break;
-                }
-            }
-            Err(e) => {
-                writeln!(out.inner, "{e:?}");
-            }
-        }
-    }
+#[tokio::test]
+async fn rejects_a_weak_password() {
+    let server = TestServer::start().await;
+    let (client, account) = server.register().await;
 
-    Ok(())
+    let res = client
+        .post("/api/settings/password", json!({ "old_password": PASSWORD, "new_password": "weak" }))
+        .await;
+    assert!(res.status.is_client_error(), "{}", res.body);
+    let res = server.client().login("username", &account.username, PASSWORD).await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.body);
 }
@@ -1,61 +1,41 @@
-#![allow(unused_must_use)]
 mod common;
 
-use common::{Printer, Scanner};
-use fake::{Fake, faker::internet::en::Username};
-use reqwest::header;
-use std::io::Write;
+use common::{Account, PASSWORD, TestServer};
+use reqwest::StatusCode;
+use serde_json::json;
 
-#[test]
-fn main() -> Result<(), reqwest::Error> {
-    const SOCKET: &str = "http://127.0.0.1:8080";
-    let is_auto = std::env::var("AUTO").ok().map(|v| v.as_str() == "true").unwrap_or(false);
-    let client = reqwest::blocking::Client::builder()
-        .user_agent(fake::faker::internet::en::UserAgent().fake::<String>())
-        .build()
-        .unwrap_or_default();
+#[tokio::test]
+async fn updates_the_username() {
+    let server = TestServer::start().await;
+    let (client, account) = server.register().await;
+    let new_username = Account::random().username;
 
-    // for io
-    let mut token = Scanner::new(std::io::stdin().lock());
-    let mut out = Printer::new();
-
-    out.write("Enter cookie: ");
-    let cookies = token.next_line::<String>();
-
-    loop {
-        let new_username = if is_auto {
-            Username().fake::<String>().replace("_", ".")
-        } else {
-            out.write("Enter username: ");
-            token.next_line::<String>()
-        };
-        out.write("Enter password: ");
-        let password = token.next_line::<String>();
+    let res = client
+        .post(
+            "/api/settings/username",
+            json!({ "new_username": new_username, "password": PASSWORD }),
+        )
+        .await;
+    assert!(res.status.is_success(), "{}", res.body);
+    assert_eq!(client.get("/api/settings").await.body["username"], new_username);
+    let res = server.client().login("username", &account.username, PASSWORD).await;
+    assert!(res.status.is_client_error(), "{}", res.body);
+    let res = server.client().login("username", &new_username, PASSWORD).await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.body);
+}
 
-        let body1 = format!(r#"{{"new_username": "{new_username}", "password": "{password}"}}"#);
-        if is_auto {
-            out.write(&body1);
-            out.write("\n");
-        }
-        let res1 = client
-            .post(format!("{}/api/settings/username", SOCKET))
-            .header(header::CONTENT_TYPE, "application/json")
-            .header(header::COOKIE, &cookies)
-            .body(body1)
-            .send();
-        match res1 {
-            Ok(v) => {
-                if v.status().is_client_error() {
-                    writeln!(out.inner, "{:?}", v.text()?);
-                } else {
-                    break;
-                }
-            }
-            Err(e) => {
-                writeln!(out.inner, "{e:?}");
-            }
-        }
-    }
+#[tokio::test]
+async fn rejects_a_taken_username() {
+    let server = TestServer::start().await;
+    let (client, account) = server.register().await;
+    let (_, other) = server.register().await;
 
-    Ok(())
+    let res = client
+        .post(
+            "/api/settings/username",
+            json!({ "new_username": other.username, "password": PASSWORD }),
+        )
+        .await;
+    assert!(!res.status.is_success(), "{}", res.body);
+    assert_eq!(client.get("/api/settings").await.body["username"], account.username);
 }
@@ -0,0 +1,212 @@
+use crate::AppError;
+use std::{collections::HashMap, str::FromStr};
+use time::{Date, OffsetDateTime};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct AgeRule {
+    /// users younger than this can't have an account
+    pub minimum_age: Option<u8>,
+    /// users younger than this get a restricted account
+    pub restricted_age: Option<u8>,
+}
+
+/// what an account is allowed to be, given the age of its user
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AgeStatus {
+    Allowed,
+    Restricted,
+}
+
+#[derive(Clone, Debug)]
+pub struct AgePolicy {
+    /// applies to the users without a country and the countries without a rule
+    pub default: AgeRule,
+    /// alpha-2 country code => rule, the unset ages fall back to the default rule
+    pub countries: HashMap<String, AgeRule>,
+    /// number of times users can change their birth date themselves once it has been set
+    pub max_birth_date_changes: i32,
+}
+
+impl Default for AgePolicy {
+    fn default() -> Self {
+        Self { default: AgeRule::default(), countries: HashMap::new(), max_birth_date_changes: 1 }
+    }
+}
+
+impl AgePolicy {
+    /// builds the policy from key/value lookups (normally environment variables)
+    ///
+    /// country rules are comma separated `country:age` pairs (e.g. `KR:14,US:13`),
+    /// every unset key falls back to `AgePolicy::default()`, all the problems found are returned
+    /// together
+    pub fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> Result<Self, Vec<String>> {
+        let mut policy = Self::default();
+        let mut errors = Vec::new();
+
+        let age = |key: &str, v: &str, errors: &mut Vec<String>| match v.trim().parse::<u8>() {
+            Ok(n) => Some(n),
+            Err(_) => {
+                errors.push(format!("{key} `{v}` is not an age"));
+                None
+            }
+        };
+        if let Some(v) = lookup("MINIMUM_AGE").filter(|v| !v.is_empty()) {
+            policy.default.minimum_age = age("MINIMUM_AGE", &v, &mut errors);
+        }
+        if let Some(v) = lookup("RESTRICTED_AGE").filter(|v| !v.is_empty()) {
+            policy.default.restricted_age = age("RESTRICTED_AGE", &v, &mut errors);
+        }
+        for key in ["MINIMUM_AGE_BY_COUNTRY", "RESTRICTED_AGE_BY_COUNTRY"] {
+            let Some(v) = lookup(key) else { continue };
+            for pair in v.split(',').filter(|v| !v.trim().is_empty()) {
+                let Some((country, v)) = pair.split_once(':') else {
+                    errors.push(format!("{key} `{pair}` is not a country:age pair"));
+                    continue;
+                };
+                let Some(code) = country_code(country) else {
+                    errors.push(format!("{key} `{country}` is not a country"));
+                    continue;
+                };
+                let rule = policy.countries.entry(code.to_string()).or_default();
+                match key {
+                    "MINIMUM_AGE_BY_COUNTRY" => rule.minimum_age = age(key, v, &mut errors),
+                    _ => rule.restricted_age = age(key, v, &mut errors),
+                }
+            }
+        }
+        if let Some(v) = lookup("BIRTH_DATE_MAX_CHANGES") {
+            match v.parse::<i32>() {
+                Ok(n) if n >= 0 => policy.max_birth_date_changes = n,
+                _ => errors.push(format!("BIRTH_DATE_MAX_CHANGES `{v}` is not a number")),
+            }
+        }
+
+        if errors.is_empty() { Ok(policy) } else { Err(errors) }
+    }
+
+    /// returns the rule for users of `country` (as stored, any name celes understands)
+    pub fn rule_for(&self, country: Option<&str>) -> AgeRule {
+        match country.and_then(country_code).and_then(|code| self.countries.get(code)) {
+            Some(rule) => AgeRule {
+                minimum_age: rule.minimum_age.or(self.default.minimum_age),
+                restricted_age: rule.restricted_age.or(self.default.restricted_age),
+            },
+            None => self.default,
+        }
+    }
+
+    /// returns true if some age has to be checked, users then have to give their birth date when
+    /// they register
+    pub fn is_enforced(&self) -> bool {
+        self.default != AgeRule::default() || !self.countries.is_empty()
+    }
+
+    /// returns true if the account of someone born on `birth_date` in `country` has to be
+    /// restricted on `now`, users under the minimum age included
+    pub fn restricts(
+        &self,
+        birth_date: OffsetDateTime,
+        country: Option<&str>,
+        now: OffsetDateTime,
+    ) -> bool {
+        !matches!(self.check(birth_date, country, now), Ok(AgeStatus::Allowed))
+    }
+
+    /// checks that someone born on `birth_date` in `country` can have an account on `now`
+    pub fn check(
+        &self,
+        birth_date: OffsetDateTime,
+        country: Option<&str>,
+        now: OffsetDateTime,
+    ) -> Result<AgeStatus, AppError> {
+        let today = now.to_offset(birth_date.offset()).date();
+        self.check_on(birth_date.date(), country, today)
+    }
+
+    fn check_on(
+        &self,
+        birth_date: Date,
+        country: Option<&str>,
+        today: Date,
+    ) -> Result<AgeStatus, AppError> {
+        let rule = self.rule_for(country);
+        let age = age_on(birth_date, today);
+        if rule.minimum_age.is_some_and(|min| age < min as i32) {
+            return Err(AppError::Forbidden("You don't meet the minimum age to use this service"));
+        }
+        if rule.restricted_age.is_some_and(|min| age < min as i32) {
+            return Ok(AgeStatus::Restricted);
+        }
+        Ok(AgeStatus::Allowed)
+    }
+}
+
+/// returns the age in full years of someone born on `birth_date`
+pub fn age_on(birth_date: Date, today: Date) -> i32 {
+    let mut age = today.year() - birth_date.year();
+    if (today.month() as u8, today.day()) < (birth_date.month() as u8, birth_date.day()) {
+        age -= 1;
+    }
+    age
+}
+
+// users store the long name of their country (see `validation::is_country_valid`), which
+// `Country::from_str` doesn't parse when it has spaces
+fn country_code(country: &str) -> Option<&'static str> {
+    let country = country.trim();
+    celes::Country::from_str(country)
+        .ok()
+        .or_else(|| celes::Country::get_countries().into_iter().find(|c| c.long_name == country))
+        .map(|c| c.alpha2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::Month;
+
+    fn date(year: i32, month: Month, day: u8) -> Date {
+        Date::from_calendar_date(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn age_in_full_years() {
+        let birth = date(2010, Month::June, 15);
+        assert_eq!(age_on(birth, date(2024, Month::June, 14)), 13);
+        assert_eq!(age_on(birth, date(2024, Month::June, 15)), 14);
+        assert_eq!(age_on(date(2008, Month::February, 29), date(2026, Month::February, 28)), 17);
+        assert_eq!(age_on(date(2008, Month::February, 29), date(2026, Month::March, 1)), 18);
+    }
+
+    #[test]
+    fn policy_from_lookup() {
+        let policy = AgePolicy::from_lookup(|key| match key {
+            "MINIMUM_AGE" => Some("13".to_string()),
+            "RESTRICTED_AGE" => Some("18".to_string()),
+            "MINIMUM_AGE_BY_COUNTRY" => Some("KR:14, DE:16".to_string()),
+            "RESTRICTED_AGE_BY_COUNTRY" => Some("KR:19".to_string()),
+            _ => None,
+        })
+        .unwrap();
+        let today = date(2024, Month::January, 1);
+        let born = |age: i32| date(2024 - age, Month::January, 1);
+
+        assert!(policy.check_on(born(12), None, today).is_err());
+        assert_eq!(policy.check_on(born(13), None, today).ok(), Some(AgeStatus::Restricted));
+        assert_eq!(policy.check_on(born(18), None, today).ok(), Some(AgeStatus::Allowed));
+        assert!(policy.check_on(born(13), Some("KR"), today).is_err());
+        assert_eq!(policy.check_on(born(18), Some("KR"), today).ok(), Some(AgeStatus::Restricted));
+        let korea = crate::validation::is_country_valid("KR").unwrap();
+        assert_eq!(policy.check_on(born(18), Some(&korea), today).ok(), Some(AgeStatus::Restricted));
+        assert_eq!(policy.check_on(born(16), Some("DE"), today).ok(), Some(AgeStatus::Restricted));
+        assert_eq!(policy.max_birth_date_changes, 1);
+
+        let errors = AgePolicy::from_lookup(|key| match key {
+            "MINIMUM_AGE" => Some("-1".to_string()),
+            "MINIMUM_AGE_BY_COUNTRY" => Some("XX:13,KR".to_string()),
+            _ => None,
+        })
+        .unwrap_err();
+        assert_eq!(errors.len(), 3);
+    }
+}
@@ -0,0 +1,724 @@
+use crate::{
+    age::AgePolicy,
+    session::{BindingPolicy, CookiePolicy, SessionLimit},
+};
+use lettre::message::Mailbox;
+use std::{collections::HashMap, net::SocketAddr, path::PathBuf, time::Duration};
+
+/// settings of this deployment, read from a toml file and overridden by environment variables
+///
+/// nothing reads it globally, it is handed to whatever needs it (e.g. `server::AppState`)
+#[derive(Clone)]
+pub struct Config {
+    pub socket: SocketAddr,
+    pub database_url: String,
+    pub service_name: String,
+    /// without trailing slash
+    pub service_domain: String,
+    pub secret_key: String,
+    /// retired secret keys, still accepted when verifying signatures
+    pub previous_secret_keys: Vec<String>,
+    pub mail: MailConfig,
+    /// `None` when google login isn't set up
+    pub google: Option<GoogleConfig>,
+    pub bucket: BucketConfig,
+    pub cookies: CookiePolicy,
+    pub binding: BindingPolicy,
+    pub session_limit: SessionLimit,
+    pub age: AgePolicy,
+    /// how long an impersonation session lasts
+    pub impersonation_duration: Duration,
+    /// number of health samples (one per second) kept for backfilling dashboards
+    pub health_history_size: usize,
+    /// how long a readiness result is reused
+    pub readiness_cache_duration: Duration,
+    /// bearer token required to scrape the metrics, which are public without it
+    pub metrics_token: Option<String>,
+    pub maintenance: MaintenanceConfig,
+}
+
+#[derive(Clone)]
+pub struct MailConfig {
+    pub noreply_email: Mailbox,
+    pub transport: MailTransport,
+    /// directory of the templates overriding the built-in ones, see `crate::mail::MailTemplates`
+    pub templates: Option<PathBuf>,
+}
+
+/// how emails are delivered, see `crate::mail::Mailer`
+#[derive(Clone)]
+pub enum MailTransport {
+    Smtp {
+        host: String,
+        key: String,
+    },
+    /// written to a maildir instead of being sent
+    Maildir {
+        directory: PathBuf,
+    },
+    /// kept in memory, for tests
+    Memory,
+}
+
+#[derive(Clone)]
+pub struct GoogleConfig {
+    pub client_id: String,
+    pub client_secret: String,
+}
+
+#[derive(Clone)]
+pub struct BucketConfig {
+    pub id: String,
+    pub name: String,
+    pub access_key: String,
+    pub secret_key: String,
+    pub endpoint: String,
+    pub region: String,
+    pub public_url: String,
+}
+
+/// periodic removal of stale data, see `server::maintenance`
+#[derive(Clone, Debug)]
+pub struct MaintenanceConfig {
+    /// time between two runs
+    pub interval: Duration,
+    pub deleted_users_retention: Duration,
+    pub registrant_max_age: Duration,
+    pub audit_retention: Duration,
+}
+
+#[derive(Default, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawConfig {
+    socket: Option<String>,
+    database_url: Option<String>,
+    service_name: Option<String>,
+    service_domain: Option<String>,
+    secret_key: Option<String>,
+    previous_secret_keys: Option<Vec<String>>,
+    #[serde(default)]
+    mail: RawMailConfig,
+    #[serde(default)]
+    google: RawGoogleConfig,
+    #[serde(default)]
+    bucket: RawBucketConfig,
+    metrics_token: Option<String>,
+    impersonation_duration: Option<u64>,
+    health_history_size: Option<u64>,
+    readiness_cache_duration: Option<u64>,
+    #[serde(default)]
+    cookies: RawCookiesConfig,
+    #[serde(default)]
+    binding: RawBindingConfig,
+    #[serde(default)]
+    session_limit: RawSessionLimitConfig,
+    #[serde(default)]
+    age: RawAgeConfig,
+    #[serde(default)]
+    maintenance: RawMaintenanceConfig,
+}
+
+#[derive(Default, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawMailConfig {
+    noreply_email: Option<String>,
+    transport: Option<String>,
+    directory: Option<String>,
+    templates: Option<String>,
+    smtp_host: Option<String>,
+    smtp_key: Option<String>,
+}
+
+#[derive(Default, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawGoogleConfig {
+    client_id: Option<String>,
+    client_secret: Option<String>,
+}
+
+#[derive(Default, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawBucketConfig {
+    id: Option<String>,
+    name: Option<String>,
+    access_key: Option<String>,
+    secret_key: Option<String>,
+    endpoint: Option<String>,
+    region: Option<String>,
+    public_url: Option<String>,
+}
+
+/// durations are in seconds, like their environment variables
+#[derive(Default, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawCookiesConfig {
+    prefix: Option<String>,
+    domain: Option<String>,
+    path: Option<String>,
+    same_site: Option<String>,
+    secure: Option<bool>,
+    cache_duration: Option<u64>,
+    refresh_window: Option<u64>,
+    session_lifetime: Option<u64>,
+    session_idle_timeout: Option<u64>,
+    session_absolute_lifetime: Option<u64>,
+    remember_me_lifetime: Option<u64>,
+    remember_me_idle_timeout: Option<u64>,
+    remember_me_absolute_lifetime: Option<u64>,
+}
+
+#[derive(Default, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawBindingConfig {
+    mode: Option<String>,
+    ipv4_prefix: Option<RawLimit>,
+    ipv6_prefix: Option<RawLimit>,
+    user_agent: Option<bool>,
+    action: Option<String>,
+}
+
+#[derive(Default, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawSessionLimitConfig {
+    max_sessions: Option<RawLimit>,
+    max_admin_sessions: Option<RawLimit>,
+    strategy: Option<String>,
+}
+
+#[derive(Default, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawAgeConfig {
+    minimum_age: Option<u8>,
+    restricted_age: Option<u8>,
+    birth_date_max_changes: Option<u32>,
+    /// country => rule, e.g. `[age.countries.KR]`
+    #[serde(default)]
+    countries: HashMap<String, RawAgeRule>,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawAgeRule {
+    minimum_age: Option<u8>,
+    restricted_age: Option<u8>,
+}
+
+#[derive(Default, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawMaintenanceConfig {
+    interval: Option<u64>,
+    deleted_users_retention: Option<u64>,
+    unverified_registrant_max_age: Option<u64>,
+    audit_retention: Option<u64>,
+}
+
+/// a number, or `"off"` to disable what it limits
+#[derive(serde::Deserialize)]
+#[serde(untagged)]
+enum RawLimit {
+    Number(u64),
+    Text(String),
+}
+
+impl std::fmt::Display for RawLimit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RawLimit::Number(v) => write!(f, "{v}"),
+            RawLimit::Text(v) => f.write_str(v),
+        }
+    }
+}
+
+impl RawConfig {
+    /// environment variable => value of the typed settings found in the file
+    ///
+    /// they are parsed along with the environment, which takes precedence
+    fn settings(&self) -> HashMap<&'static str, String> {
+        fn text(v: Option<&impl ToString>) -> Option<String> {
+            v.map(ToString::to_string)
+        }
+        let (cookies, binding, limit) = (&self.cookies, &self.binding, &self.session_limit);
+        let (age, maintenance) = (&self.age, &self.maintenance);
+        // `country:age` pairs, the format of the environment
+        let by_country = |age: fn(&RawAgeRule) -> Option<u8>| {
+            let pairs = age_pairs(&self.age.countries, age);
+            (!pairs.is_empty()).then_some(pairs)
+        };
+
+        [
+            ("COOKIE_PREFIX", text(cookies.prefix.as_ref())),
+            ("COOKIE_DOMAIN", text(cookies.domain.as_ref())),
+            ("COOKIE_PATH", text(cookies.path.as_ref())),
+            ("COOKIE_SAMESITE", text(cookies.same_site.as_ref())),
+            ("COOKIE_SECURE", text(cookies.secure.as_ref())),
+            ("SESSION_CACHE_DURATION", text(cookies.cache_duration.as_ref())),
+            ("SESSION_REFRESH_WINDOW", text(cookies.refresh_window.as_ref())),
+            ("SESSION_LIFETIME", text(cookies.session_lifetime.as_ref())),
+            ("SESSION_IDLE_TIMEOUT", text(cookies.session_idle_timeout.as_ref())),
+            ("SESSION_ABSOLUTE_LIFETIME", text(cookies.session_absolute_lifetime.as_ref())),
+            ("REMEMBER_ME_LIFETIME", text(cookies.remember_me_lifetime.as_ref())),
+            ("REMEMBER_ME_IDLE_TIMEOUT", text(cookies.remember_me_idle_timeout.as_ref())),
+            ("REMEMBER_ME_ABSOLUTE_LIFETIME", text(cookies.remember_me_absolute_lifetime.as_ref())),
+            ("SESSION_BIND_MODE", text(binding.mode.as_ref())),
+            ("SESSION_BIND_IPV4_PREFIX", text(binding.ipv4_prefix.as_ref())),
+            ("SESSION_BIND_IPV6_PREFIX", text(binding.ipv6_prefix.as_ref())),
+            ("SESSION_BIND_USER_AGENT", text(binding.user_agent.as_ref())),
+            ("SESSION_BIND_ACTION", text(binding.action.as_ref())),
+            ("SESSION_LIMIT", text(limit.max_sessions.as_ref())),
+            ("SESSION_LIMIT_ADMIN", text(limit.max_admin_sessions.as_ref())),
+            ("SESSION_LIMIT_STRATEGY", text(limit.strategy.as_ref())),
+            ("MINIMUM_AGE", text(age.minimum_age.as_ref())),
+            ("RESTRICTED_AGE", text(age.restricted_age.as_ref())),
+            ("MINIMUM_AGE_BY_COUNTRY", by_country(|rule| rule.minimum_age)),
+            ("RESTRICTED_AGE_BY_COUNTRY", by_country(|rule| rule.restricted_age)),
+            ("BIRTH_DATE_MAX_CHANGES", text(age.birth_date_max_changes.as_ref())),
+            ("IMPERSONATION_DURATION", text(self.impersonation_duration.as_ref())),
+            ("HEALTH_HISTORY_SIZE", text(self.health_history_size.as_ref())),
+            ("READINESS_CACHE_DURATION", text(self.readiness_cache_duration.as_ref())),
+            ("MAINTENANCE_INTERVAL", text(maintenance.interval.as_ref())),
+            ("DELETED_USERS_RETENTION", text(maintenance.deleted_users_retention.as_ref())),
+            (
+                "UNVERIFIED_REGISTRANT_MAX_AGE",
+                text(maintenance.unverified_registrant_max_age.as_ref()),
+            ),
+            ("AUDIT_RETENTION", text(maintenance.audit_retention.as_ref())),
+        ]
+        .into_iter()
+        .filter_map(|(key, value)| Some((key, value?)))
+        .collect()
+    }
+
+    /// (environment variable, key in the file, value) of every string setting
+    fn fields(&mut self) -> [(&'static str, &'static str, &mut Option<String>); 21] {
+        [
+            ("SOCKET", "socket", &mut self.socket),
+            ("DATABASE_URL", "database_url", &mut self.database_url),
+            ("SERVICE_NAME", "service_name", &mut self.service_name),
+            ("SERVICE_DOMAIN", "service_domain", &mut self.service_domain),
+            ("SECRET_KEY", "secret_key", &mut self.secret_key),
+            ("NOREPLY_EMAIL", "mail.noreply_email", &mut self.mail.noreply_email),
+            ("MAIL_TRANSPORT", "mail.transport", &mut self.mail.transport),
+            ("MAIL_DIRECTORY", "mail.directory", &mut self.mail.directory),
+            ("MAIL_TEMPLATES", "mail.templates", &mut self.mail.templates),
+            ("SMTP_HOST", "mail.smtp_host", &mut self.mail.smtp_host),
+            ("SMTP_KEY", "mail.smtp_key", &mut self.mail.smtp_key),
+            ("GOOGLE_CLIENT_ID", "google.client_id", &mut self.google.client_id),
+            ("GOOGLE_CLIENT_SECRET", "google.client_secret", &mut self.google.client_secret),
+            ("BUCKET_ID", "bucket.id", &mut self.bucket.id),
+            ("BUCKET_NAME", "bucket.name", &mut self.bucket.name),
+            ("BUCKET_ACCESS_KEY", "bucket.access_key", &mut self.bucket.access_key),
+            ("BUCKET_SECRET_KEY", "bucket.secret_key", &mut self.bucket.secret_key),
+            ("BUCKET_ENDPOINT", "bucket.endpoint", &mut self.bucket.endpoint),
+            ("BUCKET_REGION", "bucket.region", &mut self.bucket.region),
+            ("BUCKET_PUBLIC_URL", "bucket.public_url", &mut self.bucket.public_url),
+            ("METRICS_TOKEN", "metrics_token", &mut self.metrics_token),
+        ]
+    }
+}
+
+impl Config {
+    /// returns the keys that signatures are verified with (the current one first)
+    ///
+    /// the previous ones let `secret_key` be rotated without logging everyone out
+    pub fn verification_keys(&self) -> impl Iterator<Item = &[u8]> {
+        std::iter::once(self.secret_key.as_bytes())
+            .chain(self.previous_secret_keys.iter().map(String::as_bytes))
+    }
+
+    /// loads the file at `CONFIG_FILE` (`config.toml` when it exists otherwise) and applies the
+    /// environment on top of it
+    pub fn load() -> Result<Self, Vec<String>> {
+        let file = match std::env::var("CONFIG_FILE") {
+            Ok(path) => Some(
+                std::fs::read_to_string(&path)
+                    .map_err(|e| vec![format!("config file `{path}` can't be read: {e}")])?,
+            ),
+            Err(_) => std::fs::read_to_string("config.toml").ok(),
+        };
+        Self::from_sources(file.as_deref(), |key| std::env::var(key).ok())
+    }
+
+    /// builds the configuration from the content of a toml file and key/value lookups (normally
+    /// environment variables), which take precedence
+    ///
+    /// all the problems found are returned together
+    pub fn from_sources(
+        file: Option<&str>,
+        lookup: impl Fn(&str) -> Option<String>,
+    ) -> Result<Self, Vec<String>> {
+        let mut raw = match file {
+            Some(content) => toml::from_str::<RawConfig>(content)
+                .map_err(|e| vec![format!("config file: {}", e.message())])?,
+            None => RawConfig::default(),
+        };
+        for (key, _, value) in raw.fields() {
+            if let Some(v) = lookup(key) {
+                *value = Some(v);
+            }
+        }
+        if let Some(v) = lookup("PREVIOUS_SECRET_KEYS") {
+            raw.previous_secret_keys = Some(v.split(',').map(str::to_owned).collect());
+        }
+        let settings = raw.settings();
+        let lookup = |key: &str| lookup(key).or_else(|| settings.get(key).cloned());
+
+        let previous_secret_keys = raw.previous_secret_keys.take().unwrap_or_default();
+        let mut values = raw
+            .fields()
+            .map(|(key, name, value)| (key, (name, value.take().filter(|v| !v.trim().is_empty()))))
+            .into_iter()
+            .collect::<HashMap<_, _>>();
+        let mut errors = Vec::new();
+        // returns the value of `key` along with its name for the error messages
+        let mut get = |key: &str, required: bool, errors: &mut Vec<String>| {
+            let (name, value) = values.remove(key).unwrap();
+            if value.is_none() && required {
+                errors.push(format!("{name} (`{key}`) is not set"));
+            }
+            (format!("{name} (`{key}`)"), value.unwrap_or_default())
+        };
+
+        let (name, socket) = get("SOCKET", true, &mut errors);
+        let socket = socket.parse().unwrap_or_else(|_| {
+            if !socket.is_empty() {
+                errors.push(format!("{name} `{socket}` is not a socket address"));
+            }
+            SocketAddr::from(([0, 0, 0, 0], 0))
+        });
+        let (name, database_url) = get("DATABASE_URL", true, &mut errors);
+        if !database_url.is_empty()
+            && !(database_url.starts_with("postgres://")
+                || database_url.starts_with("postgresql://"))
+        {
+            errors.push(format!("{name} is not a postgres url"));
+        }
+        let (_, service_name) = get("SERVICE_NAME", true, &mut errors);
+        let (name, service_domain) = get("SERVICE_DOMAIN", true, &mut errors);
+        if !service_domain.is_empty()
+            && !(service_domain.starts_with("https://") || service_domain.starts_with("http://"))
+        {
+            errors.push(format!("{name} `{service_domain}` is not an http(s) url"));
+        }
+        let (_, secret_key) = get("SECRET_KEY", true, &mut errors);
+
+        let (name, noreply_email) = get("NOREPLY_EMAIL", true, &mut errors);
+        let noreply_email = noreply_email.parse::<Mailbox>().map_err(|_| {
+            if !noreply_email.is_empty() {
+                errors.push(format!("{name} `{noreply_email}` is not an email address"));
+            }
+        });
+        let (name, transport) = get("MAIL_TRANSPORT", false, &mut errors);
+        let transport = match transport.as_str() {
+            "" | "smtp" => MailTransport::Smtp {
+                host: get("SMTP_HOST", true, &mut errors).1,
+                key: get("SMTP_KEY", true, &mut errors).1,
+            },
+            "maildir" => MailTransport::Maildir {
+                directory: PathBuf::from(get("MAIL_DIRECTORY", true, &mut errors).1),
+            },
+            "memory" => MailTransport::Memory,
+            _ => {
+                errors.push(format!("{name} `{transport}` is not one of smtp, maildir, memory"));
+                MailTransport::Memory
+            }
+        };
+        let (_, templates) = get("MAIL_TEMPLATES", false, &mut errors);
+        let templates = (!templates.is_empty()).then(|| PathBuf::from(templates));
+
+        let (_, client_id) = get("GOOGLE_CLIENT_ID", false, &mut errors);
+        let (_, client_secret) = get("GOOGLE_CLIENT_SECRET", false, &mut errors);
+        let google = match (client_id.is_empty(), client_secret.is_empty()) {
+            (true, true) => None,
+            (false, false) => Some(GoogleConfig { client_id, client_secret }),
+            _ => {
+                errors.push(
+                    "google.client_id (`GOOGLE_CLIENT_ID`) and google.client_secret \
+                    (`GOOGLE_CLIENT_SECRET`) have to be set together"
+                        .to_string(),
+                );
+                None
+            }
+        };
+
+        let region = get("BUCKET_REGION", false, &mut errors).1;
+        let bucket = BucketConfig {
+            id: get("BUCKET_ID", true, &mut errors).1,
+            name: get("BUCKET_NAME", true, &mut errors).1,
+            access_key: get("BUCKET_ACCESS_KEY", true, &mut errors).1,
+            secret_key: get("BUCKET_SECRET_KEY", true, &mut errors).1,
+            endpoint: get("BUCKET_ENDPOINT", true, &mut errors).1,
+            region: if region.is_empty() { "auto".to_string() } else { region },
+            public_url: get("BUCKET_PUBLIC_URL", true, &mut errors).1,
+        };
+
+        // the typed settings below are read through `lookup`, so the environment overrides the file
+        let cookies = checked(CookiePolicy::from_lookup(lookup), &mut errors);
+        let binding = checked(BindingPolicy::from_lookup(lookup), &mut errors);
+        let session_limit = checked(SessionLimit::from_lookup(lookup), &mut errors);
+        let age = checked(AgePolicy::from_lookup(lookup), &mut errors);
+        let number = |key: &str, default: u64, errors: &mut Vec<String>| match lookup(key) {
+            Some(v) if !v.trim().is_empty() => v.trim().parse::<u64>().unwrap_or_else(|_| {
+                errors.push(format!("{key} `{v}` is not a number"));
+                default
+            }),
+            _ => default,
+        };
+        let secs = |key: &str, default: u64, errors: &mut Vec<String>| {
+            Duration::from_secs(number(key, default, errors))
+        };
+        let impersonation_duration = secs("IMPERSONATION_DURATION", 900, &mut errors);
+        let health_history_size = number("HEALTH_HISTORY_SIZE", 300, &mut errors) as usize;
+        let readiness_cache_duration = secs("READINESS_CACHE_DURATION", 5, &mut errors);
+        let metrics_token =
+            Some(get("METRICS_TOKEN", false, &mut errors).1).filter(|v| !v.is_empty());
+        let maintenance = MaintenanceConfig {
+            interval: secs("MAINTENANCE_INTERVAL", 3600, &mut errors),
+            deleted_users_retention: secs("DELETED_USERS_RETENTION", 30 * 86400, &mut errors),
+            registrant_max_age: secs("UNVERIFIED_REGISTRANT_MAX_AGE", 900, &mut errors),
+            audit_retention: secs("AUDIT_RETENTION", 365 * 86400, &mut errors),
+        };
+        for (key, is_zero) in [
+            ("MAINTENANCE_INTERVAL", maintenance.interval.is_zero()),
+            ("IMPERSONATION_DURATION", impersonation_duration.is_zero()),
+            ("HEALTH_HISTORY_SIZE", health_history_size == 0),
+        ] {
+            if is_zero {
+                errors.push(format!("{key} must be greater than 0"));
+            }
+        }
+
+        match (noreply_email, cookies, binding, session_limit, age) {
+            (Ok(noreply_email), Some(cookies), Some(binding), Some(session_limit), Some(age))
+                if errors.is_empty() =>
+            {
+                Ok(Self {
+                    socket,
+                    database_url,
+                    service_name,
+                    service_domain: service_domain.trim_end_matches('/').to_string(),
+                    secret_key,
+                    previous_secret_keys: previous_secret_keys
+                        .into_iter()
+                        .map(|v| v.trim().to_string())
+                        .filter(|v| !v.is_empty())
+                        .collect(),
+                    mail: MailConfig { noreply_email, transport, templates },
+                    google,
+                    bucket,
+                    cookies,
+                    binding,
+                    session_limit,
+                    age,
+                    impersonation_duration,
+                    health_history_size,
+                    readiness_cache_duration,
+                    metrics_token,
+                    maintenance,
+                })
+            }
+            _ => Err(errors),
+        }
+    }
+}
+
+#[cfg(test)]
+impl Config {
+    /// a valid configuration whose settings can be overridden by `lookup`
+    pub(crate) fn for_tests(lookup: impl Fn(&str) -> Option<String>) -> Self {
+        Self::from_sources(Some(tests::FILE), lookup).ok().unwrap()
+    }
+}
+
+/// comma separated `country:age` pairs of the countries whose rule sets `age`
+fn age_pairs(countries: &HashMap<String, RawAgeRule>, age: fn(&RawAgeRule) -> Option<u8>) -> String {
+    let mut pairs = countries
+        .iter()
+        .filter_map(|(country, rule)| Some(format!("{country}:{}", age(rule)?)))
+        .collect::<Vec<_>>();
+    pairs.sort();
+    pairs.join(",")
+}
+
+/// moves the problems of a policy into `errors`
+fn checked<T>(result: Result<T, Vec<String>>, errors: &mut Vec<String>) -> Option<T> {
+    result.map_err(|policy_errors| errors.extend(policy_errors)).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    pub(super) const FILE: &str = r#"
+        socket = "127.0.0.1:7878"
+        database_url = "postgres://localhost/basic_auth"
+        service_name = "Basic Auth"
+        service_domain = "https://auth.example.com/"
+        secret_key = "from the file"
+        previous_secret_keys = ["old"]
+
+        [mail]
+        noreply_email = "noreply@example.com"
+        smtp_host = "smtp.example.com"
+        smtp_key = "key"
+
+        [bucket]
+        id = "id"
+        name = "name"
+        access_key = "access"
+        secret_key = "secret"
+        endpoint = "https://bucket.example.com"
+        public_url = "https://cdn.example.com"
+    "#;
+
+    /// the configuration of `FILE` with `lookup` applied on top of it
+    fn load(lookup: impl Fn(&str) -> Option<String>) -> Result<Config, Vec<String>> {
+        Config::from_sources(Some(FILE), lookup)
+    }
+
+    /// `lookup` returning `value` for `key` only
+    fn only(key: &'static str, value: &'static str) -> impl Fn(&str) -> Option<String> {
+        move |k| (k == key).then(|| value.to_string())
+    }
+
+    #[test]
+    fn the_environment_overrides_the_file() {
+        let config = load(only("SECRET_KEY", "from the env")).ok().unwrap();
+        assert_eq!(config.secret_key, "from the env");
+        assert_eq!(config.previous_secret_keys, ["old"]);
+        assert_eq!(config.service_domain, "https://auth.example.com");
+        assert_eq!(config.bucket.region, "auto");
+        assert!(config.google.is_none());
+    }
+
+    #[test]
+    fn all_the_problems_are_reported_at_once() {
+        let errors = load(|key| match key {
+            "SOCKET" => Some("localhost".to_string()),
+            "NOREPLY_EMAIL" => Some("".to_string()),
+            "GOOGLE_CLIENT_ID" => Some("id".to_string()),
+            _ => None,
+        })
+        .err()
+        .unwrap();
+        assert_eq!(errors.len(), 3, "{errors:?}");
+
+        let errors = load(|key| match key {
+            "COOKIE_SAMESITE" => Some("sometimes".to_string()),
+            "MINIMUM_AGE" => Some("-1".to_string()),
+            "MAINTENANCE_INTERVAL" => Some("1h".to_string()),
+            "IMPERSONATION_DURATION" => Some("15 minutes".to_string()),
+            _ => None,
+        })
+        .err()
+        .unwrap();
+        assert_eq!(errors.len(), 4, "{errors:?}");
+    }
+
+    #[test]
+    fn missing_settings_are_listed() {
+        let errors = Config::from_sources(None, |_| None).err().unwrap();
+        assert_eq!(errors.len(), 14, "{errors:?}");
+    }
+
+    #[test]
+    fn unknown_keys_are_rejected() {
+        assert!(Config::from_sources(Some("unknown = 1"), |_| None).is_err());
+        let file = format!("{FILE}\n[cookies]\nunknown = 1");
+        assert!(Config::from_sources(Some(&file), |_| None).is_err());
+    }
+
+    #[test]
+    fn maildir_transport_needs_a_directory() {
+        let config = load(|key| match key {
+            "MAIL_TRANSPORT" => Some("maildir".to_string()),
+            "MAIL_DIRECTORY" => Some("/var/mail/auth".to_string()),
+            _ => None,
+        })
+        .ok()
+        .unwrap();
+        assert!(matches!(config.mail.transport, MailTransport::Maildir { .. }));
+
+        let errors = load(only("MAIL_TRANSPORT", "maildir")).err().unwrap();
+        assert_eq!(errors, ["mail.directory (`MAIL_DIRECTORY`) is not set"]);
+    }
+
+    #[test]
+    fn unset_durations_fall_back_to_their_defaults() {
+        let config = load(|key| match key {
+            "AUDIT_RETENTION" => Some("86400".to_string()),
+            "METRICS_TOKEN" => Some("".to_string()),
+            _ => None,
+        })
+        .ok()
+        .unwrap();
+        assert_eq!(config.maintenance.audit_retention, Duration::from_secs(86400));
+        assert_eq!(config.maintenance.interval, Duration::from_secs(3600));
+        assert_eq!(config.impersonation_duration, Duration::from_secs(900));
+        assert!(config.metrics_token.is_none());
+    }
+
+    #[test]
+    fn zero_is_rejected_where_it_would_disable_the_feature() {
+        for key in ["MAINTENANCE_INTERVAL", "IMPERSONATION_DURATION", "HEALTH_HISTORY_SIZE"] {
+            let errors = load(only(key, "0")).err().unwrap();
+            assert_eq!(errors, [format!("{key} must be greater than 0")]);
+        }
+        let file = format!("health_history_size = 0\n{FILE}");
+        let errors = Config::from_sources(Some(&file), |_| None).err().unwrap();
+        assert_eq!(errors, ["HEALTH_HISTORY_SIZE must be greater than 0"]);
+    }
+
+    #[test]
+    fn typed_sections_are_read_from_the_file() {
+        let file = format!(
+            r#"
+            impersonation_duration = 600
+            metrics_token = "token"
+            {FILE}
+
+            [cookies]
+            prefix = "host"
+            session_idle_timeout = 3600
+
+            [binding]
+            mode = "all"
+            ipv6_prefix = "off"
+            action = "notify"
+
+            [session_limit]
+            max_sessions = 5
+            strategy = "reject"
+
+            [age]
+            restricted_age = 16
+
+            [age.countries.KR]
+            restricted_age = 19
+
+            [maintenance]
+            interval = 60
+            "#
+        );
+        let config = Config::from_sources(Some(&file), |_| None).ok().unwrap();
+        assert_eq!(config.impersonation_duration, Duration::from_secs(600));
+        assert_eq!(config.metrics_token.as_deref(), Some("token"));
+        assert_eq!(config.cookies.prefix, crate::session::CookiePrefix::Host);
+        assert_eq!(config.cookies.standard.idle_timeout, Duration::from_secs(3600));
+        assert_eq!(config.binding.mode, crate::session::BindingMode::All);
+        assert_eq!(config.binding.ipv6_prefix, None);
+        assert_eq!(config.binding.action, crate::session::MismatchAction::Notify);
+        assert_eq!(config.session_limit.max_sessions, Some(5));
+        assert_eq!(config.session_limit.strategy, crate::session::LimitStrategy::Reject);
+        assert_eq!(config.age.rule_for(None).restricted_age, Some(16));
+        assert_eq!(config.age.rule_for(Some("KR")).restricted_age, Some(19));
+        assert_eq!(config.maintenance.interval, Duration::from_secs(60));
+
+        // the environment still takes precedence
+        let config = Config::from_sources(Some(&file), only("SESSION_BIND_MODE", "off")).unwrap();
+        assert_eq!(config.binding.mode, crate::session::BindingMode::Off);
+        let errors = Config::from_sources(Some(&file.replace("\"all\"", "\"some\"")), |_| None)
+            .err()
+            .unwrap();
+        assert_eq!(errors, ["SESSION_BIND_MODE `some` is not one of off, opt_in, all"]);
+    }
+}
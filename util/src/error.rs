@@ -4,6 +4,7 @@ use axum::http::{HeaderMap, StatusCode};
 pub enum AppError {
     BadReq(&'static str),
     Unauthorized(&'static str),
+    Forbidden(&'static str),
     NotFound,
     InvalidData(&'static str),
     InvalidDataFmt(String),
@@ -14,8 +15,14 @@ pub enum AppError {
     UsernameTaken,
     EmailTaken,
     PasswordMismatch,
+    AccountSuspended,
+    PasswordResetRequired,
     SessionExpired,
     InvalidSession(HeaderMap),
+    StepUpRequired,
+    SessionLimitReached(Vec<SessionInfo>),
+    LegalAcceptanceRequired(Vec<PendingDocument>),
+    InvalidCsrfToken,
     ServerError,
 }
 
@@ -29,6 +36,9 @@ impl axum::response::IntoResponse for AppError {
             Self::Unauthorized(e) => {
                 (StatusCode::UNAUTHORIZED, JsonMsg::new(e)).into_response()
             }
+            Self::Forbidden(e) => {
+                (StatusCode::FORBIDDEN, JsonMsg::new(e)).into_response()
+            }
             Self::NotFound => {
                 (StatusCode::NOT_FOUND).into_response()
             }
@@ -59,12 +69,30 @@ impl axum::response::IntoResponse for AppError {
             Self::PasswordMismatch => {
                 (StatusCode::UNAUTHORIZED, JsonMsg::new("Password didn't match")).into_response()
             }
+            Self::AccountSuspended => {
+                (StatusCode::FORBIDDEN, JsonMsg::new("Your account has been suspended")).into_response()
+            }
+            Self::PasswordResetRequired => {
+                (StatusCode::FORBIDDEN, JsonMsg::new("Please reset your password to continue")).into_response()
+            }
             Self::SessionExpired => {
                 (StatusCode::UNAUTHORIZED, JsonMsg::new("Your session has expired")).into_response()
             }
             Self::InvalidSession(set_cookies) => {
                 (StatusCode::UNAUTHORIZED, set_cookies, JsonMsg::new("Invalid Session")).into_response()
             }
+            Self::StepUpRequired => {
+                (StatusCode::UNAUTHORIZED, JsonMsg::new("Please confirm your password to continue")).into_response()
+            }
+            Self::SessionLimitReached(sessions) => {
+                (StatusCode::CONFLICT, axum::Json(SessionLimitMsg::new(sessions))).into_response()
+            }
+            Self::LegalAcceptanceRequired(documents) => {
+                (StatusCode::FORBIDDEN, axum::Json(LegalAcceptanceMsg::new(documents))).into_response()
+            }
+            Self::InvalidCsrfToken => {
+                (StatusCode::FORBIDDEN, JsonMsg::new("Invalid CSRF token")).into_response()
+            }
             Self::ServerError => {
                 (StatusCode::INTERNAL_SERVER_ERROR, JsonMsg::new("Something went wrong")).into_response()
             }
@@ -72,6 +100,58 @@ impl axum::response::IntoResponse for AppError {
     }
 }
 
+/// a session as presented to its owner
+#[derive(PartialEq, Debug, serde::Serialize)]
+pub struct SessionInfo {
+    unsigned_ssid: String,
+    user_agent: Option<String>,
+    created_at: String,
+    last_used: String,
+}
+
+impl From<&crate::session::Session> for SessionInfo {
+    fn from(session: &crate::session::Session) -> Self {
+        Self {
+            unsigned_ssid: session.unsigned_ssid.to_string(),
+            user_agent: session.user_agent.clone(),
+            created_at: session.created_at.to_string(),
+            last_used: session.last_used.to_string(),
+        }
+    }
+}
+
+#[derive(serde::Serialize)]
+struct SessionLimitMsg {
+    message: &'static str,
+    sessions: Vec<SessionInfo>,
+}
+
+impl SessionLimitMsg {
+    fn new(sessions: Vec<SessionInfo>) -> Self {
+        Self { message: "Too many active sessions, choose the sessions to log out", sessions }
+    }
+}
+
+/// a legal document the user has to accept before using the api
+#[derive(PartialEq, Debug, serde::Serialize)]
+pub struct PendingDocument {
+    pub kind: String,
+    pub version: String,
+    pub url: String,
+}
+
+#[derive(serde::Serialize)]
+struct LegalAcceptanceMsg {
+    message: &'static str,
+    documents: Vec<PendingDocument>,
+}
+
+impl LegalAcceptanceMsg {
+    fn new(documents: Vec<PendingDocument>) -> Self {
+        Self { message: "Please accept the updated terms to continue", documents }
+    }
+}
+
 #[derive(serde::Serialize)]
 pub struct JsonMsg<'a> {
     message: &'a str,
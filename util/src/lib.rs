@@ -1,17 +1,14 @@
+pub mod age;
+pub mod clock;
+pub mod config;
 mod error;
 pub mod generate;
 pub mod mail;
+pub mod metrics;
 pub mod oauth;
+pub mod password;
+pub mod permission;
 pub mod session;
 pub mod validation;
 
-pub use error::AppError;
-
-pub static SERVICE_NAME: std::sync::LazyLock<String> =
-    std::sync::LazyLock::new(|| std::env::var("SERVICE_NAME").unwrap());
-
-pub static SERVICE_DOMAIN: std::sync::LazyLock<String> =
-    std::sync::LazyLock::new(|| std::env::var("SERVICE_DOMAIN").unwrap());
-
-pub static SECRET_KEY: std::sync::LazyLock<Vec<u8>> =
-    std::sync::LazyLock::new(|| Vec::from(std::env::var("SECRET_KEY").unwrap()));
+pub use error::{AppError, PendingDocument, SessionInfo};
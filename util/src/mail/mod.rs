@@ -0,0 +1,371 @@
+use crate::{
+    AppError,
+    config::{MailConfig, MailTransport},
+};
+use futures_util::future::BoxFuture;
+use lettre::{
+    AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+    message::{Mailbox, MultiPart},
+    transport::smtp::authentication::Credentials,
+};
+use std::{
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+mod templates;
+
+pub use templates::{MailContent, MailTemplates, accepted_locales};
+
+/// an email to deliver from the noreply address of the server
+#[derive(Clone, Debug, PartialEq)]
+pub struct Email {
+    pub to: String,
+    pub subject: String,
+    pub text: String,
+    /// sent as a multipart/alternative along with `text` when set
+    pub html: Option<String>,
+}
+
+impl Email {
+    fn message(&self, from: &Mailbox) -> Result<Message, AppError> {
+        let builder = Message::builder()
+            .from(from.clone())
+            .to(self.to.parse().map_err(|_| AppError::InvalidEmailFormat)?)
+            .subject(self.subject.clone());
+        match &self.html {
+            Some(html) => {
+                builder.multipart(MultiPart::alternative_plain_html(self.text.clone(), html.clone()))
+            }
+            None => builder.body(self.text.clone()),
+        }
+        .map_err(|e| {
+            tracing::error!("{e:?}");
+            AppError::ServerError
+        })
+    }
+}
+
+/// delivers the emails of the server, the implementation is chosen by `MailConfig::transport`
+pub trait Mailer: Send + Sync {
+    fn deliver(&self, email: Email) -> BoxFuture<'_, Result<(), AppError>>;
+
+    /// checks that emails can be delivered
+    fn test_connection(&self) -> BoxFuture<'_, Result<(), AppError>>;
+}
+
+impl dyn Mailer {
+    /// builds the mailer of `config`
+    pub fn from_config(config: &MailConfig) -> Result<Arc<Self>, String> {
+        Ok(match &config.transport {
+            MailTransport::Smtp { host, key } => {
+                Arc::new(SmtpMailer::new(config.noreply_email.clone(), host, key)?)
+            }
+            MailTransport::Maildir { directory } => {
+                Arc::new(MaildirMailer::new(config.noreply_email.clone(), directory.clone())?)
+            }
+            MailTransport::Memory => Arc::new(MemoryMailer::default()),
+        })
+    }
+
+    // function to send any mail to the given mail address
+    pub async fn send(&self, to_email: String, content: MailContent) -> Result<(), AppError> {
+        to_email.parse::<Mailbox>().map_err(|_| AppError::InvalidEmailFormat)?;
+        let MailContent { subject, text, html } = content;
+        let result = self.deliver(Email { to: to_email, subject, text, html }).await;
+        if result.is_err() {
+            crate::metrics::MAIL_FAILURES.inc();
+        }
+        result
+    }
+}
+
+/// relays the emails through an smtp server
+///
+/// the transport is kept for the lifetime of the server to not drop the connection after each
+/// mail send
+pub struct SmtpMailer {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+impl SmtpMailer {
+    pub fn new(from: Mailbox, host: &str, key: &str) -> Result<Self, String> {
+        let creds = Credentials::new(from.email.to_string(), key.to_string());
+        let transport = AsyncSmtpTransport::<Tokio1Executor>::relay(host)
+            .map_err(|e| format!("invalid smtp host `{host}`: {e}"))?
+            .credentials(creds)
+            .build();
+        Ok(Self { transport, from })
+    }
+}
+
+impl Mailer for SmtpMailer {
+    fn deliver(&self, email: Email) -> BoxFuture<'_, Result<(), AppError>> {
+        Box::pin(async move {
+            let msg = email.message(&self.from)?;
+            // sending the email and wait for completion
+            let transport = self.transport.clone();
+            tokio::spawn(async move {
+                transport
+                    .send(msg)
+                    .await
+                    .map_err(|e| {
+                        tracing::error!("{e:?}");
+                        AppError::ServerError
+                    })
+                    .map(|_| ())
+            })
+            .await
+            .map_err(|e| {
+                tracing::error!("Task join error: {e:?}");
+                AppError::ServerError
+            })
+            .flatten()
+        })
+    }
+
+    fn test_connection(&self) -> BoxFuture<'_, Result<(), AppError>> {
+        Box::pin(async move {
+            match self.transport.test_connection().await {
+                Ok(true) => Ok(()),
+                Ok(false) => Err(AppError::ServerError),
+                Err(e) => {
+                    tracing::error!("{e:?}");
+                    Err(AppError::ServerError)
+                }
+            }
+        })
+    }
+}
+
+/// writes every email as an `.eml` file in the `new` folder of a maildir, for local development
+pub struct MaildirMailer {
+    directory: PathBuf,
+    from: Mailbox,
+}
+
+impl MaildirMailer {
+    /// creates the `tmp`, `new` and `cur` folders of `directory` if needed
+    pub fn new(from: Mailbox, directory: PathBuf) -> Result<Self, String> {
+        for folder in ["tmp", "new", "cur"] {
+            let path = directory.join(folder);
+            std::fs::create_dir_all(&path)
+                .map_err(|e| format!("can't create `{}`: {e}", path.display()))?;
+        }
+        Ok(Self { directory, from })
+    }
+}
+
+impl Mailer for MaildirMailer {
+    fn deliver(&self, email: Email) -> BoxFuture<'_, Result<(), AppError>> {
+        Box::pin(async move {
+            let msg = email.message(&self.from)?;
+            let now = time::OffsetDateTime::now_utc().unix_timestamp_nanos();
+            let name = format!("{now}.{}.eml", uuid::Uuid::new_v4().simple());
+            // written in `tmp` first so that readers never see a partial email
+            let tmp = self.directory.join("tmp").join(&name);
+            let result = async {
+                tokio::fs::write(&tmp, msg.formatted()).await?;
+                tokio::fs::rename(&tmp, self.directory.join("new").join(&name)).await
+            };
+            result.await.map_err(|e| {
+                tracing::error!("{e:?}");
+                AppError::ServerError
+            })
+        })
+    }
+
+    fn test_connection(&self) -> BoxFuture<'_, Result<(), AppError>> {
+        Box::pin(async move {
+            match tokio::fs::metadata(self.directory.join("new")).await {
+                Ok(metadata) if metadata.is_dir() => Ok(()),
+                _ => Err(AppError::ServerError),
+            }
+        })
+    }
+}
+
+/// keeps the emails in memory so that tests can read them
+#[derive(Default)]
+pub struct MemoryMailer {
+    sent: Mutex<Vec<Email>>,
+}
+
+impl MemoryMailer {
+    /// returns the emails delivered so far, oldest first
+    pub fn sent(&self) -> Vec<Email> {
+        self.sent.lock().unwrap().clone()
+    }
+
+    /// returns the last email delivered to `to`
+    pub fn last_sent_to(&self, to: &str) -> Option<Email> {
+        self.sent.lock().unwrap().iter().rev().find(|e| e.to.eq_ignore_ascii_case(to)).cloned()
+    }
+
+    /// forgets the emails delivered so far
+    pub fn clear(&self) {
+        self.sent.lock().unwrap().clear();
+    }
+}
+
+impl Mailer for MemoryMailer {
+    fn deliver(&self, email: Email) -> BoxFuture<'_, Result<(), AppError>> {
+        tracing::debug!("[Mail] to: {}, subject: {}", email.to, email.subject);
+        self.sent.lock().unwrap().push(email);
+        Box::pin(std::future::ready(Ok(())))
+    }
+
+    fn test_connection(&self) -> BoxFuture<'_, Result<(), AppError>> {
+        Box::pin(std::future::ready(Ok(())))
+    }
+}
+
+/// emails sent by the server, each one can be overridden per tenant
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MailKind {
+    /// `{code}`
+    VerificationCode,
+    /// `{email}`
+    EmailVerified,
+    /// `{email}`, `{link}`
+    PasswordReset,
+    /// `{email}`, `{link}`
+    PasswordResetRequired,
+    /// `{email}`
+    PasswordChanged,
+    /// `{email}` (the previous one), `{new_email}`
+    EmailChanged,
+    /// `{ip}`, `{client}`
+    NewActivity,
+    /// `{organization}`, `{inviter}`, `{role}`
+    OrganizationInvite,
+}
+
+impl MailKind {
+    pub const ALL: [MailKind; 8] = [
+        MailKind::VerificationCode,
+        MailKind::EmailVerified,
+        MailKind::PasswordReset,
+        MailKind::PasswordResetRequired,
+        MailKind::PasswordChanged,
+        MailKind::EmailChanged,
+        MailKind::NewActivity,
+        MailKind::OrganizationInvite,
+    ];
+
+    pub fn get_str(&self) -> &'static str {
+        match self {
+            MailKind::VerificationCode => "verification_code",
+            MailKind::EmailVerified => "email_verified",
+            MailKind::PasswordReset => "password_reset",
+            MailKind::PasswordResetRequired => "password_reset_required",
+            MailKind::PasswordChanged => "password_changed",
+            MailKind::EmailChanged => "email_changed",
+            MailKind::NewActivity => "new_activity",
+            MailKind::OrganizationInvite => "organization_invite",
+        }
+    }
+}
+
+impl TryFrom<&str> for MailKind {
+    type Error = AppError;
+
+    fn try_from(kind: &str) -> Result<Self, Self::Error> {
+        MailKind::ALL
+            .into_iter()
+            .find(|k| k.get_str() == kind)
+            .ok_or_else(|| AppError::InvalidDataFmt(format!("Unknown email kind: {kind}")))
+    }
+}
+
+/// replaces the `{name}` placeholders of `template` with `vars`
+///
+/// values are inserted as they are, so placeholders inside them are left untouched
+pub fn render(template: &str, vars: &[(&str, &str)]) -> String {
+    let mut text = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        text.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let var = after.find('}').and_then(|end| {
+            vars.iter().find(|(name, _)| *name == &after[..end]).map(|(_, value)| (end, value))
+        });
+        match var {
+            Some((end, value)) => {
+                text.push_str(value);
+                rest = &after[end + 1..];
+            }
+            None => {
+                text.push('{');
+                rest = after;
+            }
+        }
+    }
+    text.push_str(rest);
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let subject = "{code} is your {service_name} verification code";
+        let rendered = render(subject, &[("code", "123456"), ("service_name", "Acme")]);
+        assert_eq!(rendered, "123456 is your Acme verification code");
+        // unknown placeholders are left as they are
+        assert_eq!(render("{code} {other} {", &[("code", "1")]), "1 {other} {");
+        // and the values aren't rendered again
+        assert_eq!(render("{a}{b}", &[("a", "{b}"), ("b", "2")]), "{b}2");
+    }
+
+    fn content(subject: &str, text: &str) -> MailContent {
+        MailContent { subject: subject.to_string(), text: text.to_string(), html: None }
+    }
+
+    #[test]
+    fn test_mail_kind_names_round_trip() {
+        for kind in MailKind::ALL {
+            assert_eq!(MailKind::try_from(kind.get_str()), Ok(kind));
+        }
+        assert!(MailKind::try_from("welcome").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_memory_mailer() {
+        let memory = Arc::new(MemoryMailer::default());
+        let mailer: Arc<dyn Mailer> = memory.clone();
+        mailer.send("a@example.com".into(), content("1", "first")).await.unwrap();
+        mailer.send("b@example.com".into(), content("2", "second")).await.unwrap();
+        mailer.send("A@example.com".into(), content("3", "third")).await.unwrap();
+        assert!(mailer.send("invalid".into(), content("4", "")).await.is_err());
+
+        assert_eq!(memory.sent().len(), 3);
+        assert_eq!(memory.last_sent_to("a@example.com").unwrap().text, "third");
+        memory.clear();
+        assert!(memory.last_sent_to("b@example.com").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_maildir_mailer() {
+        let directory = std::env::temp_dir().join(format!("maildir-{}", uuid::Uuid::new_v4()));
+        let from = "noreply@example.com".parse().unwrap();
+        let mailer: Arc<dyn Mailer> = Arc::new(MaildirMailer::new(from, directory.clone()).unwrap());
+        mailer.test_connection().await.unwrap();
+        let mut mail = content("Code", "123456");
+        mail.html = Some("<p>123456</p>".to_string());
+        mailer.send("a@example.com".into(), mail).await.unwrap();
+
+        let files = std::fs::read_dir(directory.join("new")).unwrap().collect::<Vec<_>>();
+        assert_eq!(files.len(), 1);
+        let path = files[0].as_ref().unwrap().path();
+        assert_eq!(path.extension().unwrap(), "eml");
+        let content = std::fs::read_to_string(path).unwrap();
+        assert!(content.contains("To: a@example.com") && content.contains("multipart/alternative"));
+        assert!(content.contains("text/html") && content.contains("<p>123456</p>"));
+        assert_eq!(std::fs::read_dir(directory.join("tmp")).unwrap().count(), 0);
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+}
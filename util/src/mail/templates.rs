@@ -0,0 +1,330 @@
+use super::MailKind;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+/// html document every html body is inserted in, as `{content}`
+const LAYOUT: &str = "layout.html";
+
+/// parts of a template, the html one is optional (`{kind}.subject`, `{kind}.txt`, `{kind}.html`)
+const PARTS: [&str; 3] = ["subject", "txt", "html"];
+
+/// an email rendered for a single recipient
+#[derive(Clone, Debug, PartialEq)]
+pub struct MailContent {
+    pub subject: String,
+    pub text: String,
+    /// sent as the alternative of `text` when set
+    pub html: Option<String>,
+}
+
+/// templates of the emails sent by the server, the built-in ones overridden by the operator
+///
+/// overrides are read once from a directory laid out like `util/templates/mail`: a file at the
+/// root replaces the built-in part with the same name, a `{locale}/` folder (e.g. `fr/` or
+/// `pt-br/`) holds translated variants, each with at least a subject and a text part
+#[derive(Default)]
+pub struct MailTemplates {
+    /// `{file}` or `{locale}/{file}` => content
+    files: HashMap<String, String>,
+}
+
+impl MailTemplates {
+    /// reads the overrides in `directory`, only the built-in templates are used without one
+    ///
+    /// all the problems found are returned together
+    pub fn load(directory: Option<&Path>) -> Result<Self, Vec<String>> {
+        let mut templates = Self::default();
+        let Some(directory) = directory else { return Ok(templates) };
+
+        let mut errors = Vec::new();
+        for (path, locale) in list_files(directory, &mut errors) {
+            let name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+            if !is_known_file(&name) {
+                errors.push(format!("unknown mail template `{}`", path.display()));
+                continue;
+            }
+            match std::fs::read_to_string(&path) {
+                Ok(content) => {
+                    let key = match &locale {
+                        Some(locale) => format!("{locale}/{name}"),
+                        None => name,
+                    };
+                    templates.files.insert(key, content);
+                }
+                Err(e) => {
+                    errors.push(format!("mail template `{}` can't be read: {e}", path.display()))
+                }
+            }
+        }
+        // a translated variant can't fall back to the english subject or text
+        for key in templates.files.keys() {
+            let Some((locale, name)) = key.split_once('/') else { continue };
+            let Some((kind, _)) = name.rsplit_once('.').filter(|_| name != LAYOUT) else { continue };
+            for part in ["subject", "txt"] {
+                if !templates.files.contains_key(&format!("{locale}/{kind}.{part}")) {
+                    errors.push(format!("mail template `{locale}/{kind}.{part}` is missing"));
+                }
+            }
+        }
+        errors.sort();
+        errors.dedup();
+
+        if errors.is_empty() { Ok(templates) } else { Err(errors) }
+    }
+
+    /// renders the template of `kind` in the first of `locales` it has been translated in, a
+    /// translation without html part is sent as text only
+    ///
+    /// the values of `vars` are escaped in the html part
+    pub fn render(&self, kind: MailKind, locales: &[String], vars: &[(&str, &str)]) -> MailContent {
+        let locale = locales
+            .iter()
+            .find(|locale| self.files.contains_key(&format!("{locale}/{}.txt", kind.get_str())));
+        let part = |part: &str| {
+            let name = format!("{}.{part}", kind.get_str());
+            match locale {
+                // a translation is used as a whole, without the english parts
+                Some(locale) => self.files.get(&format!("{locale}/{name}")).map(String::as_str),
+                None => self.file(None, &name),
+            }
+        };
+
+        let subject = part("subject").map(|v| super::render(v.trim(), vars)).unwrap_or_default();
+        let text = part("txt").map(|v| super::render(v, vars)).unwrap_or_default();
+        let html = part("html").map(|v| self.html(locale, &subject, v, vars));
+        MailContent { subject, text, html }
+    }
+
+    /// renders an email from the `subject`, `text` and `html` templates of a tenant
+    pub fn render_custom(
+        &self,
+        locales: &[String],
+        subject: &str,
+        text: &str,
+        html: Option<&str>,
+        vars: &[(&str, &str)],
+    ) -> MailContent {
+        let locale = locales.iter().find(|locale| self.files.contains_key(&layout_key(locale)));
+        let subject = super::render(subject.trim(), vars);
+        let html = html.map(|v| self.html(locale, &subject, v, vars));
+        MailContent { text: super::render(text, vars), subject, html }
+    }
+
+    /// renders the html `body` inside the layout
+    fn html(
+        &self,
+        locale: Option<&String>,
+        subject: &str,
+        body: &str,
+        vars: &[(&str, &str)],
+    ) -> String {
+        let content = render_html(body, vars);
+        let layout = self.file(locale, LAYOUT).unwrap_or("{content}");
+        super::render(layout, &[("subject", &escape_html(subject)), ("content", &content)])
+    }
+
+    /// returns the `name` file of `locale`, then the one of the operator and the built-in one
+    fn file(&self, locale: Option<&String>, name: &str) -> Option<&str> {
+        locale
+            .and_then(|locale| self.files.get(&format!("{locale}/{name}")))
+            .or_else(|| self.files.get(name))
+            .map(String::as_str)
+            .or_else(|| builtin(name))
+    }
+}
+
+fn layout_key(locale: &str) -> String {
+    format!("{locale}/{LAYOUT}")
+}
+
+/// returns the files of `directory` and of its locale folders, along with their locale
+fn list_files(directory: &Path, errors: &mut Vec<String>) -> Vec<(PathBuf, Option<String>)> {
+    let mut read_dir = |path: &Path| match std::fs::read_dir(path) {
+        Ok(entries) => entries.filter_map(Result::ok).map(|e| e.path()).collect::<Vec<_>>(),
+        Err(e) => {
+            errors.push(format!("mail templates `{}` can't be read: {e}", path.display()));
+            vec![]
+        }
+    };
+    let mut files = Vec::new();
+    for path in read_dir(directory) {
+        if !path.is_dir() {
+            files.push((path, None));
+            continue;
+        }
+        let locale = path.file_name().unwrap_or_default().to_string_lossy().to_ascii_lowercase();
+        for file in read_dir(&path) {
+            files.push((file, Some(locale.clone())));
+        }
+    }
+    files
+}
+
+fn is_known_file(name: &str) -> bool {
+    name == LAYOUT
+        || name
+            .rsplit_once('.')
+            .is_some_and(|(kind, part)| MailKind::try_from(kind).is_ok() && PARTS.contains(&part))
+}
+
+/// returns the locales of an `Accept-Language` header, preferred first
+///
+/// regional locales are followed by their language, e.g. `fr-CA` by `fr`
+pub fn accepted_locales(header: &str) -> Vec<String> {
+    let mut weighted = header
+        .split(',')
+        .filter_map(|item| {
+            let mut params = item.split(';');
+            let tag = params.next()?.trim().to_ascii_lowercase();
+            let quality = params
+                .find_map(|p| p.trim().strip_prefix("q="))
+                .map_or(Some(1.0), |q| q.trim().parse::<f32>().ok())?;
+            (!tag.is_empty() && tag != "*" && quality > 0.0).then_some((tag, quality))
+        })
+        .collect::<Vec<_>>();
+    // stable, so that the order of the header breaks ties
+    weighted.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    let mut locales = Vec::new();
+    for (tag, _) in weighted {
+        let language = tag.split('-').next().unwrap_or_default().to_string();
+        for locale in [tag, language] {
+            if !locales.contains(&locale) {
+                locales.push(locale);
+            }
+        }
+    }
+    locales
+}
+
+/// replaces the `{name}` placeholders of `template` with the escaped `vars`
+pub fn render_html(template: &str, vars: &[(&str, &str)]) -> String {
+    let escaped = vars.iter().map(|(name, value)| (*name, escape_html(value))).collect::<Vec<_>>();
+    let escaped = escaped.iter().map(|(name, value)| (*name, value.as_str())).collect::<Vec<_>>();
+    super::render(template, &escaped)
+}
+
+pub fn escape_html(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// returns the built-in template file `name`, written in english
+#[rustfmt::skip]
+fn builtin(name: &str) -> Option<&'static str> {
+    Some(match name {
+        "layout.html" => include_str!("../../templates/mail/layout.html"),
+        "verification_code.subject" => include_str!("../../templates/mail/verification_code.subject"),
+        "verification_code.txt" => include_str!("../../templates/mail/verification_code.txt"),
+        "verification_code.html" => include_str!("../../templates/mail/verification_code.html"),
+        "email_verified.subject" => include_str!("../../templates/mail/email_verified.subject"),
+        "email_verified.txt" => include_str!("../../templates/mail/email_verified.txt"),
+        "email_verified.html" => include_str!("../../templates/mail/email_verified.html"),
+        "password_reset.subject" => include_str!("../../templates/mail/password_reset.subject"),
+        "password_reset.txt" => include_str!("../../templates/mail/password_reset.txt"),
+        "password_reset.html" => include_str!("../../templates/mail/password_reset.html"),
+        "password_reset_required.subject" => include_str!("../../templates/mail/password_reset_required.subject"),
+        "password_reset_required.txt" => include_str!("../../templates/mail/password_reset_required.txt"),
+        "password_reset_required.html" => include_str!("../../templates/mail/password_reset_required.html"),
+        "password_changed.subject" => include_str!("../../templates/mail/password_changed.subject"),
+        "password_changed.txt" => include_str!("../../templates/mail/password_changed.txt"),
+        "password_changed.html" => include_str!("../../templates/mail/password_changed.html"),
+        "email_changed.subject" => include_str!("../../templates/mail/email_changed.subject"),
+        "email_changed.txt" => include_str!("../../templates/mail/email_changed.txt"),
+        "email_changed.html" => include_str!("../../templates/mail/email_changed.html"),
+        "new_activity.subject" => include_str!("../../templates/mail/new_activity.subject"),
+        "new_activity.txt" => include_str!("../../templates/mail/new_activity.txt"),
+        "new_activity.html" => include_str!("../../templates/mail/new_activity.html"),
+        "organization_invite.subject" => include_str!("../../templates/mail/organization_invite.subject"),
+        "organization_invite.txt" => include_str!("../../templates/mail/organization_invite.txt"),
+        "organization_invite.html" => include_str!("../../templates/mail/organization_invite.html"),
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accepted_locales() {
+        assert_eq!(accepted_locales("fr-CA,fr;q=0.9,en;q=0.8"), ["fr-ca", "fr", "en"]);
+        assert_eq!(accepted_locales("en;q=0.5, pt-BR, *;q=0.1"), ["pt-br", "pt", "en"]);
+        assert_eq!(accepted_locales("de;q=0, es"), ["es"]);
+        assert!(accepted_locales("").is_empty());
+    }
+
+    #[test]
+    fn test_builtin_templates() {
+        for kind in MailKind::ALL {
+            for part in PARTS {
+                assert!(builtin(&format!("{}.{part}", kind.get_str())).is_some(), "{kind:?} {part}");
+            }
+            // the text part is sent as text/plain, markup would show up as is
+            for part in ["subject", "txt"] {
+                let template = builtin(&format!("{}.{part}", kind.get_str())).unwrap();
+                assert!(!template.contains('<') && !template.contains('>'), "{kind:?} {part}");
+            }
+        }
+
+        let templates = MailTemplates::default();
+        let mail = templates.render(
+            MailKind::OrganizationInvite,
+            &["fr".to_string()],
+            &[("inviter", "<Bob>"), ("organization", "Acme"), ("role", "admin")],
+        );
+        assert_eq!(mail.subject, "<Bob> invited you to join Acme on {service_name}");
+        assert!(mail.text.starts_with("<Bob> invited you to join Acme as admin."));
+        let html = mail.html.unwrap();
+        assert!(html.contains("<p>&lt;Bob&gt; invited you") && html.contains("<title>&lt;Bob&gt;"));
+    }
+
+    #[test]
+    fn test_overrides() {
+        let directory = std::env::temp_dir().join(format!("templates-{}", uuid::Uuid::new_v4()));
+        let write = |name: &str, content: &str| {
+            let path = directory.join(name);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        };
+        write("verification_code.txt", "Code: {code}");
+        write("fr/verification_code.subject", "Votre code {code}\n");
+        write("fr/verification_code.txt", "Code : {code}");
+        write("fr/layout.html", "<html lang=\"fr\">{content}</html>");
+        write("es/verification_code.subject", "Tu código {code}");
+        write("es/verification_code.txt", "Código: {code}");
+        write("fr/verification_code.html", "<p>{code}</p>");
+
+        let templates = MailTemplates::load(Some(&directory)).ok().unwrap();
+        let vars = [("code", "123456")];
+        let mail = templates.render(MailKind::VerificationCode, &[], &vars);
+        assert_eq!(mail.text, "Code: 123456");
+        assert!(mail.subject.starts_with("123456 is your"));
+        let mail = templates.render(MailKind::VerificationCode, &["fr".to_string()], &vars);
+        assert_eq!(
+            (mail.subject.as_str(), mail.text.as_str()),
+            ("Votre code 123456", "Code : 123456")
+        );
+        assert_eq!(mail.html.unwrap(), "<html lang=\"fr\"><p>123456</p></html>");
+        let mail = templates.render(MailKind::VerificationCode, &["es".to_string()], &vars);
+        assert_eq!((mail.text.as_str(), mail.html), ("Código: 123456", None));
+
+        write("de/password_reset.html", "<p>{link}</p>");
+        write("welcome.txt", "Welcome");
+        let errors = MailTemplates::load(Some(&directory)).err().unwrap();
+        assert_eq!(errors.len(), 3, "{errors:?}");
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+}
@@ -0,0 +1,143 @@
+use prometheus::{
+    Encoder, HistogramVec, IntCounter, IntCounterVec, IntGauge, IntGaugeVec, TextEncoder,
+    register_histogram_vec, register_int_counter, register_int_counter_vec, register_int_gauge,
+    register_int_gauge_vec,
+};
+use std::sync::LazyLock;
+
+// all the metrics are registered in the default prometheus registry
+
+pub static HTTP_REQUESTS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "http_requests_total",
+        "Number of handled requests",
+        &["method", "route", "status"]
+    )
+    .unwrap()
+});
+
+pub static HTTP_REQUEST_DURATION: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register_histogram_vec!(
+        "http_request_duration_seconds",
+        "Time taken to handle a request",
+        &["method", "route", "status"]
+    )
+    .unwrap()
+});
+
+/// `method` is `password` or `oidc`, `result` is `success` or `failure`
+pub static LOGINS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!("logins_total", "Number of login attempts", &["method", "result"])
+        .unwrap()
+});
+
+/// `kind` is `registration`, `email_update` or `password_reset`
+pub static VERIFICATION_CODES: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "verification_codes_issued_total",
+        "Number of OTPs and password reset codes issued",
+        &["kind"]
+    )
+    .unwrap()
+});
+
+pub static MAIL_FAILURES: LazyLock<IntCounter> = LazyLock::new(|| {
+    register_int_counter!("mail_send_failures_total", "Number of mails that couldn't be sent")
+        .unwrap()
+});
+
+pub static BUCKET_UPLOAD_FAILURES: LazyLock<IntCounter> = LazyLock::new(|| {
+    register_int_counter!(
+        "bucket_upload_failures_total",
+        "Number of failed uploads to the object storage"
+    )
+    .unwrap()
+});
+
+/// `result` is `hit` or `miss`
+pub static CACHE_LOOKUPS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "cache_lookups_total",
+        "Number of lookups in the in-memory caches",
+        &["cache", "result"]
+    )
+    .unwrap()
+});
+
+pub static CACHE_ENTRIES: LazyLock<IntGaugeVec> = LazyLock::new(|| {
+    register_int_gauge_vec!(
+        "cache_entries",
+        "Approximate number of entries in the in-memory caches",
+        &["cache"]
+    )
+    .unwrap()
+});
+
+/// `state` is `idle` or `in_use`
+pub static DB_POOL_CONNECTIONS: LazyLock<IntGaugeVec> = LazyLock::new(|| {
+    register_int_gauge_vec!(
+        "db_pool_connections",
+        "Number of connections in the database pool",
+        &["state"]
+    )
+    .unwrap()
+});
+
+pub static DB_POOL_MAX_CONNECTIONS: LazyLock<IntGauge> = LazyLock::new(|| {
+    register_int_gauge!(
+        "db_pool_max_connections",
+        "Maximum number of connections in the database pool"
+    )
+    .unwrap()
+});
+
+/// counts a lookup in `cache` as a hit or miss and passes `value` through
+pub fn cache_lookup<T>(cache: &str, value: Option<T>) -> Option<T> {
+    let result = if value.is_some() { "hit" } else { "miss" };
+    CACHE_LOOKUPS.with_label_values(&[cache, result]).inc();
+    value
+}
+
+/// returns every registered metric in the prometheus text format
+pub fn render() -> String {
+    // registering the metrics that may not have been used yet
+    LazyLock::force(&HTTP_REQUESTS);
+    LazyLock::force(&HTTP_REQUEST_DURATION);
+    LazyLock::force(&LOGINS);
+    LazyLock::force(&VERIFICATION_CODES);
+    LazyLock::force(&MAIL_FAILURES);
+    LazyLock::force(&BUCKET_UPLOAD_FAILURES);
+    LazyLock::force(&CACHE_LOOKUPS);
+    LazyLock::force(&CACHE_ENTRIES);
+    LazyLock::force(&DB_POOL_CONNECTIONS);
+    LazyLock::force(&DB_POOL_MAX_CONNECTIONS);
+
+    let mut buffer = vec![];
+    if let Err(e) = TextEncoder::new().encode(&prometheus::gather(), &mut buffer) {
+        tracing::error!("{e:?}");
+    }
+    String::from_utf8(buffer).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cache_lookups_are_counted() {
+        let hits = || CACHE_LOOKUPS.with_label_values(&["test", "hit"]).get();
+        let misses = || CACHE_LOOKUPS.with_label_values(&["test", "miss"]).get();
+        let (h, m) = (hits(), misses());
+
+        assert_eq!(cache_lookup("test", Some(1)), Some(1));
+        assert_eq!(cache_lookup::<u8>("test", None), None);
+        assert_eq!((hits(), misses()), (h + 1, m + 1));
+    }
+
+    #[test]
+    fn unused_metrics_are_rendered() {
+        let text = render();
+        assert!(text.contains("mail_send_failures_total 0"));
+        assert!(text.contains("# TYPE bucket_upload_failures_total counter"));
+    }
+}
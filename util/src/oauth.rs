@@ -1,13 +1,6 @@
+use crate::config::Config;
 use std::sync::Arc;
 
-static OAUTH_PROVIDERS: std::sync::LazyLock<OAuthProviders> =
-    std::sync::LazyLock::new(OAuthProviders::default);
-
-struct OAuthProviders<'a> {
-    /// https://accounts.google.com/.well-known/openid-configuration
-    pub google: Arc<OAuthConfig<'a>>,
-}
-
 pub struct OAuthConfig<'a> {
     pub client_id: String,
     pub client_secret: String,
@@ -17,20 +10,19 @@ pub struct OAuthConfig<'a> {
     pub provider: OAuthProvider,
 }
 
-impl<'a> Default for OAuthProviders<'a> {
-    fn default() -> Self {
+impl OAuthConfig<'static> {
+    /// google's endpoints (https://accounts.google.com/.well-known/openid-configuration) with the given client credentials (like the ones of a tenant)
+    pub fn google(client_id: String, client_secret: String) -> Self {
         Self {
-            google: Arc::new(OAuthConfig {
-                client_id: std::env::var("GOOGLE_CLIENT_ID").unwrap(),
-                client_secret: std::env::var("GOOGLE_CLIENT_SECRET").unwrap(),
-                authorization_endpoint: reqwest::Url::parse(
-                    "https://accounts.google.com/o/oauth2/v2/auth",
-                )
-                .unwrap(),
-                token_endpoint: "https://oauth2.googleapis.com/token",
-                userinfo_endpoint: "https://openidconnect.googleapis.com/v1/userinfo",
-                provider: OAuthProvider::Google,
-            }),
+            client_id,
+            client_secret,
+            authorization_endpoint: reqwest::Url::parse(
+                "https://accounts.google.com/o/oauth2/v2/auth",
+            )
+            .unwrap(),
+            token_endpoint: "https://oauth2.googleapis.com/token",
+            userinfo_endpoint: "https://openidconnect.googleapis.com/v1/userinfo",
+            provider: OAuthProvider::Google,
         }
     }
 }
@@ -79,10 +71,17 @@ impl OAuthProvider {
     }
 }
 
-pub fn get_oauth_provider(provider: OAuthProvider) -> Option<Arc<OAuthConfig<'static>>> {
-    match provider {
-        OAuthProvider::Google => Some(OAUTH_PROVIDERS.google.clone()),
-        OAuthProvider::None => None,
+/// returns the client of `provider` set up in `config`, if any
+pub fn get_oauth_provider(
+    config: &Config,
+    provider: OAuthProvider,
+) -> Option<Arc<OAuthConfig<'static>>> {
+    match (provider, &config.google) {
+        (OAuthProvider::Google, Some(google)) => Some(Arc::new(OAuthConfig::google(
+            google.client_id.clone(),
+            google.client_secret.clone(),
+        ))),
+        _ => None,
     }
 }
 
@@ -0,0 +1,173 @@
+use base64::Engine;
+use std::str::FromStr;
+
+/// iteration counts above this are rejected, so a bad import can't stall logins
+const MAX_PBKDF2_ROUNDS: u32 = 1_000_000;
+
+/// password hash produced by another system, checked at login until the user sets a password
+///
+/// supported formats:
+/// - bcrypt (`$2a$`, `$2b$`, `$2x$`, `$2y$`)
+/// - django (`pbkdf2_sha256$<rounds>$<salt>$<base64 hash>`, `pbkdf2_sha1$...`)
+/// - passlib (`$pbkdf2-sha256$<rounds>$<ab64 salt>$<ab64 hash>`, `$pbkdf2-sha512$...`, `$pbkdf2$...`)
+/// - phc (`$pbkdf2-sha256$i=<rounds>,l=<length>$<b64 salt>$<b64 hash>`)
+#[derive(Clone, Debug, PartialEq)]
+pub enum ForeignHash {
+    Bcrypt(String),
+    Pbkdf2 { digest: Pbkdf2Digest, rounds: u32, salt: Vec<u8>, hash: Vec<u8> },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Pbkdf2Digest {
+    Sha1,
+    Sha256,
+    Sha512,
+}
+
+impl FromStr for ForeignHash {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.starts_with("$2") {
+            bcrypt::HashParts::from_str(s).map_err(|_| "Invalid bcrypt hash")?;
+            return Ok(ForeignHash::Bcrypt(s.to_string()));
+        }
+
+        let parts = s.split('$').collect::<Vec<_>>();
+        match parts.as_slice() {
+            // django
+            [algorithm, rounds, salt, hash] => {
+                let digest = match *algorithm {
+                    "pbkdf2_sha1" => Pbkdf2Digest::Sha1,
+                    "pbkdf2_sha256" => Pbkdf2Digest::Sha256,
+                    _ => return Err("Unsupported hash algorithm"),
+                };
+                let hash = base64::prelude::BASE64_STANDARD
+                    .decode(hash)
+                    .map_err(|_| "Invalid hash encoding")?;
+                pbkdf2_hash(digest, rounds.parse().ok(), salt.as_bytes().to_vec(), hash)
+            }
+            // passlib and phc
+            ["", algorithm, params, salt, hash] => {
+                let digest = match *algorithm {
+                    "pbkdf2" => Pbkdf2Digest::Sha1,
+                    "pbkdf2-sha256" => Pbkdf2Digest::Sha256,
+                    "pbkdf2-sha512" => Pbkdf2Digest::Sha512,
+                    _ => return Err("Unsupported hash algorithm"),
+                };
+                if params.starts_with("i=") {
+                    let rounds = params
+                        .split(',')
+                        .find_map(|v| v.strip_prefix("i="))
+                        .and_then(|v| v.parse().ok());
+                    pbkdf2_hash(digest, rounds, decode_b64(salt)?, decode_b64(hash)?)
+                } else {
+                    pbkdf2_hash(digest, params.parse().ok(), decode_ab64(salt)?, decode_ab64(hash)?)
+                }
+            }
+            _ => Err("Unsupported hash format"),
+        }
+    }
+}
+
+impl ForeignHash {
+    /// checks `password` against the hash (slow on purpose, call it from a blocking task)
+    pub fn verify(&self, password: &str) -> bool {
+        match self {
+            ForeignHash::Bcrypt(hash) => bcrypt::verify(password, hash).unwrap_or(false),
+            ForeignHash::Pbkdf2 { digest, rounds, salt, hash } => {
+                let mut derived = vec![0u8; hash.len()];
+                let password = password.as_bytes();
+                match digest {
+                    Pbkdf2Digest::Sha1 => {
+                        pbkdf2::pbkdf2_hmac::<sha1::Sha1>(password, salt, *rounds, &mut derived)
+                    }
+                    Pbkdf2Digest::Sha256 => {
+                        pbkdf2::pbkdf2_hmac::<sha2::Sha256>(password, salt, *rounds, &mut derived)
+                    }
+                    Pbkdf2Digest::Sha512 => {
+                        pbkdf2::pbkdf2_hmac::<sha2::Sha512>(password, salt, *rounds, &mut derived)
+                    }
+                }
+                // constant time comparison
+                derived.iter().zip(hash.iter()).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+            }
+        }
+    }
+}
+
+fn pbkdf2_hash(
+    digest: Pbkdf2Digest,
+    rounds: Option<u32>,
+    salt: Vec<u8>,
+    hash: Vec<u8>,
+) -> Result<ForeignHash, &'static str> {
+    let rounds =
+        rounds.filter(|v| (1..=MAX_PBKDF2_ROUNDS).contains(v)).ok_or("Invalid iteration count")?;
+    if salt.is_empty() || hash.len() < 16 {
+        return Err("Invalid hash length");
+    }
+    Ok(ForeignHash::Pbkdf2 { digest, rounds, salt, hash })
+}
+
+fn decode_b64(value: &str) -> Result<Vec<u8>, &'static str> {
+    base64::prelude::BASE64_STANDARD_NO_PAD
+        .decode(value.trim_end_matches('='))
+        .map_err(|_| "Invalid hash encoding")
+}
+
+// passlib's "adapted base64" uses `.` instead of `+`
+fn decode_ab64(value: &str) -> Result<Vec<u8>, &'static str> {
+    decode_b64(&value.replace('.', "+"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bcrypt() {
+        let hash: ForeignHash =
+            "$2a$05$CCCCCCCCCCCCCCCCCCCCC.E5YPO9kmyuRGyh0XouQYb4YMJKvyOeW".parse().unwrap();
+        assert!(hash.verify("U*U"));
+        assert!(!hash.verify("U*V"));
+        assert!("$2b$05$short".parse::<ForeignHash>().is_err());
+    }
+
+    #[test]
+    fn test_django_pbkdf2() {
+        let hash: ForeignHash =
+            "pbkdf2_sha256$1000$saltsalt$inQhJyM09A+CbtVFDcVOj+vKkjUBiGWuXgOX+SQKSkE="
+                .parse()
+                .unwrap();
+        assert!(hash.verify("Passw0rd!x"));
+        assert!(!hash.verify("passw0rd!x"));
+    }
+
+    #[test]
+    fn test_passlib_pbkdf2() {
+        let hash: ForeignHash =
+            "$pbkdf2-sha256$1000$c2FsdHNhbHQ$inQhJyM09A.CbtVFDcVOj.vKkjUBiGWuXgOX.SQKSkE"
+                .parse()
+                .unwrap();
+        assert!(hash.verify("Passw0rd!x"));
+        let phc: ForeignHash =
+            "$pbkdf2-sha256$i=1000,l=32$c2FsdHNhbHQ$inQhJyM09A+CbtVFDcVOj+vKkjUBiGWuXgOX+SQKSkE"
+                .parse()
+                .unwrap();
+        assert_eq!(hash, phc);
+    }
+
+    #[test]
+    fn test_invalid_hashes() {
+        assert!("plaintext".parse::<ForeignHash>().is_err());
+        assert!("md5$salt$abcdef".parse::<ForeignHash>().is_err());
+        assert!("pbkdf2_sha256$0$salt$VA4bozV0LKo3zj7VBwixWA==".parse::<ForeignHash>().is_err());
+        let slow = "pbkdf2_sha256$1000001$salt$VA4bozV0LKo3zj7VBwixWA==";
+        assert_eq!(slow.parse::<ForeignHash>(), Err("Invalid iteration count"));
+        assert!(
+            "pbkdf2_sha256$1000000$salt$VA4bozV0LKo3zj7VBwixWA==".parse::<ForeignHash>().is_ok()
+        );
+        assert!("$argon2id$v=19$m=65536,t=3,p=4$c2FsdA$aGFzaA".parse::<ForeignHash>().is_err());
+    }
+}
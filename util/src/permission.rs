@@ -0,0 +1,64 @@
+/// permissions that routes can require, granted to users through their roles
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Permission {
+    HealthRead,
+    UsersRead,
+    UsersManage,
+    UsersImpersonate,
+    RolesManage,
+    AuditRead,
+    TenantsManage,
+    RegistrationManage,
+    LegalManage,
+}
+
+impl Permission {
+    pub const ALL: [Permission; 9] = [
+        Permission::HealthRead,
+        Permission::UsersRead,
+        Permission::UsersManage,
+        Permission::UsersImpersonate,
+        Permission::RolesManage,
+        Permission::AuditRead,
+        Permission::TenantsManage,
+        Permission::RegistrationManage,
+        Permission::LegalManage,
+    ];
+
+    pub fn get_str(&self) -> &'static str {
+        match self {
+            Permission::HealthRead => "health:read",
+            Permission::UsersRead => "users:read",
+            Permission::UsersManage => "users:manage",
+            Permission::UsersImpersonate => "users:impersonate",
+            Permission::RolesManage => "roles:manage",
+            Permission::AuditRead => "audit:read",
+            Permission::TenantsManage => "tenants:manage",
+            Permission::RegistrationManage => "registration:manage",
+            Permission::LegalManage => "legal:manage",
+        }
+    }
+}
+
+impl TryFrom<&str> for Permission {
+    type Error = crate::AppError;
+
+    fn try_from(permission: &str) -> Result<Self, Self::Error> {
+        Permission::ALL.into_iter().find(|p| p.get_str() == permission).ok_or_else(|| {
+            crate::AppError::InvalidDataFmt(format!("Unknown permission: {permission}"))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn permission_names_round_trip() {
+        for permission in Permission::ALL {
+            assert_eq!(Permission::try_from(permission.get_str()), Ok(permission));
+        }
+        assert!(Permission::try_from("users:everything").is_err());
+    }
+}
@@ -0,0 +1,228 @@
+use super::Session;
+use std::net::IpAddr;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BindingMode {
+    /// sessions are never bound
+    Off,
+    /// only sessions of users who enabled `bind_sessions` are bound
+    OptIn,
+    /// every session is bound
+    All,
+}
+
+/// what happens when a request doesn't match the client its session is bound to
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MismatchAction {
+    /// the session is removed and the user has to log in again
+    Reauthenticate,
+    /// the session is blocked until the user confirms their password
+    StepUp,
+    /// the user is notified by email and the session is rebound to the new client
+    Notify,
+}
+
+#[derive(Clone, Debug)]
+pub struct BindingPolicy {
+    pub mode: BindingMode,
+    pub ipv4_prefix: Option<u8>,
+    pub ipv6_prefix: Option<u8>,
+    pub user_agent: bool,
+    pub action: MismatchAction,
+}
+
+impl Default for BindingPolicy {
+    fn default() -> Self {
+        Self {
+            mode: BindingMode::OptIn,
+            ipv4_prefix: Some(16),
+            ipv6_prefix: Some(48),
+            user_agent: true,
+            action: MismatchAction::StepUp,
+        }
+    }
+}
+
+impl BindingPolicy {
+    /// builds the policy from key/value lookups (normally environment variables)
+    ///
+    /// every unset key falls back to `BindingPolicy::default()`,
+    /// all the problems found are returned together
+    pub fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> Result<Self, Vec<String>> {
+        let mut policy = Self::default();
+        let mut errors = Vec::new();
+
+        if let Some(v) = lookup("SESSION_BIND_MODE") {
+            match v.to_ascii_lowercase().as_str() {
+                "off" => policy.mode = BindingMode::Off,
+                "opt_in" => policy.mode = BindingMode::OptIn,
+                "all" => policy.mode = BindingMode::All,
+                _ => errors.push(format!("SESSION_BIND_MODE `{v}` is not one of off, opt_in, all")),
+            }
+        }
+        let mut prefix = |key: &str, max: u8, field: &mut Option<u8>| {
+            if let Some(v) = lookup(key) {
+                match v.as_str() {
+                    "" | "off" => *field = None,
+                    _ => match v.parse::<u8>() {
+                        Ok(p) if p <= max => *field = Some(p),
+                        _ => errors.push(format!("{key} `{v}` is not a prefix length (0..={max})")),
+                    },
+                }
+            }
+        };
+        prefix("SESSION_BIND_IPV4_PREFIX", 32, &mut policy.ipv4_prefix);
+        prefix("SESSION_BIND_IPV6_PREFIX", 128, &mut policy.ipv6_prefix);
+        if let Some(v) = lookup("SESSION_BIND_USER_AGENT") {
+            match v.parse::<bool>() {
+                Ok(b) => policy.user_agent = b,
+                Err(_) => errors.push(format!("SESSION_BIND_USER_AGENT `{v}` is not a boolean")),
+            }
+        }
+        if let Some(v) = lookup("SESSION_BIND_ACTION") {
+            match v.to_ascii_lowercase().as_str() {
+                "reauthenticate" => policy.action = MismatchAction::Reauthenticate,
+                "step_up" => policy.action = MismatchAction::StepUp,
+                "notify" => policy.action = MismatchAction::Notify,
+                _ => errors.push(format!(
+                    "SESSION_BIND_ACTION `{v}` is not one of reauthenticate, step_up, notify"
+                )),
+            }
+        }
+
+        if errors.is_empty() { Ok(policy) } else { Err(errors) }
+    }
+
+    /// returns true if sessions of a user with the given opt-in flag are bound
+    pub fn applies_to(&self, bind_sessions: bool) -> bool {
+        match self.mode {
+            BindingMode::Off => false,
+            BindingMode::OptIn => bind_sessions,
+            BindingMode::All => true,
+        }
+    }
+
+    /// returns true if the client (`ip`, `user_agent`) matches the one `session` is bound to
+    pub fn matches(&self, session: &Session, ip: IpAddr, user_agent: Option<&str>) -> bool {
+        self.same_client((session.ip_address, session.user_agent.as_deref()), ip, user_agent)
+    }
+
+    /// returns true if the client (`ip`, `user_agent`) matches the `known` one, with the same
+    /// network prefixes and user agent comparison as bound sessions
+    pub fn same_client(
+        &self,
+        known: (IpAddr, Option<&str>),
+        ip: IpAddr,
+        user_agent: Option<&str>,
+    ) -> bool {
+        let ip_matches = match (known.0.to_canonical(), ip.to_canonical()) {
+            (IpAddr::V4(a), IpAddr::V4(b)) => self.ipv4_prefix.is_none_or(|p| {
+                let mask = u32::MAX.checked_shl(32 - p as u32).unwrap_or(0);
+                u32::from(a) & mask == u32::from(b) & mask
+            }),
+            (IpAddr::V6(a), IpAddr::V6(b)) => self.ipv6_prefix.is_none_or(|p| {
+                let mask = u128::MAX.checked_shl(128 - p as u32).unwrap_or(0);
+                u128::from(a) & mask == u128::from(b) & mask
+            }),
+            _ => self.ipv4_prefix.is_none() && self.ipv6_prefix.is_none(),
+        };
+        let user_agent_matches = !self.user_agent
+            || user_agent_family(known.1.unwrap_or_default())
+                == user_agent_family(user_agent.unwrap_or_default());
+        ip_matches && user_agent_matches
+    }
+}
+
+/// returns the browser family of a user agent, ignoring versions
+pub fn user_agent_family(user_agent: &str) -> &'static str {
+    // order matters as most browsers also claim to be Chrome and/or Safari
+    const FAMILIES: [(&str, &str); 9] = [
+        ("Edg/", "Edge"),
+        ("OPR/", "Opera"),
+        ("SamsungBrowser/", "Samsung Internet"),
+        ("Firefox/", "Firefox"),
+        ("FxiOS/", "Firefox"),
+        ("CriOS/", "Chrome"),
+        ("Chrome/", "Chrome"),
+        ("Safari/", "Safari"),
+        ("curl/", "curl"),
+    ];
+    FAMILIES
+        .iter()
+        .find(|(token, _)| user_agent.contains(token))
+        .map(|(_, family)| *family)
+        .unwrap_or("Other")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn session(ip: &str, user_agent: &str) -> Session {
+        let now = time::OffsetDateTime::now_utc();
+        Session {
+            unsigned_ssid: uuid::Uuid::new_v4(),
+            user_agent: Some(user_agent.to_string()),
+            ip_address: IpAddr::from_str(ip).unwrap(),
+            created_at: now,
+            last_used: now,
+            expires_at: now,
+            absolute_expires_at: now,
+            remember_me: false,
+            impersonated_by: None,
+        }
+    }
+
+    const FIREFOX: &str = "Mozilla/5.0 (X11; Linux x86_64; rv:128.0) Gecko/20100101 Firefox/128.0";
+    const FIREFOX_NEW: &str =
+        "Mozilla/5.0 (X11; Linux x86_64; rv:140.0) Gecko/20100101 Firefox/140.0";
+    const CHROME: &str = "Mozilla/5.0 (X11; Linux x86_64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/126.0.0.0 Safari/537.36";
+    const EDGE: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/126.0.0.0 Safari/537.36 Edg/126.0.0.0";
+
+    #[test]
+    fn user_agent_families() {
+        assert_eq!(user_agent_family(FIREFOX), "Firefox");
+        assert_eq!(user_agent_family(CHROME), "Chrome");
+        assert_eq!(user_agent_family(EDGE), "Edge");
+        assert_eq!(user_agent_family(""), "Other");
+    }
+
+    #[test]
+    fn ip_prefix_binding() {
+        let policy = BindingPolicy::default();
+        let s = session("203.0.113.7", FIREFOX);
+        let ip = |v: &str| IpAddr::from_str(v).unwrap();
+        assert!(policy.matches(&s, ip("203.0.200.1"), Some(FIREFOX_NEW)));
+        assert!(policy.matches(&s, ip("::ffff:203.0.1.1"), Some(FIREFOX)));
+        assert!(!policy.matches(&s, ip("198.51.100.7"), Some(FIREFOX)));
+        assert!(!policy.matches(&s, ip("203.0.113.7"), Some(CHROME)));
+        assert!(!policy.matches(&s, ip("2001:db8::1"), Some(FIREFOX)));
+
+        let s = session("2001:db8:1:2::1", CHROME);
+        assert!(policy.matches(&s, ip("2001:db8:1:ffff::9"), Some(CHROME)));
+        assert!(!policy.matches(&s, ip("2001:db9:1::1"), Some(CHROME)));
+    }
+
+    #[test]
+    fn policy_from_lookup() {
+        let policy = BindingPolicy::from_lookup(|key| match key {
+            "SESSION_BIND_MODE" => Some("all".to_string()),
+            "SESSION_BIND_IPV4_PREFIX" => Some("off".to_string()),
+            "SESSION_BIND_ACTION" => Some("notify".to_string()),
+            _ => None,
+        })
+        .unwrap();
+        assert!(policy.applies_to(false));
+        assert_eq!(policy.ipv4_prefix, None);
+        assert_eq!(policy.action, MismatchAction::Notify);
+
+        let errors = BindingPolicy::from_lookup(|key| match key {
+            "SESSION_BIND_IPV4_PREFIX" => Some("33".to_string()),
+            "SESSION_BIND_ACTION" => Some("ignore".to_string()),
+            _ => None,
+        })
+        .unwrap_err();
+        assert_eq!(errors.len(), 2);
+    }
+}
@@ -1,3 +1,4 @@
+use crate::config::Config;
 use base64::Engine;
 use hmac::Mac;
 
@@ -6,8 +7,8 @@ pub const BASE64_DIGEST_LEN: usize = 44;
 /// this function is used to sign cookie value to ensure integrity and authenticity
 ///
 /// value is the `VALUE` part of the whole cookie (`KEY=VALUE`)
-pub fn sign(value: &str) -> String {
-    let mut mac = hmac::Hmac::<sha2::Sha256>::new_from_slice(&crate::SECRET_KEY).unwrap();
+pub fn sign(config: &Config, value: &str) -> String {
+    let mut mac = hmac::Hmac::<sha2::Sha256>::new_from_slice(config.secret_key.as_bytes()).unwrap();
     hmac::Mac::update(&mut mac, value.as_bytes());
     base64::prelude::BASE64_STANDARD.encode(mac.finalize().into_bytes())
 }
@@ -15,7 +16,7 @@ pub fn sign(value: &str) -> String {
 /// this function is used to verify signed cookie value to ensure integrity and authenticity
 ///
 /// value is the `VALUE` part of the whole cookie (`KEY=VALUE`)
-pub fn verify(value: &str) -> Option<String> {
+pub fn verify(config: &Config, value: &str) -> Option<String> {
     if !value.is_char_boundary(BASE64_DIGEST_LEN) {
         return None;
     }
@@ -24,8 +25,13 @@ pub fn verify(value: &str) -> Option<String> {
     let (digest_str, uid) = value.split_at(BASE64_DIGEST_LEN);
     let digest = base64::prelude::BASE64_STANDARD.decode(digest_str).ok()?;
 
-    // Perform the verification
-    let mut mac = hmac::Hmac::<sha2::Sha256>::new_from_slice(&crate::SECRET_KEY).unwrap();
-    hmac::Mac::update(&mut mac, uid.as_bytes());
-    mac.verify_slice(&digest).map(|_| uid.to_string()).ok()
+    // Perform the verification (with the previous keys too, while they're being rotated out)
+    config
+        .verification_keys()
+        .any(|key| {
+            let mut mac = hmac::Hmac::<sha2::Sha256>::new_from_slice(key).unwrap();
+            hmac::Mac::update(&mut mac, uid.as_bytes());
+            mac.verify_slice(&digest).is_ok()
+        })
+        .then(|| uid.to_string())
 }
@@ -0,0 +1,75 @@
+use super::ParsedSession;
+use crate::config::Config;
+use axum::http::{HeaderMap, HeaderValue, header};
+use base64::Engine;
+use hmac::Mac;
+
+/// header in which clients echo the value of the csrf cookie
+pub const CSRF_HEADER: &str = "x-csrf-token";
+
+/// creates a csrf token (`NONCE.MAC`) that is only valid for the session `unsigned_ssid`
+pub fn create_csrf_token(config: &Config, unsigned_ssid: &uuid::Uuid) -> String {
+    let nonce = crate::generate::random_string(22);
+    format!("{nonce}.{}", csrf_mac(config, unsigned_ssid, &nonce))
+}
+
+/// verifies that `token` was created by `create_csrf_token` for the session `unsigned_ssid`
+pub fn verify_csrf_token(config: &Config, token: &str, unsigned_ssid: &uuid::Uuid) -> bool {
+    let Some((nonce, digest_str)) = token.split_once('.') else {
+        return false;
+    };
+    let Ok(digest) = base64::prelude::BASE64_URL_SAFE_NO_PAD.decode(digest_str) else {
+        return false;
+    };
+    config
+        .verification_keys()
+        .any(|key| csrf_hmac(key, unsigned_ssid, nonce).verify_slice(&digest).is_ok())
+}
+
+fn csrf_mac(config: &Config, unsigned_ssid: &uuid::Uuid, nonce: &str) -> String {
+    let mac = csrf_hmac(config.secret_key.as_bytes(), unsigned_ssid, nonce);
+    base64::prelude::BASE64_URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes())
+}
+
+fn csrf_hmac(key: &[u8], unsigned_ssid: &uuid::Uuid, nonce: &str) -> hmac::Hmac<sha2::Sha256> {
+    let mut mac = hmac::Hmac::<sha2::Sha256>::new_from_slice(key).unwrap();
+    mac.update(format!("{unsigned_ssid}.{nonce}").as_bytes());
+    mac
+}
+
+/// creates a csrf token for `parsed_session` and the header map that sets its cookie of `tenant`
+///
+/// the cookie is readable by scripts, so that the client can echo it in `CSRF_HEADER`
+pub fn issue_csrf_token(
+    config: &Config,
+    tenant: Option<&str>,
+    parsed_session: &ParsedSession,
+) -> (String, HeaderMap) {
+    let policy = &config.cookies;
+    let token = create_csrf_token(config, &parsed_session.unsigned_ssid);
+    let set_cookie = policy.set_script_cookie(
+        &policy.csrf_cookie(tenant),
+        &token,
+        policy.profile(true).absolute_lifetime,
+    );
+    (
+        token,
+        HeaderMap::from_iter([(header::SET_COOKIE, HeaderValue::from_str(&set_cookie).unwrap())]),
+    )
+}
+
+/// returns the csrf token of `tenant` found inside the cookies sent by a client
+pub fn csrf_token_from_cookies(
+    config: &Config,
+    tenant: Option<&str>,
+    headers: &HeaderMap,
+) -> Option<String> {
+    let csrf_cookie = config.cookies.csrf_cookie(tenant);
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .flat_map(|h| h.to_str().unwrap_or_default().split(';'))
+        .filter_map(|cookie| cookie.trim().split_once('='))
+        .find(|(name, _)| *name == csrf_cookie)
+        .map(|(_, value)| value.to_string())
+}
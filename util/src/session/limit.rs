@@ -0,0 +1,97 @@
+/// what happens when a new session would exceed the limit
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LimitStrategy {
+    /// the least recently used sessions are removed
+    EvictLru,
+    /// the login is rejected with the list of sessions to choose from
+    Reject,
+}
+
+#[derive(Clone, Debug)]
+pub struct SessionLimit {
+    /// `None` means unlimited
+    pub max_sessions: Option<usize>,
+    /// `None` means the same as `max_sessions`
+    pub max_admin_sessions: Option<usize>,
+    pub strategy: LimitStrategy,
+}
+
+impl Default for SessionLimit {
+    fn default() -> Self {
+        Self { max_sessions: None, max_admin_sessions: None, strategy: LimitStrategy::EvictLru }
+    }
+}
+
+impl SessionLimit {
+    /// builds the limit from key/value lookups (normally environment variables)
+    ///
+    /// every unset key falls back to `SessionLimit::default()`,
+    /// all the problems found are returned together
+    pub fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> Result<Self, Vec<String>> {
+        let mut limit = Self::default();
+        let mut errors = Vec::new();
+
+        let mut max = |key: &str, field: &mut Option<usize>| {
+            if let Some(v) = lookup(key) {
+                match v.as_str() {
+                    "" | "off" => *field = None,
+                    _ => match v.parse::<usize>() {
+                        Ok(n) if n > 0 => *field = Some(n),
+                        _ => errors.push(format!("{key} `{v}` is not a positive number")),
+                    },
+                }
+            }
+        };
+        max("SESSION_LIMIT", &mut limit.max_sessions);
+        max("SESSION_LIMIT_ADMIN", &mut limit.max_admin_sessions);
+        if let Some(v) = lookup("SESSION_LIMIT_STRATEGY") {
+            match v.to_ascii_lowercase().as_str() {
+                "evict_lru" => limit.strategy = LimitStrategy::EvictLru,
+                "reject" => limit.strategy = LimitStrategy::Reject,
+                _ => errors
+                    .push(format!("SESSION_LIMIT_STRATEGY `{v}` is not one of evict_lru, reject")),
+            }
+        }
+
+        if errors.is_empty() { Ok(limit) } else { Err(errors) }
+    }
+
+    /// returns the maximum number of sessions a user can have at once
+    pub fn max_for(&self, is_admin: bool) -> Option<usize> {
+        if is_admin { self.max_admin_sessions.or(self.max_sessions) } else { self.max_sessions }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn limit_from_lookup() {
+        let limit = SessionLimit::from_lookup(|key| match key {
+            "SESSION_LIMIT" => Some("3".to_string()),
+            "SESSION_LIMIT_STRATEGY" => Some("reject".to_string()),
+            _ => None,
+        })
+        .unwrap();
+        assert_eq!(limit.max_for(false), Some(3));
+        assert_eq!(limit.max_for(true), Some(3));
+        assert_eq!(limit.strategy, LimitStrategy::Reject);
+
+        let limit = SessionLimit::from_lookup(|key| match key {
+            "SESSION_LIMIT_ADMIN" => Some("1".to_string()),
+            _ => None,
+        })
+        .unwrap();
+        assert_eq!(limit.max_for(false), None);
+        assert_eq!(limit.max_for(true), Some(1));
+
+        let errors = SessionLimit::from_lookup(|key| match key {
+            "SESSION_LIMIT" => Some("0".to_string()),
+            "SESSION_LIMIT_STRATEGY" => Some("fifo".to_string()),
+            _ => None,
+        })
+        .unwrap_err();
+        assert_eq!(errors.len(), 2);
+    }
+}
@@ -1,12 +1,24 @@
+mod binding;
 mod cookie;
+mod csrf;
+mod limit;
 mod parsed_session;
+mod policy;
 mod session_fns;
 mod session_struct;
 
+pub use binding::{BindingMode, BindingPolicy, MismatchAction, user_agent_family};
 pub use cookie::BASE64_DIGEST_LEN;
 use cookie::{sign, verify};
+pub use csrf::{
+    CSRF_HEADER, create_csrf_token, csrf_token_from_cookies, issue_csrf_token, verify_csrf_token,
+};
+pub use limit::{LimitStrategy, SessionLimit};
 pub use parsed_session::{ParsedSession, ParsedSessionError};
-pub use session_fns::{create_session, expire_session};
+pub use policy::{CookiePolicy, CookiePrefix, SameSite, SessionProfile};
+pub use session_fns::{
+    create_impersonation_session, create_session, expire_session, refresh_session,
+};
 pub use session_struct::{Session, SessionStatus};
 
 #[cfg(test)]
@@ -17,39 +29,124 @@ mod tests {
     use std::net::{Ipv4Addr, SocketAddr};
 
     use super::*;
+    use crate::config::Config;
 
     #[test]
     fn db_session_test() {
-        dotenv::dotenv().ok();
+        let config = Config::for_tests(|_| None);
         let headers = HeaderMap::from_iter([(
             header::USER_AGENT,
             HeaderValue::from_str("Mozilla Firefox").unwrap(),
         )]);
         let uid = uuid::Uuid::new_v4();
         let sock_addr = SocketAddr::new(std::net::IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 39849);
-        let (new_session, parsed_session, set_cookie_headermap) =
-            create_session(uid, &headers, sock_addr);
+        let (new_session, parsed_session, set_cookie_headermap) = create_session(
+            &config,
+            time::OffsetDateTime::now_utc(),
+            None,
+            uid,
+            &headers,
+            sock_addr,
+            false,
+        );
         dbg!(&new_session);
         dbg!(&parsed_session);
         dbg!(&set_cookie_headermap);
         assert!(parsed_session.ssid.ends_with(new_session.unsigned_ssid.to_string().as_str()));
         assert!(
-            parsed_session.ssid.starts_with(&sign(new_session.unsigned_ssid.to_string().as_str()))
+            parsed_session
+                .ssid
+                .starts_with(&sign(&config, new_session.unsigned_ssid.to_string().as_str()))
+        );
+    }
+
+    #[test]
+    fn session_status_follows_the_clock() {
+        use crate::clock::{Clock, ManualClock};
+
+        let config = Config::for_tests(|_| None);
+        let clock =
+            ManualClock::new(time::OffsetDateTime::from_unix_timestamp(1_700_000_000).unwrap());
+        let sock_addr = SocketAddr::new(std::net::IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 39849);
+        let (session, _, _) = create_session(
+            &config,
+            clock.now(),
+            None,
+            uuid::Uuid::new_v4(),
+            &HeaderMap::new(),
+            sock_addr,
+            false,
         );
+        assert!(matches!(
+            session.session_status(&config.cookies, clock.now()),
+            SessionStatus::Valid(_)
+        ));
+        assert!(!session.needs_touch(clock.now()));
+
+        let idle_timeout = config.cookies.profile(false).idle_timeout;
+        clock.advance(time::Duration::try_from(idle_timeout).unwrap() + time::Duration::SECOND);
+        assert!(session.needs_touch(clock.now()));
+        assert!(matches!(
+            session.session_status(&config.cookies, clock.now()),
+            SessionStatus::Invalid
+        ));
     }
 
     #[test]
     fn sign_then_verify() {
-        dotenv::dotenv().ok();
+        let config = Config::for_tests(|_| None);
         let uid = uuid::Uuid::new_v4().to_string();
-        let signed_uid = sign(&uid);
-        let decrypted_uid = verify(&format!("{signed_uid}{uid}")).unwrap();
+        let signed_uid = sign(&config, &uid);
+        let decrypted_uid = verify(&config, &format!("{signed_uid}{uid}")).unwrap();
         dbg!(&uid);
         dbg!(&signed_uid);
         dbg!(&decrypted_uid);
         assert_eq!(uid, decrypted_uid);
     }
 
+    #[test]
+    fn csrf_token_is_bound_to_session() {
+        let config = Config::for_tests(|_| None);
+        let unsigned_ssid = uuid::Uuid::new_v4();
+        let token = create_csrf_token(&config, &unsigned_ssid);
+        assert!(verify_csrf_token(&config, &token, &unsigned_ssid));
+        assert!(!verify_csrf_token(&config, &token, &uuid::Uuid::new_v4()));
+        assert!(!verify_csrf_token(&config, &token.replace('.', ""), &unsigned_ssid));
+        let (nonce, _) = token.split_once('.').unwrap();
+        assert!(!verify_csrf_token(
+            &config,
+            &format!("{nonce}x.{}", &token[nonce.len() + 1..]),
+            &unsigned_ssid
+        ));
+    }
+
+    #[test]
+    fn signatures_depend_on_the_config() {
+        let config = Config::for_tests(|_| None);
+        let other = Config::for_tests(|key| match key {
+            "SECRET_KEY" => Some("another key".to_string()),
+            _ => None,
+        });
+        let uid = uuid::Uuid::new_v4().to_string();
+        let value = format!("{}{uid}", sign(&config, &uid));
+        assert!(verify(&config, &value).is_some());
+        assert!(verify(&other, &value).is_none());
+        let unsigned_ssid = uuid::Uuid::new_v4();
+        assert!(!verify_csrf_token(
+            &other,
+            &create_csrf_token(&config, &unsigned_ssid),
+            &unsigned_ssid
+        ));
+
+        // the key being rotated out is still accepted
+        let rotated = Config::for_tests(|key| match key {
+            "SECRET_KEY" => Some("another key".to_string()),
+            "PREVIOUS_SECRET_KEYS" => Some(config.secret_key.clone()),
+            _ => None,
+        });
+        assert_eq!(verify(&rotated, &value), Some(uid));
+    }
+
     // #[test]
     // fn syncing_session_test() {
     //     dotenv::dotenv().ok();
@@ -1,11 +1,11 @@
-use crate::AppError;
+use crate::{AppError, config::Config};
 use axum::http::{HeaderMap, header};
 use std::str::FromStr;
 use uuid::Uuid;
 
 #[derive(Hash, Eq, PartialEq, Clone, Debug)]
 pub struct ParsedSession {
-    pub ssid: String, // SSID={}
+    pub ssid: String, // {ssid_cookie}={}
     pub unsigned_ssid: Uuid,
     pub user_id: Uuid,
 }
@@ -17,29 +17,38 @@ pub enum ParsedSessionError {
 }
 
 impl ParsedSession {
-    /// parses all the cookies sent by a client and creates an `ActiveSession`
-    pub fn parse_and_verify(cookies_list: &Vec<String>) -> Result<Self, ParsedSessionError> {
+    /// parses all the cookies of `tenant` sent by a client and creates an `ActiveSession`
+    pub fn parse_and_verify(
+        config: &Config,
+        tenant: Option<&str>,
+        cookies_list: &Vec<String>,
+    ) -> Result<Self, ParsedSessionError> {
         if cookies_list.is_empty() {
             return Err(ParsedSessionError::NoCookieHeader);
         }
+        let (ssid_cookie, uuid_cookie) =
+            (config.cookies.ssid_cookie(tenant), config.cookies.uuid_cookie(tenant));
         let mut ssid = None;
         let mut unsigned_ssid = None;
         let mut uuid = None;
         for cookie_header in cookies_list {
             for cookie in cookie_header.split(';') {
-                if cookie.trim().starts_with("SSID=") {
-                    ssid = Some(cookie.trim()[5..].to_string());
+                let Some((name, value)) = cookie.trim().split_once('=') else {
+                    continue;
+                };
+                if name == ssid_cookie {
+                    ssid = Some(value.to_string());
                     unsigned_ssid = Some(
                         Uuid::from_str(
-                            &super::verify(&cookie.trim()[5..])
+                            &super::verify(config, value)
                                 .ok_or(ParsedSessionError::VerificationError)?,
                         )
                         .map_err(|_| ParsedSessionError::VerificationError)?,
                     );
                 }
-                if cookie.trim().starts_with("UUID=") {
+                if name == uuid_cookie {
                     uuid = Some(
-                        uuid::Uuid::from_str(&cookie.trim()[5..])
+                        uuid::Uuid::from_str(value)
                             .map_err(|_| ParsedSessionError::VerificationError)?,
                     );
                 }
@@ -55,24 +64,30 @@ impl ParsedSession {
         }
     }
 
-    pub fn parse_and_verify_from_headers(headers: &HeaderMap) -> Result<Self, ParsedSessionError> {
+    pub fn parse_and_verify_from_headers(
+        config: &Config,
+        tenant: Option<&str>,
+        headers: &HeaderMap,
+    ) -> Result<Self, ParsedSessionError> {
         // collecting all the user sent cookie headers into `cookies_list`
         let cookies_list = headers
             .get_all(header::COOKIE)
             .iter()
             .map(|h| h.to_str().unwrap_or_default().to_string())
             .collect::<Vec<String>>();
-        Self::parse_and_verify(&cookies_list)
+        Self::parse_and_verify(config, tenant, &cookies_list)
     }
 }
 
-impl From<ParsedSessionError> for AppError {
-    fn from(value: ParsedSessionError) -> Self {
-        match value {
+impl ParsedSessionError {
+    /// returns the error answered to the client, whose cookies are cleared if they can't be
+    /// verified with the keys of `config`
+    pub fn into_app_error(self, config: &Config, tenant: Option<&str>) -> AppError {
+        match self {
             ParsedSessionError::NoCookieHeader => AppError::Unauthorized("No cookie header found"),
             ParsedSessionError::ParseError => AppError::InvalidSession(HeaderMap::new()),
             ParsedSessionError::VerificationError => {
-                AppError::InvalidSession(super::expire_session())
+                AppError::InvalidSession(super::expire_session(config, tenant))
             }
         }
     }
@@ -0,0 +1,347 @@
+use std::time::Duration;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SameSite {
+    Strict,
+    Lax,
+    None,
+}
+
+impl SameSite {
+    pub fn get_str(&self) -> &'static str {
+        match self {
+            SameSite::Strict => "Strict",
+            SameSite::Lax => "Lax",
+            SameSite::None => "None",
+        }
+    }
+}
+
+/// cookie name prefixes understood by browsers
+/// (https://developer.mozilla.org/en-US/docs/Web/HTTP/Cookies#cookie_prefixes)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CookiePrefix {
+    None,
+    Secure,
+    Host,
+}
+
+impl CookiePrefix {
+    pub fn get_str(&self) -> &'static str {
+        match self {
+            CookiePrefix::None => "",
+            CookiePrefix::Secure => "__Secure-",
+            CookiePrefix::Host => "__Host-",
+        }
+    }
+}
+
+/// lifetimes applied to a session, picked at login time
+#[derive(Clone, Debug)]
+pub struct SessionProfile {
+    /// lifetime of the session cookie, renewed on every refresh
+    pub lifetime: Duration,
+    /// a session that isn't used for this long is no longer accepted or refreshed
+    pub idle_timeout: Duration,
+    /// a session can never be used or refreshed past `created_at + absolute_lifetime`
+    pub absolute_lifetime: Duration,
+}
+
+#[derive(Clone, Debug)]
+pub struct CookiePolicy {
+    pub prefix: CookiePrefix,
+    pub ssid_name: String,
+    pub uuid_name: String,
+    pub csrf_name: String,
+    pub domain: Option<String>,
+    pub path: String,
+    pub same_site: SameSite,
+    pub secure: bool,
+    /// profile used by default
+    pub standard: SessionProfile,
+    /// longer profile used when the user asks to be remembered on login
+    pub remember_me: SessionProfile,
+    /// time for which a user and its sessions are kept inside `Db::active`
+    pub mem_cache_duration: Duration,
+    /// time after expiry within which a session can still be refreshed
+    pub max_refresh_duration: Duration,
+}
+
+impl Default for CookiePolicy {
+    fn default() -> Self {
+        Self {
+            prefix: CookiePrefix::None,
+            ssid_name: "SSID".to_string(),
+            uuid_name: "UUID".to_string(),
+            csrf_name: "CSRF".to_string(),
+            domain: None,
+            path: "/".to_string(),
+            same_site: SameSite::Strict,
+            secure: true,
+            standard: SessionProfile {
+                lifetime: Duration::from_secs(2 * 86400), // days * 86400
+                idle_timeout: Duration::from_secs(86400),
+                absolute_lifetime: Duration::from_secs(14 * 86400),
+            },
+            remember_me: SessionProfile {
+                lifetime: Duration::from_secs(37 * 86400 + 60), // days * 86400 + secs
+                idle_timeout: Duration::from_secs(30 * 86400),
+                absolute_lifetime: Duration::from_secs(90 * 86400),
+            },
+            mem_cache_duration: Duration::from_secs(28800), // 8 hours
+            max_refresh_duration: Duration::from_secs(604800), // 7 days
+        }
+    }
+}
+
+impl CookiePolicy {
+    /// builds the policy from key/value lookups (normally environment variables)
+    ///
+    /// every unset key falls back to `CookiePolicy::default()`,
+    /// all the problems found are returned together
+    pub fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> Result<Self, Vec<String>> {
+        let mut policy = Self::default();
+        let mut errors = Vec::new();
+
+        if let Some(v) = lookup("COOKIE_PREFIX") {
+            match v.as_str() {
+                "" | "none" => policy.prefix = CookiePrefix::None,
+                "__Secure-" | "secure" => policy.prefix = CookiePrefix::Secure,
+                "__Host-" | "host" => policy.prefix = CookiePrefix::Host,
+                _ => errors.push(format!("COOKIE_PREFIX `{v}` is not one of none, secure, host")),
+            }
+        }
+        if let Some(v) = lookup("COOKIE_DOMAIN")
+            && !v.is_empty()
+        {
+            policy.domain = Some(v);
+        }
+        if let Some(v) = lookup("COOKIE_PATH") {
+            policy.path = v;
+        }
+        if let Some(v) = lookup("COOKIE_SAMESITE") {
+            match v.to_ascii_lowercase().as_str() {
+                "strict" => policy.same_site = SameSite::Strict,
+                "lax" => policy.same_site = SameSite::Lax,
+                "none" => policy.same_site = SameSite::None,
+                _ => errors.push(format!("COOKIE_SAMESITE `{v}` is not one of strict, lax, none")),
+            }
+        }
+        if let Some(v) = lookup("COOKIE_SECURE") {
+            match v.parse::<bool>() {
+                Ok(b) => policy.secure = b,
+                Err(_) => errors.push(format!("COOKIE_SECURE `{v}` is not a boolean")),
+            }
+        }
+
+        let mut secs = |key: &str, field: &mut Duration| {
+            if let Some(v) = lookup(key) {
+                match v.parse::<u64>() {
+                    Ok(0) => errors.push(format!("{key} cannot be zero")),
+                    Ok(s) => *field = Duration::from_secs(s),
+                    Err(_) => errors.push(format!("{key} `{v}` is not a number of seconds")),
+                }
+            }
+        };
+        secs("SESSION_LIFETIME", &mut policy.standard.lifetime);
+        secs("SESSION_IDLE_TIMEOUT", &mut policy.standard.idle_timeout);
+        secs("SESSION_ABSOLUTE_LIFETIME", &mut policy.standard.absolute_lifetime);
+        secs("REMEMBER_ME_LIFETIME", &mut policy.remember_me.lifetime);
+        secs("REMEMBER_ME_IDLE_TIMEOUT", &mut policy.remember_me.idle_timeout);
+        secs("REMEMBER_ME_ABSOLUTE_LIFETIME", &mut policy.remember_me.absolute_lifetime);
+        secs("SESSION_CACHE_DURATION", &mut policy.mem_cache_duration);
+        secs("SESSION_REFRESH_WINDOW", &mut policy.max_refresh_duration);
+
+        if let Err(mut e) = policy.validate() {
+            errors.append(&mut e);
+        }
+        if errors.is_empty() { Ok(policy) } else { Err(errors) }
+    }
+
+    /// rejects combinations that browsers would silently ignore or that weaken the cookie
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut errors = Vec::new();
+
+        if self.same_site == SameSite::None && !self.secure {
+            errors.push("SameSite=None requires Secure cookies".to_string());
+        }
+        match self.prefix {
+            CookiePrefix::Host => {
+                if !self.secure {
+                    errors.push("__Host- prefixed cookies must be Secure".to_string());
+                }
+                if self.domain.is_some() {
+                    errors.push("__Host- prefixed cookies cannot have a Domain".to_string());
+                }
+                if self.path != "/" {
+                    errors.push("__Host- prefixed cookies must have Path=/".to_string());
+                }
+            }
+            CookiePrefix::Secure if !self.secure => {
+                errors.push("__Secure- prefixed cookies must be Secure".to_string());
+            }
+            _ => {}
+        }
+        if !self.path.starts_with('/') {
+            errors.push(format!("cookie path `{}` must start with `/`", self.path));
+        }
+        let is_token = |s: &str| {
+            !s.is_empty() && s.chars().all(|c| c.is_ascii_graphic() && !";,=\"\\".contains(c))
+        };
+        if let Some(domain) = &self.domain
+            && !is_token(domain)
+        {
+            errors.push(format!("cookie domain `{domain}` is invalid"));
+        }
+        if !self.path.chars().all(|c| c.is_ascii_graphic() && c != ';') {
+            errors.push(format!("cookie path `{}` is invalid", self.path));
+        }
+        if !is_token(&self.ssid_name) || !is_token(&self.uuid_name) || !is_token(&self.csrf_name) {
+            errors.push("cookie names must be non empty tokens".to_string());
+        }
+        for (name, profile) in [("SESSION", &self.standard), ("REMEMBER_ME", &self.remember_me)] {
+            if profile.lifetime <= self.mem_cache_duration {
+                errors.push(format!("{name}_LIFETIME must be greater than SESSION_CACHE_DURATION"));
+            }
+            if profile.lifetime > profile.absolute_lifetime {
+                errors.push(format!("{name}_LIFETIME cannot exceed {name}_ABSOLUTE_LIFETIME"));
+            }
+            if profile.idle_timeout > profile.absolute_lifetime {
+                errors.push(format!("{name}_IDLE_TIMEOUT cannot exceed {name}_ABSOLUTE_LIFETIME"));
+            }
+        }
+
+        if errors.is_empty() { Ok(()) } else { Err(errors) }
+    }
+
+    /// returns the profile of a session created with or without "remember me"
+    pub fn profile(&self, remember_me: bool) -> &SessionProfile {
+        if remember_me { &self.remember_me } else { &self.standard }
+    }
+
+    /// full name of the session id cookie (including prefix) of `tenant`, see `cookie_name`
+    pub fn ssid_cookie(&self, tenant: Option<&str>) -> String {
+        self.cookie_name(&self.ssid_name, tenant)
+    }
+
+    /// full name of the user id cookie (including prefix) of `tenant`
+    pub fn uuid_cookie(&self, tenant: Option<&str>) -> String {
+        self.cookie_name(&self.uuid_name, tenant)
+    }
+
+    /// full name of the csrf token cookie (including prefix) of `tenant`
+    pub fn csrf_cookie(&self, tenant: Option<&str>) -> String {
+        self.cookie_name(&self.csrf_name, tenant)
+    }
+
+    /// the tenants served on the same host receive each other's cookies, so every tenant but
+    /// the default one (`None`) has its own names, suffixed with its id
+    ///
+    /// a path per tenant would also work for the `/t/{tenant}` prefix, but `__Host-` cookies
+    /// must have `Path=/`
+    fn cookie_name(&self, name: &str, tenant: Option<&str>) -> String {
+        match tenant {
+            Some(tenant) => format!("{}{name}_{tenant}", self.prefix.get_str()),
+            None => format!("{}{name}", self.prefix.get_str()),
+        }
+    }
+
+    /// common attributes appended to every session cookie
+    fn attributes(&self, http_only: bool) -> String {
+        let mut attrs = format!("SameSite={}; Path={}", self.same_site.get_str(), self.path);
+        if http_only {
+            attrs.insert_str(0, "HttpOnly; ");
+        }
+        if self.secure {
+            attrs.push_str("; Secure");
+        }
+        if let Some(domain) = &self.domain {
+            attrs.push_str("; Domain=");
+            attrs.push_str(domain);
+        }
+        attrs
+    }
+
+    /// `Set-Cookie` header value that lives for `max_age`
+    pub fn set_cookie(&self, name: &str, value: &str, max_age: Duration) -> String {
+        format!("{name}={value}; {}; Max-Age={}", self.attributes(true), max_age.as_secs())
+    }
+
+    /// same as `set_cookie` but the cookie stays readable by scripts (used for csrf tokens)
+    pub fn set_script_cookie(&self, name: &str, value: &str, max_age: Duration) -> String {
+        format!("{name}={value}; {}; Max-Age={}", self.attributes(false), max_age.as_secs())
+    }
+
+    /// `Set-Cookie` header value that removes the cookie from the client
+    pub fn expire_cookie(&self, name: &str) -> String {
+        format!("{name}=; {}; Max-Age=0", self.attributes(true))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy_from(vars: &[(&str, &str)]) -> Result<CookiePolicy, Vec<String>> {
+        CookiePolicy::from_lookup(|key| {
+            vars.iter().find(|(k, _)| *k == key).map(|(_, v)| v.to_string())
+        })
+    }
+
+    #[test]
+    fn default_policy_is_valid() {
+        let policy = policy_from(&[]).unwrap();
+        assert_eq!(policy.ssid_cookie(None), "SSID");
+        assert_eq!(
+            policy.set_cookie("SSID", "abc", Duration::from_secs(10)),
+            "SSID=abc; HttpOnly; SameSite=Strict; Path=/; Secure; Max-Age=10"
+        );
+    }
+
+    #[test]
+    fn same_site_none_requires_secure() {
+        assert!(policy_from(&[("COOKIE_SAMESITE", "none"), ("COOKIE_SECURE", "false")]).is_err());
+        assert!(policy_from(&[("COOKIE_SAMESITE", "none")]).is_ok());
+    }
+
+    #[test]
+    fn host_prefix_rules() {
+        let policy = policy_from(&[("COOKIE_PREFIX", "__Host-")]).unwrap();
+        assert_eq!(policy.uuid_cookie(None), "__Host-UUID");
+        assert_eq!(policy.uuid_cookie(Some("acme")), "__Host-UUID_acme");
+        let errors =
+            policy_from(&[("COOKIE_PREFIX", "host"), ("COOKIE_DOMAIN", "example.com")]).unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert!(policy_from(&[("COOKIE_PREFIX", "host"), ("COOKIE_PATH", "/api")]).is_err());
+    }
+
+    #[test]
+    fn shared_domain_cookie() {
+        let policy =
+            policy_from(&[("COOKIE_DOMAIN", ".example.com"), ("COOKIE_SAMESITE", "lax")]).unwrap();
+        assert_eq!(
+            policy.expire_cookie("UUID"),
+            "UUID=; HttpOnly; SameSite=Lax; Path=/; Secure; Domain=.example.com; Max-Age=0"
+        );
+    }
+
+    #[test]
+    fn all_errors_are_reported() {
+        let errors = policy_from(&[
+            ("COOKIE_SAMESITE", "sometimes"),
+            ("COOKIE_SECURE", "yes"),
+            ("SESSION_LIFETIME", "0"),
+        ])
+        .unwrap_err();
+        assert_eq!(errors.len(), 3);
+        assert!(policy_from(&[("SESSION_LIFETIME", "60")]).is_err());
+    }
+
+    #[test]
+    fn profile_limits() {
+        let policy = policy_from(&[]).unwrap();
+        assert!(policy.profile(true).absolute_lifetime > policy.profile(false).absolute_lifetime);
+        assert!(policy_from(&[("REMEMBER_ME_IDLE_TIMEOUT", "999999999")]).is_err());
+        assert!(policy_from(&[("SESSION_ABSOLUTE_LIFETIME", "86400")]).is_err());
+    }
+}
@@ -1,22 +1,105 @@
 use super::{ParsedSession, Session};
+use crate::config::Config;
 use axum::http::{HeaderMap, HeaderValue, header};
-use std::time::Duration;
 use time::OffsetDateTime;
 
 /// this function creates a session that is passed to the user
 /// and stored in both in-memory and primary database
 pub fn create_session(
+    config: &Config,
+    now: OffsetDateTime,
+    tenant: Option<&str>,
     user_id: uuid::Uuid,
     headers: &HeaderMap,
     socket_addr: std::net::SocketAddr,
+    remember_me: bool,
+) -> (Session, ParsedSession, HeaderMap) {
+    let absolute_expires_at = now + config.cookies.profile(remember_me).absolute_lifetime;
+    build_session(
+        config,
+        tenant,
+        user_id,
+        headers,
+        socket_addr,
+        now,
+        absolute_expires_at,
+        remember_me,
+        None,
+    )
+}
+
+/// this function creates a session of `user_id` for the admin `admin_id`
+///
+/// the session ends after `duration` and can't be refreshed
+#[allow(clippy::too_many_arguments)]
+pub fn create_impersonation_session(
+    config: &Config,
+    now: OffsetDateTime,
+    tenant: Option<&str>,
+    user_id: uuid::Uuid,
+    admin_id: uuid::Uuid,
+    headers: &HeaderMap,
+    socket_addr: std::net::SocketAddr,
+    duration: std::time::Duration,
+) -> (Session, ParsedSession, HeaderMap) {
+    build_session(
+        config,
+        tenant,
+        user_id,
+        headers,
+        socket_addr,
+        now,
+        now + duration,
+        false,
+        Some(admin_id),
+    )
+}
+
+/// this function creates the session that replaces `old_session` on automatic refresh
+///
+/// the new session keeps the absolute expiry and profile of the old one
+pub fn refresh_session(
+    config: &Config,
+    now: OffsetDateTime,
+    tenant: Option<&str>,
+    old_session: &Session,
+    user_id: uuid::Uuid,
+    headers: &HeaderMap,
+    socket_addr: std::net::SocketAddr,
+) -> (Session, ParsedSession, HeaderMap) {
+    build_session(
+        config,
+        tenant,
+        user_id,
+        headers,
+        socket_addr,
+        now,
+        old_session.absolute_expires_at,
+        old_session.remember_me,
+        old_session.impersonated_by,
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+fn build_session(
+    config: &Config,
+    tenant: Option<&str>,
+    user_id: uuid::Uuid,
+    headers: &HeaderMap,
+    socket_addr: std::net::SocketAddr,
+    now: OffsetDateTime,
+    absolute_expires_at: OffsetDateTime,
+    remember_me: bool,
+    impersonated_by: Option<uuid::Uuid>,
 ) -> (Session, ParsedSession, HeaderMap) {
     let user_agent =
         headers.get(header::USER_AGENT).map(|v| v.to_str().unwrap_or_default().to_owned());
 
-    let now = OffsetDateTime::now_utc();
-    let expires_at = now + Duration::from_secs(37 * 86400 + 60); // days * 86400 + secs
+    let policy = &config.cookies;
+    let expires_at = (now + policy.profile(remember_me).lifetime).min(absolute_expires_at);
+    let max_age = (expires_at - now).unsigned_abs();
     let uid = uuid::Uuid::new_v4();
-    let signed_uid = super::sign(&uid.to_string());
+    let signed_uid = super::sign(config, &uid.to_string());
 
     (
         Session {
@@ -26,20 +109,27 @@ pub fn create_session(
             created_at: now,
             last_used: now,
             expires_at,
+            absolute_expires_at,
+            remember_me,
+            impersonated_by,
         },
         ParsedSession { ssid: format!("{signed_uid}{uid}"), unsigned_ssid: uid, user_id },
         HeaderMap::from_iter([
             (
                 header::SET_COOKIE,
-                HeaderValue::from_str(&format!(
-                    "SSID={signed_uid}{uid}; HttpOnly; SameSite=Strict; Secure; Path=/; Expires={expires_at}",
+                HeaderValue::from_str(&policy.set_cookie(
+                    &policy.ssid_cookie(tenant),
+                    &format!("{signed_uid}{uid}"),
+                    max_age,
                 ))
                 .unwrap(),
             ),
             (
                 header::SET_COOKIE,
-                HeaderValue::from_str(&format!(
-                    "UUID={user_id}; HttpOnly; SameSite=Strict; Secure; Path=/; Expires={expires_at}",
+                HeaderValue::from_str(&policy.set_cookie(
+                    &policy.uuid_cookie(tenant),
+                    &user_id.to_string(),
+                    max_age,
                 ))
                 .unwrap(),
             ),
@@ -47,17 +137,16 @@ pub fn create_session(
     )
 }
 
-pub fn expire_session() -> HeaderMap {
+pub fn expire_session(config: &Config, tenant: Option<&str>) -> HeaderMap {
+    let policy = &config.cookies;
     HeaderMap::from_iter([
         (
             header::SET_COOKIE,
-            HeaderValue::from_str("SSID={}; HttpOnly; SameSite=Strict; Secure; Path=/; Max-Age=0")
-                .unwrap(),
+            HeaderValue::from_str(&policy.expire_cookie(&policy.ssid_cookie(tenant))).unwrap(),
         ),
         (
             header::SET_COOKIE,
-            HeaderValue::from_str("UUID={}; HttpOnly; SameSite=Strict; Secure; Path=/; Max-Age=0")
-                .unwrap(),
+            HeaderValue::from_str(&policy.expire_cookie(&policy.uuid_cookie(tenant))).unwrap(),
         ),
     ])
 }
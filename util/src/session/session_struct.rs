@@ -6,6 +6,11 @@ pub struct Session {
     pub created_at: time::OffsetDateTime,
     pub last_used: time::OffsetDateTime,
     pub expires_at: time::OffsetDateTime,
+    /// refreshed sessions inherit this from the session they replace
+    pub absolute_expires_at: time::OffsetDateTime,
+    pub remember_me: bool,
+    /// id of the admin using this session to impersonate its user
+    pub impersonated_by: Option<uuid::Uuid>,
 }
 
 pub enum SessionStatus {
@@ -17,28 +22,46 @@ pub enum SessionStatus {
 
 impl Session {
     // timestamp in seconds
-    pub const MEM_CACHE_DURATION: u64 = 28800; // 8 hours
-    pub const MAX_REFRESH_DURATION: u64 = 604800; // 7 days
+    pub const LAST_USED_RESOLUTION: i64 = 300; // 5 minutes
 
-    /// returns the timestamp difference of the session with current time
-    pub fn session_status(&self) -> SessionStatus {
-        let diff = (self.expires_at - time::OffsetDateTime::now_utc()).whole_seconds();
+    /// returns the timestamp difference of the session with `now`
+    pub fn session_status(
+        &self,
+        policy: &super::CookiePolicy,
+        now: time::OffsetDateTime,
+    ) -> SessionStatus {
+        // idle and absolute limits can't be bypassed by refreshing
+        if now >= self.absolute_expires_at
+            || now - self.last_used > policy.profile(self.remember_me).idle_timeout
+        {
+            return SessionStatus::Invalid;
+        }
+
+        let diff = (self.expires_at - now).whole_seconds();
 
         if diff > 0 {
-            if diff > Self::MEM_CACHE_DURATION as i64 {
+            // a session capped at its absolute expiry has nothing left to refresh
+            if diff > policy.mem_cache_duration.as_secs() as i64
+                || self.expires_at >= self.absolute_expires_at
+            {
                 SessionStatus::Valid(diff as u64)
             } else {
                 SessionStatus::Expiring(diff as u64)
             }
         } else {
             #[allow(clippy::collapsible_else_if)]
-            if -diff < Self::MAX_REFRESH_DURATION as i64 {
+            if -diff < policy.max_refresh_duration.as_secs() as i64 {
                 SessionStatus::Refreshable(diff as u64)
             } else {
                 SessionStatus::Invalid
             }
         }
     }
+
+    /// returns true if `last_used` is old enough to be written back to the primary database
+    pub fn needs_touch(&self, now: time::OffsetDateTime) -> bool {
+        (now - self.last_used).whole_seconds() > Self::LAST_USED_RESOLUTION
+    }
 }
 
 impl AsRef<Session> for Session {
@@ -78,6 +78,29 @@ fn is_domain_valid(domain: &str) -> bool {
     true
 }
 
+/// checks a domain of the registration allow and deny lists
+pub fn is_email_domain_valid(domain: &str) -> Result<(), AppError> {
+    if is_domain_valid(domain) { Ok(()) } else { Err(AppError::InvalidData("Invalid email domain")) }
+}
+
+/// checks the domain of `email` against the registration allow and deny lists,
+/// a listed domain also matches its subdomains
+pub fn is_email_domain_allowed(
+    email: &str,
+    allowed: &[String],
+    denied: &[String],
+) -> Result<(), AppError> {
+    let domain = email.rsplit('@').next().unwrap_or_default().to_ascii_lowercase();
+    let matches = |listed: &String| {
+        let listed = listed.to_ascii_lowercase();
+        domain == listed || domain.ends_with(&format!(".{listed}"))
+    };
+    if denied.iter().any(matches) || (!allowed.is_empty() && !allowed.iter().any(matches)) {
+        return Err(AppError::Forbidden("Registrations with this email domain aren't allowed"));
+    }
+    Ok(())
+}
+
 pub fn is_username_valid(s: &str) -> Result<(), AppError> {
     if s.len() < 6 || s.len() > 20 {
         return Err(AppError::InvalidData("Username should be between 6 and 20 characters"));
@@ -137,6 +160,20 @@ mod tests {
         email_test_17: ("hi@sample-.com", None),
     }
 
+    #[test]
+    fn email_domain_lists() {
+        let list = |v: &[&str]| v.iter().map(|v| v.to_string()).collect::<Vec<_>>();
+        assert!(is_email_domain_allowed("a@example.com", &[], &[]).is_ok());
+        assert!(is_email_domain_allowed("a@Mail.Example.com", &list(&["example.com"]), &[]).is_ok());
+        assert!(is_email_domain_allowed("a@notexample.com", &list(&["example.com"]), &[]).is_err());
+        assert!(is_email_domain_allowed("a@spam.io", &[], &list(&["spam.io"])).is_err());
+        // the deny list wins over the allow list
+        let allowed = list(&["example.com"]);
+        assert!(
+            is_email_domain_allowed("a@x.example.com", &allowed, &list(&["x.example.com"])).is_err()
+        );
+    }
+
     macro_rules! username_test {
         ($($name:ident: $exp:expr,)*) => {
             $(
@@ -1,4 +1,10 @@
+use database::{Db, bucket::BlackBlazeB2};
+use std::sync::Arc;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+use util::{
+    config::Config,
+    mail::{MailTemplates, Mailer},
+};
 
 #[tokio::main]
 pub async fn main() {
@@ -9,15 +15,42 @@ pub async fn main() {
         .with(tracing_subscriber::fmt::Layer::default())
         .init();
 
+    // every problem of the configuration is reported before accepting any connection
+    let config = util::config::Config::load().unwrap_or_else(|errors| {
+        tracing::error!("invalid configuration:\n  {}", errors.join("\n  "));
+        std::process::exit(1);
+    });
+    let config = Arc::new(config);
+
+    let state = app_state(config).await.unwrap_or_else(|e| {
+        tracing::error!("{e}");
+        std::process::exit(1);
+    });
+    let listener =
+        server::get_custom_listener(&state.config, state.db.clone()).await.unwrap_or_else(|e| {
+            tracing::error!("can't listen on {}: {e}", state.config.socket);
+            std::process::exit(1);
+        });
+
     axum::serve(
-        server::get_custom_listener().await,
-        server::routes().await.into_make_service_with_connect_info::<server::ClientSocket>(),
+        listener,
+        server::routes(state).into_make_service_with_connect_info::<server::ClientSocket>(),
     )
     .with_graceful_shutdown(shutdown_signal())
     .await
     .unwrap();
 }
 
+/// connects the database, storage and mailer of `config` and loads its mail templates
+async fn app_state(config: Arc<Config>) -> Result<server::AppState, String> {
+    let storage = Arc::new(BlackBlazeB2::from(&config.bucket));
+    let db = Db::connect(&config, storage.clone()).await?;
+    let mailer = <dyn Mailer>::from_config(&config.mail)?;
+    let templates = MailTemplates::load(config.mail.templates.as_deref())
+        .map_err(|errors| format!("invalid mail templates:\n  {}", errors.join("\n  ")))?;
+    Ok(server::AppState::new(db, mailer, storage, config).with_templates(Arc::new(templates)))
+}
+
 /// Shutdown signal to run axum with graceful shutdown when
 /// a user presses Ctrl+C or Unix sends a terminate signal.
 pub async fn shutdown_signal() {